@@ -2,26 +2,262 @@
 //!
 //! Configures and starts the HTTP server with session management.
 
-use auth::models::SbAuthenticator;
-use axum::Router;
+use auth::authorization::PgRoles;
+use auth::models::{Authenticator, PgAuthenticator, SbAuthenticator};
+use auth::rate_limit::{OtpRateLimiter, PgStore, rate_limit_otp};
+use auth::sender::{ConsoleSender, SmsSender, SmtpSender};
+use axum::{Router, middleware};
+use chat::filter::{FilterAction, Links, Wordlist};
+use chat::{ChatApi, ChatHub, InactivitySweeper, MessageFilters, SourcesApi};
+use db::pubsub::{InProcessPubSub, PgPubSub, PubSub};
+use matchmaking::{
+    ExpiryWorker, MatchmakingWorker, NotificationHub, OpposingStances, PromptTtlsApi,
+    PubSubNotifier, RequestsApi,
+};
+use moderation::{BlocksApi, ModerationApi, ReportsApi};
+use shared::request_id::request_id;
+use sqlx::PgPool;
 use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
 
-/// Creates the main application router with all middleware and route configurations.
-async fn create_router() -> Router {
-    let authenticator = SbAuthenticator::default();
-    let _pool = db::create_pool().await.unwrap();
+/// Creates the main application router, selecting the authentication backend
+/// from the `AUTH_BACKEND` environment variable (`supabase` by default, or `postgres`).
+fn create_router(pool: PgPool, pubsub: Arc<dyn PubSub>) -> Router {
+    match dotenvy::var("AUTH_BACKEND").as_deref() {
+        Ok("postgres") => app(pg_authenticator(pool.clone()), pool, pubsub),
+        _ => app(
+            SbAuthenticator::default().with_pool(pool.clone()),
+            pool,
+            pubsub,
+        ),
+    }
+}
+
+/// Creates the pub/sub backend through which chat messages and notifications are relayed.
+///
+/// Messages are relayed through Postgres, reaching every gateway instance, unless
+/// `PUBSUB_BACKEND=memory`, which keeps them within this instance.
+fn pubsub(pool: PgPool) -> Arc<dyn PubSub> {
+    match dotenvy::var("PUBSUB_BACKEND").as_deref() {
+        Ok("memory") => Arc::new(InProcessPubSub::new()),
+        _ => Arc::new(PgPubSub::new(pool)),
+    }
+}
+
+/// Creates the self-hosted authenticator, configuring OTP delivery from the environment.
+///
+/// Codes are sent over SMTP if `SMTP_HOST` is set and over SMS if `SMS_ACCOUNT_SID` is set.
+/// Otherwise they are appended to `OTP_LOG_FILE` if set, or printed to standard output.
+fn pg_authenticator(pool: PgPool) -> PgAuthenticator {
+    let console = match dotenvy::var("OTP_LOG_FILE") {
+        Ok(path) => ConsoleSender::file(path),
+        Err(_) => ConsoleSender::stdout(),
+    };
+
+    let authenticator = PgAuthenticator::from_env(pool)
+        .unwrap()
+        .with_email_sender(console.clone())
+        .with_sms_sender(console);
+
+    let authenticator = match dotenvy::var("SMTP_HOST") {
+        Ok(_) => authenticator.with_email_sender(SmtpSender::from_env().unwrap()),
+        Err(_) => authenticator,
+    };
+
+    match dotenvy::var("SMS_ACCOUNT_SID") {
+        Ok(_) => authenticator.with_sms_sender(SmsSender::from_env().unwrap()),
+        Err(_) => authenticator,
+    }
+}
+
+/// Creates the rate limiter for the OTP endpoints.
+///
+/// State is kept in memory unless `RATE_LIMIT_STORE=postgres`, which shares it
+/// between instances and prunes it in the background. With `RATE_LIMIT_TRUST_PROXY=true`, client IPs are taken
+/// from the `X-Forwarded-For` header set by a reverse proxy.
+fn otp_rate_limiter(pool: PgPool) -> OtpRateLimiter {
+    let limiter = match dotenvy::var("RATE_LIMIT_STORE").as_deref() {
+        Ok("postgres") => {
+            let store = PgStore::new(pool);
+            store.spawn_prune_task();
+            OtpRateLimiter::new(store)
+        }
+        _ => OtpRateLimiter::in_memory(),
+    };
+
+    match dotenvy::var("RATE_LIMIT_TRUST_PROXY").as_deref() {
+        Ok("true") => limiter.trust_forwarded_for(),
+        _ => limiter,
+    }
+}
+
+/// Creates the worker pairing pending conversation requests.
+///
+/// Rounds run every `MATCHMAKING_INTERVAL_MS` milliseconds if set. Requests
+/// that found no opposing stance are paired with nearby stances after
+/// `MATCHMAKING_FALLBACK_SECS` seconds if set. Users who talked together are not
+/// matched again for `REMATCH_COOLDOWN_DAYS` days if set.
+fn matchmaking_worker(pool: PgPool, pubsub: Arc<dyn PubSub>) -> MatchmakingWorker {
+    let worker = MatchmakingWorker::new(pool).with_notifier(PubSubNotifier::from_arc(pubsub));
+    let worker = match dotenvy::var("MATCHMAKING_FALLBACK_SECS").map(|secs| secs.parse()) {
+        Ok(Ok(secs)) => worker.with_strategy(OpposingStances::new(chrono::Duration::seconds(secs))),
+        _ => worker,
+    };
+    let worker = match dotenvy::var("REMATCH_COOLDOWN_DAYS").map(|days| days.parse()) {
+        Ok(Ok(days)) => worker.with_rematch_cooldown(chrono::Duration::days(days)),
+        _ => worker,
+    };
+    match dotenvy::var("MATCHMAKING_INTERVAL_MS").map(|ms| ms.parse()) {
+        Ok(Ok(ms)) => worker.with_interval(Duration::from_millis(ms)),
+        _ => worker,
+    }
+}
+
+/// Creates the worker expiring stale conversation requests.
+///
+/// Requests on prompts without a TTL of their own expire after
+/// `REQUEST_TTL_SECS` seconds if set.
+fn expiry_worker(pool: PgPool, pubsub: Arc<dyn PubSub>) -> ExpiryWorker {
+    let worker = ExpiryWorker::new(pool).with_notifier(PubSubNotifier::from_arc(pubsub));
+    match dotenvy::var("REQUEST_TTL_SECS").map(|secs| secs.parse()) {
+        Ok(Ok(secs)) => worker.with_default_ttl(chrono::Duration::seconds(secs)),
+        _ => worker,
+    }
+}
+
+/// Creates the job ending conversations left without messages.
+///
+/// Conversations end after `CONVERSATION_INACTIVITY_SECS` seconds without
+/// messages if set.
+fn inactivity_sweeper(pool: PgPool, pubsub: Arc<dyn PubSub>) -> InactivitySweeper {
+    let sweeper = InactivitySweeper::new(pool, ChatHub::from_arc(pubsub));
+    match dotenvy::var("CONVERSATION_INACTIVITY_SECS").map(|secs| secs.parse()) {
+        Ok(Ok(secs)) => sweeper.with_timeout(chrono::Duration::seconds(secs)),
+        _ => sweeper,
+    }
+}
+
+/// Creates the filters chat messages go through before they are stored.
+///
+/// Besides the default length and flood limits, words listed in `MESSAGE_WORDLIST_FILE`
+/// are masked, or rejected or flagged if `MESSAGE_WORDLIST_ACTION` says so, and links
+/// are masked, rejected or flagged as `MESSAGE_LINK_ACTION` says if set.
+fn message_filters() -> MessageFilters {
+    let filters = MessageFilters::default();
+    let filters = match dotenvy::var("MESSAGE_WORDLIST_FILE") {
+        Ok(path) => {
+            let action = filter_action("MESSAGE_WORDLIST_ACTION").unwrap_or(FilterAction::Mask);
+            filters.with(
+                Wordlist::from_file(path, action).expect("Unable to read MESSAGE_WORDLIST_FILE"),
+            )
+        }
+        Err(_) => filters,
+    };
+    match filter_action("MESSAGE_LINK_ACTION") {
+        Some(action) => filters.with(Links(action)),
+        None => filters,
+    }
+}
+
+/// Reads the filter action set in the given environment variable, if any.
+///
+/// Panics if the variable holds anything but `mask`, `reject` or `flag`, so that
+/// a typo does not silently weaken the filters.
+fn filter_action(var: &str) -> Option<FilterAction> {
+    let action = dotenvy::var(var).ok()?;
+    Some(
+        action
+            .parse()
+            .unwrap_or_else(|e| panic!("Invalid {var}: {e}")),
+    )
+}
+
+/// Creates the chat API, delivering events through the given hub.
+///
+/// Messages go through the filters configured by `message_filters`, and may be
+/// edited or deleted for `MESSAGE_EDIT_WINDOW_SECS` seconds after being sent if set.
+fn chat_api(pool: PgPool, hub: ChatHub) -> ChatApi {
+    let api = ChatApi::new(pool)
+        .with_hub(hub)
+        .with_filters(message_filters());
+    match dotenvy::var("MESSAGE_EDIT_WINDOW_SECS").map(|secs| secs.parse()) {
+        Ok(Ok(secs)) => api.with_edit_window(chrono::Duration::seconds(secs)),
+        _ => api,
+    }
+}
+
+/// Creates the conversation requests API, relaying the notifications published
+/// by the workers to connected users.
+///
+/// Users may have at most `MAX_PENDING_REQUESTS` requests pending at once if set.
+fn requests_api(pool: PgPool, pubsub: Arc<dyn PubSub>) -> RequestsApi {
+    let api = RequestsApi::new(pool).with_notifications(NotificationHub::from_arc(pubsub));
+    match dotenvy::var("MAX_PENDING_REQUESTS").map(|max| max.parse()) {
+        Ok(Ok(max)) => api.with_max_pending(max),
+        _ => api,
+    }
+}
+
+/// Builds the application router with all middleware and route configurations.
+fn app<A: Authenticator>(authenticator: A, pool: PgPool, pubsub: Arc<dyn PubSub>) -> Router {
+    let requests_api = requests_api(pool.clone(), pubsub.clone());
+    let hub = ChatHub::from_arc(pubsub);
+    let chat_api = chat_api(pool.clone(), hub.clone());
+    let reports_api = ReportsApi::new(pool.clone()).with_hub(hub);
+    let moderation_api =
+        ModerationApi::new(pool.clone()).with_role_source(PgRoles::new(pool.clone()));
+    let prompt_ttls_api =
+        PromptTtlsApi::new(pool.clone()).with_role_source(PgRoles::new(pool.clone()));
+    let auth_router = auth::router(authenticator.clone()).layer(middleware::from_fn_with_state(
+        otp_rate_limiter(pool.clone()),
+        rate_limit_otp,
+    ));
 
     Router::new()
-        .nest("/auth", auth::router(authenticator.clone()))
-    // TODO: rate limiting
+        .nest("/auth", auth_router)
+        .nest(
+            "/conversation-requests",
+            requests_api.router(authenticator.clone()),
+        )
+        .nest("/conversations", chat_api.router(authenticator.clone()))
+        .nest(
+            "/sources",
+            SourcesApi::new(pool.clone()).router(authenticator.clone()),
+        )
+        .nest("/reports", reports_api.router(authenticator.clone()))
+        .nest(
+            "/blocks",
+            BlocksApi::new(pool.clone()).router(authenticator.clone()),
+        )
+        .nest("/moderation", moderation_api.router(authenticator.clone()))
+        .nest("/admin/prompt-ttls", prompt_ttls_api.router(authenticator))
+        .layer(middleware::from_fn(request_id))
 }
 
 /// The back-end entry point.
-/// The auth service currently uses supabase_auth, and thus
+/// The auth service uses supabase_auth by default, and thus
 /// requires the following environment variables to be set:
 ///     SUPABASE_URL, SUPABASE_API_KEY, SUPABASE_JWT_SECRET
+/// With AUTH_BACKEND=postgres, the self-hosted backend is used instead, requiring:
+///     AUTH_JWT_SECRET, AUTH_REFRESH_SECRET
+/// and optionally SMTP_* and SMS_* variables for OTP delivery.
+/// OTP rate limiting is configured with RATE_LIMIT_STORE and RATE_LIMIT_TRUST_PROXY.
+/// The number of pending conversation requests per user is limited by MAX_PENDING_REQUESTS.
+/// Moderators and admins are recognized by the roles granted in the database, falling back to
+/// token claims. Admins set how long requests on a prompt stay pending at /admin/prompt-ttls.
+/// Chat messages are filtered as configured by MESSAGE_WORDLIST_FILE, MESSAGE_WORDLIST_ACTION
+/// and MESSAGE_LINK_ACTION; flagged messages are reported to the moderation queue.
+/// Messages may be edited or deleted for MESSAGE_EDIT_WINDOW_SECS after being sent.
 /// The db in use is set up using the environment variables:
 ///     DATABASE_URL, DATABASE_MAX_CON, DATABASE_MIN_CON
+/// Pending database migrations are applied at startup; with --migrate-only, the
+/// gateway exits once they are applied instead of serving requests.
+/// The matchmaking worker runs in the background, configured with MATCHMAKING_INTERVAL_MS,
+/// MATCHMAKING_FALLBACK_SECS and REMATCH_COOLDOWN_DAYS, as is the job expiring stale
+/// requests, configured with REQUEST_TTL_SECS, and the job ending idle conversations, configured with
+/// CONVERSATION_INACTIVITY_SECS. Chat messages and notifications are relayed between instances
+/// through Postgres unless PUBSUB_BACKEND=memory.
 #[tokio::main]
 async fn main() {
     // TODO: set up HTTPS (TLS) secure communication; read rustls, tokio_rustls docs
@@ -29,9 +265,25 @@ async fn main() {
     // load .env file
     dotenvy::dotenv().expect("Unable to find .env file");
 
+    let pool = db::create_pool().await.unwrap();
+    db::migrate(&pool)
+        .await
+        .expect("Failed to apply database migrations");
+    if std::env::args().any(|arg| arg == "--migrate-only") {
+        println!("Database migrations applied");
+        return;
+    }
+
+    let pubsub = pubsub(pool.clone());
+    matchmaking_worker(pool.clone(), pubsub.clone()).spawn();
+    expiry_worker(pool.clone(), pubsub.clone()).spawn();
+    inactivity_sweeper(pool.clone(), pubsub.clone()).spawn();
+
     let addr = SocketAddr::from(([127, 0, 0, 1], 3000));
     let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
 
     println!("Server listening on {}", addr);
-    axum::serve(listener, create_router().await).await.unwrap();
+    // client addresses are needed to rate limit by IP
+    let app = create_router(pool, pubsub).into_make_service_with_connect_info::<SocketAddr>();
+    axum::serve(listener, app).await.unwrap();
 }
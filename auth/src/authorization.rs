@@ -0,0 +1,199 @@
+//! Role-based authorization for routes protected by the authentication middleware.
+//!
+//! The authentication middleware establishes who the user is; the [`authorize`]
+//! middleware decides whether they may proceed, based on their [`Role`]. Roles
+//! are read from the token's `role` claim by default, or looked up in the
+//! database with [`PgRoles`].
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use axum::extract::{Request, State};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::Response;
+use serde_json::json;
+use shared::error::{ApiError, ErrorCode};
+use shared::types::role::{Permission, Role};
+use sqlx::PgPool;
+
+use crate::error::AuthError;
+use crate::models::AuthenticatedUser;
+
+/// Trait for types that determine the role of an authenticated user.
+#[async_trait]
+pub trait RoleSource: Send + Sync + 'static {
+    /// Returns the role of the given user.
+    async fn role(&self, user: &AuthenticatedUser) -> Result<Role, AuthError>;
+}
+
+/// Role source reading the role from the token's `role` claim.
+///
+/// See [`AuthenticatedUser::claimed_role`] for how claims map to roles.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClaimRoles;
+
+#[async_trait]
+impl RoleSource for ClaimRoles {
+    async fn role(&self, user: &AuthenticatedUser) -> Result<Role, AuthError> {
+        Ok(user.claimed_role())
+    }
+}
+
+/// Role source looking up roles granted in the `user_roles` table.
+///
+/// Unlike [`ClaimRoles`], changes to a user's role take effect immediately rather
+/// than when their next access token is issued. Users without a granted role
+/// fall back to the role claimed by their token.
+#[derive(Clone)]
+pub struct PgRoles {
+    pool: PgPool,
+}
+
+impl PgRoles {
+    /// Create a new PgRoles using the provided pool.
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl RoleSource for PgRoles {
+    async fn role(&self, user: &AuthenticatedUser) -> Result<Role, AuthError> {
+        let granted = db::queries::roles::get_role(&self.pool, user.id)
+            .await
+            .map_err(|e| AuthError::RoleLookup(e.to_string()))?;
+
+        match granted {
+            Some(role) => role
+                .parse()
+                .map_err(|e| AuthError::RoleLookup(format!("{e}"))),
+            None => Ok(user.claimed_role()),
+        }
+    }
+}
+
+/// What a user must hold to pass [`authorize`].
+#[derive(Debug, Clone, Copy)]
+enum Requirement {
+    Role(Role),
+    Permission(Permission),
+}
+
+/// State for the [`authorize`] middleware: the required role or permission and
+/// where to read the user's role from.
+///
+/// Create one with [`require_role`] or [`require_permission`].
+#[derive(Clone)]
+pub struct Authorization {
+    requirement: Requirement,
+    roles: Arc<dyn RoleSource>,
+}
+
+impl Authorization {
+    /// Determine the user's role using the given source instead of the token's claims.
+    pub fn with_role_source(mut self, roles: impl RoleSource) -> Self {
+        self.roles = Arc::new(roles);
+        self
+    }
+
+    fn new(requirement: Requirement) -> Self {
+        Self {
+            requirement,
+            roles: Arc::new(ClaimRoles),
+        }
+    }
+
+    /// The least privileged role satisfying the requirement.
+    fn required_role(&self) -> Role {
+        match self.requirement {
+            Requirement::Role(role) => role,
+            Requirement::Permission(permission) => Role::minimum_for(permission),
+        }
+    }
+}
+
+/// Require the user to hold at least the given role.
+pub fn require_role(role: Role) -> Authorization {
+    Authorization::new(Requirement::Role(role))
+}
+
+/// Require the user to hold a role granting the given permission.
+pub fn require_permission(permission: Permission) -> Authorization {
+    Authorization::new(Requirement::Permission(permission))
+}
+
+/// Authorization middleware rejecting users who lack a required role or permission.
+///
+/// This middleware must run after [`auth_standard`](crate::middleware::auth_standard)
+/// or [`auth_strict`](crate::middleware::auth_strict), which insert the
+/// [`AuthenticatedUser`] it authorizes. Since the last `route_layer` added runs
+/// first, add it before the authentication layer.
+///
+/// Users lacking the requirement are rejected with `403 Forbidden` and the
+/// `forbidden` code, with the user's `role`, the `required_role` and, for
+/// permissions, the `required_permission` in the error details.
+/// Otherwise, the user's [`Role`] is inserted into request extensions and can be
+/// accessed in handlers using `axum::Extension`.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use axum::{Router, routing::get, middleware};
+/// use auth::authorization::{authorize, require_permission};
+/// use auth::{middleware::auth_standard, models::SbAuthenticator};
+/// use shared::types::role::Permission;
+///
+/// let authenticator = SbAuthenticator::default();
+/// let app = Router::new()
+///     .route("/reports", get(list_reports))
+///     .route_layer(middleware::from_fn_with_state(
+///         require_permission(Permission::ReviewReports),
+///         authorize,
+///     ))
+///     .route_layer(middleware::from_fn_with_state(
+///         authenticator.clone(),
+///         auth_standard,
+///     ));
+/// ```
+pub async fn authorize(
+    State(authorization): State<Authorization>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let user = request
+        .extensions()
+        .get::<AuthenticatedUser>()
+        .ok_or_else(|| {
+            ApiError::new(ErrorCode::MissingCredentials, "Not authenticated")
+                .with_status(StatusCode::UNAUTHORIZED)
+        })?;
+
+    let role = authorization.roles.role(user).await?;
+
+    let permitted = match authorization.requirement {
+        Requirement::Role(required) => role.includes(required),
+        Requirement::Permission(permission) => role.has_permission(permission),
+    };
+    if !permitted {
+        let required_role = authorization.required_role();
+        let error = match authorization.requirement {
+            Requirement::Role(required) => {
+                ApiError::forbidden(format!("Requires the {required} role"))
+                    .with_details(json!({ "role": role, "required_role": required_role }))
+            }
+            Requirement::Permission(permission) => ApiError::forbidden(format!(
+                "Requires the {permission} permission"
+            ))
+            .with_details(json!({
+                "role": role,
+                "required_role": required_role,
+                "required_permission": permission,
+            })),
+        };
+        return Err(error);
+    }
+
+    request.extensions_mut().insert(role);
+    Ok(next.run(request).await)
+}
@@ -1,6 +1,6 @@
 //! Data Transfer Objects for API requests and responses.
 
-use crate::models::AuthSession;
+use crate::models::{AuthSession, OtpChannel};
 use serde::{Deserialize, Serialize};
 
 // -----------------
@@ -8,14 +8,22 @@ use serde::{Deserialize, Serialize};
 // -----------------
 
 /// Request to send OTP to a user's contact (e.g. email).
+///
+/// The channel defaults to email when omitted.
 #[derive(Deserialize)]
 pub struct SendOtpRequest {
+    #[serde(default)]
+    pub channel: OtpChannel,
     pub contact: String,
 }
 
 /// Request to verify OTP and authenticate user.
+///
+/// The channel must match the one the OTP was sent over; it defaults to email when omitted.
 #[derive(Deserialize)]
 pub struct VerifyOtpRequest {
+    #[serde(default)]
+    pub channel: OtpChannel,
     pub contact: String,
     pub token: String,
 }
@@ -25,11 +33,15 @@ pub struct VerifyOtpRequest {
 // -----------------
 
 /// Authentication response containing tokens and expiration.
+///
+/// `is_new_user` is set when the user signed in for the first time, so that
+/// clients know to show onboarding.
 #[derive(Serialize)]
 pub struct AuthResponse {
     pub access_token: String,
     pub refresh_token: String,
     pub expires_at: u64,
+    pub is_new_user: bool,
 }
 
 impl<S: AuthSession> From<S> for AuthResponse {
@@ -38,6 +50,7 @@ impl<S: AuthSession> From<S> for AuthResponse {
             access_token: value.access_token().to_string(),
             refresh_token: value.refresh_token().to_string(),
             expires_at: value.expires_at(),
+            is_new_user: value.is_new_user(),
         }
     }
 }
@@ -47,9 +60,3 @@ impl<S: AuthSession> From<S> for AuthResponse {
 pub struct MessageResponse {
     pub message: String,
 }
-
-/// Error response for failed operations.
-#[derive(Serialize)]
-pub struct ErrorResponse {
-    pub error: String,
-}
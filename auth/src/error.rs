@@ -1,5 +1,6 @@
 //! Error types for the auth crate.
 
+use shared::error::{ApiError, ErrorCode};
 use thiserror::Error;
 
 /// Unified error type for authentication operations.
@@ -17,4 +18,27 @@ pub enum AuthError {
 
     #[error("Invalid JWT token: {0}")]
     InvalidToken(String),
+
+    #[error("Failed to load JSON Web Key Set: {0}")]
+    KeyFetch(String),
+
+    #[error("Failed to look up role: {0}")]
+    RoleLookup(String),
+}
+
+impl From<AuthError> for ApiError {
+    fn from(error: AuthError) -> Self {
+        match error {
+            AuthError::MissingAuthHeader | AuthError::InvalidAuthHeader => {
+                ApiError::new(ErrorCode::MissingCredentials, error.to_string())
+            }
+            AuthError::InvalidToken(_) => ApiError::new(ErrorCode::InvalidToken, error.to_string()),
+            AuthError::KeyFetch(_) => ApiError::new(
+                ErrorCode::ServiceUnavailable,
+                "Unable to verify tokens at the moment",
+            )
+            .with_source(error),
+            AuthError::RoleLookup(_) => ApiError::internal(error),
+        }
+    }
 }
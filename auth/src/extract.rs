@@ -0,0 +1,221 @@
+//! Extractors for the authenticated user, protecting individual handlers.
+//!
+//! Unlike the authentication middleware, which protects every route it is layered
+//! on, these extractors protect the handlers that take them. They reuse the user
+//! inserted by the middleware when it has run, and otherwise verify the request's
+//! token themselves using the [`AuthVerifier`] taken from the router state, and
+//! reject suspended or banned users just like the middleware does.
+
+use std::ops::Deref;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use axum::extract::{FromRef, FromRequestParts};
+use axum::http::request::Parts;
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use shared::error::ApiError;
+
+use crate::error::AuthError;
+use crate::jwt::{self, Claims, JwtVerifier};
+use crate::middleware::ensure_not_barred;
+use crate::models::{AuthenticatedUser, Authenticator};
+
+/// Verifier used by [`AuthUser`] and [`OptionalAuthUser`] to validate tokens.
+///
+/// Handlers using the extractors need router state from which an `AuthVerifier`
+/// can be obtained with [`FromRef`].
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use auth::extract::{AuthUser, AuthVerifier};
+/// use axum::extract::FromRef;
+///
+/// #[derive(Clone)]
+/// struct AppState {
+///     verifier: AuthVerifier,
+/// }
+///
+/// impl FromRef<AppState> for AuthVerifier {
+///     fn from_ref(state: &AppState) -> Self {
+///         state.verifier.clone()
+///     }
+/// }
+///
+/// async fn profile(user: AuthUser) -> String {
+///     format!("Hello, user {}!", user.id)
+/// }
+///
+/// let state = AppState { verifier: AuthVerifier::new(SbAuthenticator::default()) };
+/// let app = Router::new().route("/profile", get(profile)).with_state(state);
+/// ```
+#[derive(Clone)]
+pub struct AuthVerifier(Arc<dyn UserVerifier>);
+
+impl AuthVerifier {
+    /// Verify tokens with the authenticator's [`JwtVerifier`], and reject users
+    /// the authenticator reports as barred by a suspension or ban.
+    pub fn new<A: Authenticator>(authenticator: A) -> Self {
+        Self(Arc::new(AuthenticatorVerifier(authenticator)))
+    }
+
+    /// Verify tokens with the given verifier.
+    ///
+    /// Without an authenticator to look them up, sanctions are not checked; use
+    /// [`AuthVerifier::new`] wherever suspended users must be kept out.
+    pub fn from_verifier(verifier: impl JwtVerifier) -> Self {
+        Self(Arc::new(TokenVerifier(verifier)))
+    }
+
+    /// Verify the token of a request and build the user from its claims.
+    async fn authenticate(&self, token: &str) -> Result<AuthenticatedUser, AuthRejection> {
+        self.0.authenticate(token).await
+    }
+}
+
+/// Routers whose state is the authenticator itself can use the extractors directly.
+impl<A: Authenticator> FromRef<A> for AuthVerifier {
+    fn from_ref(authenticator: &A) -> Self {
+        Self::new(authenticator.clone())
+    }
+}
+
+/// Builds the authenticated user from a request's token.
+#[async_trait]
+trait UserVerifier: Send + Sync {
+    async fn authenticate(&self, token: &str) -> Result<AuthenticatedUser, AuthRejection>;
+}
+
+/// Verify a token and build the user from its claims.
+async fn user_from_token(
+    verifier: &dyn JwtVerifier,
+    token: &str,
+) -> Result<AuthenticatedUser, AuthRejection> {
+    let claims: Claims = verifier
+        .verify(token)
+        .await
+        .map_err(AuthRejection::Invalid)?;
+    AuthenticatedUser::try_from(claims).map_err(AuthRejection::Invalid)
+}
+
+/// Adapter borrowing the verifier of an owned authenticator, which also checks sanctions.
+struct AuthenticatorVerifier<A>(A);
+
+#[async_trait]
+impl<A: Authenticator> UserVerifier for AuthenticatorVerifier<A> {
+    async fn authenticate(&self, token: &str) -> Result<AuthenticatedUser, AuthRejection> {
+        let user = user_from_token(self.0.jwt_verifier(), token).await?;
+        ensure_not_barred(&self.0, &user)
+            .await
+            .map_err(AuthRejection::Barred)?;
+        Ok(user)
+    }
+}
+
+/// Adapter for a bare verifier, which cannot check sanctions.
+struct TokenVerifier<V>(V);
+
+#[async_trait]
+impl<V: JwtVerifier> UserVerifier for TokenVerifier<V> {
+    async fn authenticate(&self, token: &str) -> Result<AuthenticatedUser, AuthRejection> {
+        user_from_token(&self.0, token).await
+    }
+}
+
+/// Rejection returned when the authenticated user cannot be extracted.
+///
+/// Responds with `401 Unauthorized` and a `WWW-Authenticate: Bearer` header, unless
+/// the token could not be verified for reasons other than the token itself, such
+/// as the signing keys being unavailable. Suspended or banned users are rejected
+/// with `403 Forbidden` and the `account_suspended` code, as by the middleware.
+#[derive(Debug)]
+pub enum AuthRejection {
+    /// The request carries no usable bearer token.
+    Missing(AuthError),
+    /// The bearer token failed verification.
+    Invalid(AuthError),
+    /// The user is barred by a sanction, or their sanctions could not be looked up.
+    Barred(ApiError),
+}
+
+impl IntoResponse for AuthRejection {
+    fn into_response(self) -> Response {
+        let error = match self {
+            AuthRejection::Missing(error) => {
+                ApiError::from(error).with_status(StatusCode::UNAUTHORIZED)
+            }
+            AuthRejection::Invalid(error) => ApiError::from(error),
+            AuthRejection::Barred(error) => error,
+        };
+        if error.status() != StatusCode::UNAUTHORIZED {
+            return error.into_response();
+        }
+        ([(header::WWW_AUTHENTICATE, "Bearer")], error).into_response()
+    }
+}
+
+/// The authenticated user making the request.
+///
+/// Requests without a valid access token are rejected with `401 Unauthorized`.
+/// If `auth_standard` or `auth_strict` has already authenticated the request, the
+/// user it inserted is used as is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthUser(pub AuthenticatedUser);
+
+impl Deref for AuthUser {
+    type Target = AuthenticatedUser;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<S> FromRequestParts<S> for AuthUser
+where
+    AuthVerifier: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = AuthRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(user) = parts.extensions.get::<AuthenticatedUser>() {
+            return Ok(AuthUser(user.clone()));
+        }
+
+        let token =
+            jwt::extract_jwt_from_headers(&parts.headers).map_err(AuthRejection::Missing)?;
+        let user = AuthVerifier::from_ref(state).authenticate(&token).await?;
+
+        parts.extensions.insert(user.clone());
+        Ok(AuthUser(user))
+    }
+}
+
+/// The authenticated user making the request, if any.
+///
+/// Requests without an `Authorization` header extract `None`, so that handlers can
+/// serve anonymous visitors. Requests presenting an invalid token are still
+/// rejected with `401 Unauthorized`, rather than silently treated as anonymous.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptionalAuthUser(pub Option<AuthenticatedUser>);
+
+impl<S> FromRequestParts<S> for OptionalAuthUser
+where
+    AuthVerifier: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = AuthRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if !parts.headers.contains_key(header::AUTHORIZATION)
+            && parts.extensions.get::<AuthenticatedUser>().is_none()
+        {
+            return Ok(OptionalAuthUser(None));
+        }
+
+        AuthUser::from_request_parts(parts, state)
+            .await
+            .map(|user| OptionalAuthUser(Some(user.0)))
+    }
+}
@@ -2,52 +2,48 @@
 
 use crate::dto::*;
 use crate::jwt;
-use crate::models::Authenticator;
+use crate::models::{Authenticator, OtpChannel};
 
 use axum::{
     Json,
-    extract::State,
-    http::{HeaderMap, StatusCode},
+    extract::{State, rejection::JsonRejection},
+    http::HeaderMap,
 };
+use shared::error::ApiError;
 
-/// Send OTP to the user's provided contact (e.g. email address).
+/// Send OTP to the user's provided contact (e.g. email address or phone number).
 pub async fn send_otp<A: Authenticator>(
     State(authenticator): State<A>,
-    Json(payload): Json<SendOtpRequest>,
-) -> Result<Json<MessageResponse>, (StatusCode, Json<ErrorResponse>)> {
+    payload: Result<Json<SendOtpRequest>, JsonRejection>,
+) -> Result<Json<MessageResponse>, ApiError> {
+    let Json(payload) = payload?;
+
     authenticator
-        .send_otp(&payload.contact)
+        .send_otp(payload.channel, &payload.contact)
         .await
-        .map_err(|e| {
-            (
-                StatusCode::BAD_REQUEST,
-                Json(ErrorResponse {
-                    error: format!("Failed to send OTP: {}", e),
-                }),
-            )
-        })?;
+        .map_err(Into::into)?;
+
+    let message = match payload.channel {
+        OtpChannel::Email => "OTP sent. Please check your inbox.",
+        OtpChannel::Phone => "OTP sent. Please check your messages.",
+    };
 
     Ok(Json(MessageResponse {
-        message: "OTP sent. Please check your inbox.".to_string(),
+        message: message.to_string(),
     }))
 }
 
 /// Verify OTP and return authentication tokens.
 pub async fn verify_otp<A: Authenticator>(
     State(authenticator): State<A>,
-    Json(payload): Json<VerifyOtpRequest>,
-) -> Result<Json<AuthResponse>, (StatusCode, Json<ErrorResponse>)> {
+    payload: Result<Json<VerifyOtpRequest>, JsonRejection>,
+) -> Result<Json<AuthResponse>, ApiError> {
+    let Json(payload) = payload?;
+
     let session = authenticator
-        .verify_otp(&payload.contact, &payload.token)
+        .verify_otp(payload.channel, &payload.contact, &payload.token)
         .await
-        .map_err(|e| {
-            (
-                StatusCode::BAD_REQUEST,
-                Json(ErrorResponse {
-                    error: format!("OTP verification failed: {}", e),
-                }),
-            )
-        })?;
+        .map_err(Into::into)?;
 
     Ok(Json(session.into()))
 }
@@ -56,24 +52,10 @@ pub async fn verify_otp<A: Authenticator>(
 pub async fn logout<A: Authenticator>(
     State(authenticator): State<A>,
     headers: HeaderMap,
-) -> Result<Json<MessageResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let token = jwt::extract_jwt_from_headers(&headers).map_err(|e| {
-        (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                error: format!("Logout failed: {}", e),
-            }),
-        )
-    })?;
+) -> Result<Json<MessageResponse>, ApiError> {
+    let token = jwt::extract_jwt_from_headers(&headers)?;
 
-    authenticator.logout(&token).await.map_err(|e| {
-        (
-            StatusCode::UNAUTHORIZED,
-            Json(ErrorResponse {
-                error: format!("Logout failed: {}", e),
-            }),
-        )
-    })?;
+    authenticator.logout(&token).await.map_err(Into::into)?;
 
     Ok(Json(MessageResponse {
         message: "Successfully logged out".to_string(),
@@ -84,27 +66,13 @@ pub async fn logout<A: Authenticator>(
 pub async fn refresh_token<A: Authenticator>(
     State(authenticator): State<A>,
     headers: HeaderMap,
-) -> Result<Json<AuthResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let refresh_token = jwt::extract_jwt_from_headers(&headers).map_err(|e| {
-        (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                error: format!("Token refresh failed: {}", e),
-            }),
-        )
-    })?;
+) -> Result<Json<AuthResponse>, ApiError> {
+    let refresh_token = jwt::extract_jwt_from_headers(&headers)?;
 
     let session = authenticator
         .refresh_token(&refresh_token)
         .await
-        .map_err(|e| {
-            (
-                StatusCode::UNAUTHORIZED,
-                Json(ErrorResponse {
-                    error: format!("Token refresh failed: {}", e),
-                }),
-            )
-        })?;
+        .map_err(Into::into)?;
 
     Ok(Json(session.into()))
 }
@@ -0,0 +1,463 @@
+//! Verification of asymmetrically signed JWTs using a JSON Web Key Set (JWKS).
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use jsonwebtoken::jwk::{AlgorithmParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm};
+use jsonwebtoken::{Algorithm, DecodingKey, decode, decode_header};
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+
+use super::{ClaimExpectations, Claims, HmacVerifier, JwtVerifier};
+use crate::error::AuthError;
+
+/// Default time after which the cached key set is considered stale.
+const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Minimum time between refreshes triggered by tokens with unknown key IDs,
+/// preventing such tokens from causing a fetch on every request.
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Location from which a JSON Web Key Set is loaded.
+#[derive(Debug, Clone)]
+pub enum JwksSource {
+    /// An HTTP(S) endpoint, e.g. `https://<project>.supabase.co/auth/v1/.well-known/jwks.json`.
+    Url(String),
+    /// A local JSON file.
+    File(PathBuf),
+}
+
+/// A decoding key together with the only algorithm it may be used with.
+struct CachedKey {
+    key: DecodingKey,
+    algorithm: Algorithm,
+}
+
+#[derive(Default)]
+struct KeyCache {
+    keys: HashMap<String, CachedKey>,
+    fetched_at: Option<Instant>,
+}
+
+impl KeyCache {
+    fn is_older_than(&self, age: Duration) -> bool {
+        self.fetched_at.is_none_or(|t| t.elapsed() >= age)
+    }
+}
+
+/// Verifier for tokens signed with keys published in a JSON Web Key Set.
+///
+/// Supports HS256, RS256 and ES256 keys. The key used to verify a token is selected
+/// by the `kid` in the token header, and the token's algorithm must match the one the
+/// key is published for. The key set is cached, and is refreshed when it becomes stale
+/// or when a token refers to an unknown key (e.g. after key rotation). Call
+/// [`JwksVerifier::spawn_refresh_task`] to also refresh it in the background.
+///
+/// Tokens without a `kid` are rejected, unless an HMAC fallback secret is configured
+/// for HS256 tokens with [`JwksVerifier::with_hmac_fallback`].
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use auth::jwt::JwksVerifier;
+/// use std::time::Duration;
+///
+/// # async fn example() {
+/// let verifier = JwksVerifier::from_url("https://example.supabase.co/auth/v1/.well-known/jwks.json")
+///     .with_refresh_interval(Duration::from_secs(300));
+/// verifier.refresh().await.unwrap();
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct JwksVerifier {
+    source: JwksSource,
+    client: reqwest::Client,
+    cache: Arc<RwLock<KeyCache>>,
+    refresh_interval: Duration,
+    expectations: ClaimExpectations,
+    fallback: Option<HmacVerifier>,
+}
+
+impl JwksVerifier {
+    /// Create a new JwksVerifier loading keys from the given source.
+    pub fn new(source: JwksSource) -> Self {
+        Self {
+            source,
+            client: reqwest::Client::new(),
+            cache: Arc::default(),
+            refresh_interval: DEFAULT_REFRESH_INTERVAL,
+            expectations: ClaimExpectations::default(),
+            fallback: None,
+        }
+    }
+
+    /// Create a new JwksVerifier loading keys from an HTTP(S) endpoint.
+    pub fn from_url(url: impl Into<String>) -> Self {
+        Self::new(JwksSource::Url(url.into()))
+    }
+
+    /// Create a new JwksVerifier loading keys from a local file.
+    pub fn from_file(path: impl Into<PathBuf>) -> Self {
+        Self::new(JwksSource::File(path.into()))
+    }
+
+    /// Set the time after which the cached key set is refreshed.
+    pub fn with_refresh_interval(mut self, refresh_interval: Duration) -> Self {
+        self.refresh_interval = refresh_interval;
+        self
+    }
+
+    /// Check the audience and issuer of tokens against the given expectations.
+    pub fn with_claim_expectations(mut self, expectations: ClaimExpectations) -> Self {
+        self.fallback = self
+            .fallback
+            .map(|fallback| fallback.with_claim_expectations(expectations.clone()));
+        self.expectations = expectations;
+        self
+    }
+
+    /// Verify HS256 tokens without a `kid` using the given shared secret.
+    pub fn with_hmac_fallback(mut self, secret: impl Into<String>) -> Self {
+        self.fallback =
+            Some(HmacVerifier::new(secret).with_claim_expectations(self.expectations.clone()));
+        self
+    }
+
+    /// Fetch the key set from its source and replace the cached keys.
+    ///
+    /// Keys with unsupported types or algorithms are skipped.
+    pub async fn refresh(&self) -> Result<(), AuthError> {
+        let jwks = self.fetch().await?;
+
+        let keys = jwks
+            .keys
+            .iter()
+            .filter_map(|jwk| {
+                let kid = jwk.common.key_id.clone()?;
+                let algorithm = key_algorithm(jwk)?;
+                let key = DecodingKey::from_jwk(jwk).ok()?;
+                Some((kid, CachedKey { key, algorithm }))
+            })
+            .collect();
+
+        let mut cache = self.cache.write().await;
+        cache.keys = keys;
+        cache.fetched_at = Some(Instant::now());
+        Ok(())
+    }
+
+    /// Spawn a background task that refreshes the key set every refresh interval.
+    ///
+    /// Failed refreshes are retried at the next interval; in the meantime the
+    /// previously cached keys remain in use.
+    pub fn spawn_refresh_task(&self) -> JoinHandle<()> {
+        let verifier = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(verifier.refresh_interval);
+            loop {
+                interval.tick().await;
+                if let Err(e) = verifier.refresh().await {
+                    eprintln!("JWKS refresh failed: {}", e);
+                }
+            }
+        })
+    }
+
+    async fn fetch(&self) -> Result<JwkSet, AuthError> {
+        let body = match &self.source {
+            JwksSource::Url(url) => self
+                .client
+                .get(url)
+                .send()
+                .await
+                .and_then(|r| r.error_for_status())
+                .map_err(|e| AuthError::KeyFetch(e.to_string()))?
+                .text()
+                .await
+                .map_err(|e| AuthError::KeyFetch(e.to_string()))?,
+            JwksSource::File(path) => tokio::fs::read_to_string(path)
+                .await
+                .map_err(|e| AuthError::KeyFetch(format!("{}: {}", path.display(), e)))?,
+        };
+
+        serde_json::from_str(&body).map_err(|e| AuthError::KeyFetch(e.to_string()))
+    }
+
+    /// Look up a key by ID, refreshing the cache if it is stale or the key is unknown.
+    async fn key_for(&self, kid: &str) -> Result<(DecodingKey, Algorithm), AuthError> {
+        let lookup = |cache: &KeyCache| {
+            cache
+                .keys
+                .get(kid)
+                .map(|cached| (cached.key.clone(), cached.algorithm))
+        };
+
+        let (found, stale, may_refetch) = {
+            let cache = self.cache.read().await;
+            (
+                lookup(&cache),
+                cache.is_older_than(self.refresh_interval),
+                cache.is_older_than(MIN_REFRESH_INTERVAL),
+            )
+        };
+
+        if stale || (found.is_none() && may_refetch) {
+            if let Err(e) = self.refresh().await {
+                // keep using stale keys if the source is temporarily unavailable
+                return found.ok_or(e);
+            }
+        } else if let Some(key) = found {
+            return Ok(key);
+        }
+
+        lookup(&*self.cache.read().await)
+            .ok_or_else(|| AuthError::InvalidToken(format!("Unknown key ID: {}", kid)))
+    }
+}
+
+#[async_trait]
+impl JwtVerifier for JwksVerifier {
+    async fn verify(&self, token: &str) -> Result<Claims, AuthError> {
+        let header = decode_header(token).map_err(|e| AuthError::InvalidToken(e.to_string()))?;
+
+        let Some(kid) = header.kid else {
+            return match (&self.fallback, header.alg) {
+                (Some(fallback), Algorithm::HS256) => fallback.verify(token).await,
+                _ => Err(AuthError::InvalidToken("Missing key ID".to_string())),
+            };
+        };
+
+        let (key, algorithm) = self.key_for(&kid).await?;
+        if header.alg != algorithm {
+            return Err(AuthError::InvalidToken(format!(
+                "Algorithm {:?} does not match key {}",
+                header.alg, kid
+            )));
+        }
+
+        let token = decode::<Claims>(token, &key, &self.expectations.validation(algorithm))
+            .map_err(|e| AuthError::InvalidToken(e.to_string()))?;
+        Ok(token.claims)
+    }
+}
+
+/// Determine the algorithm a JWK may be used with, if it is supported.
+///
+/// The published `alg` is used if present; otherwise it is inferred from the key type.
+fn key_algorithm(jwk: &Jwk) -> Option<Algorithm> {
+    let algorithm = match (&jwk.common.key_algorithm, &jwk.algorithm) {
+        (Some(KeyAlgorithm::HS256), _) => Algorithm::HS256,
+        (Some(KeyAlgorithm::RS256), _) => Algorithm::RS256,
+        (Some(KeyAlgorithm::ES256), _) => Algorithm::ES256,
+        (Some(_), _) => return None,
+        (None, AlgorithmParameters::OctetKey(_)) => Algorithm::HS256,
+        (None, AlgorithmParameters::RSA(_)) => Algorithm::RS256,
+        (None, AlgorithmParameters::EllipticCurve(params))
+            if params.curve == EllipticCurve::P256 =>
+        {
+            Algorithm::ES256
+        }
+        (None, _) => return None,
+    };
+
+    // reject keys published for an algorithm of a different family
+    let matches_key = matches!(
+        (algorithm, &jwk.algorithm),
+        (Algorithm::HS256, AlgorithmParameters::OctetKey(_))
+            | (Algorithm::RS256, AlgorithmParameters::RSA(_))
+            | (Algorithm::ES256, AlgorithmParameters::EllipticCurve(_))
+    );
+    matches_key.then_some(algorithm)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use axum::{Router, routing::get};
+    use jsonwebtoken::{EncodingKey, Header, encode};
+
+    use super::*;
+
+    const JWKS: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/jwks.json"
+    ));
+    const RSA_KEY: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/rsa_private.pem"
+    ));
+    const EC_KEY: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/ec_private.pem"
+    ));
+
+    fn fixture_path() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/jwks.json")
+    }
+
+    /// Create a token for user "user" expiring in an hour, signed with the given key.
+    fn create_token(algorithm: Algorithm, kid: Option<&str>, key: &EncodingKey) -> String {
+        let mut header = Header::new(algorithm);
+        header.kid = kid.map(str::to_string);
+        let claims = Claims {
+            sub: "user".to_string(),
+            exp: (chrono::Utc::now().timestamp() + 3600) as usize,
+            ..Default::default()
+        };
+        encode(&header, &claims, key).unwrap()
+    }
+
+    fn rsa_key() -> EncodingKey {
+        EncodingKey::from_rsa_pem(RSA_KEY.as_bytes()).unwrap()
+    }
+
+    fn ec_key() -> EncodingKey {
+        EncodingKey::from_ec_pem(EC_KEY.as_bytes()).unwrap()
+    }
+
+    /// Serve the fixture key set over HTTP on a random local port, counting requests.
+    async fn serve_jwks(body: &'static str) -> (String, Arc<AtomicUsize>) {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let app = Router::new().route(
+            "/jwks.json",
+            get(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async move { body }
+            }),
+        );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        (format!("http://{}/jwks.json", addr), hits)
+    }
+
+    #[tokio::test]
+    async fn test_verify_rs256_from_file() {
+        let verifier = JwksVerifier::from_file(fixture_path());
+        let token = create_token(Algorithm::RS256, Some("rsa-key"), &rsa_key());
+
+        let claims = verifier.verify(&token).await.unwrap();
+        assert_eq!(claims.sub, "user");
+    }
+
+    #[tokio::test]
+    async fn test_verify_es256_from_file() {
+        let verifier = JwksVerifier::from_file(fixture_path());
+        let token = create_token(Algorithm::ES256, Some("ec-key"), &ec_key());
+
+        let claims = verifier.verify(&token).await.unwrap();
+        assert_eq!(claims.sub, "user");
+    }
+
+    #[tokio::test]
+    async fn test_verify_hs256_from_file() {
+        let verifier = JwksVerifier::from_file(fixture_path());
+        let key = EncodingKey::from_secret(b"test-secret");
+        let token = create_token(Algorithm::HS256, Some("hmac-key"), &key);
+
+        assert!(verifier.verify(&token).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_verify_rejects_wrong_key() {
+        let verifier = JwksVerifier::from_file(fixture_path());
+        // signed with the EC key, but claims to use the RSA key
+        let token = create_token(Algorithm::ES256, Some("rsa-key"), &ec_key());
+
+        let result = verifier.verify(&token).await;
+        assert!(matches!(result, Err(AuthError::InvalidToken(_))));
+    }
+
+    #[tokio::test]
+    async fn test_verify_rejects_unknown_and_missing_kid() {
+        let verifier = JwksVerifier::from_file(fixture_path());
+
+        let token = create_token(Algorithm::RS256, Some("rotated-away"), &rsa_key());
+        assert!(matches!(
+            verifier.verify(&token).await,
+            Err(AuthError::InvalidToken(_))
+        ));
+
+        let token = create_token(Algorithm::RS256, None, &rsa_key());
+        assert!(matches!(
+            verifier.verify(&token).await,
+            Err(AuthError::InvalidToken(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_verify_hmac_fallback_without_kid() {
+        let verifier = JwksVerifier::from_file(fixture_path()).with_hmac_fallback("legacy-secret");
+        let token = create_token(
+            Algorithm::HS256,
+            None,
+            &EncodingKey::from_secret(b"legacy-secret"),
+        );
+
+        assert!(verifier.verify(&token).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_missing_file_is_key_fetch_error() {
+        let verifier = JwksVerifier::from_file("/nonexistent/jwks.json");
+        let token = create_token(Algorithm::RS256, Some("rsa-key"), &rsa_key());
+
+        assert!(matches!(
+            verifier.verify(&token).await,
+            Err(AuthError::KeyFetch(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_keys_cached_between_requests() {
+        let (url, hits) = serve_jwks(JWKS).await;
+        let verifier = JwksVerifier::from_url(url);
+        let token = create_token(Algorithm::RS256, Some("rsa-key"), &rsa_key());
+
+        for _ in 0..3 {
+            assert!(verifier.verify(&token).await.is_ok());
+        }
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_stale_keys_refreshed() {
+        let (url, hits) = serve_jwks(JWKS).await;
+        let verifier = JwksVerifier::from_url(url).with_refresh_interval(Duration::ZERO);
+        let token = create_token(Algorithm::RS256, Some("rsa-key"), &rsa_key());
+
+        assert!(verifier.verify(&token).await.is_ok());
+        assert!(verifier.verify(&token).await.is_ok());
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_kid_refetch_is_rate_limited() {
+        let (url, hits) = serve_jwks(JWKS).await;
+        let verifier = JwksVerifier::from_url(url);
+        let token = create_token(Algorithm::RS256, Some("unknown"), &rsa_key());
+
+        assert!(verifier.verify(&token).await.is_err());
+        assert!(verifier.verify(&token).await.is_err());
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_background_refresh_task() {
+        let (url, hits) = serve_jwks(JWKS).await;
+        let verifier = JwksVerifier::from_url(url).with_refresh_interval(Duration::from_millis(20));
+
+        let task = verifier.spawn_refresh_task();
+        tokio::time::sleep(Duration::from_millis(90)).await;
+        task.abort();
+
+        assert!(hits.load(Ordering::SeqCst) >= 2);
+    }
+}
@@ -0,0 +1,380 @@
+//! JWT utilities for token extraction and validation.
+
+mod jwks;
+mod verifier;
+
+use axum::http::HeaderMap;
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation, decode, encode};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::error::AuthError;
+
+pub use jwks::{JwksSource, JwksVerifier};
+pub use verifier::{HmacVerifier, JwtVerifier};
+
+/// JWT claims structure for access tokens.
+///
+/// Only `sub` and `exp` are required; the remaining claims are read if present.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Claims {
+    /// Subject (user UUID)
+    pub sub: String,
+    /// Expiration time as Unix epoch timestamp
+    pub exp: usize,
+    /// Intended audience(s) of the token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aud: Option<Audience>,
+    /// Issuer of the token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    /// Issue time as Unix epoch timestamp
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iat: Option<usize>,
+    /// Time before which the token must not be accepted, as Unix epoch timestamp
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nbf: Option<usize>,
+    /// Role of the user (e.g. `authenticated`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    /// ID of the session the token belongs to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    /// Email address of the user
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+}
+
+/// The `aud` claim, which may hold a single audience or a list of audiences.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Audience {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+/// Expected values of the audience and issuer claims of a token.
+///
+/// When an audience or issuer is expected, tokens must carry a matching claim;
+/// otherwise the claim is not checked. The expiration and not-before times are
+/// always checked.
+///
+/// # Example
+///
+/// ```rust
+/// use auth::jwt::{ClaimExpectations, HmacVerifier};
+///
+/// let expectations = ClaimExpectations::new()
+///     .audience("authenticated")
+///     .issuer("https://example.supabase.co/auth/v1");
+/// let verifier = HmacVerifier::new("jwt-secret").with_claim_expectations(expectations);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ClaimExpectations {
+    audience: Vec<String>,
+    issuer: Vec<String>,
+}
+
+impl ClaimExpectations {
+    /// Create expectations that accept any audience and issuer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accept tokens issued for the given audience. May be called repeatedly to
+    /// accept any of several audiences.
+    pub fn audience(mut self, audience: impl Into<String>) -> Self {
+        self.audience.push(audience.into());
+        self
+    }
+
+    /// Accept tokens from the given issuer. May be called repeatedly to accept
+    /// any of several issuers.
+    pub fn issuer(mut self, issuer: impl Into<String>) -> Self {
+        self.issuer.push(issuer.into());
+        self
+    }
+
+    /// Build the validation rules for a token signed with the given algorithm.
+    pub(crate) fn validation(&self, algorithm: Algorithm) -> Validation {
+        let mut validation = Validation::new(algorithm);
+        validation.validate_nbf = true;
+
+        if self.audience.is_empty() {
+            validation.validate_aud = false;
+        } else {
+            validation.set_audience(&self.audience);
+            validation.set_required_spec_claims(&["exp", "aud"]);
+        }
+
+        if !self.issuer.is_empty() {
+            validation.set_issuer(&self.issuer);
+            validation.required_spec_claims.insert("iss".to_string());
+        }
+
+        validation
+    }
+}
+
+/// Extract JWT from Authorization header.
+///
+/// Expects the header to be in the format: `Authorization: Bearer <token>`
+pub fn extract_jwt_from_headers(headers: &HeaderMap) -> Result<String, AuthError> {
+    let auth_header = headers
+        .get("authorization")
+        .ok_or(AuthError::MissingAuthHeader)?;
+
+    let auth_value = auth_header
+        .to_str()
+        .map_err(|_| AuthError::InvalidAuthHeader)?;
+
+    let token = auth_value
+        .strip_prefix("Bearer ")
+        .ok_or(AuthError::InvalidAuthHeader)?;
+
+    Ok(token.to_string())
+}
+
+/// Verify JWT using HMAC signature verification.
+///
+/// Verifies the token signature, expiration and not-before times; the audience
+/// and issuer are not checked.
+pub fn validate_jwt_hmac(token: &str, secret: &str) -> Result<Claims, AuthError> {
+    decode_jwt_hmac(token, secret, &ClaimExpectations::default())
+}
+
+/// Verify JWT using HMAC signature verification, decoding into custom claims.
+///
+/// Behaves like [`validate_jwt_hmac`], but additionally checks the audience and
+/// issuer against `expectations`, and allows backends that embed additional
+/// claims in their tokens to read them back.
+pub fn decode_jwt_hmac<T: DeserializeOwned>(
+    token: &str,
+    secret: &str,
+    expectations: &ClaimExpectations,
+) -> Result<T, AuthError> {
+    let key = DecodingKey::from_secret(secret.as_ref());
+
+    // decode will result in an error if the token or signature is invalid,
+    // the token has invalid base64, or validation of a reserved claim fails
+    let token = decode::<T>(token, &key, &expectations.validation(Algorithm::HS256))
+        .map_err(|e| AuthError::InvalidToken(e.to_string()))?;
+    Ok(token.claims)
+}
+
+/// Sign a set of claims as an HS256 JWT using the provided secret.
+///
+/// Tokens issued by this function can be validated with [`validate_jwt_hmac`].
+pub fn issue_jwt_hmac<T: Serialize>(claims: &T, secret: &str) -> Result<String, AuthError> {
+    let key = EncodingKey::from_secret(secret.as_ref());
+
+    encode(&Header::default(), claims, &key).map_err(|e| AuthError::InvalidToken(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+    use base64::{Engine, prelude::BASE64_STANDARD_NO_PAD};
+    use jsonwebtoken::{Algorithm, errors::Error as JwtError};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use super::*;
+
+    /// Create an HS256 encrypted test JWT using the given secret, with specified
+    /// expiration offset `exp` in seconds, and UUID `sub` set to "user."
+    fn create_test_jwt(secret: &str, offset: i32) -> Result<String, JwtError> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i32;
+
+        encode(
+            &Header::new(Algorithm::HS256),
+            &Claims {
+                sub: "user".to_string(),
+                exp: (now + offset) as usize,
+                ..Default::default()
+            },
+            &EncodingKey::from_secret(secret.as_ref()),
+        )
+    }
+
+    #[test]
+    fn test_extract_jwt_from_headers_success() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "authorization",
+            HeaderValue::from_str("Bearer test-token").unwrap(),
+        );
+
+        let result = extract_jwt_from_headers(&headers);
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), "test-token");
+    }
+
+    #[test]
+    fn test_extract_jwt_from_headers_missing() {
+        let headers = HeaderMap::new();
+
+        let result = extract_jwt_from_headers(&headers);
+        assert!(matches!(result, Err(AuthError::MissingAuthHeader)));
+    }
+
+    #[test]
+    fn test_extract_jwt_from_headers_invalid_format() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "authorization",
+            HeaderValue::from_str("Token test-token").unwrap(),
+        );
+
+        let result = extract_jwt_from_headers(&headers);
+        assert!(matches!(result, Err(AuthError::InvalidAuthHeader)));
+    }
+
+    #[test]
+    fn test_validate_jwt_hmac_valid() {
+        let secret = "test-secret";
+        let token = create_test_jwt(secret, 3600).unwrap();
+
+        let result = validate_jwt_hmac(&token, secret);
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().sub, "user");
+    }
+
+    #[test]
+    fn test_validate_jwt_hmac_expired() {
+        let secret = "test-secret";
+        let token = create_test_jwt(secret, -3600).unwrap();
+
+        let result = validate_jwt_hmac(&token, secret);
+        assert!(matches!(result, Err(AuthError::InvalidToken(_))));
+    }
+
+    #[test]
+    fn test_tampered_jwt_invalid() {
+        let secret = "test-secret";
+        let token = create_test_jwt(secret, 3600).unwrap();
+
+        let parts: Vec<&str> = token.split('.').collect();
+        assert_eq!(parts.len(), 3); // the JWT should have three components
+
+        // decode the body and convert to a JSON Value
+        let mut payload = BASE64_STANDARD_NO_PAD.decode(parts[1]).unwrap();
+        let mut payload_json: serde_json::Value = serde_json::from_slice(&payload).unwrap();
+
+        // tamper with the payload and convert back to token
+        payload_json["sub"] = serde_json::Value::String("admin".to_string());
+        payload = serde_json::to_vec(&payload_json).unwrap();
+
+        let tampered_token = format!(
+            "{}.{}.{}",
+            parts[0],
+            BASE64_STANDARD_NO_PAD.encode(payload),
+            parts[2]
+        );
+
+        let result = validate_jwt_hmac(&tampered_token, secret);
+        assert!(matches!(result, Err(AuthError::InvalidToken(_))));
+    }
+
+    #[test]
+    fn test_issue_jwt_hmac_roundtrip() {
+        let secret = "test-secret";
+        let claims = Claims {
+            sub: "user".to_string(),
+            exp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs() as usize
+                + 3600,
+            ..Default::default()
+        };
+        let token = issue_jwt_hmac(&claims, secret).unwrap();
+
+        let result = validate_jwt_hmac(&token, secret);
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().sub, "user");
+    }
+
+    #[test]
+    fn test_validate_jwt_hmac_wrong_secret() {
+        let token = create_test_jwt("correct-secret", 3600).unwrap();
+        let result = validate_jwt_hmac(&token, "wrong-secret");
+        assert!(matches!(result, Err(AuthError::InvalidToken(_))));
+    }
+
+    /// Issue an HS256 token for "user" expiring in an hour, with the given audience and issuer.
+    fn create_token_for(aud: Option<&str>, iss: Option<&str>) -> String {
+        let claims = Claims {
+            sub: "user".to_string(),
+            exp: (chrono::Utc::now().timestamp() + 3600) as usize,
+            aud: aud.map(|aud| Audience::Single(aud.to_string())),
+            iss: iss.map(str::to_string),
+            ..Default::default()
+        };
+        issue_jwt_hmac(&claims, "test-secret").unwrap()
+    }
+
+    #[test]
+    fn test_audience_and_issuer_ignored_unless_expected() {
+        let token = create_token_for(Some("authenticated"), Some("issuer"));
+        assert!(validate_jwt_hmac(&token, "test-secret").is_ok());
+    }
+
+    #[test]
+    fn test_audience_and_issuer_checked_when_expected() {
+        let expectations = ClaimExpectations::new()
+            .audience("authenticated")
+            .issuer("issuer");
+        let decode = |token: &str| decode_jwt_hmac::<Claims>(token, "test-secret", &expectations);
+
+        let claims = decode(&create_token_for(Some("authenticated"), Some("issuer"))).unwrap();
+        assert_eq!(claims.aud, Some(Audience::Single("authenticated".into())));
+        assert_eq!(claims.iss.as_deref(), Some("issuer"));
+
+        assert!(decode(&create_token_for(Some("other"), Some("issuer"))).is_err());
+        assert!(decode(&create_token_for(Some("authenticated"), Some("other"))).is_err());
+        assert!(decode(&create_token_for(None, Some("issuer"))).is_err());
+        assert!(decode(&create_token_for(Some("authenticated"), None)).is_err());
+    }
+
+    #[test]
+    fn test_audience_list_accepted() {
+        let token = issue_jwt_hmac(
+            &serde_json::json!({
+                "sub": "user",
+                "exp": chrono::Utc::now().timestamp() + 3600,
+                "aud": ["other", "authenticated"],
+            }),
+            "test-secret",
+        )
+        .unwrap();
+
+        let expectations = ClaimExpectations::new().audience("authenticated");
+        let claims: Claims = decode_jwt_hmac(&token, "test-secret", &expectations).unwrap();
+        assert_eq!(
+            claims.aud,
+            Some(Audience::Multiple(vec![
+                "other".into(),
+                "authenticated".into()
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_token_not_yet_valid_rejected() {
+        let now = chrono::Utc::now().timestamp() as usize;
+        let claims = Claims {
+            sub: "user".to_string(),
+            exp: now + 3600,
+            nbf: Some(now + 600),
+            ..Default::default()
+        };
+        let token = issue_jwt_hmac(&claims, "test-secret").unwrap();
+
+        assert!(matches!(
+            validate_jwt_hmac(&token, "test-secret"),
+            Err(AuthError::InvalidToken(_))
+        ));
+    }
+}
@@ -0,0 +1,61 @@
+//! Verification of JWT signatures and standard claims.
+
+use async_trait::async_trait;
+
+use super::{ClaimExpectations, Claims, decode_jwt_hmac};
+use crate::error::AuthError;
+
+/// Trait for types that verify JWTs locally, without contacting the authentication backend.
+///
+/// Implementations check the token signature and its reserved claims (such as
+/// expiration) and return the decoded claims.
+#[async_trait]
+pub trait JwtVerifier: Send + Sync + 'static {
+    /// Verify a token and return its claims.
+    ///
+    /// # Returns
+    /// * `Ok(Claims)` if the token is valid
+    /// * `Err(AuthError)` if the token is invalid or could not be verified
+    async fn verify(&self, token: &str) -> Result<Claims, AuthError>;
+}
+
+/// Verifier for HS256 tokens signed with a shared secret.
+///
+/// # Example
+///
+/// ```rust
+/// use auth::jwt::HmacVerifier;
+///
+/// let verifier = HmacVerifier::new("jwt-secret");
+/// ```
+#[derive(Clone)]
+pub struct HmacVerifier {
+    secret: String,
+    expectations: ClaimExpectations,
+}
+
+impl HmacVerifier {
+    /// Create a new HmacVerifier using the given secret.
+    ///
+    /// The audience and issuer of tokens are not checked unless configured with
+    /// [`HmacVerifier::with_claim_expectations`].
+    pub fn new(secret: impl Into<String>) -> Self {
+        Self {
+            secret: secret.into(),
+            expectations: ClaimExpectations::default(),
+        }
+    }
+
+    /// Check the audience and issuer of tokens against the given expectations.
+    pub fn with_claim_expectations(mut self, expectations: ClaimExpectations) -> Self {
+        self.expectations = expectations;
+        self
+    }
+}
+
+#[async_trait]
+impl JwtVerifier for HmacVerifier {
+    async fn verify(&self, token: &str) -> Result<Claims, AuthError> {
+        decode_jwt_hmac(token, &self.secret, &self.expectations)
+    }
+}
@@ -8,7 +8,8 @@
 //! - OTP-based authentication
 //! - JWT token management (access & refresh tokens)
 //! - Flexible authentication backends (Supabase included)
-//! - Authentication middleware for route protection
+//! - Authentication middleware and extractors for route protection
+//! - Role-based authorization for moderator and admin routes
 //! - Type-safe error handling
 //!
 //! ## Quick Start
@@ -33,16 +34,20 @@ use axum::{Router, routing::post};
 
 mod dto;
 mod handlers;
-mod jwt;
-mod error;
 
-pub mod models;
+pub mod authorization;
+pub mod error;
+pub mod extract;
+pub mod jwt;
 pub mod middleware;
+pub mod models;
+pub mod rate_limit;
+pub mod sender;
 
 /// Creates an authentication router with the standard endpoints using the provided authenticator.
 ///
 /// The router includes the following endpoints:
-///  - `POST /send-otp` - send OTP to user via their contact information; defaults to email
+///  - `POST /send-otp` - send OTP to user via their contact information over email (default) or phone
 ///  - `POST /verify-otp` - verify OTP and retrieve access and refresh tokens
 ///  - `POST /logout` - invalidate associated session
 ///  - `POST /refresh` - refresh access token using refresh token
@@ -1,22 +1,34 @@
 //! Authentication middleware for protecting routes.
 
-use axum::Json;
 use axum::extract::{Request, State};
-use axum::http::StatusCode;
 use axum::middleware::Next;
 use axum::response::Response;
+use serde_json::json;
+use shared::error::{ApiError, ErrorCode};
+use shared::types::moderation::{Sanction, SanctionKind};
 
-use crate::models::Authenticator;
-use crate::{dto, jwt};
+use crate::jwt;
+use crate::models::{AuthenticatedUser, Authenticator};
 
 /// Standard authentication middleware that validates JWT tokens locally.
 ///
-/// This middleware validates JWT tokens using HMAC signature verification
-/// but does not check against the authentication backend. Use this for
+/// This middleware validates JWT tokens locally using the authenticator's
+/// [`JwtVerifier`](crate::jwt::JwtVerifier), without asking the authentication
+/// backend whether the session is still active. It does ask the authenticator
+/// whether the user is suspended or banned, through
+/// [`Authenticator::barring_sanction`]; the database-backed authenticators cache
+/// the answer for a short time, so most requests cost no query. Use this for
 /// most authentication needs where performance is important.
 ///
-/// The validated user UUID is inserted into request extensions and can be
-/// accessed in handlers using `axum::Extension`.
+/// The validated token's claims are inserted into request extensions as an
+/// [`AuthenticatedUser`], which can be accessed in handlers using
+/// `axum::Extension<AuthenticatedUser>`.
+///
+/// Requests without a bearer token are rejected with `400 Bad Request` and the
+/// `missing_credentials` code, and invalid tokens with `401 Unauthorized` and the
+/// `invalid_token` code. Users who are suspended or banned are rejected with
+/// `403 Forbidden` and the `account_suspended` code, with the `sanction` and
+/// when it `expires_at` in the error details.
 ///
 /// # Example
 ///
@@ -37,35 +49,12 @@ pub async fn auth_standard<A: Authenticator>(
     State(authenticator): State<A>,
     mut request: Request,
     next: Next,
-) -> Result<Response, (StatusCode, Json<dto::ErrorResponse>)> {
-    let token = jwt::extract_jwt_from_headers(request.headers()).map_err(|e| {
-        (
-            StatusCode::BAD_REQUEST,
-            Json(dto::ErrorResponse {
-                error: e.to_string(),
-            }),
-        )
-    })?;
-
-    let claims = jwt::validate_jwt_hmac(&token, authenticator.jwt_secret()).map_err(|e| {
-        (
-            StatusCode::UNAUTHORIZED,
-            Json(dto::ErrorResponse {
-                error: e.to_string(),
-            }),
-        )
-    })?;
+) -> Result<Response, ApiError> {
+    let token = jwt::extract_jwt_from_headers(request.headers())?;
 
-    let user_id = uuid::Uuid::parse_str(&claims.sub).map_err(|e| {
-        (
-            StatusCode::UNAUTHORIZED,
-            Json(dto::ErrorResponse {
-                error: format!("Invalid user ID in token: {}", e),
-            }),
-        )
-    })?;
+    let user = verify_locally(&authenticator, &token).await?;
 
-    request.extensions_mut().insert(user_id);
+    request.extensions_mut().insert(user);
     Ok(next.run(request).await)
 }
 
@@ -76,8 +65,12 @@ pub async fn auth_standard<A: Authenticator>(
 /// endpoints that require the highest level of security, though it comes with a
 /// performance cost.
 ///
-/// The validated user UUID is inserted into request extensions and can be
-/// accessed in handlers using `axum::Extension`.
+/// The token is first validated locally like in [`auth_standard`], and the
+/// resulting [`AuthenticatedUser`] is inserted into request extensions once the
+/// backend has confirmed the session. Sessions the backend no longer knows are
+/// rejected with the error code chosen by the authenticator's error conversion,
+/// typically `session_inactive`, and suspended or banned users as in
+/// [`auth_standard`].
 ///
 /// # Example
 ///
@@ -98,25 +91,69 @@ pub async fn auth_strict<A: Authenticator>(
     State(authenticator): State<A>,
     mut request: Request,
     next: Next,
-) -> Result<Response, (StatusCode, Json<dto::ErrorResponse>)> {
-    let token = jwt::extract_jwt_from_headers(request.headers()).map_err(|e| {
-        (
-            StatusCode::BAD_REQUEST,
-            Json(dto::ErrorResponse {
-                error: e.to_string(),
-            }),
-        )
-    })?;
+) -> Result<Response, ApiError> {
+    let token = jwt::extract_jwt_from_headers(request.headers())?;
+
+    let user = verify_locally(&authenticator, &token).await?;
+
+    let user_id = authenticator
+        .verify_token(&token)
+        .await
+        .map_err(Into::into)?;
 
-    let user_id = authenticator.verify_token(&token).await.map_err(|e| {
-        (
-            StatusCode::UNAUTHORIZED,
-            Json(dto::ErrorResponse {
-                error: format!("Token verification failed: {}", e),
-            }),
-        )
-    })?;
+    if user_id != user.id {
+        return Err(ApiError::new(
+            ErrorCode::InvalidToken,
+            "Session belongs to another user",
+        ));
+    }
 
-    request.extensions_mut().insert(user_id);
+    request.extensions_mut().insert(user);
     Ok(next.run(request).await)
 }
+
+/// Validate a token with the authenticator's JWT verifier and build the user from its claims.
+///
+/// Users barred from the service by a suspension or ban are rejected.
+async fn verify_locally<A: Authenticator>(
+    authenticator: &A,
+    token: &str,
+) -> Result<AuthenticatedUser, ApiError> {
+    let claims = authenticator.jwt_verifier().verify(token).await?;
+    let user = AuthenticatedUser::try_from(claims)?;
+    ensure_not_barred(authenticator, &user).await?;
+    Ok(user)
+}
+
+/// Reject users barred from the service by a suspension or ban.
+///
+/// Shared by the middleware and the [`AuthUser`](crate::extract::AuthUser)
+/// extractors so that both reject with `403 Forbidden` and the same details.
+pub(crate) async fn ensure_not_barred<A: Authenticator>(
+    authenticator: &A,
+    user: &AuthenticatedUser,
+) -> Result<(), ApiError> {
+    match authenticator
+        .barring_sanction(user.id)
+        .await
+        .map_err(Into::into)?
+    {
+        Some(sanction) => Err(barred_error(&sanction)),
+        None => Ok(()),
+    }
+}
+
+/// The error rejecting a user barred from the service by the given sanction.
+///
+/// Services that keep serving users after authenticating them, such as over a
+/// WebSocket, use this to report sanctions imposed in the meantime the same way.
+pub fn barred_error(sanction: &Sanction) -> ApiError {
+    let message = match sanction.kind {
+        SanctionKind::Ban => "Account banned",
+        _ => "Account suspended",
+    };
+    ApiError::new(ErrorCode::AccountSuspended, message).with_details(json!({
+        "sanction": sanction.kind,
+        "expires_at": sanction.expires_at,
+    }))
+}
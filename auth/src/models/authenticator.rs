@@ -1,6 +1,55 @@
 //! Core authentication traits defining the interface for authentication backends.
 
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use shared::error::ApiError;
+use shared::types::moderation::Sanction;
+
+use crate::jwt::JwtVerifier;
+
+/// The channel over which an OTP is delivered, and thus the kind of contact
+/// information used to identify the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OtpChannel {
+    /// An email address.
+    #[default]
+    Email,
+    /// A phone number in E.164 format (e.g. `+14155550123`).
+    Phone,
+}
+
+impl OtpChannel {
+    /// Normalize contact information for this channel.
+    ///
+    /// Email addresses are trimmed and lowercased. Phone numbers have common
+    /// separators removed and must then be in E.164 format. Returns `None`
+    /// if the contact is not valid for this channel.
+    pub fn normalize(&self, contact: &str) -> Option<String> {
+        let contact = contact.trim();
+        match self {
+            OtpChannel::Email => {
+                let (local, domain) = contact.split_once('@')?;
+                if local.is_empty() || domain.is_empty() || domain.contains('@') {
+                    return None;
+                }
+                Some(contact.to_lowercase())
+            }
+            OtpChannel::Phone => {
+                let digits: String = contact
+                    .strip_prefix('+')?
+                    .chars()
+                    .filter(|c| !matches!(c, ' ' | '-' | '.' | '(' | ')'))
+                    .collect();
+                if !(8..=15).contains(&digits.len()) || !digits.chars().all(|c| c.is_ascii_digit())
+                {
+                    return None;
+                }
+                Some(format!("+{}", digits))
+            }
+        }
+    }
+}
 
 /// Trait for types that represent an authenticated session.
 ///
@@ -15,6 +64,14 @@ pub trait AuthSession {
 
     // Returns the expiration time as a Unix epoch timestamp.
     fn expires_at(&self) -> u64;
+
+    /// Returns whether the session's user signed in for the first time, and so has
+    /// a newly created profile.
+    ///
+    /// Only sessions created by verifying an OTP can be new; refreshed sessions are not.
+    fn is_new_user(&self) -> bool {
+        false
+    }
 }
 
 /// Trait for authentication backends that handle JWT and OTP-based authentication.
@@ -24,40 +81,52 @@ pub trait AuthSession {
 /// and token operations.
 #[async_trait]
 pub trait Authenticator: Clone + Send + Sync + 'static {
-
     /// The error type returned by authentication operations.
-    type Error: std::error::Error + Send + Sync + 'static;
+    ///
+    /// Errors are returned to clients as an [`ApiError`], so the conversion
+    /// decides which error code and message they see.
+    type Error: std::error::Error + Send + Sync + 'static + Into<ApiError>;
 
     /// The session type containing authentication tokens and metadata.
     type Session: AuthSession + Send + Sync + 'static;
 
-    /// Returns the JWT secret used for token signatures.
+    /// Returns the verifier for access tokens issued by this backend.
     ///
-    /// This secret is used by middleware for local JWT validation without
+    /// The verifier is used by middleware for local JWT validation without
     /// requiring a call to the authentication backend.
-    fn jwt_secret(&self) -> &str;
+    fn jwt_verifier(&self) -> &dyn JwtVerifier;
 
     /// Send an OTP (One-Time Password) to the specified contact.
     ///
     /// # Arguments
+    /// * `channel` - The channel over which the OTP is delivered
     /// * `contact` - The contact information to which the OTP is sent
     ///
     /// # Returns
     /// * `Ok(())` if the OTP was sent successfully
     /// * `Err(Self::Error)` if the operation failed
-    async fn send_otp(&self, contact: &str) -> Result<(), Self::Error>;
+    async fn send_otp(&self, channel: OtpChannel, contact: &str) -> Result<(), Self::Error>;
 
     /// Verify an OTP and create an authenticated session.
     ///
+    /// The user's public profile is created on their first sign-in, which the
+    /// session reports through [`AuthSession::is_new_user`].
+    ///
     /// # Arguments
+    /// * `channel` - The channel over which the OTP was delivered
     /// * `contact` - The contact information to which the OTP was sent
     /// * `token` - The OTP token to verify
     ///
     /// # Returns
     /// * `Ok(Self::Session)` with the new session if verification succeeded
     /// * `Err(Self::Error)` if verification failed
-    async fn verify_otp(&self, contact: &str, token: &str) -> Result<Self::Session, Self::Error>;
-    
+    async fn verify_otp(
+        &self,
+        channel: OtpChannel,
+        contact: &str,
+        token: &str,
+    ) -> Result<Self::Session, Self::Error>;
+
     /// Log out a user by invalidating their session.
     ///
     /// # Arguments
@@ -69,7 +138,7 @@ pub trait Authenticator: Clone + Send + Sync + 'static {
     /// token was invalid or there exists no session associated to the token
     async fn logout(&self, bearer_token: &str) -> Result<(), Self::Error>;
 
-    /// Refresh an access token using a refresh token. 
+    /// Refresh an access token using a refresh token.
     ///
     /// # Arguments
     /// * `refresh_token` - The refresh token to use for getting a new access token
@@ -87,8 +156,8 @@ pub trait Authenticator: Clone + Send + Sync + 'static {
     ///
     /// Use this method when you need the highest level of security assurance,
     /// such as for administrative operations or sensitive data access. Otherwise,
-    /// JWT validation is not implementation-specific but instead encryption 
-    /// algorithm specific, so that `crate::verify::validate_jwt_hmac` can be used.
+    /// JWT validation is not implementation-specific but instead encryption
+    /// algorithm specific, so that `Self::jwt_verifier` can be used.
     ///
     /// # Arguments
     /// * `access_token` - The access token to verify
@@ -97,4 +166,51 @@ pub trait Authenticator: Clone + Send + Sync + 'static {
     /// * `Ok(uuid::Uuid)` with the user ID if the token is valid
     /// * `Err(Self::Error)` if the token is invalid or verification failed
     async fn verify_token(&self, access_token: &str) -> Result<uuid::Uuid, Self::Error>;
+
+    /// Return the suspension or ban currently barring a user from the service, if any.
+    ///
+    /// The authentication middleware calls this on every request, rejecting users
+    /// who are barred, so backends looking sanctions up in a database should cache
+    /// them briefly. Backends that do not track sanctions bar no one.
+    ///
+    /// # Arguments
+    /// * `user_id` - The ID of the authenticated user
+    ///
+    /// # Returns
+    /// * `Ok(Some(Sanction))` with the sanction if the user is barred
+    /// * `Ok(None)` if the user is not barred
+    /// * `Err(Self::Error)` if the sanctions could not be looked up
+    async fn barring_sanction(&self, user_id: uuid::Uuid) -> Result<Option<Sanction>, Self::Error> {
+        let _ = user_id;
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_email() {
+        let channel = OtpChannel::Email;
+        assert_eq!(
+            channel.normalize(" User@Example.com "),
+            Some("user@example.com".into())
+        );
+        assert_eq!(channel.normalize("user.example.com"), None);
+        assert_eq!(channel.normalize("@example.com"), None);
+        assert_eq!(channel.normalize("a@b@example.com"), None);
+    }
+
+    #[test]
+    fn test_normalize_phone() {
+        let channel = OtpChannel::Phone;
+        assert_eq!(
+            channel.normalize("+1 (415) 555-0123"),
+            Some("+14155550123".into())
+        );
+        assert_eq!(channel.normalize("4155550123"), None);
+        assert_eq!(channel.normalize("+1415abc0123"), None);
+        assert_eq!(channel.normalize("+123"), None);
+    }
 }
@@ -0,0 +1,594 @@
+//! In-memory authentication backend for tests.
+
+use crate::error::AuthError;
+use crate::jwt::{self, Claims, HmacVerifier, JwtVerifier};
+use crate::models::{AuthSession, Authenticator, OtpChannel};
+
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use rand::Rng;
+use shared::error::{ApiError, ErrorCode};
+use shared::types::moderation::Sanction;
+use shared::types::role::Role;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+use uuid::Uuid;
+
+/// Lifetime of access tokens minted by the mock.
+const ACCESS_TOKEN_TTL: Duration = Duration::hours(1);
+
+/// Errors returned by [`MockAuthenticator`].
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum MockAuthError {
+    #[error("Invalid contact information for channel {0:?}")]
+    InvalidContact(OtpChannel),
+
+    #[error("Invalid or expired OTP")]
+    InvalidOtp,
+
+    #[error("Unknown session")]
+    UnknownSession,
+
+    #[error("Session has expired")]
+    SessionExpired,
+
+    #[error("Token has been revoked")]
+    TokenRevoked,
+
+    #[error("Invalid JWT token: {0}")]
+    InvalidToken(String),
+
+    #[error("Scripted failure: {0}")]
+    Scripted(String),
+}
+
+impl From<MockAuthError> for ApiError {
+    fn from(error: MockAuthError) -> Self {
+        let code = match error {
+            MockAuthError::InvalidContact(_) => ErrorCode::InvalidContact,
+            MockAuthError::InvalidOtp => ErrorCode::InvalidOtp,
+            MockAuthError::UnknownSession
+            | MockAuthError::SessionExpired
+            | MockAuthError::TokenRevoked => ErrorCode::SessionInactive,
+            MockAuthError::InvalidToken(_) => ErrorCode::InvalidToken,
+            MockAuthError::Scripted(_) => {
+                return ApiError::new(ErrorCode::UpstreamError, "Authentication provider failed")
+                    .with_source(error);
+            }
+        };
+        ApiError::new(code, error.to_string())
+    }
+}
+
+impl From<AuthError> for MockAuthError {
+    fn from(value: AuthError) -> Self {
+        MockAuthError::InvalidToken(value.to_string())
+    }
+}
+
+/// Session issued by [`MockAuthenticator`].
+#[derive(Debug, Clone)]
+pub struct MockSession {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub expires_at: u64,
+    pub is_new_user: bool,
+}
+
+impl AuthSession for MockSession {
+    fn access_token(&self) -> &str {
+        &self.access_token
+    }
+
+    fn refresh_token(&self) -> &str {
+        &self.refresh_token
+    }
+
+    fn expires_at(&self) -> u64 {
+        self.expires_at
+    }
+
+    fn is_new_user(&self) -> bool {
+        self.is_new_user
+    }
+}
+
+/// An OTP recorded by [`MockAuthenticator::send_otp`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SentOtp {
+    pub channel: OtpChannel,
+    pub contact: String,
+    pub code: String,
+}
+
+/// Lifecycle state of a session held by the mock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SessionState {
+    Active,
+    Expired,
+    Revoked,
+}
+
+#[derive(Debug)]
+struct MockSessionRecord {
+    user_id: Uuid,
+    email: Option<String>,
+    access_token: String,
+    refresh_token: String,
+    state: SessionState,
+}
+
+#[derive(Default)]
+struct MockState {
+    sent_otps: Vec<SentOtp>,
+    outstanding_otps: HashMap<(OtpChannel, String), String>,
+    users: HashMap<String, Uuid>,
+    roles: HashMap<Uuid, Role>,
+    sanctions: Vec<Sanction>,
+    sessions: Vec<MockSessionRecord>,
+    scripted_failures: VecDeque<MockAuthError>,
+}
+
+impl MockState {
+    fn session_by_access_token(&mut self, token: &str) -> Option<&mut MockSessionRecord> {
+        self.sessions.iter_mut().find(|s| s.access_token == token)
+    }
+}
+
+/// In-memory authenticator for testing handlers and routes without an external service.
+///
+/// The mock mints real HS256 access tokens signed with a configurable secret, so
+/// tokens it issues pass `auth_standard` and [`HmacVerifier`]. Sent OTPs are
+/// recorded so that tests can read them back, and sessions can be expired or revoked
+/// to exercise failure paths of `auth_strict` and the refresh and logout endpoints.
+/// Arbitrary failures can be scripted with [`MockAuthenticator::fail_next`].
+///
+/// Clones share state, so a clone handed to a router can be inspected from the test.
+///
+/// This type is only available with the `mock` feature.
+///
+/// # Example
+///
+/// ```rust
+/// use auth::models::{Authenticator, MockAuthenticator, OtpChannel};
+///
+/// # tokio_test::block_on(async {
+/// let authenticator = MockAuthenticator::new("test-secret");
+/// authenticator.send_otp(OtpChannel::Email, "user@example.com").await.unwrap();
+///
+/// let code = authenticator.last_otp("user@example.com").unwrap();
+/// let session = authenticator
+///     .verify_otp(OtpChannel::Email, "user@example.com", &code)
+///     .await
+///     .unwrap();
+///
+/// authenticator.revoke(&session.access_token);
+/// assert!(authenticator.verify_token(&session.access_token).await.is_err());
+/// # });
+/// ```
+#[derive(Clone)]
+pub struct MockAuthenticator {
+    jwt_secret: String,
+    verifier: HmacVerifier,
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockAuthenticator {
+    /// Create a new MockAuthenticator that signs tokens with the given secret.
+    pub fn new(jwt_secret: impl Into<String>) -> Self {
+        let jwt_secret = jwt_secret.into();
+        Self {
+            verifier: HmacVerifier::new(jwt_secret.clone()),
+            jwt_secret,
+            state: Arc::default(),
+        }
+    }
+
+    /// Returns all OTPs sent so far, oldest first.
+    pub fn sent_otps(&self) -> Vec<SentOtp> {
+        self.state.lock().unwrap().sent_otps.clone()
+    }
+
+    /// Returns the most recent OTP code sent to the given contact.
+    pub fn last_otp(&self, contact: &str) -> Option<String> {
+        self.state
+            .lock()
+            .unwrap()
+            .sent_otps
+            .iter()
+            .rev()
+            .find(|otp| otp.contact == contact)
+            .map(|otp| otp.code.clone())
+    }
+
+    /// Returns the user ID assigned to a contact, if the contact has signed in.
+    pub fn user_id(&self, contact: &str) -> Option<Uuid> {
+        self.state.lock().unwrap().users.get(contact).copied()
+    }
+
+    /// Grant a role to a user. Access tokens minted for the user from now on claim it.
+    pub fn grant_role(&self, user_id: Uuid, role: Role) {
+        self.state.lock().unwrap().roles.insert(user_id, role);
+    }
+
+    /// Impose a sanction on its user. Suspensions and bans bar the user until they lapse.
+    pub fn sanction(&self, sanction: Sanction) {
+        self.state.lock().unwrap().sanctions.push(sanction);
+    }
+
+    /// Create an active session for a user directly, bypassing the OTP flow.
+    pub fn create_session(&self, user_id: Uuid) -> MockSession {
+        self.start_session(user_id, None)
+    }
+
+    /// Mint an access token for a user that expires `ttl` from now, without creating
+    /// a session. A negative `ttl` produces a token that has already expired.
+    pub fn mint_access_token(&self, user_id: Uuid, ttl: Duration) -> String {
+        self.mint_session(user_id, None, ttl).access_token
+    }
+
+    /// Sign arbitrary claims with the mock's secret, without creating a session.
+    ///
+    /// Useful for exercising claim validation and claim-dependent handlers.
+    pub fn mint_token(&self, claims: &Claims) -> String {
+        jwt::issue_jwt_hmac(claims, &self.jwt_secret)
+            .expect("HS256 encoding of mock claims cannot fail")
+    }
+
+    fn start_session(&self, user_id: Uuid, email: Option<String>) -> MockSession {
+        let session = self.mint_session(user_id, email.clone(), ACCESS_TOKEN_TTL);
+        self.state.lock().unwrap().sessions.push(MockSessionRecord {
+            user_id,
+            email,
+            access_token: session.access_token.clone(),
+            refresh_token: session.refresh_token.clone(),
+            state: SessionState::Active,
+        });
+        session
+    }
+
+    /// Mark the session of an access token as expired.
+    ///
+    /// The token still passes local JWT validation, but backend verification and
+    /// refreshing the session fail with [`MockAuthError::SessionExpired`].
+    pub fn expire(&self, access_token: &str) {
+        self.set_session_state(access_token, SessionState::Expired);
+    }
+
+    /// Mark the session of an access token as revoked.
+    ///
+    /// The token still passes local JWT validation, but backend verification,
+    /// refreshing and logging out fail with [`MockAuthError::TokenRevoked`].
+    pub fn revoke(&self, access_token: &str) {
+        self.set_session_state(access_token, SessionState::Revoked);
+    }
+
+    /// Make the next authenticator operation fail with the given error.
+    ///
+    /// Failures are queued, so calling this repeatedly scripts consecutive failures.
+    pub fn fail_next(&self, error: MockAuthError) {
+        self.state
+            .lock()
+            .unwrap()
+            .scripted_failures
+            .push_back(error);
+    }
+
+    fn set_session_state(&self, access_token: &str, state: SessionState) {
+        if let Some(session) = self
+            .state
+            .lock()
+            .unwrap()
+            .session_by_access_token(access_token)
+        {
+            session.state = state;
+        }
+    }
+
+    fn take_scripted_failure(&self) -> Result<(), MockAuthError> {
+        match self.state.lock().unwrap().scripted_failures.pop_front() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    fn mint_session(&self, user_id: Uuid, email: Option<String>, ttl: Duration) -> MockSession {
+        let now = Utc::now();
+        let expires_at = (now + ttl).timestamp();
+        let role = match self.state.lock().unwrap().roles.get(&user_id) {
+            Some(role) => role.to_string(),
+            None => "authenticated".to_string(),
+        };
+        let claims = Claims {
+            sub: user_id.to_string(),
+            exp: expires_at as usize,
+            iat: Some(now.timestamp() as usize),
+            role: Some(role),
+            session_id: Some(Uuid::new_v4().to_string()),
+            email,
+            ..Default::default()
+        };
+
+        MockSession {
+            access_token: self.mint_token(&claims),
+            refresh_token: Uuid::new_v4().to_string(),
+            expires_at: expires_at as u64,
+            is_new_user: false,
+        }
+    }
+
+    fn check_state(state: SessionState) -> Result<(), MockAuthError> {
+        match state {
+            SessionState::Active => Ok(()),
+            SessionState::Expired => Err(MockAuthError::SessionExpired),
+            SessionState::Revoked => Err(MockAuthError::TokenRevoked),
+        }
+    }
+}
+
+#[async_trait]
+impl Authenticator for MockAuthenticator {
+    type Error = MockAuthError;
+    type Session = MockSession;
+
+    fn jwt_verifier(&self) -> &dyn JwtVerifier {
+        &self.verifier
+    }
+
+    async fn send_otp(&self, channel: OtpChannel, contact: &str) -> Result<(), Self::Error> {
+        self.take_scripted_failure()?;
+        let contact = channel
+            .normalize(contact)
+            .ok_or(MockAuthError::InvalidContact(channel))?;
+
+        let code = format!("{:06}", rand::thread_rng().gen_range(0..1_000_000));
+        let mut state = self.state.lock().unwrap();
+        state
+            .outstanding_otps
+            .insert((channel, contact.clone()), code.clone());
+        state.sent_otps.push(SentOtp {
+            channel,
+            contact,
+            code,
+        });
+        Ok(())
+    }
+
+    async fn verify_otp(
+        &self,
+        channel: OtpChannel,
+        contact: &str,
+        token: &str,
+    ) -> Result<Self::Session, Self::Error> {
+        self.take_scripted_failure()?;
+        let contact = channel
+            .normalize(contact)
+            .ok_or(MockAuthError::InvalidContact(channel))?;
+
+        let email = (channel == OtpChannel::Email).then(|| contact.clone());
+        let (user_id, is_new_user) = {
+            let mut state = self.state.lock().unwrap();
+            let key = (channel, contact);
+            if state.outstanding_otps.get(&key).map(String::as_str) != Some(token) {
+                return Err(MockAuthError::InvalidOtp);
+            }
+            // each code can only be used once
+            state.outstanding_otps.remove(&key);
+
+            match state.users.get(&key.1) {
+                Some(user_id) => (*user_id, false),
+                None => {
+                    let user_id = Uuid::new_v4();
+                    state.users.insert(key.1, user_id);
+                    (user_id, true)
+                }
+            }
+        };
+
+        Ok(MockSession {
+            is_new_user,
+            ..self.start_session(user_id, email)
+        })
+    }
+
+    async fn logout(&self, bearer_token: &str) -> Result<(), Self::Error> {
+        self.take_scripted_failure()?;
+        jwt::validate_jwt_hmac(bearer_token, &self.jwt_secret)?;
+
+        let mut state = self.state.lock().unwrap();
+        let session = state
+            .session_by_access_token(bearer_token)
+            .ok_or(MockAuthError::UnknownSession)?;
+
+        Self::check_state(session.state)?;
+        session.state = SessionState::Revoked;
+        Ok(())
+    }
+
+    async fn refresh_token(&self, refresh_token: &str) -> Result<Self::Session, Self::Error> {
+        self.take_scripted_failure()?;
+
+        let (user_id, email) = {
+            let state = self.state.lock().unwrap();
+            let session = state
+                .sessions
+                .iter()
+                .find(|s| s.refresh_token == refresh_token)
+                .ok_or(MockAuthError::UnknownSession)?;
+
+            Self::check_state(session.state)?;
+            (session.user_id, session.email.clone())
+        };
+
+        // rotate by replacing the old session with a new one
+        let new_session = self.mint_session(user_id, email, ACCESS_TOKEN_TTL);
+        let mut state = self.state.lock().unwrap();
+        if let Some(session) = state
+            .sessions
+            .iter_mut()
+            .find(|s| s.refresh_token == refresh_token)
+        {
+            session.access_token = new_session.access_token.clone();
+            session.refresh_token = new_session.refresh_token.clone();
+        }
+        Ok(new_session)
+    }
+
+    async fn verify_token(&self, access_token: &str) -> Result<Uuid, Self::Error> {
+        self.take_scripted_failure()?;
+        jwt::validate_jwt_hmac(access_token, &self.jwt_secret)?;
+
+        let mut state = self.state.lock().unwrap();
+        let session = state
+            .session_by_access_token(access_token)
+            .ok_or(MockAuthError::UnknownSession)?;
+
+        Self::check_state(session.state)?;
+        Ok(session.user_id)
+    }
+
+    async fn barring_sanction(&self, user_id: Uuid) -> Result<Option<Sanction>, Self::Error> {
+        let now = Utc::now();
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .sanctions
+            .iter()
+            .find(|sanction| sanction.user_id == user_id && sanction.bars_access(now))
+            .cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_otp_flow_issues_valid_tokens() {
+        let authenticator = MockAuthenticator::new("secret");
+        authenticator
+            .send_otp(OtpChannel::Phone, "+1 415 555 0123")
+            .await
+            .unwrap();
+
+        let sent = authenticator.sent_otps();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].contact, "+14155550123");
+
+        let session = authenticator
+            .verify_otp(OtpChannel::Phone, "+14155550123", &sent[0].code)
+            .await
+            .unwrap();
+        let user_id = authenticator.user_id("+14155550123").unwrap();
+
+        let claims = jwt::validate_jwt_hmac(session.access_token(), "secret").unwrap();
+        assert_eq!(claims.sub, user_id.to_string());
+        assert_eq!(
+            authenticator.verify_token(session.access_token()).await,
+            Ok(user_id)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_otp_is_single_use() {
+        let authenticator = MockAuthenticator::new("secret");
+        authenticator
+            .send_otp(OtpChannel::Email, "user@example.com")
+            .await
+            .unwrap();
+        let code = authenticator.last_otp("user@example.com").unwrap();
+
+        let verify = || authenticator.verify_otp(OtpChannel::Email, "user@example.com", &code);
+        assert!(verify().await.is_ok());
+        assert_eq!(verify().await.unwrap_err(), MockAuthError::InvalidOtp);
+    }
+
+    #[tokio::test]
+    async fn test_otp_bound_to_channel() {
+        let authenticator = MockAuthenticator::new("secret");
+        authenticator
+            .send_otp(OtpChannel::Email, "user@example.com")
+            .await
+            .unwrap();
+        let code = authenticator.last_otp("user@example.com").unwrap();
+
+        let result = authenticator
+            .verify_otp(OtpChannel::Phone, "user@example.com", &code)
+            .await;
+        assert_eq!(
+            result.unwrap_err(),
+            MockAuthError::InvalidContact(OtpChannel::Phone)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_expired_and_revoked_sessions() {
+        let authenticator = MockAuthenticator::new("secret");
+        let expired = authenticator.create_session(Uuid::new_v4());
+        let revoked = authenticator.create_session(Uuid::new_v4());
+
+        authenticator.expire(expired.access_token());
+        authenticator.revoke(revoked.access_token());
+
+        assert_eq!(
+            authenticator.verify_token(expired.access_token()).await,
+            Err(MockAuthError::SessionExpired)
+        );
+        assert_eq!(
+            authenticator
+                .refresh_token(expired.refresh_token())
+                .await
+                .unwrap_err(),
+            MockAuthError::SessionExpired
+        );
+        assert_eq!(
+            authenticator.verify_token(revoked.access_token()).await,
+            Err(MockAuthError::TokenRevoked)
+        );
+        assert_eq!(
+            authenticator.logout(revoked.access_token()).await,
+            Err(MockAuthError::TokenRevoked)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_refresh_rotates_tokens() {
+        let authenticator = MockAuthenticator::new("secret");
+        let user_id = Uuid::new_v4();
+        let session = authenticator.create_session(user_id);
+
+        let refreshed = authenticator
+            .refresh_token(session.refresh_token())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            authenticator.verify_token(refreshed.access_token()).await,
+            Ok(user_id)
+        );
+        assert_eq!(
+            authenticator.verify_token(session.access_token()).await,
+            Err(MockAuthError::UnknownSession)
+        );
+        assert!(
+            authenticator
+                .refresh_token(session.refresh_token())
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scripted_failures_are_consumed_in_order() {
+        let authenticator = MockAuthenticator::new("secret");
+        authenticator.fail_next(MockAuthError::Scripted("first".into()));
+        authenticator.fail_next(MockAuthError::Scripted("second".into()));
+
+        let send = || authenticator.send_otp(OtpChannel::Email, "user@example.com");
+        assert_eq!(send().await, Err(MockAuthError::Scripted("first".into())));
+        assert_eq!(send().await, Err(MockAuthError::Scripted("second".into())));
+        assert_eq!(send().await, Ok(()));
+    }
+}
@@ -1,7 +1,16 @@
 //! Authentication models and traits.
 
 mod authenticator;
+#[cfg(feature = "mock")]
+pub mod mock_authenticator;
+pub mod pg_authenticator;
+mod sanction_cache;
 pub mod sb_authenticator;
+mod user;
 
-pub use authenticator::{Authenticator, AuthSession};
-pub use sb_authenticator::SbAuthenticator;
+pub use authenticator::{AuthSession, Authenticator, OtpChannel};
+#[cfg(feature = "mock")]
+pub use mock_authenticator::MockAuthenticator;
+pub use pg_authenticator::PgAuthenticator;
+pub use sb_authenticator::{SbAuthError, SbAuthenticator, SbSession};
+pub use user::AuthenticatedUser;
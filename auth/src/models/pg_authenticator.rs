@@ -0,0 +1,633 @@
+//! Self-hosted authentication backend implementation backed by PostgreSQL.
+
+use crate::error::AuthError;
+use crate::jwt::{self, Audience, ClaimExpectations, Claims, HmacVerifier, JwtVerifier};
+use crate::models::sanction_cache::{DEFAULT_SANCTION_TTL, SanctionCache};
+use crate::models::{AuthSession, Authenticator, OtpChannel};
+use crate::sender::{ConsoleSender, OtpSender, SendError};
+
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use db::error::DbError;
+use db::queries::auth as queries;
+use db::queries::{roles, users};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use shared::error::{ApiError, ErrorCode};
+use shared::types::moderation::Sanction;
+use sqlx::PgPool;
+use std::sync::Arc;
+use thiserror::Error;
+use uuid::Uuid;
+
+/// Lifetime of a one-time password.
+const OTP_TTL: Duration = Duration::minutes(10);
+
+/// Lifetime of an access token.
+const ACCESS_TOKEN_TTL: Duration = Duration::hours(1);
+
+/// Lifetime of a refresh token, and thus the maximum idle time of a session.
+const REFRESH_TOKEN_TTL: Duration = Duration::days(30);
+
+/// Audience of issued tokens unless configured otherwise.
+const DEFAULT_AUDIENCE: &str = "authenticated";
+
+/// Issuer of issued tokens unless configured otherwise.
+const DEFAULT_ISSUER: &str = "middleground";
+
+/// Role claimed by access tokens of signed-in users who have not been granted a role.
+const AUTHENTICATED_ROLE: &str = "authenticated";
+
+/// Errors returned by [`PgAuthenticator`].
+#[derive(Error, Debug)]
+pub enum PgAuthError {
+    #[error("Invalid contact information for channel {0:?}")]
+    InvalidContact(OtpChannel),
+
+    #[error("Invalid or expired OTP")]
+    InvalidOtp,
+
+    #[error("Session is not active")]
+    InactiveSession,
+
+    #[error(transparent)]
+    Token(#[from] AuthError),
+
+    #[error(transparent)]
+    Delivery(#[from] SendError),
+
+    #[error(transparent)]
+    Database(#[from] DbError),
+}
+
+impl From<PgAuthError> for ApiError {
+    fn from(error: PgAuthError) -> Self {
+        match error {
+            PgAuthError::InvalidContact(_) => {
+                ApiError::new(ErrorCode::InvalidContact, error.to_string())
+            }
+            PgAuthError::InvalidOtp => ApiError::new(ErrorCode::InvalidOtp, error.to_string()),
+            PgAuthError::InactiveSession => {
+                ApiError::new(ErrorCode::SessionInactive, error.to_string())
+            }
+            PgAuthError::Token(e) => e.into(),
+            PgAuthError::Delivery(SendError::InvalidRecipient(_)) => {
+                ApiError::new(ErrorCode::InvalidContact, error.to_string())
+            }
+            PgAuthError::Delivery(_) => {
+                ApiError::new(ErrorCode::OtpDeliveryFailed, "Failed to deliver OTP")
+                    .with_source(error)
+            }
+            PgAuthError::Database(e) => e.into(),
+        }
+    }
+}
+
+/// Session issued by [`PgAuthenticator`].
+#[derive(Debug, Clone)]
+pub struct PgSession {
+    access_token: String,
+    refresh_token: String,
+    expires_at: u64,
+    is_new_user: bool,
+}
+
+impl AuthSession for PgSession {
+    fn access_token(&self) -> &str {
+        &self.access_token
+    }
+
+    fn refresh_token(&self) -> &str {
+        &self.refresh_token
+    }
+
+    fn expires_at(&self) -> u64 {
+        self.expires_at
+    }
+
+    fn is_new_user(&self) -> bool {
+        self.is_new_user
+    }
+}
+
+/// Claims embedded in refresh tokens issued by [`PgAuthenticator`].
+///
+/// `jti` is the single-use refresh token ID stored alongside the session. The
+/// email address is carried over to the access tokens issued on refresh.
+#[derive(Deserialize, Serialize)]
+struct RefreshClaims {
+    sub: String,
+    exp: usize,
+    iat: usize,
+    aud: String,
+    iss: String,
+    jti: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    email: Option<String>,
+}
+
+/// PostgreSQL-based authenticator implementation.
+///
+/// This authenticator generates OTP codes itself, storing only their hashes
+/// in the database, and issues its own HS256 access and refresh tokens. Sessions
+/// are kept in the database so that they can be verified and revoked without any
+/// external service. Access tokens can be validated locally with an
+/// [`HmacVerifier`] using the JWT secret.
+///
+/// Access tokens carry the session ID, the role granted in the `user_roles` table
+/// (or `authenticated` if none) and, for users signed in by email, the email
+/// address. Their audience and issuer default to
+/// `authenticated` and `middleground` and can be changed with `with_audience` and
+/// `with_issuer`; tokens with a different audience or issuer are rejected.
+///
+/// Refresh tokens are signed with a separate secret so that they cannot be used
+/// as access tokens, and each refresh token can be used only once.
+///
+/// Generated OTPs are delivered through one [`OtpSender`] per channel. By default,
+/// both channels use [`ConsoleSender::stdout`], which suits development and testing;
+/// use `with_email_sender` and `with_sms_sender` to deliver codes for real.
+///
+/// The tables used by this authenticator are created by the migrations in the
+/// `db` crate.
+///
+/// # Environment Variables
+///
+/// The following environment variables must be set when using `from_env`:
+/// - `AUTH_JWT_SECRET` - the secret used to sign access tokens
+/// - `AUTH_REFRESH_SECRET` - the secret used to sign refresh tokens
+///
+/// Optionally, `AUTH_JWT_AUDIENCE` and `AUTH_JWT_ISSUER` override the audience
+/// and issuer of issued tokens.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use auth::models::PgAuthenticator;
+/// use auth::sender::ConsoleSender;
+///
+/// # async fn example() {
+/// let pool = db::create_pool().await.unwrap();
+/// let authenticator = PgAuthenticator::from_env(pool)
+///     .unwrap()
+///     .with_email_sender(ConsoleSender::file("otp.log"));
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct PgAuthenticator {
+    pool: PgPool,
+    jwt_secret: String,
+    refresh_secret: String,
+    audience: String,
+    issuer: String,
+    verifier: HmacVerifier,
+    email_sender: Arc<dyn OtpSender>,
+    sms_sender: Arc<dyn OtpSender>,
+    sanctions: SanctionCache,
+}
+
+impl PgAuthenticator {
+    /// Create a new PgAuthenticator using the provided pool and signing secrets.
+    ///
+    /// OTPs are written to standard output until senders are configured.
+    pub fn new(pool: PgPool, jwt_secret: String, refresh_secret: String) -> Self {
+        Self {
+            pool,
+            verifier: HmacVerifier::new(jwt_secret.clone())
+                .with_claim_expectations(expectations(DEFAULT_AUDIENCE, DEFAULT_ISSUER)),
+            jwt_secret,
+            refresh_secret,
+            audience: DEFAULT_AUDIENCE.to_string(),
+            issuer: DEFAULT_ISSUER.to_string(),
+            email_sender: Arc::new(ConsoleSender::stdout()),
+            sms_sender: Arc::new(ConsoleSender::stdout()),
+            sanctions: SanctionCache::new(DEFAULT_SANCTION_TTL),
+        }
+    }
+
+    /// Issue tokens for, and accept only tokens issued for, the given audience.
+    pub fn with_audience(mut self, audience: impl Into<String>) -> Self {
+        self.audience = audience.into();
+        self.reset_verifier();
+        self
+    }
+
+    /// Issue tokens as, and accept only tokens issued by, the given issuer.
+    pub fn with_issuer(mut self, issuer: impl Into<String>) -> Self {
+        self.issuer = issuer.into();
+        self.reset_verifier();
+        self
+    }
+
+    /// Deliver OTPs for the email channel using the given sender.
+    pub fn with_email_sender(mut self, sender: impl OtpSender) -> Self {
+        self.email_sender = Arc::new(sender);
+        self
+    }
+
+    /// Deliver OTPs for the phone channel using the given sender.
+    pub fn with_sms_sender(mut self, sender: impl OtpSender) -> Self {
+        self.sms_sender = Arc::new(sender);
+        self
+    }
+
+    /// Cache the sanctions barring users for the given time, 30 seconds by default.
+    ///
+    /// Users sanctioned within that time of their last request may make requests
+    /// until it passes.
+    pub fn with_sanction_ttl(mut self, ttl: std::time::Duration) -> Self {
+        self.sanctions = SanctionCache::new(ttl);
+        self
+    }
+
+    /// Create a new PgAuthenticator, reading the signing secrets from environment variables.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the required environment variables are not set.
+    pub fn from_env(pool: PgPool) -> Result<Self, String> {
+        let jwt_secret = dotenvy::var("AUTH_JWT_SECRET").map_err(|e| format!("{e}"))?;
+        let refresh_secret = dotenvy::var("AUTH_REFRESH_SECRET").map_err(|e| format!("{e}"))?;
+
+        let mut authenticator = Self::new(pool, jwt_secret, refresh_secret);
+        if let Ok(audience) = dotenvy::var("AUTH_JWT_AUDIENCE") {
+            authenticator = authenticator.with_audience(audience);
+        }
+        if let Ok(issuer) = dotenvy::var("AUTH_JWT_ISSUER") {
+            authenticator = authenticator.with_issuer(issuer);
+        }
+        Ok(authenticator)
+    }
+
+    /// Rebuild the access token verifier after the audience or issuer changed.
+    fn reset_verifier(&mut self) {
+        self.verifier = HmacVerifier::new(self.jwt_secret.clone())
+            .with_claim_expectations(expectations(&self.audience, &self.issuer));
+    }
+
+    /// Hash an OTP code for storage, binding it to the channel and contact it was sent to.
+    fn hash_otp(&self, channel: OtpChannel, contact: &str, code: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.jwt_secret.as_bytes());
+        hasher.update(b":");
+        hasher.update(format!("{channel:?}").as_bytes());
+        hasher.update(b":");
+        hasher.update(contact.as_bytes());
+        hasher.update(b":");
+        hasher.update(code.as_bytes());
+        hex::encode(hasher.finalize())
+    }
+
+    /// Issue a fresh pair of access and refresh tokens for a session.
+    fn issue_session(
+        &self,
+        user_id: Uuid,
+        session_id: Uuid,
+        refresh_token_id: Uuid,
+        email: Option<String>,
+        role: String,
+    ) -> Result<PgSession, PgAuthError> {
+        let now = Utc::now();
+        let expires_at = (now + ACCESS_TOKEN_TTL).timestamp();
+
+        let access_claims = Claims {
+            sub: user_id.to_string(),
+            exp: expires_at as usize,
+            aud: Some(Audience::Single(self.audience.clone())),
+            iss: Some(self.issuer.clone()),
+            iat: Some(now.timestamp() as usize),
+            nbf: None,
+            role: Some(role),
+            session_id: Some(session_id.to_string()),
+            email: email.clone(),
+        };
+        let refresh_claims = RefreshClaims {
+            sub: user_id.to_string(),
+            exp: (now + REFRESH_TOKEN_TTL).timestamp() as usize,
+            iat: now.timestamp() as usize,
+            aud: self.audience.clone(),
+            iss: self.issuer.clone(),
+            jti: refresh_token_id.to_string(),
+            email,
+        };
+
+        Ok(PgSession {
+            access_token: jwt::issue_jwt_hmac(&access_claims, &self.jwt_secret)?,
+            refresh_token: jwt::issue_jwt_hmac(&refresh_claims, &self.refresh_secret)?,
+            expires_at: expires_at as u64,
+            is_new_user: false,
+        })
+    }
+
+    /// Return the role to claim in access tokens of the given user.
+    async fn role_claim(&self, user_id: Uuid) -> Result<String, PgAuthError> {
+        Ok(roles::get_role(&self.pool, user_id)
+            .await?
+            .unwrap_or_else(|| AUTHENTICATED_ROLE.to_string()))
+    }
+
+    /// Validate an access token and return the user and session IDs it refers to.
+    fn decode_access_token(&self, access_token: &str) -> Result<(Uuid, Uuid), PgAuthError> {
+        let claims: Claims = jwt::decode_jwt_hmac(
+            access_token,
+            &self.jwt_secret,
+            &expectations(&self.audience, &self.issuer),
+        )?;
+        let session_id = claims
+            .session_id
+            .ok_or_else(|| AuthError::InvalidToken("Missing session ID".to_string()))?;
+        Ok((parse_uuid(&claims.sub)?, parse_uuid(&session_id)?))
+    }
+}
+
+#[async_trait]
+impl Authenticator for PgAuthenticator {
+    type Error = PgAuthError;
+    type Session = PgSession;
+
+    fn jwt_verifier(&self) -> &dyn JwtVerifier {
+        &self.verifier
+    }
+
+    async fn send_otp(&self, channel: OtpChannel, contact: &str) -> Result<(), Self::Error> {
+        let contact = normalize_contact(channel, contact)?;
+        let code = generate_otp();
+
+        let code_hash = self.hash_otp(channel, &contact, &code);
+        let expires_at = Utc::now() + OTP_TTL;
+        queries::upsert_otp(&self.pool, &contact, &code_hash, expires_at).await?;
+
+        let sender = match channel {
+            OtpChannel::Email => &self.email_sender,
+            OtpChannel::Phone => &self.sms_sender,
+        };
+        sender.send(&contact, &code).await?;
+        Ok(())
+    }
+
+    async fn verify_otp(
+        &self,
+        channel: OtpChannel,
+        contact: &str,
+        token: &str,
+    ) -> Result<Self::Session, Self::Error> {
+        let contact = normalize_contact(channel, contact)?;
+
+        let code_hash = self.hash_otp(channel, &contact, token.trim());
+        let mut tx = self.pool.begin().await.map_err(DbError::Connection)?;
+        if !queries::consume_otp(&mut *tx, &contact, &code_hash).await? {
+            return Err(PgAuthError::InvalidOtp);
+        }
+
+        // the identity, profile and session are created together or not at all
+        let user_id = queries::upsert_identity(&mut *tx, &contact).await?;
+        let (email, phone) = match channel {
+            OtpChannel::Email => (Some(contact.as_str()), None),
+            OtpChannel::Phone => (None, Some(contact.as_str())),
+        };
+        let login = users::upsert_user_on_login(&mut *tx, user_id, email, phone).await?;
+        let session_id = Uuid::new_v4();
+        let refresh_token_id = Uuid::new_v4();
+
+        queries::insert_session(
+            &mut *tx,
+            session_id,
+            user_id,
+            refresh_token_id,
+            Utc::now() + REFRESH_TOKEN_TTL,
+        )
+        .await?;
+        tx.commit().await.map_err(DbError::Query)?;
+
+        let role = self.role_claim(user_id).await?;
+        let email = email.map(str::to_string);
+        let session = self.issue_session(user_id, session_id, refresh_token_id, email, role)?;
+        Ok(PgSession {
+            is_new_user: login.is_new_user,
+            ..session
+        })
+    }
+
+    async fn logout(&self, bearer_token: &str) -> Result<(), Self::Error> {
+        let (_, session_id) = self.decode_access_token(bearer_token)?;
+
+        if queries::revoke_session(&self.pool, session_id).await? {
+            Ok(())
+        } else {
+            Err(PgAuthError::InactiveSession)
+        }
+    }
+
+    async fn refresh_token(&self, refresh_token: &str) -> Result<Self::Session, Self::Error> {
+        let claims: RefreshClaims = jwt::decode_jwt_hmac(
+            refresh_token,
+            &self.refresh_secret,
+            &expectations(&self.audience, &self.issuer),
+        )?;
+        let refresh_token_id = parse_uuid(&claims.jti)?;
+        let new_refresh_token_id = Uuid::new_v4();
+
+        let (session_id, user_id) = queries::rotate_refresh_token(
+            &self.pool,
+            refresh_token_id,
+            new_refresh_token_id,
+            Utc::now() + REFRESH_TOKEN_TTL,
+        )
+        .await?
+        .ok_or(PgAuthError::InactiveSession)?;
+
+        let role = self.role_claim(user_id).await?;
+        self.issue_session(
+            user_id,
+            session_id,
+            new_refresh_token_id,
+            claims.email,
+            role,
+        )
+    }
+
+    async fn verify_token(&self, access_token: &str) -> Result<Uuid, Self::Error> {
+        let (user_id, session_id) = self.decode_access_token(access_token)?;
+
+        match queries::get_active_session_user(&self.pool, session_id).await? {
+            Some(session_user) if session_user == user_id => Ok(user_id),
+            _ => Err(PgAuthError::InactiveSession),
+        }
+    }
+
+    async fn barring_sanction(&self, user_id: Uuid) -> Result<Option<Sanction>, Self::Error> {
+        Ok(self.sanctions.barring_sanction(&self.pool, user_id).await?)
+    }
+}
+
+/// Generate a random six digit OTP code.
+fn generate_otp() -> String {
+    format!("{:06}", rand::thread_rng().gen_range(0..1_000_000))
+}
+
+/// Normalize contact information so that the same contact always maps to the same identity.
+fn normalize_contact(channel: OtpChannel, contact: &str) -> Result<String, PgAuthError> {
+    channel
+        .normalize(contact)
+        .ok_or(PgAuthError::InvalidContact(channel))
+}
+
+/// Expect tokens issued by `issuer` for `audience`.
+fn expectations(audience: &str, issuer: &str) -> ClaimExpectations {
+    ClaimExpectations::new().audience(audience).issuer(issuer)
+}
+
+fn parse_uuid(value: &str) -> Result<Uuid, PgAuthError> {
+    Uuid::parse_str(value)
+        .map_err(|e| AuthError::InvalidToken(format!("Invalid ID in token: {}", e)).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Create an authenticator whose pool never connects; suitable for testing
+    /// functionality that does not touch the database.
+    fn test_authenticator() -> PgAuthenticator {
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/unused")
+            .unwrap();
+        PgAuthenticator::new(pool, "jwt-secret".into(), "refresh-secret".into())
+    }
+
+    #[test]
+    fn test_generate_otp_format() {
+        for _ in 0..100 {
+            let code = generate_otp();
+            assert_eq!(code.len(), 6);
+            assert!(code.chars().all(|c| c.is_ascii_digit()));
+        }
+    }
+
+    #[test]
+    fn test_normalize_contact() {
+        assert_eq!(
+            normalize_contact(OtpChannel::Email, "  User@Example.com ").unwrap(),
+            "user@example.com"
+        );
+        assert!(matches!(
+            normalize_contact(OtpChannel::Phone, "user@example.com"),
+            Err(PgAuthError::InvalidContact(OtpChannel::Phone))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_hash_otp_bound_to_contact() {
+        let authenticator = test_authenticator();
+        let email = OtpChannel::Email;
+        let hash = authenticator.hash_otp(email, "a@example.com", "123456");
+
+        assert_eq!(
+            hash,
+            authenticator.hash_otp(email, "a@example.com", "123456")
+        );
+        assert_ne!(
+            hash,
+            authenticator.hash_otp(email, "b@example.com", "123456")
+        );
+        assert_ne!(
+            hash,
+            authenticator.hash_otp(email, "a@example.com", "654321")
+        );
+        assert_ne!(
+            hash,
+            authenticator.hash_otp(OtpChannel::Phone, "a@example.com", "123456")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_issued_access_token_validates_with_jwt_secret() {
+        let authenticator = test_authenticator();
+        let user_id = Uuid::new_v4();
+        let session_id = Uuid::new_v4();
+        let session = authenticator
+            .issue_session(
+                user_id,
+                session_id,
+                Uuid::new_v4(),
+                Some("user@example.com".into()),
+                "moderator".into(),
+            )
+            .unwrap();
+
+        let claims = authenticator
+            .jwt_verifier()
+            .verify(session.access_token())
+            .await
+            .unwrap();
+        assert_eq!(claims.sub, user_id.to_string());
+        assert_eq!(claims.aud, Some(Audience::Single("authenticated".into())));
+        assert_eq!(claims.iss.as_deref(), Some("middleground"));
+        assert_eq!(claims.role.as_deref(), Some("moderator"));
+        assert_eq!(claims.session_id, Some(session_id.to_string()));
+        assert_eq!(claims.email.as_deref(), Some("user@example.com"));
+        assert_eq!(
+            authenticator
+                .decode_access_token(session.access_token())
+                .unwrap(),
+            (user_id, session_id)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_refresh_token_rejected_as_access_token() {
+        let authenticator = test_authenticator();
+        let session = authenticator
+            .issue_session(
+                Uuid::new_v4(),
+                Uuid::new_v4(),
+                Uuid::new_v4(),
+                None,
+                AUTHENTICATED_ROLE.into(),
+            )
+            .unwrap();
+
+        assert!(jwt::validate_jwt_hmac(session.refresh_token(), "jwt-secret").is_err());
+        assert!(
+            authenticator
+                .decode_access_token(session.refresh_token())
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_access_token_rejected_for_other_audience_or_issuer() {
+        let session = test_authenticator()
+            .issue_session(
+                Uuid::new_v4(),
+                Uuid::new_v4(),
+                Uuid::new_v4(),
+                None,
+                AUTHENTICATED_ROLE.into(),
+            )
+            .unwrap();
+
+        let other_audience = test_authenticator().with_audience("other");
+        assert!(
+            other_audience
+                .jwt_verifier()
+                .verify(session.access_token())
+                .await
+                .is_err()
+        );
+        assert!(
+            other_audience
+                .decode_access_token(session.access_token())
+                .is_err()
+        );
+
+        let other_issuer = test_authenticator().with_issuer("other");
+        assert!(
+            other_issuer
+                .jwt_verifier()
+                .verify(session.access_token())
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,156 @@
+//! Short-lived cache of the sanctions barring users, sparing the database a
+//! lookup on every authenticated request.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use db::queries::sanctions;
+use shared::types::moderation::Sanction;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Time for which a user's sanctions are cached unless configured otherwise.
+pub(crate) const DEFAULT_SANCTION_TTL: Duration = Duration::from_secs(30);
+
+/// Users cached before stale entries are evicted.
+const MAX_ENTRIES: usize = 10_000;
+
+/// The sanction barring a user, or `None`, as looked up at `fetched_at`.
+struct CachedSanction {
+    sanction: Option<Sanction>,
+    fetched_at: Instant,
+}
+
+/// Cache of the sanctions barring users, each looked up at most once per TTL.
+///
+/// Sanctions imposed after a user was looked up take effect once the entry goes
+/// stale, so within the TTL. Suspensions lapsing while cached stop barring the
+/// user right away.
+#[derive(Clone)]
+pub(crate) struct SanctionCache {
+    ttl: Duration,
+    entries: Arc<Mutex<HashMap<Uuid, CachedSanction>>>,
+}
+
+impl SanctionCache {
+    /// Create an empty cache keeping sanctions for the given time; a zero TTL
+    /// looks up every request.
+    pub(crate) fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Arc::default(),
+        }
+    }
+
+    /// Return the sanction barring a user, looking it up in the given database
+    /// unless a fresh entry is cached.
+    pub(crate) async fn barring_sanction(
+        &self,
+        pool: &PgPool,
+        user_id: Uuid,
+    ) -> db::error::Result<Option<Sanction>> {
+        let now = Utc::now();
+        let cached = self
+            .entries
+            .lock()
+            .unwrap()
+            .get(&user_id)
+            .filter(|cached| cached.fetched_at.elapsed() < self.ttl)
+            .map(|cached| cached.sanction.clone());
+        if let Some(sanction) = cached {
+            return Ok(sanction.filter(|sanction| sanction.bars_access(now)));
+        }
+
+        let sanction = sanctions::barring_sanction(pool, user_id, now).await?;
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= MAX_ENTRIES {
+            entries.retain(|_, cached| cached.fetched_at.elapsed() < self.ttl);
+        }
+        entries.insert(
+            user_id,
+            CachedSanction {
+                sanction: sanction.clone(),
+                fetched_at: Instant::now(),
+            },
+        );
+        Ok(sanction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::types::moderation::SanctionKind;
+    use sqlx::postgres::PgPoolOptions;
+
+    fn lazy_pool() -> PgPool {
+        PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/unused")
+            .unwrap()
+    }
+
+    fn suspension(user_id: Uuid, expires_in: chrono::Duration) -> Sanction {
+        Sanction {
+            id: Uuid::new_v4(),
+            user_id,
+            kind: SanctionKind::Suspension,
+            report_id: None,
+            reason: None,
+            issued_by: Uuid::new_v4(),
+            issued_at: Utc::now(),
+            expires_at: Some(Utc::now() + expires_in),
+        }
+    }
+
+    fn cache_with(ttl: Duration, sanction: Sanction) -> SanctionCache {
+        let cache = SanctionCache::new(ttl);
+        cache.entries.lock().unwrap().insert(
+            sanction.user_id,
+            CachedSanction {
+                sanction: Some(sanction),
+                fetched_at: Instant::now(),
+            },
+        );
+        cache
+    }
+
+    #[tokio::test]
+    async fn test_fresh_entries_are_served_from_the_cache() {
+        let user_id = Uuid::new_v4();
+        let sanction = suspension(user_id, chrono::Duration::days(1));
+        let cache = cache_with(DEFAULT_SANCTION_TTL, sanction.clone());
+
+        // The pool never connects, so the sanction can only come from the cache.
+        let cached = cache.barring_sanction(&lazy_pool(), user_id).await.unwrap();
+        assert_eq!(cached, Some(sanction));
+    }
+
+    #[tokio::test]
+    async fn test_lapsed_suspensions_no_longer_bar() {
+        let user_id = Uuid::new_v4();
+        let cache = cache_with(
+            DEFAULT_SANCTION_TTL,
+            suspension(user_id, -chrono::Duration::seconds(1)),
+        );
+
+        let cached = cache.barring_sanction(&lazy_pool(), user_id).await.unwrap();
+        assert_eq!(cached, None);
+    }
+
+    #[tokio::test]
+    async fn test_stale_entries_are_looked_up_again() {
+        let user_id = Uuid::new_v4();
+        let cache = cache_with(
+            Duration::ZERO,
+            suspension(user_id, chrono::Duration::days(1)),
+        );
+
+        let pool = PgPoolOptions::new()
+            .acquire_timeout(Duration::from_millis(100))
+            .connect_lazy("postgres://localhost:1/unused")
+            .unwrap();
+        assert!(cache.barring_sanction(&pool, user_id).await.is_err());
+    }
+}
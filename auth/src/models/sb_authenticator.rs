@@ -1,17 +1,30 @@
 //! Supabase authentication backend implementation.
 
-use crate::models::{AuthSession, Authenticator};
+use crate::jwt::{ClaimExpectations, HmacVerifier, JwksVerifier, JwtVerifier};
+use crate::models::sanction_cache::{DEFAULT_SANCTION_TTL, SanctionCache};
+use crate::models::{AuthSession, Authenticator, OtpChannel};
 
 use async_trait::async_trait;
+use db::error::DbError;
+use db::queries::users;
+use reqwest::StatusCode;
+use shared::error::{ApiError, ErrorCode};
+use shared::types::moderation::Sanction;
+use sqlx::PgPool;
+use std::sync::Arc;
 use supabase_auth::error as sb_error;
 use supabase_auth::models as sb_models;
+use thiserror::Error;
+
+/// Audience of access tokens issued by Supabase to signed-in users.
+const DEFAULT_AUDIENCE: &str = "authenticated";
 
 // Implement AuthSession for Supabase's Session type
 impl AuthSession for sb_models::Session {
     fn access_token(&self) -> &str {
         &self.access_token
     }
-    
+
     fn refresh_token(&self) -> &str {
         &self.refresh_token
     }
@@ -21,6 +34,119 @@ impl AuthSession for sb_models::Session {
     }
 }
 
+/// Session issued by [`SbAuthenticator`]: a Supabase session, and whether it
+/// belongs to a user signing in for the first time.
+#[derive(Debug, Clone)]
+pub struct SbSession {
+    pub session: sb_models::Session,
+    pub is_new_user: bool,
+}
+
+impl AuthSession for SbSession {
+    fn access_token(&self) -> &str {
+        self.session.access_token()
+    }
+
+    fn refresh_token(&self) -> &str {
+        self.session.refresh_token()
+    }
+
+    fn expires_at(&self) -> u64 {
+        self.session.expires_at()
+    }
+
+    fn is_new_user(&self) -> bool {
+        self.is_new_user
+    }
+}
+
+impl From<sb_models::Session> for SbSession {
+    fn from(session: sb_models::Session) -> Self {
+        Self {
+            session,
+            is_new_user: false,
+        }
+    }
+}
+
+/// Errors returned by [`SbAuthenticator`].
+///
+/// Supabase errors carry internal details, so they are converted into an
+/// [`ApiError`] with a generic message and logged in full instead.
+#[derive(Error, Debug)]
+pub enum SbAuthError {
+    #[error(transparent)]
+    Supabase(#[from] sb_error::Error),
+
+    #[error(transparent)]
+    Database(#[from] DbError),
+}
+
+impl From<SbAuthError> for ApiError {
+    fn from(error: SbAuthError) -> Self {
+        match error {
+            SbAuthError::Supabase(e) => supabase_api_error(e),
+            SbAuthError::Database(e) => e.into(),
+        }
+    }
+}
+
+/// Convert a Supabase client error, keeping its details out of the response.
+fn supabase_api_error(error: sb_error::Error) -> ApiError {
+    let code = match &error {
+        sb_error::Error::Supabase(e) => match e.error_code.as_deref() {
+            Some("otp_expired") => ErrorCode::InvalidOtp,
+            Some("validation_failed") => ErrorCode::ValidationFailed,
+            Some("email_address_invalid" | "phone_not_confirmed" | "sms_send_failed") => {
+                ErrorCode::InvalidContact
+            }
+            Some("bad_jwt" | "no_authorization") => ErrorCode::InvalidToken,
+            Some(
+                "session_not_found"
+                | "session_expired"
+                | "refresh_token_not_found"
+                | "refresh_token_already_used"
+                | "user_not_found",
+            ) => ErrorCode::SessionInactive,
+            Some(code) if code.starts_with("over_") => ErrorCode::RateLimited,
+            _ => code_for_status(
+                u16::try_from(e.code)
+                    .ok()
+                    .and_then(|c| StatusCode::from_u16(c).ok()),
+            ),
+        },
+        sb_error::Error::AuthError { status, .. } => code_for_status(Some(*status)),
+        sb_error::Error::WrongCredentials => ErrorCode::InvalidOtp,
+        sb_error::Error::WrongToken
+        | sb_error::Error::NotAuthenticated
+        | sb_error::Error::MissingRefreshToken => ErrorCode::InvalidToken,
+        sb_error::Error::UserNotFound => ErrorCode::SessionInactive,
+        _ => ErrorCode::UpstreamError,
+    };
+
+    let message = match code {
+        ErrorCode::InvalidOtp => "Invalid or expired OTP",
+        ErrorCode::ValidationFailed => "The request was rejected as invalid",
+        ErrorCode::InvalidContact => "Invalid contact information",
+        ErrorCode::InvalidToken => "Invalid or expired token",
+        ErrorCode::SessionInactive => "Session is not active",
+        ErrorCode::RateLimited => "Too many requests. Please try again later.",
+        ErrorCode::BadRequest => "The request was rejected by the authentication provider",
+        _ => "Authentication provider unavailable",
+    };
+    ApiError::new(code, message).with_source(error)
+}
+
+/// Error code for a Supabase response with the given status and no recognized error code.
+fn code_for_status(status: Option<StatusCode>) -> ErrorCode {
+    match status {
+        Some(StatusCode::TOO_MANY_REQUESTS) => ErrorCode::RateLimited,
+        Some(StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) => ErrorCode::InvalidToken,
+        Some(status) if status.is_client_error() => ErrorCode::BadRequest,
+        _ => ErrorCode::UpstreamError,
+    }
+}
+
 /// Supabase-based authenticator implementation.
 ///
 /// This authenticator uses Supabase's authentication service to handle
@@ -34,6 +160,21 @@ impl AuthSession for sb_models::Session {
 /// - `SUPABASE_API_KEY` - the Supabase API key
 /// - `SUPABASE_JWT_SECRET` - the JWT encryption secret
 ///
+/// Projects using asymmetric signing keys should instead set `SUPABASE_JWKS_URL`
+/// to the project's JWKS endpoint (`<SUPABASE_URL>/auth/v1/.well-known/jwks.json`).
+/// If `SUPABASE_JWT_SECRET` is also set, HS256 tokens without a key ID are still
+/// accepted using the secret. When created within a Tokio runtime, the key set is
+/// fetched right away and refreshed in the background.
+///
+/// When created with `from_env`, tokens must be issued by `<SUPABASE_URL>/auth/v1`
+/// for the `authenticated` audience. `SUPABASE_JWT_ISSUER` and
+/// `SUPABASE_JWT_AUDIENCE` override the expected issuer and audience.
+///
+/// Users live in Supabase's `auth` schema. With `with_pool`, the public profile
+/// of a user is created in the `users` table on their first sign-in, and users
+/// suspended or banned by moderators are barred; without it, no profiles are
+/// created, sessions are never reported as new and no one is barred.
+///
 /// # Example
 ///
 /// ```rust,no_run
@@ -46,17 +187,53 @@ impl AuthSession for sb_models::Session {
 /// let client = supabase_auth::models::AuthClient::new_from_env().unwrap();
 /// let jwt_secret = dotenvy::var("SUPABASE_JWT_SECRET").unwrap();
 /// let authenticator = SbAuthenticator::new(client, jwt_secret);
+///
+/// // Or verify tokens using the project's signing keys
+/// let client = supabase_auth::models::AuthClient::new_from_env().unwrap();
+/// let verifier = auth::jwt::JwksVerifier::from_url(
+///     "https://example.supabase.co/auth/v1/.well-known/jwks.json",
+/// );
+/// let authenticator = SbAuthenticator::with_verifier(client, verifier);
 /// ```
 #[derive(Clone)]
 pub struct SbAuthenticator {
     client: sb_models::AuthClient,
-    jwt_secret: String,
+    verifier: Arc<dyn JwtVerifier>,
+    pool: Option<PgPool>,
+    sanctions: SanctionCache,
 }
 
 impl SbAuthenticator {
-    /// Create a new sbAuthenticator with the provided AuthClient.
+    /// Create a new SbAuthenticator with the provided AuthClient, verifying
+    /// tokens with the project's HS256 JWT secret.
     pub fn new(client: sb_models::AuthClient, jwt_secret: String) -> Self {
-        Self { client, jwt_secret }
+        Self::with_verifier(client, HmacVerifier::new(jwt_secret))
+    }
+
+    /// Create a new SbAuthenticator with the provided AuthClient and token verifier.
+    pub fn with_verifier(client: sb_models::AuthClient, verifier: impl JwtVerifier) -> Self {
+        Self {
+            client,
+            verifier: Arc::new(verifier),
+            pool: None,
+            sanctions: SanctionCache::new(DEFAULT_SANCTION_TTL),
+        }
+    }
+
+    /// Create public profiles of users signing in for the first time, and look up
+    /// their sanctions, in the given database.
+    pub fn with_pool(mut self, pool: PgPool) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Cache the sanctions barring users for the given time, 30 seconds by default.
+    ///
+    /// Users sanctioned within that time of their last request may make requests
+    /// until it passes.
+    pub fn with_sanction_ttl(mut self, ttl: std::time::Duration) -> Self {
+        self.sanctions = SanctionCache::new(ttl);
+        self
     }
 
     /// Create a new SbAuthenticator from environment variables.
@@ -66,12 +243,42 @@ impl SbAuthenticator {
     /// Returns an error if the required environment variables are not set
     /// or if the AuthClient cannot be initialized.
     pub fn from_env() -> Result<Self, String> {
-        let client = sb_models::AuthClient::new_from_env()
-            .map_err(|e| format!("{e}"))?;
-        let jwt_secret = dotenvy::var("SUPABASE_JWT_SECRET")
-            .map_err(|e| format!("{e}"))?;
+        let client = sb_models::AuthClient::new_from_env().map_err(|e| format!("{e}"))?;
+        let expectations = Self::claim_expectations_from_env()?;
 
-        Ok(Self::new(client, jwt_secret))
+        if let Ok(jwks_url) = dotenvy::var("SUPABASE_JWKS_URL") {
+            let verifier = match dotenvy::var("SUPABASE_JWT_SECRET") {
+                Ok(jwt_secret) => JwksVerifier::from_url(jwks_url).with_hmac_fallback(jwt_secret),
+                Err(_) => JwksVerifier::from_url(jwks_url),
+            };
+            let verifier = verifier.with_claim_expectations(expectations);
+            // Keep the key set fresh in the background rather than on requests,
+            // unless there is no runtime to run the refresh task on.
+            if tokio::runtime::Handle::try_current().is_ok() {
+                verifier.spawn_refresh_task();
+            }
+            return Ok(Self::with_verifier(client, verifier));
+        }
+
+        let jwt_secret = dotenvy::var("SUPABASE_JWT_SECRET").map_err(|e| format!("{e}"))?;
+        let verifier = HmacVerifier::new(jwt_secret).with_claim_expectations(expectations);
+
+        Ok(Self::with_verifier(client, verifier))
+    }
+
+    /// Read the expected audience and issuer of access tokens from environment variables.
+    fn claim_expectations_from_env() -> Result<ClaimExpectations, String> {
+        let audience =
+            dotenvy::var("SUPABASE_JWT_AUDIENCE").unwrap_or_else(|_| DEFAULT_AUDIENCE.to_string());
+        let issuer = match dotenvy::var("SUPABASE_JWT_ISSUER") {
+            Ok(issuer) => issuer,
+            Err(_) => {
+                let url = dotenvy::var("SUPABASE_URL").map_err(|e| format!("{e}"))?;
+                format!("{}/auth/v1", url.trim_end_matches('/'))
+            }
+        };
+
+        Ok(ClaimExpectations::new().audience(audience).issuer(issuer))
     }
 }
 
@@ -89,49 +296,132 @@ impl Default for SbAuthenticator {
 
 #[async_trait]
 impl Authenticator for SbAuthenticator {
-    type Error = sb_error::Error;
-    type Session = sb_models::Session;
+    type Error = SbAuthError;
+    type Session = SbSession;
 
-    fn jwt_secret(&self) -> &str {
-        &self.jwt_secret
+    fn jwt_verifier(&self) -> &dyn JwtVerifier {
+        self.verifier.as_ref()
     }
 
-    async fn send_otp(&self, contact: &str) -> Result<(), Self::Error> {
-        self.client
-            .send_email_with_otp(contact, None)
-            .await
-            .map(|_| ())
+    async fn send_otp(&self, channel: OtpChannel, contact: &str) -> Result<(), Self::Error> {
+        match channel {
+            OtpChannel::Email => self
+                .client
+                .send_email_with_otp(contact, None)
+                .await
+                .map(|_| ())?,
+            OtpChannel::Phone => self.client.send_sms_with_otp(contact).await.map(|_| ())?,
+        };
+        Ok(())
     }
 
-    async fn verify_otp(&self, contact: &str, token: &str) -> Result<Self::Session, Self::Error> {
-        let params = sb_models::VerifyEmailOtpParams {
-            email: contact.to_string(),
-            token: token.to_string(),
-            otp_type: sb_models::OtpType::Email,
-            options: None,
+    async fn verify_otp(
+        &self,
+        channel: OtpChannel,
+        contact: &str,
+        token: &str,
+    ) -> Result<Self::Session, Self::Error> {
+        let params = match channel {
+            OtpChannel::Email => {
+                sb_models::VerifyOtpParams::Email(sb_models::VerifyEmailOtpParams {
+                    email: contact.to_string(),
+                    token: token.to_string(),
+                    otp_type: sb_models::OtpType::Email,
+                    options: None,
+                })
+            }
+            OtpChannel::Phone => {
+                sb_models::VerifyOtpParams::Mobile(sb_models::VerifyMobileOtpParams {
+                    phone: contact.to_string(),
+                    token: token.to_string(),
+                    otp_type: sb_models::OtpType::Sms,
+                    options: None,
+                })
+            }
         };
 
-        let session = self.client
-            .verify_otp(sb_models::VerifyOtpParams::Email(params))
-            .await?;
+        let session = self.client.verify_otp(params).await?;
 
-        // TODO: verify that the session corresponds to an existing user in our 'public' schema; 
-        // if it does not, add the user (UUID + any information to be filled in)
+        let Some(pool) = &self.pool else {
+            return Ok(session.into());
+        };
+        let user = &session.user;
+        let email = Some(user.email.as_str()).filter(|email| !email.is_empty());
+        let phone = Some(user.phone.as_str()).filter(|phone| !phone.is_empty());
+        let login = users::upsert_user_on_login(pool, user.id, email, phone).await?;
 
-        Ok(session)
+        Ok(SbSession {
+            session,
+            is_new_user: login.is_new_user,
+        })
     }
 
     async fn logout(&self, bearer_token: &str) -> Result<(), Self::Error> {
         self.client
             .logout(Some(sb_models::LogoutScope::Global), bearer_token)
-            .await
+            .await?;
+        Ok(())
     }
 
     async fn refresh_token(&self, refresh_token: &str) -> Result<Self::Session, Self::Error> {
-        self.client.refresh_session(refresh_token).await
+        Ok(self.client.refresh_session(refresh_token).await?.into())
     }
 
     async fn verify_token(&self, access_token: &str) -> Result<uuid::Uuid, Self::Error> {
-        self.client.get_user(access_token).await.map(|u| u.id)
+        Ok(self.client.get_user(access_token).await?.id)
+    }
+
+    async fn barring_sanction(&self, user_id: uuid::Uuid) -> Result<Option<Sanction>, Self::Error> {
+        let Some(pool) = &self.pool else {
+            return Ok(None);
+        };
+        Ok(self.sanctions.barring_sanction(pool, user_id).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn supabase_error(code: i32, error_code: &str, message: &str) -> SbAuthError {
+        SbAuthError::Supabase(sb_error::Error::Supabase(sb_error::SupabaseHTTPError {
+            code,
+            error_code: Some(error_code.to_string()),
+            message: message.to_string(),
+            internal_error: None,
+            internal_message: None,
+            error_id: None,
+        }))
+    }
+
+    #[test]
+    fn test_supabase_errors_map_to_codes() {
+        let error = ApiError::from(supabase_error(
+            403,
+            "otp_expired",
+            "Token has expired or is invalid",
+        ));
+        assert_eq!(error.code(), ErrorCode::InvalidOtp);
+        assert_eq!(error.status(), StatusCode::BAD_REQUEST);
+
+        let error = ApiError::from(supabase_error(429, "over_email_send_rate_limit", "limit"));
+        assert_eq!(error.code(), ErrorCode::RateLimited);
+
+        let error = ApiError::from(supabase_error(418, "something_new", "teapot"));
+        assert_eq!(error.code(), ErrorCode::BadRequest);
+
+        let error = ApiError::from(supabase_error(500, "unexpected_failure", "db down"));
+        assert_eq!(error.code(), ErrorCode::UpstreamError);
+    }
+
+    #[test]
+    fn test_supabase_internals_not_exposed() {
+        let error = ApiError::from(supabase_error(
+            500,
+            "unexpected_failure",
+            "relation auth.users is locked",
+        ));
+        assert_eq!(error.message(), "Authentication provider unavailable");
+        assert!(error.details().is_none());
     }
 }
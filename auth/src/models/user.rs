@@ -0,0 +1,121 @@
+//! The authenticated user attached to requests by the authentication middleware.
+
+use serde::Serialize;
+use shared::types::role::Role;
+use uuid::Uuid;
+
+use crate::error::AuthError;
+use crate::jwt::Claims;
+
+/// A user whose access token has been verified.
+///
+/// The authentication middleware inserts this into the request extensions, so
+/// that handlers can access it using `axum::Extension<AuthenticatedUser>`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AuthenticatedUser {
+    /// The user's UUID, taken from the `sub` claim.
+    pub id: Uuid,
+    /// The session the access token belongs to, if the token names one.
+    pub session_id: Option<Uuid>,
+    /// The role claimed by the access token, if any.
+    pub role: Option<String>,
+    /// The user's email address, if the token carries one.
+    pub email: Option<String>,
+}
+
+impl AuthenticatedUser {
+    /// The application role claimed by the access token.
+    ///
+    /// Tokens whose `role` claim does not name a [`Role`], such as Supabase's
+    /// `authenticated`, or that carry no role claim, claim the `user` role.
+    pub fn claimed_role(&self) -> Role {
+        self.role
+            .as_deref()
+            .and_then(|role| role.parse().ok())
+            .unwrap_or_default()
+    }
+}
+
+impl TryFrom<Claims> for AuthenticatedUser {
+    type Error = AuthError;
+
+    /// Build the user from verified claims.
+    ///
+    /// Fails if the subject or session ID is not a valid UUID.
+    fn try_from(claims: Claims) -> Result<Self, Self::Error> {
+        let parse = |value: &str, name: &str| {
+            Uuid::parse_str(value)
+                .map_err(|e| AuthError::InvalidToken(format!("Invalid {name} in token: {e}")))
+        };
+
+        Ok(Self {
+            id: parse(&claims.sub, "user ID")?,
+            session_id: claims
+                .session_id
+                .as_deref()
+                .map(|id| parse(id, "session ID"))
+                .transpose()?,
+            role: claims.role,
+            email: claims.email,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_claims() {
+        let id = Uuid::new_v4();
+        let session_id = Uuid::new_v4();
+        let claims = Claims {
+            sub: id.to_string(),
+            session_id: Some(session_id.to_string()),
+            role: Some("authenticated".into()),
+            email: Some("user@example.com".into()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            AuthenticatedUser::try_from(claims).unwrap(),
+            AuthenticatedUser {
+                id,
+                session_id: Some(session_id),
+                role: Some("authenticated".into()),
+                email: Some("user@example.com".into()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_from_claims_rejects_invalid_ids() {
+        let claims = Claims {
+            sub: "user".into(),
+            ..Default::default()
+        };
+        assert!(AuthenticatedUser::try_from(claims).is_err());
+
+        let claims = Claims {
+            sub: Uuid::new_v4().to_string(),
+            session_id: Some("session".into()),
+            ..Default::default()
+        };
+        assert!(AuthenticatedUser::try_from(claims).is_err());
+    }
+
+    #[test]
+    fn test_claimed_role() {
+        let user = |role: Option<&str>| AuthenticatedUser {
+            id: Uuid::new_v4(),
+            session_id: None,
+            role: role.map(str::to_string),
+            email: None,
+        };
+
+        assert_eq!(user(Some("moderator")).claimed_role(), Role::Moderator);
+        assert_eq!(user(Some("admin")).claimed_role(), Role::Admin);
+        assert_eq!(user(Some("authenticated")).claimed_role(), Role::User);
+        assert_eq!(user(None).claimed_role(), Role::User);
+    }
+}
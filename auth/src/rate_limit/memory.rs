@@ -0,0 +1,245 @@
+//! Rate limiting state kept in process memory.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+
+use super::{FAILURE_DECAY, Failures, Hits, RateLimitError, RateLimitStore};
+
+/// Number of entries above which expired entries are pruned.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+#[derive(Default)]
+struct MemoryState {
+    windows: HashMap<String, Hits>,
+    failures: HashMap<String, FailureRecord>,
+}
+
+struct FailureRecord {
+    failures: u32,
+    lockouts: u32,
+    locked_until: Option<DateTime<Utc>>,
+    last_failure_at: DateTime<Utc>,
+}
+
+impl FailureRecord {
+    fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            failures: 0,
+            lockouts: 0,
+            locked_until: None,
+            last_failure_at: now,
+        }
+    }
+
+    /// Whether the key has been left alone for long enough for its failures and
+    /// lockouts to be forgotten. Keys still locked out never are.
+    fn has_decayed(&self, now: DateTime<Utc>) -> bool {
+        let last_active = self.locked_until.map_or(self.last_failure_at, |until| {
+            until.max(self.last_failure_at)
+        });
+        last_active + FAILURE_DECAY <= now
+    }
+}
+
+/// Store keeping rate limiting state in memory.
+///
+/// State is lost on restart and not shared between instances; use
+/// [`PgStore`](super::PgStore) for deployments running several instances.
+/// Clones share state.
+///
+/// Failures and lockouts of a key are forgotten a day after its last failure or
+/// the end of its last lockout, whichever is later. Only such keys are evicted
+/// when the store grows large, so eviction never lifts a lockout or resets the
+/// escalation of lockout durations early.
+#[derive(Clone, Default)]
+pub struct MemoryStore {
+    state: Arc<Mutex<MemoryState>>,
+}
+
+impl MemoryStore {
+    /// Create a new, empty MemoryStore.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for MemoryStore {
+    async fn hit(&self, key: &str, window: Duration) -> Result<Hits, RateLimitError> {
+        let now = Utc::now();
+        let mut state = self.state.lock().unwrap();
+
+        if state.windows.len() > PRUNE_THRESHOLD {
+            state.windows.retain(|_, hits| hits.resets_at > now);
+        }
+
+        let hits = state.windows.entry(key.to_string()).or_insert(Hits {
+            count: 0,
+            resets_at: now + window,
+        });
+        if hits.resets_at <= now {
+            *hits = Hits {
+                count: 0,
+                resets_at: now + window,
+            };
+        }
+        hits.count += 1;
+        Ok(*hits)
+    }
+
+    async fn locked_until(&self, key: &str) -> Result<Option<DateTime<Utc>>, RateLimitError> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .failures
+            .get(key)
+            .and_then(|record| record.locked_until)
+            .filter(|until| *until > Utc::now()))
+    }
+
+    async fn record_failure(&self, key: &str) -> Result<Failures, RateLimitError> {
+        let now = Utc::now();
+        let mut state = self.state.lock().unwrap();
+
+        if state.failures.len() > PRUNE_THRESHOLD {
+            state.failures.retain(|_, record| !record.has_decayed(now));
+        }
+
+        let record = state
+            .failures
+            .entry(key.to_string())
+            .or_insert_with(|| FailureRecord::new(now));
+        if record.has_decayed(now) {
+            *record = FailureRecord::new(now);
+        }
+        record.failures += 1;
+        record.last_failure_at = now;
+        Ok(Failures {
+            failures: record.failures,
+            lockouts: record.lockouts,
+        })
+    }
+
+    async fn lock(&self, key: &str, until: DateTime<Utc>) -> Result<(), RateLimitError> {
+        let mut state = self.state.lock().unwrap();
+        let record = state
+            .failures
+            .entry(key.to_string())
+            .or_insert_with(|| FailureRecord::new(Utc::now()));
+        record.failures = 0;
+        record.lockouts += 1;
+        record.locked_until = Some(until);
+        Ok(())
+    }
+
+    async fn clear_failures(&self, key: &str) -> Result<(), RateLimitError> {
+        self.state.lock().unwrap().failures.remove(key);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_hits_reset_after_window() {
+        let store = MemoryStore::new();
+
+        let first = store.hit("key", Duration::hours(1)).await.unwrap();
+        let second = store.hit("key", Duration::hours(1)).await.unwrap();
+        assert_eq!(first.count, 1);
+        assert_eq!(second.count, 2);
+        assert_eq!(second.resets_at, first.resets_at);
+        assert_eq!(
+            store.hit("other", Duration::hours(1)).await.unwrap().count,
+            1
+        );
+
+        // an elapsed window starts over
+        store.hit("short", Duration::zero()).await.unwrap();
+        assert_eq!(store.hit("short", Duration::zero()).await.unwrap().count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_lock_resets_failures_and_counts_lockouts() {
+        let store = MemoryStore::new();
+        store.record_failure("key").await.unwrap();
+        store.record_failure("key").await.unwrap();
+
+        let until = Utc::now() + Duration::minutes(1);
+        store.lock("key", until).await.unwrap();
+        assert_eq!(store.locked_until("key").await.unwrap(), Some(until));
+        assert_eq!(
+            store.record_failure("key").await.unwrap(),
+            Failures {
+                failures: 1,
+                lockouts: 1
+            }
+        );
+
+        store.clear_failures("key").await.unwrap();
+        assert_eq!(store.locked_until("key").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_failures_decay_once_idle() {
+        let store = MemoryStore::new();
+        store.record_failure("key").await.unwrap();
+        store
+            .lock("key", Utc::now() + Duration::minutes(1))
+            .await
+            .unwrap();
+        store.record_failure("key").await.unwrap();
+
+        let idle_for = |idle: Duration| {
+            let mut state = store.state.lock().unwrap();
+            let record = state.failures.get_mut("key").unwrap();
+            record.last_failure_at = Utc::now() - idle;
+            record.locked_until = Some(Utc::now() - idle);
+        };
+        // Just short of the decay window, failures and lockouts still count.
+        idle_for(FAILURE_DECAY - Duration::minutes(1));
+        assert_eq!(
+            store.record_failure("key").await.unwrap(),
+            Failures {
+                failures: 2,
+                lockouts: 1
+            }
+        );
+
+        idle_for(FAILURE_DECAY);
+        assert_eq!(
+            store.record_failure("key").await.unwrap(),
+            Failures {
+                failures: 1,
+                lockouts: 0
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pruning_keeps_lockouts_and_recent_failures() {
+        let store = MemoryStore::new();
+        let until = Utc::now() + Duration::minutes(1);
+        store.lock("locked", until).await.unwrap();
+        store.record_failure("failing").await.unwrap();
+        {
+            let mut state = store.state.lock().unwrap();
+            let idle_since = Utc::now() - FAILURE_DECAY;
+            for i in 0..PRUNE_THRESHOLD {
+                state
+                    .failures
+                    .insert(format!("idle-{i}"), FailureRecord::new(idle_since));
+            }
+        }
+
+        store.record_failure("new").await.unwrap();
+        let state = store.state.lock().unwrap();
+        assert_eq!(state.failures.len(), 3);
+        assert_eq!(state.failures["failing"].failures, 1);
+        assert_eq!(state.failures["locked"].locked_until, Some(until));
+    }
+}
@@ -0,0 +1,414 @@
+//! Rate limiting and brute-force protection for the OTP endpoints.
+//!
+//! Without limits, `/send-otp` can be used to flood a victim's inbox and
+//! `/verify-otp` to guess codes. The [`rate_limit_otp`] middleware counts requests
+//! per client IP and per contact in fixed windows, and locks a contact out of
+//! verification after repeated failed attempts, doubling the lockout each time.
+//! Limited requests are rejected with `429 Too Many Requests` and a `Retry-After`
+//! header.
+//!
+//! Counters are kept in a [`RateLimitStore`]: in memory by default, or in Postgres
+//! with [`PgStore`] so that limits hold across several gateway instances.
+
+mod memory;
+mod postgres;
+
+pub use memory::MemoryStore;
+pub use postgres::PgStore;
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use axum::body::{Body, to_bytes};
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::header;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, Duration, Utc};
+use db::error::DbError;
+use serde::Deserialize;
+use serde_json::json;
+use shared::error::{ApiError, ErrorCode};
+use sqlx::PgPool;
+use thiserror::Error;
+
+use crate::models::OtpChannel;
+
+/// Largest request body inspected for the contact; larger bodies are rejected.
+const MAX_BODY_SIZE: usize = 16 * 1024;
+
+/// Time after its last failure, or the end of its last lockout, after which a
+/// key's failures and lockouts are forgotten.
+const FAILURE_DECAY: Duration = Duration::hours(24);
+
+/// Errors returned by a [`RateLimitStore`].
+#[derive(Error, Debug)]
+pub enum RateLimitError {
+    #[error(transparent)]
+    Database(#[from] DbError),
+}
+
+/// A number of requests allowed per window of time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quota {
+    pub limit: u32,
+    pub window: Duration,
+}
+
+impl Quota {
+    /// Allow `limit` requests per `window`.
+    pub fn new(limit: u32, window: Duration) -> Self {
+        Self { limit, window }
+    }
+}
+
+/// Limits enforced by [`OtpRateLimiter`].
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    /// OTPs a single client IP may request.
+    pub send_per_ip: Quota,
+    /// OTPs that may be sent to a single contact.
+    pub send_per_contact: Quota,
+    /// Verification attempts a single client IP may make.
+    pub verify_per_ip: Quota,
+    /// Failed verifications after which a contact is locked out.
+    pub max_failed_attempts: u32,
+    /// Duration of the first lockout of a contact; each further lockout doubles it.
+    pub lockout: Duration,
+    /// Upper bound of the lockout duration.
+    pub max_lockout: Duration,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            send_per_ip: Quota::new(20, Duration::hours(1)),
+            send_per_contact: Quota::new(5, Duration::minutes(15)),
+            verify_per_ip: Quota::new(50, Duration::minutes(15)),
+            max_failed_attempts: 5,
+            lockout: Duration::minutes(1),
+            max_lockout: Duration::hours(1),
+        }
+    }
+}
+
+impl RateLimitConfig {
+    /// Duration of a lockout, given the number of earlier lockouts of the contact.
+    fn lockout_duration(&self, earlier_lockouts: u32) -> Duration {
+        let factor = 2_i32.saturating_pow(earlier_lockouts.min(30));
+        self.lockout
+            .checked_mul(factor)
+            .unwrap_or(self.max_lockout)
+            .min(self.max_lockout)
+    }
+}
+
+/// Hits counted against a key in its current window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hits {
+    pub count: u32,
+    pub resets_at: DateTime<Utc>,
+}
+
+/// Failed attempts recorded against a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Failures {
+    /// Failures since the last lockout.
+    pub failures: u32,
+    /// Lockouts so far.
+    pub lockouts: u32,
+}
+
+/// Trait for types that keep rate limiting state.
+#[async_trait]
+pub trait RateLimitStore: Send + Sync + 'static {
+    /// Count a hit against `key` in its current fixed window of length `window`,
+    /// starting a new window if the previous one has elapsed.
+    async fn hit(&self, key: &str, window: Duration) -> Result<Hits, RateLimitError>;
+
+    /// Returns when the lockout of `key` ends, if it is currently locked out.
+    async fn locked_until(&self, key: &str) -> Result<Option<DateTime<Utc>>, RateLimitError>;
+
+    /// Record a failed attempt for `key`.
+    ///
+    /// Failures and lockouts are forgotten once `key` has neither failed nor been
+    /// locked out for a day.
+    async fn record_failure(&self, key: &str) -> Result<Failures, RateLimitError>;
+
+    /// Lock `key` out until the given time, resetting its failures and counting the lockout.
+    async fn lock(&self, key: &str, until: DateTime<Utc>) -> Result<(), RateLimitError>;
+
+    /// Forget the failures and lockouts of `key`.
+    async fn clear_failures(&self, key: &str) -> Result<(), RateLimitError>;
+}
+
+/// State for the [`rate_limit_otp`] middleware.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use auth::models::SbAuthenticator;
+/// use auth::rate_limit::{OtpRateLimiter, rate_limit_otp};
+/// use axum::middleware;
+///
+/// let limiter = OtpRateLimiter::in_memory();
+/// let auth_router = auth::router(SbAuthenticator::default())
+///     .layer(middleware::from_fn_with_state(limiter, rate_limit_otp));
+/// ```
+#[derive(Clone)]
+pub struct OtpRateLimiter {
+    store: Arc<dyn RateLimitStore>,
+    config: RateLimitConfig,
+    trust_forwarded_for: bool,
+}
+
+impl OtpRateLimiter {
+    /// Create a new OtpRateLimiter keeping its state in the given store, with the default limits.
+    pub fn new(store: impl RateLimitStore) -> Self {
+        Self {
+            store: Arc::new(store),
+            config: RateLimitConfig::default(),
+            trust_forwarded_for: false,
+        }
+    }
+
+    /// Create a new OtpRateLimiter keeping its state in memory.
+    pub fn in_memory() -> Self {
+        Self::new(MemoryStore::new())
+    }
+
+    /// Create a new OtpRateLimiter keeping its state in Postgres.
+    pub fn postgres(pool: PgPool) -> Self {
+        Self::new(PgStore::new(pool))
+    }
+
+    /// Enforce the given limits instead of the defaults.
+    pub fn with_config(mut self, config: RateLimitConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Take the client IP from the last entry of the `X-Forwarded-For` header.
+    ///
+    /// Only enable this behind a reverse proxy that appends the address it received
+    /// the request from; otherwise clients can choose their own IP. By default, the
+    /// IP of the connection is used, which requires serving the router with
+    /// `into_make_service_with_connect_info::<SocketAddr>()`.
+    pub fn trust_forwarded_for(mut self) -> Self {
+        self.trust_forwarded_for = true;
+        self
+    }
+
+    /// Count a hit against `key`, returning how long to wait if the quota is exhausted.
+    async fn check_quota(
+        &self,
+        key: &str,
+        quota: Quota,
+    ) -> Result<Option<Duration>, RateLimitError> {
+        let hits = self.store.hit(key, quota.window).await?;
+        Ok((hits.count > quota.limit).then(|| hits.resets_at - Utc::now()))
+    }
+
+    /// Returns how long `key` remains locked out, if it is.
+    async fn check_lockout(&self, key: &str) -> Result<Option<Duration>, RateLimitError> {
+        let locked_until = self.store.locked_until(key).await?;
+        Ok(locked_until.map(|until| until - Utc::now()))
+    }
+
+    /// Record a failed verification, locking `key` out once it has failed too often.
+    async fn record_failure(&self, key: &str) -> Result<(), RateLimitError> {
+        let failures = self.store.record_failure(key).await?;
+        if failures.failures >= self.config.max_failed_attempts {
+            let until = Utc::now() + self.config.lockout_duration(failures.lockouts);
+            self.store.lock(key, until).await?;
+        }
+        Ok(())
+    }
+
+    /// Returns the IP of the client making the request, if known.
+    fn client_ip(&self, request: &Request) -> Option<IpAddr> {
+        if self.trust_forwarded_for {
+            let forwarded = request
+                .headers()
+                .get("x-forwarded-for")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.rsplit(',').next())
+                .and_then(|ip| ip.trim().parse().ok());
+            if forwarded.is_some() {
+                return forwarded;
+            }
+        }
+
+        request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip())
+    }
+}
+
+/// The OTP endpoint a request is made to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Endpoint {
+    SendOtp,
+    VerifyOtp,
+}
+
+/// The contact fields shared by the bodies of both OTP endpoints.
+#[derive(Deserialize)]
+struct ContactPayload {
+    #[serde(default)]
+    channel: OtpChannel,
+    contact: String,
+}
+
+/// Rate limiting middleware for the OTP endpoints of [`router`](crate::router).
+///
+/// Requests to `/send-otp` are limited per client IP and per contact. Requests to
+/// `/verify-otp` are limited per client IP, and rejected outright while the contact
+/// is locked out; failed verifications count towards a lockout, and a successful
+/// one clears the count. Other requests pass through unchanged.
+///
+/// Limited requests are rejected with `429 Too Many Requests`, the `rate_limited`
+/// code and a `Retry-After` header giving the number of seconds to wait, which is
+/// repeated as `retry_after_seconds` in the error details. If the rate limiting
+/// state cannot be read, requests are rejected with `503 Service Unavailable`.
+pub async fn rate_limit_otp(
+    State(limiter): State<OtpRateLimiter>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let endpoint = match request.uri().path() {
+        path if path.ends_with("/send-otp") => Endpoint::SendOtp,
+        path if path.ends_with("/verify-otp") => Endpoint::VerifyOtp,
+        _ => return next.run(request).await,
+    };
+    let ip = limiter.client_ip(&request);
+
+    // the contact is read from the body, which is then handed on to the handler
+    let (parts, body) = request.into_parts();
+    let Ok(bytes) = to_bytes(body, MAX_BODY_SIZE).await else {
+        return ApiError::new(ErrorCode::PayloadTooLarge, "Request body too large").into_response();
+    };
+    let contact = serde_json::from_slice::<ContactPayload>(&bytes)
+        .ok()
+        .and_then(|payload| payload.channel.normalize(&payload.contact));
+    let request = Request::from_parts(parts, Body::from(bytes));
+
+    match check(&limiter, endpoint, ip, contact.as_deref()).await {
+        Ok(None) => {}
+        Ok(Some(retry_after)) => return too_many_requests(retry_after),
+        Err(e) => return unavailable(e),
+    }
+
+    let response = next.run(request).await;
+
+    if let (Endpoint::VerifyOtp, Some(contact)) = (endpoint, contact) {
+        let key = format!("otp-verify:contact:{contact}");
+        let result = if response.status().is_success() {
+            limiter.store.clear_failures(&key).await
+        } else if response.status().is_client_error() {
+            limiter.record_failure(&key).await
+        } else {
+            Ok(())
+        };
+        if let Err(e) = result {
+            return unavailable(e);
+        }
+    }
+
+    response
+}
+
+/// Check the limits applying to a request, returning how long to wait if any is exceeded.
+async fn check(
+    limiter: &OtpRateLimiter,
+    endpoint: Endpoint,
+    ip: Option<IpAddr>,
+    contact: Option<&str>,
+) -> Result<Option<Duration>, RateLimitError> {
+    let config = &limiter.config;
+    let mut checks = Vec::new();
+
+    match endpoint {
+        Endpoint::SendOtp => {
+            if let Some(ip) = ip {
+                checks.push((format!("otp-send:ip:{ip}"), config.send_per_ip));
+            }
+            if let Some(contact) = contact {
+                checks.push((
+                    format!("otp-send:contact:{contact}"),
+                    config.send_per_contact,
+                ));
+            }
+        }
+        Endpoint::VerifyOtp => {
+            if let Some(contact) = contact {
+                let key = format!("otp-verify:contact:{contact}");
+                if let Some(remaining) = limiter.check_lockout(&key).await? {
+                    return Ok(Some(remaining));
+                }
+            }
+            if let Some(ip) = ip {
+                checks.push((format!("otp-verify:ip:{ip}"), config.verify_per_ip));
+            }
+        }
+    }
+
+    for (key, quota) in checks {
+        if let Some(retry_after) = limiter.check_quota(&key, quota).await? {
+            return Ok(Some(retry_after));
+        }
+    }
+    Ok(None)
+}
+
+/// Respond with `429 Too Many Requests`, telling the client how long to wait.
+fn too_many_requests(retry_after: Duration) -> Response {
+    // round up so that clients retrying on time are not rejected again
+    let seconds = (retry_after.num_milliseconds().max(0) as u64)
+        .div_ceil(1000)
+        .max(1);
+    let error = ApiError::new(
+        ErrorCode::RateLimited,
+        format!("Too many requests. Please try again in {seconds} seconds."),
+    )
+    .with_details(json!({ "retry_after_seconds": seconds }));
+    ([(header::RETRY_AFTER, seconds)], error).into_response()
+}
+
+/// Respond with `503 Service Unavailable` when the rate limiting state cannot be used.
+fn unavailable(error: RateLimitError) -> Response {
+    ApiError::new(
+        ErrorCode::ServiceUnavailable,
+        "Rate limiting is temporarily unavailable",
+    )
+    .with_source(error)
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+
+    #[test]
+    fn test_lockout_duration_doubles_up_to_max() {
+        let config = RateLimitConfig::default();
+        assert_eq!(config.lockout_duration(0), Duration::minutes(1));
+        assert_eq!(config.lockout_duration(1), Duration::minutes(2));
+        assert_eq!(config.lockout_duration(3), Duration::minutes(8));
+        assert_eq!(config.lockout_duration(10), Duration::hours(1));
+        assert_eq!(config.lockout_duration(u32::MAX), Duration::hours(1));
+    }
+
+    #[test]
+    fn test_retry_after_rounds_up() {
+        let response = too_many_requests(Duration::milliseconds(1500));
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "2");
+
+        let response = too_many_requests(Duration::zero());
+        assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+    }
+}
@@ -0,0 +1,91 @@
+//! Rate limiting state kept in PostgreSQL.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use db::queries::rate_limits as queries;
+use sqlx::PgPool;
+use tokio::task::JoinHandle;
+
+use super::{FAILURE_DECAY, Failures, Hits, RateLimitError, RateLimitStore};
+
+/// Time between deletions of the elapsed windows and forgotten failures.
+const PRUNE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// Store keeping rate limiting state in PostgreSQL, so that limits hold across
+/// all instances of the gateway.
+///
+/// The tables used by this store are created by the migrations in the `db` crate.
+/// Their elapsed windows and forgotten failures are deleted by the task started
+/// with [`PgStore::spawn_prune_task`].
+#[derive(Clone)]
+pub struct PgStore {
+    pool: PgPool,
+}
+
+impl PgStore {
+    /// Create a new PgStore using the provided pool.
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Delete elapsed windows and the failures and lockouts that have been
+    /// forgotten, returning the number of rows deleted.
+    pub async fn prune(&self) -> Result<u64, RateLimitError> {
+        Ok(queries::prune(&self.pool, seconds(FAILURE_DECAY)).await?)
+    }
+
+    /// Spawn a background task that prunes the store every hour.
+    ///
+    /// Failed prunes are retried at the next interval.
+    pub fn spawn_prune_task(&self) -> JoinHandle<()> {
+        let store = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = store.prune().await {
+                    eprintln!("Rate limit pruning failed: {}", e);
+                }
+            }
+        })
+    }
+}
+
+/// A duration in fractional seconds, as bound to the queries.
+fn seconds(duration: Duration) -> f64 {
+    duration.num_milliseconds() as f64 / 1000.0
+}
+
+#[async_trait]
+impl RateLimitStore for PgStore {
+    async fn hit(&self, key: &str, window: Duration) -> Result<Hits, RateLimitError> {
+        let (count, started_at) = queries::hit(&self.pool, key, seconds(window)).await?;
+
+        Ok(Hits {
+            count: count as u32,
+            resets_at: started_at + window,
+        })
+    }
+
+    async fn locked_until(&self, key: &str) -> Result<Option<DateTime<Utc>>, RateLimitError> {
+        Ok(queries::get_locked_until(&self.pool, key).await?)
+    }
+
+    async fn record_failure(&self, key: &str) -> Result<Failures, RateLimitError> {
+        let (failures, lockouts) =
+            queries::record_failure(&self.pool, key, seconds(FAILURE_DECAY)).await?;
+
+        Ok(Failures {
+            failures: failures as u32,
+            lockouts: lockouts as u32,
+        })
+    }
+
+    async fn lock(&self, key: &str, until: DateTime<Utc>) -> Result<(), RateLimitError> {
+        Ok(queries::lock(&self.pool, key, until).await?)
+    }
+
+    async fn clear_failures(&self, key: &str) -> Result<(), RateLimitError> {
+        Ok(queries::clear_failures(&self.pool, key).await?)
+    }
+}
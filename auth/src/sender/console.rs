@@ -0,0 +1,82 @@
+//! OTP sender that logs codes locally instead of delivering them.
+
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use tokio::io::AsyncWriteExt;
+
+use super::{OtpSender, SendError};
+
+/// OTP sender for local development that writes codes to standard output or
+/// appends them to a file, one line per code.
+///
+/// # Example
+///
+/// ```rust
+/// use auth::sender::ConsoleSender;
+///
+/// let stdout = ConsoleSender::stdout();
+/// let file = ConsoleSender::file("otp.log");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ConsoleSender {
+    path: Option<PathBuf>,
+}
+
+impl ConsoleSender {
+    /// Create a sender that prints codes to standard output.
+    pub fn stdout() -> Self {
+        Self { path: None }
+    }
+
+    /// Create a sender that appends codes to the file at `path`.
+    pub fn file(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: Some(path.into()),
+        }
+    }
+}
+
+#[async_trait]
+impl OtpSender for ConsoleSender {
+    async fn send(&self, contact: &str, code: &str) -> Result<(), SendError> {
+        let line = format!("OTP for {}: {}", contact, code);
+
+        let Some(path) = &self.path else {
+            println!("{line}");
+            return Ok(());
+        };
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await
+            .map_err(|e| SendError::Delivery(e.to_string()))?;
+
+        file.write_all(format!("{line}\n").as_bytes())
+            .await
+            .map_err(|e| SendError::Delivery(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_file_sender_appends_codes() {
+        let path = std::env::temp_dir().join(format!("otp-{}.log", uuid::Uuid::new_v4()));
+        let sender = ConsoleSender::file(&path);
+
+        sender.send("user@example.com", "123456").await.unwrap();
+        sender.send("+14155550123", "654321").await.unwrap();
+
+        let contents = tokio::fs::read_to_string(&path).await.unwrap();
+        tokio::fs::remove_file(&path).await.unwrap();
+        assert_eq!(
+            contents,
+            "OTP for user@example.com: 123456\nOTP for +14155550123: 654321\n"
+        );
+    }
+}
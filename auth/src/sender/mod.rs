@@ -0,0 +1,54 @@
+//! OTP delivery channels.
+//!
+//! Authentication backends that generate their own OTP codes (such as
+//! [`PgAuthenticator`](crate::models::PgAuthenticator)) hand them to an
+//! [`OtpSender`] for delivery. The following senders are included:
+//!
+//! - [`ConsoleSender`] - writes codes to standard output or a file; for local development
+//! - `SmtpSender` - sends codes by email over SMTP (requires the `smtp` feature)
+//! - `SmsSender` - sends codes by SMS through a Twilio-compatible API (requires the `sms` feature)
+
+mod console;
+#[cfg(feature = "sms")]
+mod sms;
+#[cfg(feature = "smtp")]
+mod smtp;
+
+use async_trait::async_trait;
+use thiserror::Error;
+
+pub use console::ConsoleSender;
+#[cfg(feature = "sms")]
+pub use sms::SmsSender;
+#[cfg(feature = "smtp")]
+pub use smtp::SmtpSender;
+
+/// Errors that can occur while delivering an OTP.
+#[derive(Error, Debug)]
+pub enum SendError {
+    #[error("Invalid recipient: {0}")]
+    InvalidRecipient(String),
+
+    #[error("Sender configuration error: {0}")]
+    Configuration(String),
+
+    #[error("Failed to deliver OTP: {0}")]
+    Delivery(String),
+}
+
+/// Trait for services that deliver OTP codes to users.
+#[async_trait]
+pub trait OtpSender: Send + Sync + 'static {
+    /// Deliver an OTP code to the given contact.
+    ///
+    /// # Arguments
+    /// * `contact` - The normalized contact information of the recipient
+    /// * `code` - The OTP code to deliver
+    async fn send(&self, contact: &str, code: &str) -> Result<(), SendError>;
+}
+
+/// Build the message text containing an OTP code.
+#[cfg(any(feature = "smtp", feature = "sms"))]
+fn otp_message(code: &str) -> String {
+    format!("Your middleground verification code is {code}.")
+}
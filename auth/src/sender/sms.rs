@@ -0,0 +1,99 @@
+//! OTP sender delivering codes by SMS through a Twilio-compatible messaging API.
+
+use async_trait::async_trait;
+
+use super::{OtpSender, SendError, otp_message};
+
+/// Default base URL of the messaging API.
+const DEFAULT_API_URL: &str = "https://api.twilio.com/2010-04-01";
+
+/// OTP sender that delivers codes by SMS using the Twilio Messages API, or any
+/// provider exposing a compatible endpoint.
+///
+/// # Environment Variables
+///
+/// The following environment variables must be set when using `from_env`:
+/// - `SMS_ACCOUNT_SID` - the account identifier
+/// - `SMS_AUTH_TOKEN` - the account authentication token
+/// - `SMS_FROM` - the phone number or sender ID messages are sent from
+///
+/// Optionally, `SMS_API_URL` overrides the base URL of the messaging API.
+#[derive(Clone)]
+pub struct SmsSender {
+    client: reqwest::Client,
+    api_url: String,
+    account_sid: String,
+    auth_token: String,
+    from: String,
+}
+
+impl SmsSender {
+    /// Create a new SmsSender using the default API URL.
+    pub fn new(account_sid: String, auth_token: String, from: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            api_url: DEFAULT_API_URL.to_string(),
+            account_sid,
+            auth_token,
+            from,
+        }
+    }
+
+    /// Use a different base URL for the messaging API.
+    pub fn with_api_url(mut self, api_url: impl Into<String>) -> Self {
+        self.api_url = api_url.into();
+        self
+    }
+
+    /// Create a new SmsSender from environment variables.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the required environment variables are not set.
+    pub fn from_env() -> Result<Self, SendError> {
+        let var = |name: &str| {
+            dotenvy::var(name).map_err(|e| SendError::Configuration(format!("{name}: {e}")))
+        };
+
+        let sender = Self::new(
+            var("SMS_ACCOUNT_SID")?,
+            var("SMS_AUTH_TOKEN")?,
+            var("SMS_FROM")?,
+        );
+        Ok(match dotenvy::var("SMS_API_URL") {
+            Ok(api_url) => sender.with_api_url(api_url),
+            Err(_) => sender,
+        })
+    }
+}
+
+#[async_trait]
+impl OtpSender for SmsSender {
+    async fn send(&self, contact: &str, code: &str) -> Result<(), SendError> {
+        let url = format!(
+            "{}/Accounts/{}/Messages.json",
+            self.api_url.trim_end_matches('/'),
+            self.account_sid
+        );
+        let body = otp_message(code);
+        let params = [("To", contact), ("From", &self.from), ("Body", &body)];
+
+        let response = self
+            .client
+            .post(url)
+            .basic_auth(&self.account_sid, Some(&self.auth_token))
+            .form(&params)
+            .send()
+            .await
+            .map_err(|e| SendError::Delivery(e.to_string()))?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(SendError::Delivery(format!(
+                "SMS provider responded with {}",
+                response.status()
+            )))
+        }
+    }
+}
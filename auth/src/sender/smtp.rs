@@ -0,0 +1,87 @@
+//! OTP sender delivering codes by email over SMTP.
+
+use async_trait::async_trait;
+use lettre::message::{Mailbox, header::ContentType};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+use super::{OtpSender, SendError, otp_message};
+
+/// OTP sender that delivers codes by email through an SMTP relay using STARTTLS.
+///
+/// # Environment Variables
+///
+/// The following environment variables must be set when using `from_env`:
+/// - `SMTP_HOST` - the SMTP relay host name
+/// - `SMTP_USERNAME` - the SMTP user name
+/// - `SMTP_PASSWORD` - the SMTP password
+/// - `SMTP_FROM` - the sender address, e.g. `middleground <no-reply@example.com>`
+///
+/// Optionally, `SMTP_PORT` overrides the default submission port.
+#[derive(Clone)]
+pub struct SmtpSender {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpSender {
+    /// Create a new SmtpSender using the provided transport and sender address.
+    pub fn new(transport: AsyncSmtpTransport<Tokio1Executor>, from: Mailbox) -> Self {
+        Self { transport, from }
+    }
+
+    /// Create a new SmtpSender from environment variables.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the required environment variables are not set
+    /// or contain invalid values.
+    pub fn from_env() -> Result<Self, SendError> {
+        let var = |name: &str| {
+            dotenvy::var(name).map_err(|e| SendError::Configuration(format!("{name}: {e}")))
+        };
+
+        let mut builder = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&var("SMTP_HOST")?)
+            .map_err(|e| SendError::Configuration(e.to_string()))?
+            .credentials(Credentials::new(
+                var("SMTP_USERNAME")?,
+                var("SMTP_PASSWORD")?,
+            ));
+
+        if let Ok(port) = dotenvy::var("SMTP_PORT") {
+            let port = port
+                .parse()
+                .map_err(|_| SendError::Configuration(format!("SMTP_PORT: invalid port {port}")))?;
+            builder = builder.port(port);
+        }
+
+        let from = var("SMTP_FROM")?
+            .parse()
+            .map_err(|e| SendError::Configuration(format!("SMTP_FROM: {e}")))?;
+
+        Ok(Self::new(builder.build(), from))
+    }
+}
+
+#[async_trait]
+impl OtpSender for SmtpSender {
+    async fn send(&self, contact: &str, code: &str) -> Result<(), SendError> {
+        let to: Mailbox = contact
+            .parse()
+            .map_err(|_| SendError::InvalidRecipient(contact.to_string()))?;
+
+        let email = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject("Your middleground verification code")
+            .header(ContentType::TEXT_PLAIN)
+            .body(otp_message(code))
+            .map_err(|e| SendError::Delivery(e.to_string()))?;
+
+        self.transport
+            .send(email)
+            .await
+            .map(|_| ())
+            .map_err(|e| SendError::Delivery(e.to_string()))
+    }
+}
@@ -0,0 +1,152 @@
+//! End-to-end tests of the role-based authorization middleware using `MockAuthenticator`.
+
+use async_trait::async_trait;
+use auth::authorization::{Authorization, RoleSource, authorize, require_permission, require_role};
+use auth::error::AuthError;
+use auth::middleware::auth_standard;
+use auth::models::{AuthSession, AuthenticatedUser, MockAuthenticator};
+use axum::body::{Body, to_bytes};
+use axum::http::{Request, StatusCode, header};
+use axum::routing::get;
+use axum::{Extension, Json, Router, middleware};
+use serde_json::{Value, json};
+use shared::types::role::{Permission, Role};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+const SECRET: &str = "test-secret";
+
+/// Send a GET request with the given bearer token and return the response status and JSON body.
+async fn get_as(router: Router, uri: &str, token: &str) -> (StatusCode, Value) {
+    let request = Request::get(uri)
+        .header(header::AUTHORIZATION, format!("Bearer {token}"))
+        .body(Body::empty())
+        .unwrap();
+    let response = router.oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+/// Router with a single route behind `auth_standard` and the given authorization, echoing the role.
+fn authorized_router(authenticator: MockAuthenticator, authorization: Authorization) -> Router {
+    async fn whoami(Extension(role): Extension<Role>) -> Json<Role> {
+        Json(role)
+    }
+
+    Router::new()
+        .route("/protected", get(whoami))
+        .route_layer(middleware::from_fn_with_state(authorization, authorize))
+        .route_layer(middleware::from_fn_with_state(
+            authenticator,
+            auth_standard::<MockAuthenticator>,
+        ))
+}
+
+/// Create a session for a new user holding the given role.
+fn token_for(authenticator: &MockAuthenticator, role: Option<Role>) -> String {
+    let user_id = Uuid::new_v4();
+    if let Some(role) = role {
+        authenticator.grant_role(user_id, role);
+    }
+    authenticator
+        .create_session(user_id)
+        .access_token()
+        .to_string()
+}
+
+#[tokio::test]
+async fn test_require_role_rejects_less_privileged_users() {
+    let authenticator = MockAuthenticator::new(SECRET);
+    let router = authorized_router(authenticator.clone(), require_role(Role::Moderator));
+
+    let (status, body) = get_as(router, "/protected", &token_for(&authenticator, None)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(
+        body,
+        json!({
+            "code": "forbidden",
+            "message": "Requires the moderator role",
+            "details": { "role": "user", "required_role": "moderator" },
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_require_role_accepts_role_and_above() {
+    let authenticator = MockAuthenticator::new(SECRET);
+    let router = authorized_router(authenticator.clone(), require_role(Role::Moderator));
+
+    for role in [Role::Moderator, Role::Admin] {
+        let token = token_for(&authenticator, Some(role));
+        let (status, body) = get_as(router.clone(), "/protected", &token).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!(role));
+    }
+}
+
+#[tokio::test]
+async fn test_require_permission() {
+    let authenticator = MockAuthenticator::new(SECRET);
+    let router = authorized_router(
+        authenticator.clone(),
+        require_permission(Permission::ManageSources),
+    );
+
+    let token = token_for(&authenticator, Some(Role::Moderator));
+    let (status, body) = get_as(router.clone(), "/protected", &token).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["details"]["required_role"], "admin");
+    assert_eq!(body["details"]["required_permission"], "manage_sources");
+
+    let token = token_for(&authenticator, Some(Role::Admin));
+    let (status, _) = get_as(router, "/protected", &token).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_custom_role_source() {
+    struct Admins(Vec<Uuid>);
+
+    #[async_trait]
+    impl RoleSource for Admins {
+        async fn role(&self, user: &AuthenticatedUser) -> Result<Role, AuthError> {
+            Ok(if self.0.contains(&user.id) {
+                Role::Admin
+            } else {
+                user.claimed_role()
+            })
+        }
+    }
+
+    let authenticator = MockAuthenticator::new(SECRET);
+    let admin_id = Uuid::new_v4();
+    let router = authorized_router(
+        authenticator.clone(),
+        require_role(Role::Admin).with_role_source(Admins(vec![admin_id])),
+    );
+
+    let token = authenticator
+        .create_session(admin_id)
+        .access_token()
+        .to_string();
+    let (status, _) = get_as(router.clone(), "/protected", &token).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let token = token_for(&authenticator, Some(Role::Moderator));
+    let (status, _) = get_as(router, "/protected", &token).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_authorize_without_authentication_layer() {
+    let router = Router::new()
+        .route("/protected", get(|| async { "unreachable" }))
+        .route_layer(middleware::from_fn_with_state(
+            require_role(Role::User),
+            authorize,
+        ));
+
+    let (status, _) = get_as(router, "/protected", "token").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,183 @@
+//! End-to-end tests of the `AuthUser` and `OptionalAuthUser` extractors using `MockAuthenticator`.
+
+use auth::extract::{AuthUser, AuthVerifier, OptionalAuthUser};
+use auth::jwt::HmacVerifier;
+use auth::middleware::auth_strict;
+use auth::models::{AuthSession, MockAuthenticator};
+use axum::body::{Body, to_bytes};
+use axum::http::{Request, StatusCode, header};
+use axum::routing::get;
+use axum::{Json, Router, middleware};
+use chrono::{Duration, Utc};
+use serde_json::{Value, json};
+use shared::types::moderation::{Sanction, SanctionKind};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+const SECRET: &str = "test-secret";
+
+/// Send a GET request, with a bearer token if given, and return the response status,
+/// `WWW-Authenticate` header and JSON body.
+async fn get_as(
+    router: Router,
+    uri: &str,
+    token: Option<&str>,
+) -> (StatusCode, Option<String>, Value) {
+    let mut request = Request::get(uri);
+    if let Some(token) = token {
+        request = request.header(header::AUTHORIZATION, format!("Bearer {token}"));
+    }
+    let response = router
+        .oneshot(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    let status = response.status();
+    let challenge = response
+        .headers()
+        .get(header::WWW_AUTHENTICATE)
+        .map(|value| value.to_str().unwrap().to_string());
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (
+        status,
+        challenge,
+        serde_json::from_slice(&body).unwrap_or(Value::Null),
+    )
+}
+
+async fn whoami(user: AuthUser) -> Json<Uuid> {
+    Json(user.id)
+}
+
+async fn greeting(OptionalAuthUser(user): OptionalAuthUser) -> String {
+    match user {
+        Some(user) => format!("Hello, {}!", user.id),
+        None => "Hello, stranger!".to_string(),
+    }
+}
+
+/// Router without any authentication layer; handlers protect themselves.
+fn router(authenticator: MockAuthenticator) -> Router {
+    Router::new()
+        .route("/whoami", get(whoami))
+        .route("/greeting", get(greeting))
+        .with_state(authenticator)
+}
+
+#[tokio::test]
+async fn test_auth_user_verifies_token() {
+    let authenticator = MockAuthenticator::new(SECRET);
+    let user_id = Uuid::new_v4();
+    let session = authenticator.create_session(user_id);
+
+    let (status, _, body) = get_as(
+        router(authenticator),
+        "/whoami",
+        Some(session.access_token()),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!(user_id));
+}
+
+#[tokio::test]
+async fn test_auth_user_rejects_missing_and_invalid_tokens() {
+    let authenticator = MockAuthenticator::new(SECRET);
+    let router = router(authenticator.clone());
+
+    let (status, challenge, body) = get_as(router.clone(), "/whoami", None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(challenge.as_deref(), Some("Bearer"));
+    assert_eq!(body["code"], "missing_credentials");
+
+    let expired = authenticator.mint_access_token(Uuid::new_v4(), Duration::hours(-1));
+    let (status, _, body) = get_as(router.clone(), "/whoami", Some(&expired)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["code"], "invalid_token");
+
+    let foreign = MockAuthenticator::new("other-secret").create_session(Uuid::new_v4());
+    let (status, _, _) = get_as(router, "/whoami", Some(foreign.access_token())).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_optional_auth_user() {
+    let authenticator = MockAuthenticator::new(SECRET);
+    let user_id = Uuid::new_v4();
+    let session = authenticator.create_session(user_id);
+    let router = router(authenticator);
+
+    let (status, _, _) = get_as(router.clone(), "/greeting", None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _, _) = get_as(router.clone(), "/greeting", Some(session.access_token())).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // an invalid token is rejected rather than treated as anonymous
+    let (status, _, _) = get_as(router, "/greeting", Some("not-a-token")).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_auth_user_rejects_suspended_users() {
+    let authenticator = MockAuthenticator::new(SECRET);
+    let user_id = Uuid::new_v4();
+    let session = authenticator.create_session(user_id);
+    let expires_at = Utc::now() + Duration::days(1);
+    authenticator.sanction(Sanction {
+        id: Uuid::new_v4(),
+        user_id,
+        kind: SanctionKind::Suspension,
+        report_id: None,
+        reason: None,
+        issued_by: Uuid::new_v4(),
+        issued_at: Utc::now(),
+        expires_at: Some(expires_at),
+    });
+    let router = router(authenticator);
+
+    let (status, challenge, body) =
+        get_as(router.clone(), "/whoami", Some(session.access_token())).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(challenge, None);
+    assert_eq!(body["code"], "account_suspended");
+    assert_eq!(body["details"]["sanction"], "suspension");
+    assert_eq!(body["details"]["expires_at"], json!(expires_at));
+
+    // a suspended user is not treated as anonymous either
+    let (status, _, _) = get_as(router, "/greeting", Some(session.access_token())).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_auth_user_reuses_middleware_user() {
+    let authenticator = MockAuthenticator::new(SECRET);
+    let session = authenticator.create_session(Uuid::new_v4());
+    let router = Router::new()
+        .route("/whoami", get(whoami))
+        .route_layer(middleware::from_fn_with_state(
+            authenticator.clone(),
+            auth_strict::<MockAuthenticator>,
+        ))
+        .with_state(authenticator.clone());
+
+    let (status, _, _) = get_as(router.clone(), "/whoami", Some(session.access_token())).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // the strict middleware rejects the revoked session before the extractor runs
+    authenticator.revoke(session.access_token());
+    let (status, _, _) = get_as(router, "/whoami", Some(session.access_token())).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_auth_user_with_custom_state() {
+    let authenticator = MockAuthenticator::new(SECRET);
+    let session = authenticator.create_session(Uuid::new_v4());
+    let router = Router::new()
+        .route("/whoami", get(whoami))
+        .with_state(AuthVerifier::from_verifier(HmacVerifier::new(SECRET)));
+
+    let (status, _, _) = get_as(router, "/whoami", Some(session.access_token())).await;
+    assert_eq!(status, StatusCode::OK);
+}
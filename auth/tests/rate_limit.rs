@@ -0,0 +1,311 @@
+//! End-to-end tests of the OTP rate limiting middleware using `MockAuthenticator`.
+
+use std::net::SocketAddr;
+
+use auth::models::MockAuthenticator;
+use auth::rate_limit::{
+    Failures, OtpRateLimiter, PgStore, Quota, RateLimitConfig, RateLimitStore, rate_limit_otp,
+};
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{Request, StatusCode, header};
+use axum::{Router, middleware};
+use chrono::{Duration, Utc};
+use serde_json::{Value, json};
+use sqlx::PgPool;
+use tower::ServiceExt;
+
+const SECRET: &str = "test-secret";
+
+/// Limits small enough to reach in a test, with quotas that do not get in the way
+/// unless overridden.
+fn config() -> RateLimitConfig {
+    RateLimitConfig {
+        send_per_ip: Quota::new(100, Duration::hours(1)),
+        send_per_contact: Quota::new(100, Duration::hours(1)),
+        verify_per_ip: Quota::new(100, Duration::hours(1)),
+        max_failed_attempts: 3,
+        lockout: Duration::minutes(1),
+        max_lockout: Duration::hours(1),
+    }
+}
+
+fn limited_router(authenticator: MockAuthenticator, limiter: OtpRateLimiter) -> Router {
+    auth::router(authenticator).layer(middleware::from_fn_with_state(limiter, rate_limit_otp))
+}
+
+/// Send a JSON POST request from the given client address and return the response
+/// status and `Retry-After` header.
+async fn post_from(
+    router: &Router,
+    ip: [u8; 4],
+    uri: &str,
+    body: Value,
+) -> (StatusCode, Option<u64>) {
+    let mut request = Request::post(uri)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    request
+        .extensions_mut()
+        .insert(ConnectInfo(SocketAddr::from((ip, 4000))));
+
+    let response = router.clone().oneshot(request).await.unwrap();
+    let retry_after = response
+        .headers()
+        .get(header::RETRY_AFTER)
+        .map(|value| value.to_str().unwrap().parse().unwrap());
+    (response.status(), retry_after)
+}
+
+#[tokio::test]
+async fn test_send_otp_limited_per_contact() {
+    let limiter = OtpRateLimiter::in_memory().with_config(RateLimitConfig {
+        send_per_contact: Quota::new(2, Duration::minutes(15)),
+        ..config()
+    });
+    let router = limited_router(MockAuthenticator::new(SECRET), limiter);
+    let send =
+        |ip, contact: &str| post_from(&router, ip, "/send-otp", json!({ "contact": contact }));
+
+    assert_eq!(
+        send([10, 0, 0, 1], "user@example.com").await.0,
+        StatusCode::OK
+    );
+    // contacts are normalized before counting, and counted across IPs
+    assert_eq!(
+        send([10, 0, 0, 2], "User@Example.com").await.0,
+        StatusCode::OK
+    );
+
+    let (status, retry_after) = send([10, 0, 0, 3], "user@example.com").await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    let retry_after = retry_after.unwrap();
+    assert!(retry_after > 0 && retry_after <= 15 * 60);
+
+    assert_eq!(
+        send([10, 0, 0, 1], "other@example.com").await.0,
+        StatusCode::OK
+    );
+}
+
+#[tokio::test]
+async fn test_send_otp_limited_per_ip() {
+    let limiter = OtpRateLimiter::in_memory().with_config(RateLimitConfig {
+        send_per_ip: Quota::new(2, Duration::hours(1)),
+        ..config()
+    });
+    let router = limited_router(MockAuthenticator::new(SECRET), limiter);
+    let send =
+        |ip, contact: &str| post_from(&router, ip, "/send-otp", json!({ "contact": contact }));
+
+    assert_eq!(send([10, 0, 0, 1], "a@example.com").await.0, StatusCode::OK);
+    assert_eq!(send([10, 0, 0, 1], "b@example.com").await.0, StatusCode::OK);
+    assert_eq!(
+        send([10, 0, 0, 1], "c@example.com").await.0,
+        StatusCode::TOO_MANY_REQUESTS
+    );
+    assert_eq!(send([10, 0, 0, 2], "c@example.com").await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_failed_verifications_lock_contact_out() {
+    let authenticator = MockAuthenticator::new(SECRET);
+    let router = limited_router(
+        authenticator.clone(),
+        OtpRateLimiter::in_memory().with_config(config()),
+    );
+    let contact = "user@example.com";
+
+    post_from(
+        &router,
+        [10, 0, 0, 1],
+        "/send-otp",
+        json!({ "contact": contact }),
+    )
+    .await;
+    let code = authenticator.last_otp(contact).unwrap();
+    let verify = |ip, token: &str| {
+        post_from(
+            &router,
+            ip,
+            "/verify-otp",
+            json!({ "contact": contact, "token": token }),
+        )
+    };
+
+    for ip in [[10, 0, 0, 1], [10, 0, 0, 2], [10, 0, 0, 3]] {
+        assert_eq!(verify(ip, "000000x").await.0, StatusCode::BAD_REQUEST);
+    }
+
+    // even the correct code is rejected while locked out, from any IP
+    let (status, retry_after) = verify([10, 0, 0, 4], &code).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(retry_after, Some(60));
+}
+
+#[tokio::test]
+async fn test_successful_verification_clears_failures() {
+    let authenticator = MockAuthenticator::new(SECRET);
+    let router = limited_router(
+        authenticator.clone(),
+        OtpRateLimiter::in_memory().with_config(config()),
+    );
+    let contact = "user@example.com";
+    let verify = |token: String| {
+        post_from(
+            &router,
+            [10, 0, 0, 1],
+            "/verify-otp",
+            json!({ "contact": contact, "token": token }),
+        )
+    };
+
+    for _ in 0..3 {
+        post_from(
+            &router,
+            [10, 0, 0, 1],
+            "/send-otp",
+            json!({ "contact": contact }),
+        )
+        .await;
+        let code = authenticator.last_otp(contact).unwrap();
+
+        assert_eq!(verify("wrong".into()).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(verify("wrong".into()).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(verify(code).await.0, StatusCode::OK);
+    }
+}
+
+#[tokio::test]
+async fn test_forwarded_for_used_only_when_trusted() {
+    let config = RateLimitConfig {
+        send_per_ip: Quota::new(1, Duration::hours(1)),
+        ..config()
+    };
+    let send = |router: Router, forwarded_for: &'static str, contact: &'static str| async move {
+        let mut request = Request::post("/send-otp")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header("x-forwarded-for", forwarded_for)
+            .body(Body::from(json!({ "contact": contact }).to_string()))
+            .unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([10, 0, 0, 1], 4000))));
+        router.oneshot(request).await.unwrap().status()
+    };
+
+    let trusted = limited_router(
+        MockAuthenticator::new(SECRET),
+        OtpRateLimiter::in_memory()
+            .with_config(config.clone())
+            .trust_forwarded_for(),
+    );
+    assert_eq!(
+        send(trusted.clone(), "1.1.1.1, 192.0.2.1", "a@example.com").await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        send(trusted, "1.1.1.1, 192.0.2.2", "b@example.com").await,
+        StatusCode::OK
+    );
+
+    let untrusted = limited_router(
+        MockAuthenticator::new(SECRET),
+        OtpRateLimiter::in_memory().with_config(config),
+    );
+    assert_eq!(
+        send(untrusted.clone(), "192.0.2.1", "a@example.com").await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        send(untrusted, "192.0.2.2", "b@example.com").await,
+        StatusCode::TOO_MANY_REQUESTS
+    );
+}
+
+#[tokio::test]
+async fn test_other_endpoints_not_limited() {
+    let limiter = OtpRateLimiter::in_memory().with_config(RateLimitConfig {
+        verify_per_ip: Quota::new(0, Duration::hours(1)),
+        send_per_ip: Quota::new(0, Duration::hours(1)),
+        ..config()
+    });
+    let router = limited_router(MockAuthenticator::new(SECRET), limiter);
+
+    let (status, _) = post_from(&router, [10, 0, 0, 1], "/logout", json!({})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+/// Make a key look as if it last failed, and its last lockout ended, `idle` ago.
+async fn idle_for(pool: &PgPool, key: &str, idle: Duration) {
+    sqlx::query("UPDATE auth_lockouts SET last_failure_at = $2, locked_until = $2 WHERE key = $1")
+        .bind(key)
+        .bind(Utc::now() - idle)
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+#[sqlx::test(migrator = "db::MIGRATOR")]
+async fn test_failures_decay_once_idle_in_postgres(pool: PgPool) {
+    let store = PgStore::new(pool.clone());
+    store.record_failure("key").await.unwrap();
+    store
+        .lock("key", Utc::now() + Duration::minutes(1))
+        .await
+        .unwrap();
+    store.record_failure("key").await.unwrap();
+
+    // Just short of a day, failures and lockouts still count.
+    idle_for(&pool, "key", Duration::hours(24) - Duration::minutes(1)).await;
+    assert_eq!(
+        store.record_failure("key").await.unwrap(),
+        Failures {
+            failures: 2,
+            lockouts: 1
+        }
+    );
+
+    idle_for(&pool, "key", Duration::hours(24)).await;
+    assert_eq!(
+        store.record_failure("key").await.unwrap(),
+        Failures {
+            failures: 1,
+            lockouts: 0
+        }
+    );
+}
+
+#[sqlx::test(migrator = "db::MIGRATOR")]
+async fn test_pruning_keeps_lockouts_and_current_windows(pool: PgPool) {
+    let store = PgStore::new(pool.clone());
+    store.hit("current", Duration::hours(1)).await.unwrap();
+    store.hit("elapsed", Duration::zero()).await.unwrap();
+    store.record_failure("locked").await.unwrap();
+    store
+        .lock("locked", Utc::now() + Duration::minutes(1))
+        .await
+        .unwrap();
+    store.record_failure("failing").await.unwrap();
+    store.record_failure("idle").await.unwrap();
+    idle_for(&pool, "idle", Duration::hours(24)).await;
+
+    assert_eq!(store.prune().await.unwrap(), 2);
+    assert_eq!(
+        store
+            .hit("current", Duration::hours(1))
+            .await
+            .unwrap()
+            .count,
+        2
+    );
+    assert!(store.locked_until("locked").await.unwrap().is_some());
+    assert_eq!(
+        store.record_failure("failing").await.unwrap(),
+        Failures {
+            failures: 2,
+            lockouts: 0
+        }
+    );
+}
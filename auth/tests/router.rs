@@ -0,0 +1,428 @@
+//! End-to-end tests of the authentication router and middleware using `MockAuthenticator`.
+
+use auth::jwt::Claims;
+use auth::middleware::{auth_standard, auth_strict};
+use auth::models::mock_authenticator::MockAuthError;
+use auth::models::{AuthSession, AuthenticatedUser, MockAuthenticator};
+use axum::body::{Body, to_bytes};
+use axum::http::{Request, StatusCode, header};
+use axum::routing::get;
+use axum::{Extension, Json, Router, middleware};
+use chrono::Duration;
+use serde_json::{Value, json};
+use shared::request_id::request_id;
+use shared::types::moderation::{Sanction, SanctionKind};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+const SECRET: &str = "test-secret";
+
+/// Send a request to the router and return the response status and JSON body.
+async fn send(router: Router, request: Request<Body>) -> (StatusCode, Value) {
+    let response = router.oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json = serde_json::from_slice(&body).unwrap_or(Value::Null);
+    (status, json)
+}
+
+fn post_json(uri: &str, body: Value) -> Request<Body> {
+    Request::post(uri)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+fn with_bearer(method: &str, uri: &str, token: &str) -> Request<Body> {
+    Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::AUTHORIZATION, format!("Bearer {token}"))
+        .body(Body::empty())
+        .unwrap()
+}
+
+/// Router with one route behind each authentication middleware, echoing the user.
+fn protected_router(authenticator: MockAuthenticator) -> Router {
+    async fn whoami(Extension(user): Extension<AuthenticatedUser>) -> Json<AuthenticatedUser> {
+        Json(user)
+    }
+
+    let standard =
+        Router::new()
+            .route("/standard", get(whoami))
+            .route_layer(middleware::from_fn_with_state(
+                authenticator.clone(),
+                auth_standard::<MockAuthenticator>,
+            ));
+    let strict =
+        Router::new()
+            .route("/strict", get(whoami))
+            .route_layer(middleware::from_fn_with_state(
+                authenticator.clone(),
+                auth_strict::<MockAuthenticator>,
+            ));
+
+    standard.merge(strict)
+}
+
+/// Run the OTP flow through the router and return the resulting tokens.
+async fn sign_in(authenticator: &MockAuthenticator, contact: &str) -> (String, String) {
+    let router = auth::router(authenticator.clone());
+
+    let (status, _) = send(
+        router.clone(),
+        post_json("/send-otp", json!({ "contact": contact })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let code = authenticator.last_otp(contact).unwrap();
+    let (status, body) = send(
+        router,
+        post_json("/verify-otp", json!({ "contact": contact, "token": code })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    (
+        body["access_token"].as_str().unwrap().to_string(),
+        body["refresh_token"].as_str().unwrap().to_string(),
+    )
+}
+
+#[tokio::test]
+async fn test_send_and_verify_otp() {
+    let authenticator = MockAuthenticator::new(SECRET);
+    let (access_token, _) = sign_in(&authenticator, "user@example.com").await;
+
+    let user_id = authenticator.user_id("user@example.com").unwrap();
+    let (status, body) = send(
+        protected_router(authenticator),
+        with_bearer("GET", "/standard", &access_token),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["id"], json!(user_id));
+    assert_eq!(body["email"], "user@example.com");
+    assert_eq!(body["role"], "authenticated");
+    assert!(body["session_id"].is_string());
+}
+
+#[tokio::test]
+async fn test_send_otp_over_phone() {
+    let authenticator = MockAuthenticator::new(SECRET);
+    let router = auth::router(authenticator.clone());
+
+    let (status, body) = send(
+        router,
+        post_json(
+            "/send-otp",
+            json!({ "channel": "phone", "contact": "+14155550123" }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["message"], "OTP sent. Please check your messages.");
+    assert!(authenticator.last_otp("+14155550123").is_some());
+}
+
+#[tokio::test]
+async fn test_verify_otp_wrong_code() {
+    let authenticator = MockAuthenticator::new(SECRET);
+    let router = auth::router(authenticator.clone());
+    send(
+        router.clone(),
+        post_json("/send-otp", json!({ "contact": "user@example.com" })),
+    )
+    .await;
+
+    let (status, body) = send(
+        router,
+        post_json(
+            "/verify-otp",
+            json!({ "contact": "user@example.com", "token": "not-it" }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "invalid_otp");
+    assert_eq!(body["message"], "Invalid or expired OTP");
+}
+
+#[tokio::test]
+async fn test_verify_otp_reports_new_users() {
+    let authenticator = MockAuthenticator::new(SECRET);
+    let router = auth::router(authenticator.clone());
+
+    for is_new_user in [true, false] {
+        send(
+            router.clone(),
+            post_json("/send-otp", json!({ "contact": "user@example.com" })),
+        )
+        .await;
+        let code = authenticator.last_otp("user@example.com").unwrap();
+        let (status, body) = send(
+            router.clone(),
+            post_json(
+                "/verify-otp",
+                json!({ "contact": "user@example.com", "token": code }),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["is_new_user"], is_new_user);
+    }
+}
+
+#[tokio::test]
+async fn test_send_otp_scripted_failure() {
+    let authenticator = MockAuthenticator::new(SECRET);
+    authenticator.fail_next(MockAuthError::Scripted("provider down".into()));
+
+    let (status, body) = send(
+        auth::router(authenticator),
+        post_json("/send-otp", json!({ "contact": "user@example.com" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert_eq!(body["code"], "upstream_error");
+    // the backend's own error is logged, not returned
+    assert!(!body.to_string().contains("provider down"));
+}
+
+#[tokio::test]
+async fn test_refresh_rotates_tokens() {
+    let authenticator = MockAuthenticator::new(SECRET);
+    let (_, refresh_token) = sign_in(&authenticator, "user@example.com").await;
+    let router = auth::router(authenticator.clone());
+
+    let (status, body) = send(
+        router.clone(),
+        with_bearer("POST", "/refresh", &refresh_token),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["access_token"].is_string());
+
+    // the old refresh token has been rotated out
+    let (status, _) = send(router, with_bearer("POST", "/refresh", &refresh_token)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_refresh_expired_session() {
+    let authenticator = MockAuthenticator::new(SECRET);
+    let session = authenticator.create_session(Uuid::new_v4());
+    authenticator.expire(session.access_token());
+
+    let (status, body) = send(
+        auth::router(authenticator),
+        with_bearer("POST", "/refresh", session.refresh_token()),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["code"], "session_inactive");
+    assert_eq!(body["message"], "Session has expired");
+}
+
+#[tokio::test]
+async fn test_logout_revokes_session() {
+    let authenticator = MockAuthenticator::new(SECRET);
+    let (access_token, _) = sign_in(&authenticator, "user@example.com").await;
+    let router = auth::router(authenticator.clone());
+
+    let (status, _) = send(
+        router.clone(),
+        with_bearer("POST", "/logout", &access_token),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = send(router, with_bearer("POST", "/logout", &access_token)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = send(
+        protected_router(authenticator),
+        with_bearer("GET", "/strict", &access_token),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_logout_missing_header() {
+    let authenticator = MockAuthenticator::new(SECRET);
+    let request = Request::post("/logout").body(Body::empty()).unwrap();
+
+    let (status, body) = send(auth::router(authenticator), request).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "missing_credentials");
+}
+
+#[tokio::test]
+async fn test_standard_accepts_revoked_token_but_strict_rejects() {
+    let authenticator = MockAuthenticator::new(SECRET);
+    let user_id = Uuid::new_v4();
+    let session = authenticator.create_session(user_id);
+    let router = protected_router(authenticator.clone());
+
+    let (status, _) = send(
+        router.clone(),
+        with_bearer("GET", "/strict", session.access_token()),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    authenticator.revoke(session.access_token());
+
+    // standard middleware only validates the token locally
+    let (status, _) = send(
+        router.clone(),
+        with_bearer("GET", "/standard", session.access_token()),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = send(
+        router,
+        with_bearer("GET", "/strict", session.access_token()),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["code"], "session_inactive");
+    assert_eq!(body["message"], "Token has been revoked");
+}
+
+#[tokio::test]
+async fn test_protected_routes_reject_expired_and_foreign_tokens() {
+    let authenticator = MockAuthenticator::new(SECRET);
+    let router = protected_router(authenticator.clone());
+
+    let expired = authenticator.mint_access_token(Uuid::new_v4(), Duration::hours(-1));
+    let (status, _) = send(router.clone(), with_bearer("GET", "/standard", &expired)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let foreign = MockAuthenticator::new("other-secret").create_session(Uuid::new_v4());
+    let (status, _) = send(
+        router.clone(),
+        with_bearer("GET", "/standard", foreign.access_token()),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let request = Request::get("/standard").body(Body::empty()).unwrap();
+    let (status, _) = send(router, request).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_protected_routes_reject_token_not_yet_valid() {
+    let authenticator = MockAuthenticator::new(SECRET);
+    let now = chrono::Utc::now().timestamp() as usize;
+    let token = authenticator.mint_token(&Claims {
+        sub: Uuid::new_v4().to_string(),
+        exp: now + 3600,
+        nbf: Some(now + 600),
+        ..Default::default()
+    });
+
+    let (status, _) = send(
+        protected_router(authenticator),
+        with_bearer("GET", "/standard", &token),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_protected_routes_reject_suspended_users() {
+    let authenticator = MockAuthenticator::new(SECRET);
+    let router = protected_router(authenticator.clone());
+    let sanction = |kind, expires_at| Sanction {
+        id: Uuid::new_v4(),
+        user_id: Uuid::new_v4(),
+        kind,
+        report_id: None,
+        reason: None,
+        issued_by: Uuid::new_v4(),
+        issued_at: chrono::Utc::now(),
+        expires_at,
+    };
+
+    let until = chrono::Utc::now() + Duration::days(7);
+    let suspension = sanction(SanctionKind::Suspension, Some(until));
+    let session = authenticator.create_session(suspension.user_id);
+    authenticator.sanction(suspension);
+    for uri in ["/standard", "/strict"] {
+        let (status, body) = send(
+            router.clone(),
+            with_bearer("GET", uri, session.access_token()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["code"], "account_suspended");
+        assert_eq!(body["details"]["sanction"], "suspension");
+        assert_eq!(body["details"]["expires_at"], json!(until));
+    }
+
+    // lapsed suspensions and warnings do not bar users
+    let lapsed = sanction(
+        SanctionKind::Suspension,
+        Some(chrono::Utc::now() - Duration::days(1)),
+    );
+    let warning = sanction(SanctionKind::Warning, None);
+    for sanction in [lapsed, warning] {
+        let session = authenticator.create_session(sanction.user_id);
+        authenticator.sanction(sanction);
+        let (status, _) = send(
+            router.clone(),
+            with_bearer("GET", "/standard", session.access_token()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+}
+
+#[tokio::test]
+async fn test_malformed_body_rejected_with_error_code() {
+    let authenticator = MockAuthenticator::new(SECRET);
+
+    let (status, body) = send(
+        auth::router(authenticator),
+        post_json("/send-otp", json!({ "email": "user@example.com" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["code"], "validation_failed");
+}
+
+#[tokio::test]
+async fn test_errors_carry_request_id() {
+    let authenticator = MockAuthenticator::new(SECRET);
+    let router = auth::router(authenticator).layer(middleware::from_fn(request_id));
+
+    let mut request = post_json(
+        "/verify-otp",
+        json!({ "contact": "user@example.com", "token": "not-it" }),
+    );
+    request
+        .headers_mut()
+        .insert("x-request-id", "req-123".parse().unwrap());
+    let response = router.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.headers()["x-request-id"], "req-123");
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["request_id"], "req-123");
+
+    // a request id is generated when the client sends none
+    let (_, body) = send(
+        router,
+        post_json(
+            "/verify-otp",
+            json!({ "contact": "user@example.com", "token": "x" }),
+        ),
+    )
+    .await;
+    assert!(body["request_id"].as_str().is_some_and(|id| !id.is_empty()));
+}
@@ -0,0 +1,782 @@
+//! The WebSocket endpoint through which participants chat.
+
+use std::collections::HashSet;
+
+use auth::middleware::{auth_standard, barred_error};
+use auth::models::{AuthenticatedUser, Authenticator};
+use axum::extract::rejection::{PathRejection, QueryRejection};
+use axum::extract::ws::{self, WebSocket, WebSocketUpgrade, rejection::WebSocketUpgradeRejection};
+use axum::extract::{Path, Query, State};
+use axum::response::Response;
+use axum::routing::{get, post};
+use axum::{Extension, Json, Router, middleware};
+use chrono::{Duration, Utc};
+use db::error::DbError;
+use db::queries::{conversations, messages, reports, sanctions, sources};
+use serde::Deserialize;
+use serde_json::json;
+use shared::error::{ApiError, ErrorCode};
+use shared::types::conversation::{
+    Conversation, ConversationEndReason, ConversationState, Message,
+};
+use shared::types::source::{Source, SourceSummary};
+use sqlx::PgPool;
+use tokio::sync::broadcast::error::RecvError;
+use uuid::Uuid;
+
+use crate::filter::{FilterContext, MessageFilters};
+use crate::history;
+use crate::hub::ChatHub;
+use crate::lifecycle;
+use crate::protocol::{ClientEvent, ServerEvent};
+
+/// Most sources a message may cite.
+const MAX_CITED_SOURCES: usize = 5;
+
+/// How long senders may edit and delete their messages, in minutes.
+const DEFAULT_EDIT_WINDOW_MINUTES: i64 = 15;
+
+/// Time between checks that connected participants have not been suspended or
+/// banned, unless configured otherwise.
+const DEFAULT_SANCTION_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// The chat API, served by [`ChatApi::router`].
+///
+/// # Protocol
+///
+/// Participants connect to `GET /{id}/ws`, optionally passing the id of the
+/// last message they have seen as `?last_seen=`. They are first sent every
+/// message after it (or every message of the conversation), then `edited` and
+/// `deleted` events for earlier messages that may have changed since, then the
+/// other participant's read receipt, then a `synced` event, after which events are
+/// delivered as they happen. See [`ClientEvent`] and [`ServerEvent`] for the
+/// events exchanged.
+///
+/// Messages are stored before they are delivered, so a participant who
+/// reconnects with the id of the last message they received misses nothing.
+/// Connections that fall behind are sent an error with the `service_unavailable`
+/// code and closed, so that they resume this way.
+///
+/// Senders may edit or delete their messages within 15 minutes of sending them,
+/// unless configured otherwise with [`ChatApi::with_edit_window`].
+///
+/// Participants suspended or banned while connected are sent an error with the
+/// `account_suspended` code and disconnected within a minute, unless configured
+/// otherwise with [`ChatApi::with_sanction_check_interval`].
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use auth::models::SbAuthenticator;
+/// use chat::ChatApi;
+///
+/// # async fn example() {
+/// let pool = db::create_pool().await.unwrap();
+/// let app = axum::Router::new().nest(
+///     "/conversations",
+///     ChatApi::new(pool).router(SbAuthenticator::default()),
+/// );
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ChatApi {
+    pub(crate) pool: PgPool,
+    pub(crate) hub: ChatHub,
+    filters: MessageFilters,
+    edit_window: Duration,
+    sanction_check_interval: std::time::Duration,
+}
+
+impl ChatApi {
+    /// Create a new ChatApi using the provided pool, delivering events within
+    /// this process only.
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            hub: ChatHub::default(),
+            filters: MessageFilters::default(),
+            edit_window: Duration::minutes(DEFAULT_EDIT_WINDOW_MINUTES),
+            sanction_check_interval: DEFAULT_SANCTION_CHECK_INTERVAL,
+        }
+    }
+
+    /// Deliver events through the given hub, e.g. one backed by
+    /// [`PgPubSub`](db::pubsub::PgPubSub) to reach participants connected to
+    /// other instances.
+    pub fn with_hub(mut self, hub: ChatHub) -> Self {
+        self.hub = hub;
+        self
+    }
+
+    /// Run messages through the given filters before storing them, instead of
+    /// [`MessageFilters::default`].
+    ///
+    /// Messages the filters flag are reported to the moderation queue.
+    pub fn with_filters(mut self, filters: MessageFilters) -> Self {
+        self.filters = filters;
+        self
+    }
+
+    /// Let senders edit and delete their messages for the given time after
+    /// sending them.
+    pub fn with_edit_window(mut self, window: Duration) -> Self {
+        self.edit_window = window;
+        self
+    }
+
+    /// Check that connected participants have not been suspended or banned at
+    /// the given interval.
+    pub fn with_sanction_check_interval(mut self, interval: std::time::Duration) -> Self {
+        self.sanction_check_interval = interval;
+        self
+    }
+
+    /// Creates a router serving the chat to users authenticated by `auth_standard`.
+    ///
+    /// The router includes the following endpoints, for participants only:
+    ///  - `GET /` - list the caller's conversations, newest first, paginated with
+    ///    `?limit=` and the `next_cursor` of the previous page as `?cursor=`
+    ///  - `GET /{id}` - get the conversation, including whether and why it ended
+    ///  - `GET /{id}/messages` - list the messages of the conversation, oldest
+    ///    first, paginated like conversations
+    ///  - `GET /{id}/transcript` - download the full transcript, formatted as
+    ///    `?format=json` (the default), `markdown` or `text`
+    ///  - `GET /{id}/ws` - join the conversation over a WebSocket
+    ///  - `POST /{id}/leave` - end the conversation, having left it
+    ///  - `POST /{id}/complete` - end the conversation, having completed it
+    ///
+    /// Both participants are sent an `ended` event when the conversation ends.
+    pub fn router<A: Authenticator>(self, authenticator: A) -> Router {
+        Router::new()
+            .route("/", get(history::list_conversations))
+            .route("/{id}", get(get_conversation))
+            .route("/{id}/messages", get(history::list_messages))
+            .route("/{id}/transcript", get(history::export_transcript))
+            .route("/{id}/ws", get(connect))
+            .route("/{id}/leave", post(leave))
+            .route("/{id}/complete", post(complete))
+            .route_layer(middleware::from_fn_with_state(
+                authenticator,
+                auth_standard::<A>,
+            ))
+            .with_state(self)
+    }
+}
+
+/// Where to resume a connection from.
+#[derive(Debug, Deserialize)]
+struct ConnectQuery {
+    last_seen: Option<Uuid>,
+}
+
+/// Upgrade a participant's request to a WebSocket connected to the conversation.
+async fn connect(
+    State(api): State<ChatApi>,
+    Extension(user): Extension<AuthenticatedUser>,
+    id: Result<Path<Uuid>, PathRejection>,
+    query: Result<Query<ConnectQuery>, QueryRejection>,
+    upgrade: Result<WebSocketUpgrade, WebSocketUpgradeRejection>,
+) -> Result<Response, ApiError> {
+    let Path(id) = id?;
+    let Query(query) = query?;
+    let upgrade = upgrade.map_err(|rejection| {
+        ApiError::bad_request(rejection.body_text()).with_status(rejection.status())
+    })?;
+
+    let conversation = participant_conversation(&api.pool, id, user.id).await?;
+    let session = Session {
+        api,
+        conversation,
+        user_id: user.id,
+    };
+    Ok(upgrade.on_upgrade(move |socket| session.run(socket, query.last_seen)))
+}
+
+/// Get a conversation the caller takes part in.
+async fn get_conversation(
+    State(api): State<ChatApi>,
+    Extension(user): Extension<AuthenticatedUser>,
+    id: Result<Path<Uuid>, PathRejection>,
+) -> Result<Json<Conversation>, ApiError> {
+    let Path(id) = id?;
+    Ok(Json(
+        participant_conversation(&api.pool, id, user.id).await?,
+    ))
+}
+
+/// Leave a conversation, ending it for both participants.
+async fn leave(
+    State(api): State<ChatApi>,
+    Extension(user): Extension<AuthenticatedUser>,
+    id: Result<Path<Uuid>, PathRejection>,
+) -> Result<Json<Conversation>, ApiError> {
+    let Path(id) = id?;
+    end(&api, id, user.id, ConversationEndReason::UserLeft).await
+}
+
+/// Complete a conversation, ending it for both participants.
+async fn complete(
+    State(api): State<ChatApi>,
+    Extension(user): Extension<AuthenticatedUser>,
+    id: Result<Path<Uuid>, PathRejection>,
+) -> Result<Json<Conversation>, ApiError> {
+    let Path(id) = id?;
+    end(&api, id, user.id, ConversationEndReason::Completed).await
+}
+
+/// End a conversation on behalf of a participant.
+async fn end(
+    api: &ChatApi,
+    id: Uuid,
+    user_id: Uuid,
+    reason: ConversationEndReason,
+) -> Result<Json<Conversation>, ApiError> {
+    let conversation = participant_conversation(&api.pool, id, user_id).await?;
+    if !conversation.has_ended()
+        && let Some(ended) =
+            lifecycle::end_conversation(&api.pool, &api.hub, id, reason, Some(user_id)).await?
+    {
+        return Ok(Json(ended));
+    }
+
+    // Ended before, or concurrently by the other participant or the sweeper.
+    let conversation = participant_conversation(&api.pool, id, user_id).await?;
+    let mut error = ApiError::conflict("The conversation has already ended");
+    if let Ok(ConversationState::Ended { reason, at }) = conversation.state() {
+        error = error.with_details(json!({ "end_reason": reason, "ended_at": at }));
+    }
+    Err(error)
+}
+
+/// Look up a conversation, rejecting users who do not take part in it.
+pub(crate) async fn participant_conversation(
+    pool: &PgPool,
+    id: Uuid,
+    user_id: Uuid,
+) -> Result<Conversation, ApiError> {
+    let conversation = conversations::get_conversation(pool, id)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Conversation not found"))?;
+    if !conversation.has_participant(user_id) {
+        return Err(ApiError::forbidden(
+            "Only participants may access the conversation",
+        ));
+    }
+    Ok(conversation)
+}
+
+/// A participant's connection to a conversation.
+struct Session {
+    api: ChatApi,
+    conversation: Conversation,
+    user_id: Uuid,
+}
+
+impl Session {
+    /// Catch the participant up from `last_seen`, then relay events until either side closes.
+    async fn run(self, mut socket: WebSocket, last_seen: Option<Uuid>) {
+        // Subscribe before catching up, so that nothing sent in between is missed.
+        let subscribed = match self.api.hub.subscribe(self.conversation.id).await {
+            Ok(events) => self
+                .catch_up(&mut socket, last_seen)
+                .await
+                .map(|replayed| (events, replayed)),
+            Err(e) => Err(e.into()),
+        };
+        let (mut events, replayed) = match subscribed {
+            Ok(subscribed) => subscribed,
+            Err(e) => {
+                let _ = send(&mut socket, &ServerEvent::from(&e)).await;
+                return;
+            }
+        };
+
+        let interval = self.api.sanction_check_interval;
+        let mut sanction_checks =
+            tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+        loop {
+            tokio::select! {
+                _ = sanction_checks.tick() => {
+                    if let Some(error) = self.barred().await {
+                        let _ = send(&mut socket, &ServerEvent::from(&error)).await;
+                        break;
+                    }
+                }
+                event = events.recv() => match event {
+                    Ok(event) if self.should_forward(&event, &replayed) => {
+                        if send(&mut socket, &event).await.is_err() {
+                            break;
+                        }
+                    }
+                    Ok(_) => {}
+                    // The hub drops connections that fall behind by closing the channel.
+                    Err(RecvError::Lagged(_) | RecvError::Closed) => {
+                        let error = ApiError::new(
+                            ErrorCode::ServiceUnavailable,
+                            "Connection fell behind; reconnect with the last message seen",
+                        );
+                        let _ = send(&mut socket, &ServerEvent::from(&error)).await;
+                        break;
+                    }
+                },
+                frame = socket.recv() => match frame {
+                    Some(Ok(ws::Message::Text(text))) => {
+                        if let Err(e) = self.handle(&text).await
+                            && send(&mut socket, &ServerEvent::from(&e)).await.is_err()
+                        {
+                            break;
+                        }
+                    }
+                    Some(Ok(ws::Message::Binary(_))) => {
+                        let error = ApiError::bad_request("Events must be sent as text");
+                        if send(&mut socket, &ServerEvent::from(&error)).await.is_err() {
+                            break;
+                        }
+                    }
+                    Some(Ok(ws::Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => {}
+                },
+            }
+        }
+
+        // Don't leave the other participant thinking the user is still typing.
+        self.publish(ServerEvent::Typing {
+            user_id: self.user_id,
+            is_typing: false,
+        })
+        .await;
+    }
+
+    /// The error disconnecting the participant, if they have been suspended or
+    /// banned since connecting.
+    ///
+    /// Failed lookups keep the participant connected until the next check.
+    async fn barred(&self) -> Option<ApiError> {
+        match sanctions::barring_sanction(&self.api.pool, self.user_id, Utc::now()).await {
+            Ok(sanction) => sanction.as_ref().map(barred_error),
+            Err(e) => {
+                eprintln!("Failed to check sanctions of user {}: {e}", self.user_id);
+                None
+            }
+        }
+    }
+
+    /// Send the messages after `last_seen`, the changes to earlier messages, the
+    /// partner's read receipt and a `synced` event, returning the ids of the
+    /// messages sent.
+    async fn catch_up(
+        &self,
+        socket: &mut WebSocket,
+        last_seen: Option<Uuid>,
+    ) -> Result<HashSet<Uuid>, ApiError> {
+        let mut missed =
+            messages::messages_after(&self.api.pool, self.conversation.id, last_seen).await?;
+        let mut revised = match last_seen {
+            Some(last_seen) => {
+                messages::revised_after(&self.api.pool, self.conversation.id, last_seen).await?
+            }
+            None => Vec::new(),
+        };
+        sources::load_citations(&self.api.pool, &mut missed).await?;
+        sources::load_citations(&self.api.pool, &mut revised).await?;
+        let receipts = messages::read_receipts(&self.api.pool, self.conversation.id).await?;
+
+        let last_message_id = missed.last().map(|message| message.id).or(last_seen);
+        let replayed: HashSet<Uuid> = missed.iter().map(|message| message.id).collect();
+        let revised: Vec<ServerEvent> = revised
+            .into_iter()
+            .filter(|message| !replayed.contains(&message.id))
+            .map(revision_event)
+            .collect();
+
+        let receipts = receipts
+            .into_iter()
+            .filter(|receipt| receipt.user_id != self.user_id)
+            .map(|receipt| ServerEvent::Read {
+                user_id: receipt.user_id,
+                message_id: receipt.message_id,
+                read_at: receipt.read_at,
+            });
+        let events = missed
+            .into_iter()
+            .map(ServerEvent::Message)
+            .chain(revised)
+            .chain(receipts)
+            .chain([ServerEvent::Synced { last_message_id }]);
+        for event in events {
+            send(socket, &event).await.map_err(ApiError::internal)?;
+        }
+
+        Ok(replayed)
+    }
+
+    /// Whether a published event should be relayed to this participant.
+    ///
+    /// Participants receive their own messages, edits and deletions, confirming
+    /// they were stored, but not their own typing indicators and read receipts.
+    /// Messages already sent while catching up are not sent again.
+    fn should_forward(&self, event: &ServerEvent, replayed: &HashSet<Uuid>) -> bool {
+        match event {
+            ServerEvent::Message(message) => !replayed.contains(&message.id),
+            ServerEvent::Edited(_) | ServerEvent::Deleted { .. } => true,
+            event => event.user_id() != Some(self.user_id),
+        }
+    }
+
+    /// Handle an event sent by the participant.
+    async fn handle(&self, text: &str) -> Result<(), ApiError> {
+        let event: ClientEvent = serde_json::from_str(text)
+            .map_err(|e| ApiError::validation(format!("Invalid event: {e}")))?;
+
+        let event = match event {
+            ClientEvent::Message {
+                content,
+                source_ids,
+            } => ServerEvent::Message(self.store_message(&content, &source_ids).await?),
+            ClientEvent::Edit {
+                message_id,
+                content,
+            } => self.revise_message(message_id, Some(&content)).await?,
+            ClientEvent::Delete { message_id } => self.revise_message(message_id, None).await?,
+            ClientEvent::Typing { is_typing } => ServerEvent::Typing {
+                user_id: self.user_id,
+                is_typing,
+            },
+            ClientEvent::Read { message_id } => {
+                let receipt = messages::mark_read(
+                    &self.api.pool,
+                    self.conversation.id,
+                    self.user_id,
+                    message_id,
+                )
+                .await?
+                .ok_or_else(|| ApiError::not_found("Message not found"))?;
+                ServerEvent::Read {
+                    user_id: receipt.user_id,
+                    message_id: receipt.message_id,
+                    read_at: receipt.read_at,
+                }
+            }
+        };
+
+        self.publish(event).await;
+        Ok(())
+    }
+
+    /// Publish an event to the conversation's connections.
+    ///
+    /// Failures are logged rather than reported: messages are already stored by
+    /// then, and reach the other participant when they resume.
+    async fn publish(&self, event: ServerEvent) {
+        if let Err(e) = self.api.hub.publish(self.conversation.id, event).await {
+            eprintln!(
+                "Failed to publish to conversation {}: {e}",
+                self.conversation.id
+            );
+        }
+    }
+
+    /// Edit one of the participant's messages to the given content, or delete it
+    /// if `content` is `None`, returning the event telling participants.
+    ///
+    /// New content goes through the filters like new messages do, and counts
+    /// towards the sender's flood limits.
+    async fn revise_message(
+        &self,
+        message_id: Uuid,
+        content: Option<&str>,
+    ) -> Result<ServerEvent, ApiError> {
+        let now = Utc::now();
+        let filtered = match content {
+            Some(content) => {
+                let content = message_content(content)?.to_string();
+                let recent = self.recent_writes().await?;
+                let context = FilterContext {
+                    sender_id: self.user_id,
+                    sent_at: now,
+                    recent: &recent,
+                };
+                Some(self.api.filters.apply(content, &context)?)
+            }
+            None => None,
+        };
+
+        let conversation_id = self.conversation.id;
+        let sent_since = now - self.api.edit_window;
+        let mut tx = self.api.pool.begin().await.map_err(DbError::Connection)?;
+        let revised = match &filtered {
+            Some(filtered) => {
+                messages::edit_message(
+                    &mut *tx,
+                    conversation_id,
+                    message_id,
+                    self.user_id,
+                    &filtered.content,
+                    now,
+                    sent_since,
+                )
+                .await?
+            }
+            None => {
+                messages::delete_message(
+                    &mut *tx,
+                    conversation_id,
+                    message_id,
+                    self.user_id,
+                    now,
+                    sent_since,
+                )
+                .await?
+            }
+        };
+        let Some(mut message) = revised else {
+            tx.rollback().await.map_err(DbError::Query)?;
+            return Err(self.unrevisable(message_id).await?);
+        };
+        sources::load_citations(&self.api.pool, std::slice::from_mut(&mut message)).await?;
+        if let Some(report) = filtered.and_then(|filtered| filtered.report(&message)) {
+            reports::insert_flagged(&mut *tx, &report).await?;
+        }
+        tx.commit().await.map_err(DbError::Query)?;
+
+        Ok(revision_event(message))
+    }
+
+    /// The error telling the participant why a message cannot be edited or deleted.
+    async fn unrevisable(&self, message_id: Uuid) -> Result<ApiError, ApiError> {
+        let found =
+            messages::get_messages(&self.api.pool, self.conversation.id, &[message_id]).await?;
+        let Some(message) = found.into_iter().next() else {
+            return Ok(ApiError::not_found("Message not found"));
+        };
+        let ended = conversations::get_conversation(&self.api.pool, self.conversation.id)
+            .await?
+            .is_none_or(|conversation| conversation.has_ended());
+        let window = self.api.edit_window;
+        let window = match window.num_seconds() % 60 {
+            0 => format!("{} minutes", window.num_minutes()),
+            _ => format!("{} seconds", window.num_seconds()),
+        };
+        Ok(if message.sender_id != self.user_id {
+            ApiError::forbidden("Only the sender may edit or delete a message")
+        } else if ended {
+            ApiError::conflict("The conversation has ended")
+        } else if message.is_deleted() {
+            ApiError::conflict("The message has been deleted")
+        } else {
+            ApiError::conflict(format!(
+                "Messages can only be edited or deleted within {window} of being sent"
+            ))
+        })
+    }
+
+    /// What the participant last wrote, as far back as the filters look.
+    async fn recent_writes(&self) -> Result<Vec<Message>, ApiError> {
+        Ok(match self.api.filters.history() {
+            0 => Vec::new(),
+            history => {
+                messages::latest_writes(
+                    &self.api.pool,
+                    self.conversation.id,
+                    self.user_id,
+                    history as i64,
+                )
+                .await?
+            }
+        })
+    }
+
+    /// Validate, filter and store a message from the participant, citing the
+    /// given sources, and report it if the filters flag it.
+    async fn store_message(&self, content: &str, source_ids: &[Uuid]) -> Result<Message, ApiError> {
+        let mut message = new_message(&self.conversation, self.user_id, content)?;
+        let source_ids = distinct_sources(source_ids)?;
+        if !source_ids.is_empty() {
+            let found = sources::get_sources(&self.api.pool, &source_ids).await?;
+            message.sources = cited_sources(&source_ids, &found)?;
+        }
+
+        let recent = self.recent_writes().await?;
+        let context = FilterContext {
+            sender_id: self.user_id,
+            sent_at: message.sent_at,
+            recent: &recent,
+        };
+        let filtered = self
+            .api
+            .filters
+            .apply(std::mem::take(&mut message.content), &context)?;
+        message.content = filtered.content.clone();
+        if message.content.trim().is_empty() {
+            return Err(ApiError::validation("Message must not be empty"));
+        }
+
+        let mut tx = self.api.pool.begin().await.map_err(DbError::Connection)?;
+        if !messages::insert_message(&mut *tx, &message).await? {
+            return Err(ApiError::conflict("The conversation has ended"));
+        }
+        if !source_ids.is_empty() {
+            sources::cite(&mut *tx, message.id, &source_ids).await?;
+        }
+        if let Some(report) = filtered.report(&message) {
+            reports::insert_flagged(&mut *tx, &report).await?;
+        }
+        tx.commit().await.map_err(DbError::Query)?;
+        Ok(message)
+    }
+}
+
+/// Validate the content of a message and build it.
+///
+/// Other checks are left to the [`MessageFilters`].
+fn new_message(
+    conversation: &Conversation,
+    sender_id: Uuid,
+    content: &str,
+) -> Result<Message, ApiError> {
+    Ok(Message {
+        id: Uuid::new_v4(),
+        conversation_id: conversation.id,
+        sender_id,
+        content: message_content(content)?.to_string(),
+        sent_at: Utc::now(),
+        edited_at: None,
+        deleted_at: None,
+        sources: Vec::new(),
+    })
+}
+
+/// Deduplicate the ids of the sources cited by a message, keeping their order.
+fn distinct_sources(source_ids: &[Uuid]) -> Result<Vec<Uuid>, ApiError> {
+    let mut seen = HashSet::new();
+    let source_ids: Vec<Uuid> = source_ids
+        .iter()
+        .copied()
+        .filter(|id| seen.insert(*id))
+        .collect();
+    if source_ids.len() > MAX_CITED_SOURCES {
+        return Err(ApiError::validation(format!(
+            "A message may cite at most {MAX_CITED_SOURCES} sources"
+        )));
+    }
+    Ok(source_ids)
+}
+
+/// Summarize the sources cited by a message, in the order they were cited,
+/// rejecting the message if any of them was not found.
+fn cited_sources(source_ids: &[Uuid], found: &[Source]) -> Result<Vec<SourceSummary>, ApiError> {
+    let mut missing = Vec::new();
+    let mut summaries = Vec::new();
+    for id in source_ids {
+        match found.iter().find(|source| source.id == *id) {
+            Some(source) => summaries.push(SourceSummary::from(source)),
+            None => missing.push(*id),
+        }
+    }
+    if !missing.is_empty() {
+        return Err(ApiError::validation("Cited sources must exist")
+            .with_details(json!({ "missing_source_ids": missing })));
+    }
+    Ok(summaries)
+}
+
+/// Trim the content of a message, rejecting it if empty.
+fn message_content(content: &str) -> Result<&str, ApiError> {
+    let content = content.trim();
+    if content.is_empty() {
+        return Err(ApiError::validation("Message must not be empty"));
+    }
+    Ok(content)
+}
+
+/// The event telling participants that a message was edited or deleted.
+fn revision_event(message: Message) -> ServerEvent {
+    match message.deleted_at {
+        Some(deleted_at) => ServerEvent::Deleted {
+            message_id: message.id,
+            user_id: message.sender_id,
+            deleted_at,
+        },
+        None => ServerEvent::Edited(message),
+    }
+}
+
+/// Send an event to the participant.
+async fn send(socket: &mut WebSocket, event: &ServerEvent) -> Result<(), axum::Error> {
+    let text = serde_json::to_string(event).map_err(axum::Error::new)?;
+    socket.send(ws::Message::Text(text.into())).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::types::source::SourceInfo;
+
+    fn conversation() -> Conversation {
+        Conversation {
+            id: Uuid::new_v4(),
+            topic: "Should cities ban cars?".to_string(),
+            created_at: Utc::now(),
+            ended_at: None,
+            end_reason: None,
+            participant_a: Uuid::new_v4(),
+            participant_b: Uuid::new_v4(),
+        }
+    }
+
+    #[test]
+    fn test_new_message_validation() {
+        let conversation = conversation();
+        let message = new_message(&conversation, conversation.participant_a, " Hello ").unwrap();
+        assert_eq!(message.content, "Hello");
+        assert_eq!(message.conversation_id, conversation.id);
+
+        let error = new_message(&conversation, conversation.participant_a, "  ").unwrap_err();
+        assert_eq!(error.code(), ErrorCode::ValidationFailed);
+    }
+
+    #[test]
+    fn test_cited_sources() {
+        let first = Source::new(SourceInfo::Book(Vec::new()));
+        let second = Source::new(SourceInfo::Book(Vec::new()));
+        let ids = distinct_sources(&[second.id, first.id, second.id]).unwrap();
+        assert_eq!(ids, vec![second.id, first.id]);
+        let summaries = cited_sources(&ids, &[first.clone(), second]).unwrap();
+        let cited: Vec<Uuid> = summaries.iter().map(|summary| summary.id).collect();
+        assert_eq!(cited, ids);
+
+        let missing = Uuid::new_v4();
+        let error = cited_sources(&[first.id, missing], &[first]).unwrap_err();
+        assert_eq!(error.code(), ErrorCode::ValidationFailed);
+        assert_eq!(
+            error.details().unwrap()["missing_source_ids"],
+            json!([missing])
+        );
+
+        let too_many: Vec<Uuid> = (0..=MAX_CITED_SOURCES).map(|_| Uuid::new_v4()).collect();
+        let error = distinct_sources(&too_many).unwrap_err();
+        assert_eq!(error.code(), ErrorCode::ValidationFailed);
+    }
+
+    #[test]
+    fn test_revision_events() {
+        let conversation = conversation();
+        let mut message = new_message(&conversation, conversation.participant_a, "Hi").unwrap();
+        message.edited_at = Some(Utc::now());
+        assert_eq!(
+            revision_event(message.clone()),
+            ServerEvent::Edited(message.clone())
+        );
+
+        let deleted_at = Utc::now();
+        message.deleted_at = Some(deleted_at);
+        assert_eq!(
+            revision_event(message.clone()),
+            ServerEvent::Deleted {
+                message_id: message.id,
+                user_id: message.sender_id,
+                deleted_at,
+            }
+        );
+    }
+}
@@ -0,0 +1,547 @@
+//! Checks run on messages before they are stored and delivered.
+//!
+//! A [`MessageFilters`] chain runs each [`MessageFilter`] in turn. Filters may
+//! rewrite the content, e.g. to mask words, reject the message, or flag it. Flagged
+//! messages are still delivered, but are reported to the moderation queue.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Utc};
+use shared::error::{ApiError, ErrorCode};
+use shared::types::conversation::Message;
+use shared::types::moderation::{Report, ReportCategory, ReportStatus};
+use uuid::Uuid;
+
+/// Longest message accepted by [`MaxLength::default`], in characters.
+const DEFAULT_MAX_LENGTH: usize = 4000;
+
+/// Text masked links are replaced with.
+const LINK_MASK: &str = "[link removed]";
+
+/// What a filter decided about a message.
+#[derive(Debug)]
+pub enum Verdict {
+    /// Let the message through, possibly rewritten
+    Pass,
+    /// Let the message through, but report it to moderators
+    Flag(Flag),
+    /// Refuse the message, telling the sender why
+    Reject(ApiError),
+}
+
+/// Why a message was flagged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Flag {
+    pub category: ReportCategory,
+    pub reason: String,
+}
+
+/// What filters know about a message besides its content.
+#[derive(Debug, Clone, Copy)]
+pub struct FilterContext<'a> {
+    pub sender_id: Uuid,
+    pub sent_at: DateTime<Utc>,
+    /// What the sender last wrote in the conversation, newest first; as many
+    /// messages as the chain's filters asked for with [`MessageFilter::history`].
+    /// Edits count as messages, given the content and, as `sent_at`, the time
+    /// of the edit.
+    pub recent: &'a [Message],
+}
+
+/// A check run on messages before they are stored.
+pub trait MessageFilter: Send + Sync {
+    /// Decide about a message, possibly rewriting its content.
+    fn check(&self, content: &mut String, context: &FilterContext<'_>) -> Verdict;
+
+    /// How many of the sender's latest messages the filter needs to see.
+    fn history(&self) -> usize {
+        0
+    }
+}
+
+/// What filters do with the messages they catch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterAction {
+    /// Hide the offending part of the message
+    Mask,
+    /// Refuse the message
+    Reject,
+    /// Deliver the message, but report it to moderators
+    Flag,
+}
+
+impl FromStr for FilterAction {
+    type Err = UnknownAction;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mask" => Ok(FilterAction::Mask),
+            "reject" => Ok(FilterAction::Reject),
+            "flag" => Ok(FilterAction::Flag),
+            _ => Err(UnknownAction(s.to_string())),
+        }
+    }
+}
+
+/// Error returned when parsing an unknown [`FilterAction`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownAction(String);
+
+impl fmt::Display for UnknownAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown filter action {:?}; expected mask, reject or flag",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for UnknownAction {}
+
+/// The content of a message that passed the filters, and the flags raised on it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filtered {
+    pub content: String,
+    pub flags: Vec<Flag>,
+}
+
+impl Filtered {
+    /// The report to file against the sender of the stored message, if it was flagged.
+    ///
+    /// The report is filed under the category of the first flag raised.
+    pub fn report(&self, message: &Message) -> Option<Report> {
+        let category = self.flags.first()?.category;
+        let reasons: Vec<&str> = self.flags.iter().map(|flag| flag.reason.as_str()).collect();
+        Some(Report {
+            id: Uuid::new_v4(),
+            conversation_id: message.conversation_id,
+            reporter_id: None,
+            reported_user_id: message.sender_id,
+            category,
+            details: Some(reasons.join("; ")),
+            messages: vec![message.clone()],
+            status: ReportStatus::Open,
+            created_at: message.sent_at,
+            claimed_by: None,
+            claimed_at: None,
+            resolved_at: None,
+            action: None,
+            resolution_note: None,
+        })
+    }
+}
+
+/// A chain of filters, run in the order they were added.
+///
+/// The default chain limits messages to 4000 characters and rejects floods.
+#[derive(Clone)]
+pub struct MessageFilters {
+    filters: Vec<Arc<dyn MessageFilter>>,
+}
+
+impl MessageFilters {
+    /// Create a chain letting every message through.
+    pub fn new() -> Self {
+        Self {
+            filters: Vec::new(),
+        }
+    }
+
+    /// Run the given filter after the ones already in the chain.
+    pub fn with(mut self, filter: impl MessageFilter + 'static) -> Self {
+        self.filters.push(Arc::new(filter));
+        self
+    }
+
+    /// How many of the sender's latest messages the chain needs to see.
+    pub fn history(&self) -> usize {
+        self.filters
+            .iter()
+            .map(|filter| filter.history())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Run the chain on a message, stopping at the first filter rejecting it.
+    pub fn apply(
+        &self,
+        mut content: String,
+        context: &FilterContext<'_>,
+    ) -> Result<Filtered, ApiError> {
+        let mut flags = Vec::new();
+        for filter in &self.filters {
+            match filter.check(&mut content, context) {
+                Verdict::Pass => {}
+                Verdict::Flag(flag) => flags.push(flag),
+                Verdict::Reject(error) => return Err(error),
+            }
+        }
+        Ok(Filtered { content, flags })
+    }
+}
+
+impl Default for MessageFilters {
+    fn default() -> Self {
+        Self::new()
+            .with(MaxLength::default())
+            .with(Flood::default())
+    }
+}
+
+/// Rejects messages longer than a number of characters.
+#[derive(Debug, Clone, Copy)]
+pub struct MaxLength(pub usize);
+
+impl Default for MaxLength {
+    fn default() -> Self {
+        Self(DEFAULT_MAX_LENGTH)
+    }
+}
+
+impl MessageFilter for MaxLength {
+    fn check(&self, content: &mut String, _: &FilterContext<'_>) -> Verdict {
+        if content.chars().count() > self.0 {
+            return Verdict::Reject(ApiError::validation(format!(
+                "Message must be at most {} characters",
+                self.0
+            )));
+        }
+        Verdict::Pass
+    }
+}
+
+/// Catches messages containing words from a list, such as profanity or slurs.
+///
+/// Words are matched whole and regardless of case, so that listing "ass" does
+/// not catch "class". Entries spanning several words never match.
+#[derive(Debug, Clone)]
+pub struct Wordlist {
+    words: HashSet<String>,
+    action: FilterAction,
+    category: ReportCategory,
+}
+
+impl Wordlist {
+    /// Create a filter catching the given words, flagged messages being
+    /// reported under the `other` category.
+    pub fn new(words: impl IntoIterator<Item = impl AsRef<str>>, action: FilterAction) -> Self {
+        let words = words
+            .into_iter()
+            .map(|word| word.as_ref().trim().to_lowercase())
+            .filter(|word| !word.is_empty())
+            .collect();
+        Self {
+            words,
+            action,
+            category: ReportCategory::Other,
+        }
+    }
+
+    /// Create a filter catching the words listed in a file, one per line.
+    ///
+    /// Blank lines and lines starting with `#` are ignored.
+    pub fn from_file(path: impl AsRef<Path>, action: FilterAction) -> std::io::Result<Self> {
+        let list = std::fs::read_to_string(path)?;
+        let words = list
+            .lines()
+            .filter(|line| !line.trim_start().starts_with('#'));
+        Ok(Self::new(words, action))
+    }
+
+    /// Report flagged messages under the given category.
+    pub fn with_category(mut self, category: ReportCategory) -> Self {
+        self.category = category;
+        self
+    }
+}
+
+impl MessageFilter for Wordlist {
+    fn check(&self, content: &mut String, _: &FilterContext<'_>) -> Verdict {
+        let matches: Vec<(usize, &str)> = words(content)
+            .filter(|(_, word)| self.words.contains(&word.to_lowercase()))
+            .collect();
+        if matches.is_empty() {
+            return Verdict::Pass;
+        }
+
+        match self.action {
+            FilterAction::Mask => {
+                let mut masked = String::with_capacity(content.len());
+                let mut end = 0;
+                for (start, word) in matches {
+                    masked.push_str(&content[end..start]);
+                    masked.extend(word.chars().map(|_| '*'));
+                    end = start + word.len();
+                }
+                masked.push_str(&content[end..]);
+                *content = masked;
+                Verdict::Pass
+            }
+            FilterAction::Reject => Verdict::Reject(ApiError::validation(
+                "Message contains language that is not allowed",
+            )),
+            FilterAction::Flag => Verdict::Flag(Flag {
+                category: self.category,
+                reason: format!("Contains {} listed word(s)", matches.len()),
+            }),
+        }
+    }
+}
+
+/// The words of a text, as runs of alphanumeric characters, with their byte offsets.
+fn words(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(move |word| (word.as_ptr() as usize - text.as_ptr() as usize, word))
+}
+
+/// Catches messages containing links, i.e. words starting with `http://`,
+/// `https://` or `www.`.
+///
+/// Flagged messages are reported under the `spam` category.
+#[derive(Debug, Clone, Copy)]
+pub struct Links(pub FilterAction);
+
+impl MessageFilter for Links {
+    fn check(&self, content: &mut String, _: &FilterContext<'_>) -> Verdict {
+        let links = content
+            .split_whitespace()
+            .filter(|word| is_link(word))
+            .count();
+        if links == 0 {
+            return Verdict::Pass;
+        }
+
+        match self.0 {
+            FilterAction::Mask => {
+                let mut masked = String::with_capacity(content.len());
+                let mut rest = content.as_str();
+                while let Some(start) = rest.find(|c: char| !c.is_whitespace()) {
+                    masked.push_str(&rest[..start]);
+                    rest = &rest[start..];
+                    let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+                    let word = &rest[..end];
+                    masked.push_str(if is_link(word) { LINK_MASK } else { word });
+                    rest = &rest[end..];
+                }
+                masked.push_str(rest);
+                *content = masked;
+                Verdict::Pass
+            }
+            FilterAction::Reject => {
+                Verdict::Reject(ApiError::validation("Messages must not contain links"))
+            }
+            FilterAction::Flag => Verdict::Flag(Flag {
+                category: ReportCategory::Spam,
+                reason: format!("Contains {links} link(s)"),
+            }),
+        }
+    }
+}
+
+/// Whether a whitespace-separated word is a link.
+fn is_link(word: &str) -> bool {
+    // Links may follow punctuation, as in "(https://example.com)".
+    let word = word.trim_start_matches(|c: char| !c.is_alphanumeric());
+    ["http://", "https://", "www."].iter().any(|prefix| {
+        word.get(..prefix.len())
+            .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+    })
+}
+
+/// Rejects messages sent too fast, or repeating the sender's previous messages.
+///
+/// By default, senders may send or edit 10 messages every 30 seconds, and may
+/// not write the same message more than 3 times in a row within that window.
+#[derive(Debug, Clone, Copy)]
+pub struct Flood {
+    max_messages: usize,
+    max_repeats: usize,
+    window: Duration,
+}
+
+impl Flood {
+    /// Allow `max_messages` messages and `max_repeats` identical messages in a
+    /// row per `window`.
+    pub fn new(max_messages: usize, max_repeats: usize, window: Duration) -> Self {
+        Self {
+            max_messages,
+            max_repeats,
+            window,
+        }
+    }
+}
+
+impl Default for Flood {
+    fn default() -> Self {
+        Self::new(10, 3, Duration::seconds(30))
+    }
+}
+
+impl MessageFilter for Flood {
+    fn check(&self, content: &mut String, context: &FilterContext<'_>) -> Verdict {
+        let since = context.sent_at - self.window;
+        let mut recent = context
+            .recent
+            .iter()
+            .take_while(|message| message.sent_at > since);
+
+        let repeats = recent
+            .clone()
+            .take_while(|message| message.content.to_lowercase() == content.to_lowercase())
+            .count();
+        if repeats >= self.max_repeats {
+            return Verdict::Reject(ApiError::new(
+                ErrorCode::RateLimited,
+                "Message repeated too many times",
+            ));
+        }
+        if recent.nth(self.max_messages.saturating_sub(1)).is_some() {
+            return Verdict::Reject(ApiError::new(
+                ErrorCode::RateLimited,
+                "Messages are being sent too fast",
+            ));
+        }
+        Verdict::Pass
+    }
+
+    fn history(&self) -> usize {
+        self.max_messages.max(self.max_repeats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(filter: &impl MessageFilter, content: &str, recent: &[Message]) -> (String, Verdict) {
+        let mut content = content.to_string();
+        let context = FilterContext {
+            sender_id: Uuid::new_v4(),
+            sent_at: Utc::now(),
+            recent,
+        };
+        let verdict = filter.check(&mut content, &context);
+        (content, verdict)
+    }
+
+    fn sent(content: &str, seconds_ago: i64) -> Message {
+        Message {
+            id: Uuid::new_v4(),
+            conversation_id: Uuid::new_v4(),
+            sender_id: Uuid::new_v4(),
+            content: content.to_string(),
+            sent_at: Utc::now() - Duration::seconds(seconds_ago),
+            edited_at: None,
+            deleted_at: None,
+            sources: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_max_length() {
+        let (_, verdict) = check(&MaxLength(5), "Hello", &[]);
+        assert!(matches!(verdict, Verdict::Pass));
+        let (_, verdict) = check(&MaxLength(5), "Hello!", &[]);
+        assert!(matches!(verdict, Verdict::Reject(e) if e.code() == ErrorCode::ValidationFailed));
+    }
+
+    #[test]
+    fn test_wordlist_actions() {
+        let words = ["darn", "HECK"];
+        let (content, verdict) = check(
+            &Wordlist::new(words, FilterAction::Mask),
+            "Darn it, what the heck! Darnit.",
+            &[],
+        );
+        assert!(matches!(verdict, Verdict::Pass));
+        assert_eq!(content, "**** it, what the ****! Darnit.");
+
+        let (_, verdict) = check(&Wordlist::new(words, FilterAction::Reject), "heck", &[]);
+        assert!(matches!(verdict, Verdict::Reject(_)));
+
+        let wordlist =
+            Wordlist::new(words, FilterAction::Flag).with_category(ReportCategory::HateSpeech);
+        let (content, verdict) = check(&wordlist, "heck no", &[]);
+        assert_eq!(content, "heck no");
+        assert!(
+            matches!(verdict, Verdict::Flag(flag) if flag.category == ReportCategory::HateSpeech)
+        );
+
+        let (_, verdict) = check(&wordlist, "Checkmate", &[]);
+        assert!(matches!(verdict, Verdict::Pass));
+    }
+
+    #[test]
+    fn test_links() {
+        let (content, verdict) = check(
+            &Links(FilterAction::Mask),
+            "See (https://example.com) or WWW.example.org,  ok?",
+            &[],
+        );
+        assert!(matches!(verdict, Verdict::Pass));
+        assert_eq!(content, "See [link removed] or [link removed]  ok?");
+
+        let (_, verdict) = check(&Links(FilterAction::Flag), "http://spam.example", &[]);
+        assert!(matches!(verdict, Verdict::Flag(flag) if flag.category == ReportCategory::Spam));
+
+        let (_, verdict) = check(&Links(FilterAction::Reject), "No links, www here", &[]);
+        assert!(matches!(verdict, Verdict::Pass));
+    }
+
+    #[test]
+    fn test_flood() {
+        let flood = Flood::new(3, 2, Duration::seconds(30));
+        let repeated = [sent("Hi", 1), sent("hi", 2)];
+        let (_, verdict) = check(&flood, "HI", &repeated);
+        assert!(matches!(verdict, Verdict::Reject(e) if e.code() == ErrorCode::RateLimited));
+        let (_, verdict) = check(&flood, "Hello", &repeated);
+        assert!(matches!(verdict, Verdict::Pass));
+
+        let fast = [sent("a", 1), sent("b", 2), sent("c", 3)];
+        let (_, verdict) = check(&flood, "d", &fast);
+        assert!(matches!(verdict, Verdict::Reject(_)));
+
+        let slow = [sent("a", 1), sent("b", 2), sent("c", 60)];
+        let (_, verdict) = check(&flood, "d", &slow);
+        assert!(matches!(verdict, Verdict::Pass));
+    }
+
+    #[test]
+    fn test_chain_collects_flags() {
+        let filters = MessageFilters::new()
+            .with(Wordlist::new(["darn"], FilterAction::Mask))
+            .with(Links(FilterAction::Flag))
+            .with(Flood::default());
+        assert_eq!(filters.history(), 10);
+
+        let context = FilterContext {
+            sender_id: Uuid::new_v4(),
+            sent_at: Utc::now(),
+            recent: &[],
+        };
+        let filtered = filters
+            .apply("darn, see www.example.com".to_string(), &context)
+            .unwrap();
+        assert_eq!(filtered.content, "****, see www.example.com");
+        assert_eq!(filtered.flags.len(), 1);
+
+        let message = sent(&filtered.content, 0);
+        let report = filtered.report(&message).unwrap();
+        assert_eq!(report.reporter_id, None);
+        assert_eq!(report.reported_user_id, message.sender_id);
+        assert_eq!(report.category, ReportCategory::Spam);
+        assert_eq!(report.messages, vec![message]);
+
+        let too_long = "a".repeat(DEFAULT_MAX_LENGTH + 1);
+        let error = MessageFilters::default()
+            .apply(too_long, &context)
+            .unwrap_err();
+        assert_eq!(error.code(), ErrorCode::ValidationFailed);
+    }
+}
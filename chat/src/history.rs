@@ -0,0 +1,600 @@
+//! HTTP endpoints for participants to look back at their conversations.
+
+use std::fmt::Write;
+
+use auth::models::AuthenticatedUser;
+use axum::extract::rejection::{PathRejection, QueryRejection};
+use axum::extract::{Path, Query, State};
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, Json};
+use chrono::{DateTime, Utc};
+use db::queries::conversations::ParticipantConversation;
+use db::queries::{conversations, messages, sources, users};
+use serde::{Deserialize, Serialize};
+use shared::error::ApiError;
+use shared::types::conversation::{Conversation, ConversationEndReason, Message};
+use shared::types::source::SourceSummary;
+use uuid::Uuid;
+
+use crate::api::{ChatApi, participant_conversation};
+
+/// Items per page unless the caller asks for another number.
+const DEFAULT_PAGE_SIZE: i64 = 20;
+
+/// Most items per page.
+const MAX_PAGE_SIZE: i64 = 100;
+
+/// Text shown in place of deleted messages in Markdown and plain text transcripts.
+const DELETED_MARKER: &str = "[message deleted]";
+
+/// Format of timestamps in Markdown and plain text transcripts.
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S UTC";
+
+/// Position in a list ordered by timestamp then id, passed between pages as
+/// `"{microseconds since the epoch}_{id}"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Cursor {
+    at: DateTime<Utc>,
+    id: Uuid,
+}
+
+impl Cursor {
+    fn encode(&self) -> String {
+        format!("{}_{}", self.at.timestamp_micros(), self.id)
+    }
+
+    fn decode(cursor: &str) -> Result<Self, ApiError> {
+        let invalid = || ApiError::bad_request("Invalid cursor");
+        let (at, id) = cursor.split_once('_').ok_or_else(invalid)?;
+        let at = at
+            .parse()
+            .ok()
+            .and_then(DateTime::from_timestamp_micros)
+            .ok_or_else(invalid)?;
+        let id = id.parse().map_err(|_| invalid())?;
+        Ok(Self { at, id })
+    }
+}
+
+/// Pagination of a list, continuing after `cursor` if given.
+#[derive(Debug, Deserialize)]
+pub(crate) struct PageQuery {
+    cursor: Option<String>,
+    limit: Option<i64>,
+}
+
+impl PageQuery {
+    /// Validate the query, returning where to continue from and the page size.
+    fn validate(&self) -> Result<(Option<Cursor>, i64), ApiError> {
+        let limit = self.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+            return Err(ApiError::validation(format!(
+                "Limit must be between 1 and {MAX_PAGE_SIZE}"
+            )));
+        }
+        let cursor = self.cursor.as_deref().map(Cursor::decode).transpose()?;
+        Ok((cursor, limit))
+    }
+}
+
+/// A page of a list, with the cursor of the next page if there is one.
+#[derive(Debug, Serialize)]
+pub(crate) struct Page<T> {
+    items: Vec<T>,
+    next_cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+    /// Build a page from up to `limit + 1` items, the extra one only telling
+    /// whether there is a next page.
+    fn new(mut items: Vec<T>, limit: i64, cursor: impl Fn(&T) -> Cursor) -> Self {
+        let more = items.len() as i64 > limit;
+        items.truncate(limit as usize);
+        let next_cursor = items
+            .last()
+            .filter(|_| more)
+            .map(|last| cursor(last).encode());
+        Self { items, next_cursor }
+    }
+}
+
+/// A conversation as listed to one of its participants.
+#[derive(Debug, Serialize)]
+pub(crate) struct ConversationSummary {
+    id: Uuid,
+    topic: String,
+    partner_pseudonym: String,
+    created_at: DateTime<Utc>,
+    ended_at: Option<DateTime<Utc>>,
+    end_reason: Option<ConversationEndReason>,
+}
+
+impl ConversationSummary {
+    fn new(conversation: &Conversation, partner_pseudonym: String) -> Self {
+        Self {
+            id: conversation.id,
+            topic: conversation.topic.clone(),
+            partner_pseudonym,
+            created_at: conversation.created_at,
+            ended_at: conversation.ended_at,
+            end_reason: conversation.end_reason,
+        }
+    }
+}
+
+impl From<ParticipantConversation> for ConversationSummary {
+    fn from(listed: ParticipantConversation) -> Self {
+        let pseudonym = pseudonym(
+            listed.conversation.id,
+            listed.partner_id,
+            listed.partner_display_name.as_deref(),
+        );
+        Self::new(&listed.conversation, pseudonym)
+    }
+}
+
+/// How a partner is named to the other participant: by their display name if
+/// they set one, otherwise by a name derived from the conversation, so that
+/// they cannot be recognized across conversations.
+fn pseudonym(conversation_id: Uuid, partner_id: Uuid, display_name: Option<&str>) -> String {
+    if let Some(display_name) = display_name.map(str::trim).filter(|name| !name.is_empty()) {
+        return display_name.to_string();
+    }
+    let tag: String = conversation_id
+        .as_bytes()
+        .iter()
+        .zip(partner_id.as_bytes())
+        .take(3)
+        .map(|(a, b)| format!("{:02X}", a ^ b))
+        .collect();
+    format!("Participant {tag}")
+}
+
+/// Format of an exported transcript.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum TranscriptFormat {
+    #[default]
+    Json,
+    Markdown,
+    Text,
+}
+
+impl TranscriptFormat {
+    fn content_type(&self) -> &'static str {
+        match self {
+            TranscriptFormat::Json => "application/json",
+            TranscriptFormat::Markdown => "text/markdown; charset=utf-8",
+            TranscriptFormat::Text => "text/plain; charset=utf-8",
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            TranscriptFormat::Json => "json",
+            TranscriptFormat::Markdown => "md",
+            TranscriptFormat::Text => "txt",
+        }
+    }
+}
+
+/// Format to export a transcript in.
+#[derive(Debug, Deserialize)]
+pub(crate) struct ExportQuery {
+    #[serde(default)]
+    format: TranscriptFormat,
+}
+
+/// A message of a transcript, with its sender named as the reader knows them.
+#[derive(Debug, Serialize)]
+struct TranscriptMessage {
+    id: Uuid,
+    sender_id: Uuid,
+    sender: String,
+    content: String,
+    sent_at: DateTime<Utc>,
+    edited_at: Option<DateTime<Utc>>,
+    deleted_at: Option<DateTime<Utc>>,
+    sources: Vec<SourceSummary>,
+}
+
+impl TranscriptMessage {
+    /// The content of the message, or a marker if it was deleted.
+    fn text(&self) -> &str {
+        match self.deleted_at {
+            Some(_) => DELETED_MARKER,
+            None => &self.content,
+        }
+    }
+
+    /// The time the message was sent, marked if it was edited since.
+    fn timestamp(&self) -> String {
+        let sent_at = self.sent_at.format(TIMESTAMP_FORMAT);
+        match self.edited_at {
+            Some(_) if self.deleted_at.is_none() => format!("{sent_at} (edited)"),
+            _ => sent_at.to_string(),
+        }
+    }
+}
+
+/// The full transcript of a conversation, as exported by one of its participants.
+#[derive(Debug, Serialize)]
+struct Transcript {
+    conversation: ConversationSummary,
+    messages: Vec<TranscriptMessage>,
+}
+
+impl Transcript {
+    /// Build the transcript read by `reader`, calling them "You".
+    fn new(conversation: ConversationSummary, messages: Vec<Message>, reader: Uuid) -> Self {
+        let messages = messages
+            .into_iter()
+            .map(|message| TranscriptMessage {
+                id: message.id,
+                sender_id: message.sender_id,
+                sender: if message.sender_id == reader {
+                    "You".to_string()
+                } else {
+                    conversation.partner_pseudonym.clone()
+                },
+                content: message.content,
+                sent_at: message.sent_at,
+                edited_at: message.edited_at,
+                deleted_at: message.deleted_at,
+                sources: message.sources,
+            })
+            .collect();
+        Self {
+            conversation,
+            messages,
+        }
+    }
+
+    fn render(&self, format: TranscriptFormat) -> Result<String, ApiError> {
+        match format {
+            TranscriptFormat::Json => {
+                serde_json::to_string_pretty(self).map_err(ApiError::internal)
+            }
+            TranscriptFormat::Markdown => Ok(self.to_markdown()),
+            TranscriptFormat::Text => Ok(self.to_text()),
+        }
+    }
+
+    fn to_markdown(&self) -> String {
+        let conversation = &self.conversation;
+        let mut out = format!("# {}\n\n", conversation.topic);
+        let _ = writeln!(out, "- Partner: {}", conversation.partner_pseudonym);
+        let _ = writeln!(
+            out,
+            "- Started: {}",
+            conversation.created_at.format(TIMESTAMP_FORMAT)
+        );
+        if let Some(ended) = self.ended() {
+            let _ = writeln!(out, "- Ended: {ended}");
+        }
+        for message in &self.messages {
+            let _ = write!(
+                out,
+                "\n**{}** · {}\n\n",
+                message.sender,
+                message.timestamp()
+            );
+            for line in message.text().lines() {
+                let _ = writeln!(out, "> {line}");
+            }
+            if !message.sources.is_empty() {
+                out.push_str(">\n> Sources:\n");
+                for source in &message.sources {
+                    let _ = writeln!(out, "> - {}", citation(source));
+                }
+            }
+        }
+        out
+    }
+
+    fn to_text(&self) -> String {
+        let conversation = &self.conversation;
+        let mut out = format!("{}\n", conversation.topic);
+        let _ = writeln!(out, "Partner: {}", conversation.partner_pseudonym);
+        let _ = writeln!(
+            out,
+            "Started: {}",
+            conversation.created_at.format(TIMESTAMP_FORMAT)
+        );
+        if let Some(ended) = self.ended() {
+            let _ = writeln!(out, "Ended: {ended}");
+        }
+        out.push('\n');
+        for message in &self.messages {
+            let _ = writeln!(
+                out,
+                "[{}] {}: {}",
+                message.timestamp(),
+                message.sender,
+                message.text()
+            );
+            for source in &message.sources {
+                let _ = writeln!(out, "    Source: {}", citation(source));
+            }
+        }
+        out
+    }
+
+    /// When and why the conversation ended, if it has.
+    fn ended(&self) -> Option<String> {
+        let conversation = &self.conversation;
+        let at = conversation.ended_at?.format(TIMESTAMP_FORMAT);
+        Some(match conversation.end_reason {
+            Some(reason) => format!("{at} ({})", describe(reason)),
+            None => at.to_string(),
+        })
+    }
+}
+
+/// A cited source, in words: its title, authors, address and credibility.
+fn citation(source: &SourceSummary) -> String {
+    let mut citation = match (&source.title, &source.url) {
+        (Some(title), _) => format!("\"{title}\""),
+        (None, Some(url)) => url.clone(),
+        (None, None) => "Untitled source".to_string(),
+    };
+    if !source.authors.is_empty() {
+        let _ = write!(citation, " by {}", source.authors.join(", "));
+    }
+    if let (Some(_), Some(url)) = (&source.title, &source.url) {
+        let _ = write!(citation, " ({url})");
+    }
+    let _ = write!(citation, ", credibility {:.2}", source.credibility);
+    citation
+}
+
+/// Why a conversation ended, in words.
+fn describe(reason: ConversationEndReason) -> &'static str {
+    match reason {
+        ConversationEndReason::Completed => "completed",
+        ConversationEndReason::UserLeft => "a participant left",
+        ConversationEndReason::UserReported => "a participant was reported",
+        ConversationEndReason::Inactive => "inactive",
+    }
+}
+
+/// List the caller's conversations, newest first.
+pub(crate) async fn list_conversations(
+    State(api): State<ChatApi>,
+    Extension(user): Extension<AuthenticatedUser>,
+    query: Result<Query<PageQuery>, QueryRejection>,
+) -> Result<Json<Page<ConversationSummary>>, ApiError> {
+    let Query(query) = query?;
+    let (cursor, limit) = query.validate()?;
+
+    let listed = conversations::list_for_participant(
+        &api.pool,
+        user.id,
+        cursor.map(|cursor| (cursor.at, cursor.id)),
+        limit + 1,
+    )
+    .await?;
+    let summaries = listed.into_iter().map(ConversationSummary::from).collect();
+    Ok(Json(Page::new(
+        summaries,
+        limit,
+        |summary: &ConversationSummary| Cursor {
+            at: summary.created_at,
+            id: summary.id,
+        },
+    )))
+}
+
+/// List the messages of one of the caller's conversations, oldest first.
+pub(crate) async fn list_messages(
+    State(api): State<ChatApi>,
+    Extension(user): Extension<AuthenticatedUser>,
+    id: Result<Path<Uuid>, PathRejection>,
+    query: Result<Query<PageQuery>, QueryRejection>,
+) -> Result<Json<Page<Message>>, ApiError> {
+    let Path(id) = id?;
+    let Query(query) = query?;
+    let (cursor, limit) = query.validate()?;
+
+    let conversation = participant_conversation(&api.pool, id, user.id).await?;
+    let mut messages = messages::messages_page(
+        &api.pool,
+        conversation.id,
+        cursor.map(|cursor| (cursor.at, cursor.id)),
+        limit + 1,
+    )
+    .await?;
+    sources::load_citations(&api.pool, &mut messages).await?;
+    Ok(Json(Page::new(messages, limit, |message: &Message| {
+        Cursor {
+            at: message.sent_at,
+            id: message.id,
+        }
+    })))
+}
+
+/// Export the full transcript of one of the caller's conversations as a file.
+pub(crate) async fn export_transcript(
+    State(api): State<ChatApi>,
+    Extension(user): Extension<AuthenticatedUser>,
+    id: Result<Path<Uuid>, PathRejection>,
+    query: Result<Query<ExportQuery>, QueryRejection>,
+) -> Result<Response, ApiError> {
+    let Path(id) = id?;
+    let Query(query) = query?;
+
+    let conversation = participant_conversation(&api.pool, id, user.id).await?;
+    let partner_id = conversation
+        .partner_of(user.id)
+        .expect("participants have a partner");
+    let partner = users::get_user(&api.pool, partner_id).await?;
+    let pseudonym = pseudonym(
+        conversation.id,
+        partner_id,
+        partner
+            .as_ref()
+            .and_then(|partner| partner.display_name.as_deref()),
+    );
+    let mut messages = messages::messages_after(&api.pool, conversation.id, None).await?;
+    sources::load_citations(&api.pool, &mut messages).await?;
+
+    let transcript = Transcript::new(
+        ConversationSummary::new(&conversation, pseudonym),
+        messages,
+        user.id,
+    );
+    let body = transcript.render(query.format)?;
+    let disposition = format!(
+        "attachment; filename=\"conversation-{}.{}\"",
+        conversation.id,
+        query.format.extension()
+    );
+    Ok((
+        [
+            (
+                header::CONTENT_TYPE,
+                query.format.content_type().to_string(),
+            ),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::error::ErrorCode;
+    use shared::types::source::SourceKind;
+
+    fn transcript() -> Transcript {
+        let reader = Uuid::new_v4();
+        let partner = Uuid::new_v4();
+        let at = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let conversation = Conversation {
+            id: Uuid::new_v4(),
+            topic: "Should cities ban cars?".to_string(),
+            created_at: at,
+            ended_at: Some(at + chrono::Duration::minutes(5)),
+            end_reason: Some(ConversationEndReason::Completed),
+            participant_a: reader,
+            participant_b: partner,
+        };
+        let message = |sender_id, content: &str| Message {
+            id: Uuid::new_v4(),
+            conversation_id: conversation.id,
+            sender_id,
+            content: content.to_string(),
+            sent_at: at + chrono::Duration::minutes(1),
+            edited_at: None,
+            deleted_at: None,
+            sources: Vec::new(),
+        };
+        let mut edited = message(partner, "Buses too\nand trucks");
+        edited.edited_at = Some(at + chrono::Duration::minutes(2));
+        let mut deleted = message(reader, "");
+        deleted.deleted_at = Some(at + chrono::Duration::minutes(2));
+        let mut cited = message(reader, "Cars are loud");
+        cited.sources = vec![SourceSummary {
+            id: Uuid::new_v4(),
+            kind: SourceKind::Website,
+            title: Some("Traffic noise".to_string()),
+            authors: vec!["Jane Doe".to_string(), "John Roe".to_string()],
+            url: Some("https://example.com/noise".to_string()),
+            credibility: 0.8,
+        }];
+        let messages = vec![cited, edited, deleted];
+        let summary = ConversationSummary::new(&conversation, "Sam".to_string());
+        Transcript::new(summary, messages, reader)
+    }
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let cursor = Cursor {
+            at: DateTime::from_timestamp_micros(1_700_000_000_123_456).unwrap(),
+            id: Uuid::new_v4(),
+        };
+        assert_eq!(Cursor::decode(&cursor.encode()).unwrap(), cursor);
+
+        for invalid in [
+            "",
+            "123",
+            "abc_def",
+            &format!("x_{}", cursor.id),
+            "123_not-a-uuid",
+        ] {
+            let error = Cursor::decode(invalid).unwrap_err();
+            assert_eq!(error.code(), ErrorCode::BadRequest);
+        }
+    }
+
+    #[test]
+    fn test_page_limits() {
+        let query = |limit| PageQuery {
+            cursor: None,
+            limit,
+        };
+        assert_eq!(query(None).validate().unwrap().1, DEFAULT_PAGE_SIZE);
+        for invalid in [Some(0), Some(MAX_PAGE_SIZE + 1)] {
+            let error = query(invalid).validate().unwrap_err();
+            assert_eq!(error.code(), ErrorCode::ValidationFailed);
+        }
+
+        let page = Page::new(vec![1, 2, 3], 2, |_: &i32| Cursor {
+            at: Utc::now(),
+            id: Uuid::nil(),
+        });
+        assert_eq!(page.items, vec![1, 2]);
+        assert!(page.next_cursor.is_some());
+        let page = Page::new(vec![1, 2], 2, |_: &i32| unreachable!());
+        assert_eq!(page.next_cursor, None);
+    }
+
+    #[test]
+    fn test_pseudonyms() {
+        let conversation_id = Uuid::new_v4();
+        let partner_id = Uuid::new_v4();
+        assert_eq!(pseudonym(conversation_id, partner_id, Some(" Sam ")), "Sam");
+
+        let anonymous = pseudonym(conversation_id, partner_id, None);
+        assert!(anonymous.starts_with("Participant "));
+        assert_eq!(
+            anonymous,
+            pseudonym(conversation_id, partner_id, Some("  "))
+        );
+        assert_ne!(anonymous, pseudonym(Uuid::new_v4(), partner_id, None));
+    }
+
+    #[test]
+    fn test_transcript_rendering() {
+        let transcript = transcript();
+
+        let markdown = transcript.render(TranscriptFormat::Markdown).unwrap();
+        assert!(markdown.starts_with("# Should cities ban cars?\n"));
+        assert!(markdown.contains("- Ended: 2023-11-14 22:18:20 UTC (completed)"));
+        assert!(markdown.contains("**You** · 2023-11-14 22:14:20 UTC\n\n> Cars are loud\n"));
+        assert!(
+            markdown.contains(
+                "**Sam** · 2023-11-14 22:14:20 UTC (edited)\n\n> Buses too\n> and trucks\n"
+            )
+        );
+        assert!(markdown.contains("> [message deleted]\n"));
+        let citation =
+            "\"Traffic noise\" by Jane Doe, John Roe (https://example.com/noise), credibility 0.80";
+        assert!(markdown.contains(&format!("> Cars are loud\n>\n> Sources:\n> - {citation}\n")));
+
+        let text = transcript.render(TranscriptFormat::Text).unwrap();
+        assert!(text.contains("[2023-11-14 22:14:20 UTC (edited)] Sam: Buses too\nand trucks\n"));
+        assert!(text.ends_with("[2023-11-14 22:14:20 UTC] You: [message deleted]\n"));
+        assert!(text.contains(&format!("You: Cars are loud\n    Source: {citation}\n")));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&transcript.render(TranscriptFormat::Json).unwrap()).unwrap();
+        assert_eq!(json["conversation"]["partner_pseudonym"], "Sam");
+        assert_eq!(json["conversation"]["end_reason"], "completed");
+        assert_eq!(json["messages"][0]["sender"], "You");
+        assert_eq!(json["messages"][0]["sources"][0]["title"], "Traffic noise");
+    }
+}
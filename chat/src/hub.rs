@@ -0,0 +1,169 @@
+//! Delivery of events to the participants connected to a conversation.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use db::error::{DbError, Result};
+use db::pubsub::{InProcessPubSub, PubSub};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{OnceCell, broadcast};
+use uuid::Uuid;
+
+use crate::protocol::ServerEvent;
+
+/// Pub/sub channel carrying the events of every conversation.
+pub const CHAT_CHANNEL: &str = "chat_events";
+
+/// Events buffered per conversation for connections that fall behind.
+const CHANNEL_CAPACITY: usize = 64;
+
+/// An event published on [`CHAT_CHANNEL`], with the conversation it belongs to.
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    conversation_id: Uuid,
+    event: ServerEvent,
+}
+
+type Conversations = Arc<Mutex<HashMap<Uuid, broadcast::Sender<ServerEvent>>>>;
+
+/// Broadcasts events to every connection subscribed to a conversation.
+///
+/// Events are published through a [`PubSub`] backend, so that with
+/// [`PgPubSub`](db::pubsub::PgPubSub) they reach participants connected to
+/// other instances. Each hub subscribes to [`CHAT_CHANNEL`] once, and hands
+/// events to the local connections of their conversation.
+///
+/// Connections that fall more than a buffer's worth of events behind miss
+/// events, and must resume from the last message they have seen. If the hub
+/// itself falls behind, every local connection is closed for the same reason.
+#[derive(Clone)]
+pub struct ChatHub {
+    pubsub: Arc<dyn PubSub>,
+    conversations: Conversations,
+    dispatcher: Arc<OnceCell<()>>,
+}
+
+impl Default for ChatHub {
+    fn default() -> Self {
+        Self::new(InProcessPubSub::new())
+    }
+}
+
+impl ChatHub {
+    /// Create a new ChatHub publishing events through the given backend.
+    pub fn new(pubsub: impl PubSub) -> Self {
+        Self::from_arc(Arc::new(pubsub))
+    }
+
+    /// Create a new ChatHub publishing events through a backend shared with others.
+    pub fn from_arc(pubsub: Arc<dyn PubSub>) -> Self {
+        Self {
+            pubsub,
+            conversations: Default::default(),
+            dispatcher: Default::default(),
+        }
+    }
+
+    /// Receive the events published to a conversation from now on.
+    pub async fn subscribe(
+        &self,
+        conversation_id: Uuid,
+    ) -> Result<broadcast::Receiver<ServerEvent>> {
+        self.dispatcher
+            .get_or_try_init(|| async {
+                let payloads = self.pubsub.subscribe(CHAT_CHANNEL).await?;
+                tokio::spawn(dispatch(payloads, self.conversations.clone()));
+                Ok::<_, DbError>(())
+            })
+            .await?;
+
+        let mut conversations = self.conversations.lock().unwrap();
+        Ok(conversations
+            .entry(conversation_id)
+            .or_insert_with(|| broadcast::channel(CHANNEL_CAPACITY).0)
+            .subscribe())
+    }
+
+    /// Publish an event to the connections subscribed to a conversation.
+    pub async fn publish(&self, conversation_id: Uuid, event: ServerEvent) -> Result<()> {
+        let envelope = Envelope {
+            conversation_id,
+            event,
+        };
+        let payload = serde_json::to_string(&envelope)
+            .map_err(|e| DbError::PubSub(format!("Failed to encode chat event: {e}")))?;
+        self.pubsub.publish(CHAT_CHANNEL, &payload).await
+    }
+}
+
+/// Hand the events published on [`CHAT_CHANNEL`] to the local connections of their conversation.
+async fn dispatch(mut payloads: broadcast::Receiver<String>, conversations: Conversations) {
+    loop {
+        let payload = match payloads.recv().await {
+            Ok(payload) => payload,
+            Err(RecvError::Lagged(missed)) => {
+                // Dropping the senders closes every connection, so that they resume.
+                eprintln!("Chat hub fell {missed} events behind; closing connections");
+                conversations.lock().unwrap().clear();
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+
+        let envelope: Envelope = match serde_json::from_str(&payload) {
+            Ok(envelope) => envelope,
+            Err(e) => {
+                eprintln!("Ignoring malformed chat event: {e}");
+                continue;
+            }
+        };
+
+        let mut conversations = conversations.lock().unwrap();
+        if let Some(sender) = conversations.get(&envelope.conversation_id) {
+            // Sending only fails once every connection has gone.
+            if sender.send(envelope.event).is_err() {
+                conversations.remove(&envelope.conversation_id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_events_reach_subscribers_of_the_conversation() {
+        let hub = ChatHub::default();
+        let (conversation, other) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut first = hub.subscribe(conversation).await.unwrap();
+        let mut second = hub.subscribe(conversation).await.unwrap();
+        let mut elsewhere = hub.subscribe(other).await.unwrap();
+
+        let event = ServerEvent::Typing {
+            user_id: Uuid::new_v4(),
+            is_typing: true,
+        };
+        hub.publish(conversation, event.clone()).await.unwrap();
+
+        assert_eq!(first.recv().await.unwrap(), event);
+        assert_eq!(second.recv().await.unwrap(), event);
+        assert!(elsewhere.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_hubs_sharing_a_backend_see_each_others_events() {
+        let pubsub = InProcessPubSub::new();
+        let (here, there) = (ChatHub::new(pubsub.clone()), ChatHub::new(pubsub));
+        let conversation = Uuid::new_v4();
+        let mut receiver = there.subscribe(conversation).await.unwrap();
+
+        let event = ServerEvent::Typing {
+            user_id: Uuid::new_v4(),
+            is_typing: false,
+        };
+        here.publish(conversation, event.clone()).await.unwrap();
+        assert_eq!(receiver.recv().await.unwrap(), event);
+    }
+}
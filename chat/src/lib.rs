@@ -0,0 +1,25 @@
+//! # Chat Crate
+//!
+//! Real-time chat between the two participants of a conversation, over a
+//! WebSocket served by [`ChatApi`]. Messages go through [`MessageFilters`], are
+//! stored in Postgres, then delivered to the participants' connections through
+//! the [`ChatHub`].
+//! Conversations end when a participant leaves or completes them, or when the
+//! [`InactivitySweeper`] finds them idle. Participants can then look back at
+//! their conversations and export transcripts through the same API.
+//! Messages may cite sources, which users add through the [`SourcesApi`].
+
+mod api;
+pub mod filter;
+mod history;
+pub mod hub;
+pub mod lifecycle;
+pub mod protocol;
+pub mod sources;
+
+pub use api::ChatApi;
+pub use filter::{MessageFilter, MessageFilters};
+pub use hub::ChatHub;
+pub use lifecycle::InactivitySweeper;
+pub use protocol::{ClientEvent, ServerEvent};
+pub use sources::{Bibify, SourceLookup, SourcesApi};
@@ -0,0 +1,134 @@
+//! Ending conversations, and the job ending those left idle.
+
+use std::time::Duration;
+
+use chrono::Utc;
+use db::error::{DbError, Result};
+use db::queries::{conversations, locks};
+use shared::types::conversation::{Conversation, ConversationEndReason, ConversationState};
+use sqlx::PgPool;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::hub::ChatHub;
+use crate::protocol::ServerEvent;
+
+/// Key of the advisory lock held by the instance ending idle conversations.
+const SWEEP_LOCK_KEY: i64 = 0x6d67_5f69_646c_6573;
+
+/// Time between sweeps unless configured otherwise.
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long a conversation may go without messages unless configured otherwise.
+const DEFAULT_TIMEOUT: chrono::Duration = chrono::Duration::minutes(30);
+
+/// End an active conversation and tell both participants why.
+///
+/// `ended_by` is the participant ending it, if any. Returns `None` if the
+/// conversation does not exist or has already ended.
+pub async fn end_conversation(
+    pool: &PgPool,
+    hub: &ChatHub,
+    conversation_id: Uuid,
+    reason: ConversationEndReason,
+    ended_by: Option<Uuid>,
+) -> Result<Option<Conversation>> {
+    let ended = conversations::end_conversation(pool, conversation_id, reason, Utc::now()).await?;
+    if let Some(conversation) = &ended {
+        announce_end(hub, conversation, ended_by).await;
+    }
+    Ok(ended)
+}
+
+/// Tell the participants of a conversation ended by other means how it ended.
+///
+/// Failures are logged: participants who miss the event still find the
+/// conversation ended when they next try to send a message or reconnect.
+pub async fn announce_end(hub: &ChatHub, conversation: &Conversation, ended_by: Option<Uuid>) {
+    let Ok(ConversationState::Ended { reason, at }) = conversation.state() else {
+        return;
+    };
+    let event = ServerEvent::Ended {
+        reason,
+        ended_at: at,
+        ended_by,
+    };
+    if let Err(e) = hub.publish(conversation.id, event).await {
+        eprintln!(
+            "Failed to announce end of conversation {}: {e}",
+            conversation.id
+        );
+    }
+}
+
+/// Background worker ending conversations without messages for longer than a timeout.
+///
+/// Idle conversations are ended with [`ConversationEndReason::Inactive`], and
+/// their participants told through the [`ChatHub`]. Like the expiry job of the
+/// matchmaking crate, each sweep holds a Postgres advisory lock, so that with
+/// several instances running the sweeper, only one of them sweeps at a time.
+#[derive(Clone)]
+pub struct InactivitySweeper {
+    pool: PgPool,
+    hub: ChatHub,
+    timeout: chrono::Duration,
+    interval: Duration,
+}
+
+impl InactivitySweeper {
+    /// Create a new InactivitySweeper announcing ended conversations through the given hub.
+    pub fn new(pool: PgPool, hub: ChatHub) -> Self {
+        Self {
+            pool,
+            hub,
+            timeout: DEFAULT_TIMEOUT,
+            interval: DEFAULT_INTERVAL,
+        }
+    }
+
+    /// End conversations once they have gone without messages for the given time.
+    pub fn with_timeout(mut self, timeout: chrono::Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Wait the given time between sweeps.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Run a single sweep, returning the conversations ended.
+    ///
+    /// Returns no conversations if another instance is sweeping.
+    pub async fn run_once(&self) -> Result<Vec<Conversation>> {
+        let mut tx = self.pool.begin().await.map_err(DbError::Connection)?;
+        if !locks::try_advisory_xact_lock(&mut *tx, SWEEP_LOCK_KEY).await? {
+            return Ok(Vec::new());
+        }
+        let ended = conversations::end_inactive(&mut *tx, self.timeout).await?;
+        tx.commit().await.map_err(DbError::Query)?;
+
+        for conversation in &ended {
+            announce_end(&self.hub, conversation, None).await;
+        }
+        Ok(ended)
+    }
+
+    /// Run sweeps forever, logging failed sweeps.
+    pub async fn run(self) {
+        let mut interval = tokio::time::interval(self.interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.run_once().await {
+                eprintln!("Inactivity sweep failed: {e}");
+            }
+        }
+    }
+
+    /// Run the sweeper in the background.
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(self.run())
+    }
+}
@@ -0,0 +1,178 @@
+//! The JSON events exchanged over a conversation's WebSocket.
+//!
+//! Every event is an object whose `type` field names the event, e.g.
+//!
+//! ```json
+//! { "type": "typing", "is_typing": true }
+//! ```
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use shared::error::{ApiError, ErrorCode};
+use shared::types::conversation::{ConversationEndReason, Message};
+use uuid::Uuid;
+
+/// An event sent by a participant.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientEvent {
+    /// Send a message to the conversation, citing the given sources.
+    Message {
+        content: String,
+        #[serde(default)]
+        source_ids: Vec<Uuid>,
+    },
+    /// Tell the other participant whether the user is typing.
+    Typing { is_typing: bool },
+    /// Mark the conversation as read up to the given message.
+    Read { message_id: Uuid },
+    /// Replace the content of one of the user's recent messages.
+    Edit { message_id: Uuid, content: String },
+    /// Delete one of the user's recent messages.
+    Delete { message_id: Uuid },
+}
+
+/// An event sent to participants.
+///
+/// Events are relayed between instances in the same form, except for errors,
+/// which are only ever sent to the connection they concern.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerEvent {
+    /// A message sent in the conversation, including the participant's own.
+    Message(Message),
+    /// A message was edited, including by the participant themselves.
+    Edited(Message),
+    /// A message was deleted, including by the participant themselves.
+    Deleted {
+        message_id: Uuid,
+        user_id: Uuid,
+        deleted_at: DateTime<Utc>,
+    },
+    /// The other participant started or stopped typing.
+    Typing { user_id: Uuid, is_typing: bool },
+    /// The other participant read the conversation up to the given message.
+    Read {
+        user_id: Uuid,
+        message_id: Uuid,
+        read_at: DateTime<Utc>,
+    },
+    /// The conversation ended; no more messages can be sent.
+    ///
+    /// `ended_by` is the participant who left or completed the conversation, if
+    /// it was ended by one.
+    Ended {
+        reason: ConversationEndReason,
+        ended_at: DateTime<Utc>,
+        ended_by: Option<Uuid>,
+    },
+    /// Every message sent up to now has been delivered; events that follow are live.
+    Synced { last_message_id: Option<Uuid> },
+    /// A client event was rejected, or the connection must be resumed.
+    #[serde(skip_deserializing)]
+    Error { code: ErrorCode, message: String },
+}
+
+impl ServerEvent {
+    /// The participant who caused the event, if it is about one.
+    pub fn user_id(&self) -> Option<Uuid> {
+        match self {
+            ServerEvent::Message(message) | ServerEvent::Edited(message) => Some(message.sender_id),
+            ServerEvent::Typing { user_id, .. }
+            | ServerEvent::Read { user_id, .. }
+            | ServerEvent::Deleted { user_id, .. } => Some(*user_id),
+            ServerEvent::Ended { .. } | ServerEvent::Synced { .. } | ServerEvent::Error { .. } => {
+                None
+            }
+        }
+    }
+}
+
+impl From<&ApiError> for ServerEvent {
+    fn from(error: &ApiError) -> Self {
+        ServerEvent::Error {
+            code: error.code(),
+            message: error.message().to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_client_events() {
+        let event: ClientEvent =
+            serde_json::from_value(json!({ "type": "message", "content": "Hi" })).unwrap();
+        assert_eq!(
+            event,
+            ClientEvent::Message {
+                content: "Hi".to_string(),
+                source_ids: Vec::new(),
+            }
+        );
+
+        let id = Uuid::new_v4();
+        let event: ClientEvent =
+            serde_json::from_value(json!({ "type": "read", "message_id": id })).unwrap();
+        assert_eq!(event, ClientEvent::Read { message_id: id });
+
+        let event: ClientEvent =
+            serde_json::from_value(json!({ "type": "edit", "message_id": id, "content": "Hello" }))
+                .unwrap();
+        assert_eq!(
+            event,
+            ClientEvent::Edit {
+                message_id: id,
+                content: "Hello".to_string()
+            }
+        );
+
+        assert!(serde_json::from_value::<ClientEvent>(json!({ "type": "shout" })).is_err());
+    }
+
+    #[test]
+    fn test_server_events() {
+        let message = Message {
+            id: Uuid::new_v4(),
+            conversation_id: Uuid::new_v4(),
+            sender_id: Uuid::new_v4(),
+            content: "Hi".to_string(),
+            sent_at: Utc::now(),
+            edited_at: None,
+            deleted_at: None,
+            sources: Vec::new(),
+        };
+        let json = serde_json::to_value(ServerEvent::Message(message.clone())).unwrap();
+        assert_eq!(json["type"], "message");
+        assert_eq!(json["id"], json!(message.id));
+        assert_eq!(json["content"], "Hi");
+
+        let deleted = ServerEvent::Deleted {
+            message_id: message.id,
+            user_id: message.sender_id,
+            deleted_at: Utc::now(),
+        };
+        assert_eq!(deleted.user_id(), Some(message.sender_id));
+        assert_eq!(serde_json::to_value(deleted).unwrap()["type"], "deleted");
+
+        let ended = ServerEvent::Ended {
+            reason: ConversationEndReason::UserLeft,
+            ended_at: Utc::now(),
+            ended_by: Some(message.sender_id),
+        };
+        assert_eq!(ended.user_id(), None);
+        let json = serde_json::to_value(ended).unwrap();
+        assert_eq!(json["type"], "ended");
+        assert_eq!(json["reason"], "user_left");
+        assert_eq!(json["ended_by"], json!(message.sender_id));
+
+        let error = ApiError::validation("Message must not be empty");
+        assert_eq!(
+            serde_json::to_value(ServerEvent::from(&error)).unwrap(),
+            json!({ "type": "error", "code": "validation_failed", "message": "Message must not be empty" })
+        );
+    }
+}
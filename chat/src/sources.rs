@@ -0,0 +1,219 @@
+//! HTTP endpoints for users to add the sources they cite in messages.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use auth::middleware::auth_standard;
+use auth::models::{AuthenticatedUser, Authenticator};
+use axum::extract::rejection::{JsonRejection, PathRejection};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Extension, Json, Router, middleware};
+use chrono::Utc;
+use db::queries::sources;
+use serde::Deserialize;
+use shared::error::{ApiError, ErrorCode};
+use shared::types::source::{Source, SourceInfo, SourceKind};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Longest URL or book name looked up, in characters.
+const MAX_QUERY_LENGTH: usize = 2000;
+
+/// Longest notes accepted, in characters.
+const MAX_NOTES_LENGTH: usize = 2000;
+
+/// Looks up the details of the sources users add.
+#[async_trait]
+pub trait SourceLookup: Send + Sync + 'static {
+    /// Look up a website by its URL, or the books matching a name.
+    async fn lookup(&self, kind: SourceKind, query: &str) -> Result<SourceInfo, String>;
+}
+
+/// Looks sources up with the Bibify API.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Bibify;
+
+#[async_trait]
+impl SourceLookup for Bibify {
+    async fn lookup(&self, kind: SourceKind, query: &str) -> Result<SourceInfo, String> {
+        source_validation::extract_source(kind, query)
+            .await
+            .map(|source| source.source_info)
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// The sources API, served by [`SourcesApi::router`].
+///
+/// Users add a website or a book as a source, whose details are looked up with
+/// [`Bibify`] unless configured otherwise with [`SourcesApi::with_lookup`], then
+/// cite it in their messages by its id.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use auth::models::SbAuthenticator;
+/// use chat::SourcesApi;
+///
+/// # async fn example() {
+/// let pool = db::create_pool().await.unwrap();
+/// let app = axum::Router::new().nest(
+///     "/sources",
+///     SourcesApi::new(pool).router(SbAuthenticator::default()),
+/// );
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct SourcesApi {
+    pool: PgPool,
+    lookup: Arc<dyn SourceLookup>,
+}
+
+impl SourcesApi {
+    /// Create a new SourcesApi using the provided pool, looking sources up with [`Bibify`].
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            lookup: Arc::new(Bibify),
+        }
+    }
+
+    /// Look sources up using the given lookup.
+    pub fn with_lookup(mut self, lookup: impl SourceLookup) -> Self {
+        self.lookup = Arc::new(lookup);
+        self
+    }
+
+    /// Creates a router serving the API to users authenticated by `auth_standard`.
+    ///
+    /// The router includes the following endpoints:
+    ///  - `POST /` - add a website by its URL, or a book by its name, as a source
+    ///  - `GET /{id}` - get a source
+    pub fn router<A: Authenticator>(self, authenticator: A) -> Router {
+        Router::new()
+            .route("/", post(add_source))
+            .route("/{id}", get(get_source))
+            .route_layer(middleware::from_fn_with_state(
+                authenticator,
+                auth_standard::<A>,
+            ))
+            .with_state(self)
+    }
+}
+
+/// Source to add, looked up by `query`: the URL of a website, or the name of a book.
+#[derive(Debug, Deserialize)]
+struct AddSource {
+    kind: SourceKind,
+    query: String,
+    notes: Option<String>,
+}
+
+impl AddSource {
+    /// Validate the source, returning its trimmed query and notes.
+    fn validate(&self) -> Result<(&str, String), ApiError> {
+        let query = self.query.trim();
+        if query.is_empty() {
+            return Err(ApiError::validation("Query must not be empty"));
+        }
+        if query.chars().count() > MAX_QUERY_LENGTH {
+            return Err(ApiError::validation(format!(
+                "Query must be at most {MAX_QUERY_LENGTH} characters"
+            )));
+        }
+        if self.kind == SourceKind::Website
+            && !(query.starts_with("http://") || query.starts_with("https://"))
+        {
+            return Err(ApiError::validation(
+                "Websites must be given by their http or https URL",
+            ));
+        }
+
+        let notes = self.notes.as_deref().map(str::trim).unwrap_or_default();
+        if notes.chars().count() > MAX_NOTES_LENGTH {
+            return Err(ApiError::validation(format!(
+                "Notes must be at most {MAX_NOTES_LENGTH} characters"
+            )));
+        }
+        Ok((query, notes.to_string()))
+    }
+}
+
+/// Look a source up and add it on behalf of the caller.
+async fn add_source(
+    State(api): State<SourcesApi>,
+    Extension(user): Extension<AuthenticatedUser>,
+    payload: Result<Json<AddSource>, JsonRejection>,
+) -> Result<(StatusCode, Json<Source>), ApiError> {
+    let Json(payload) = payload?;
+    let (query, notes) = payload.validate()?;
+
+    let source_info = api.lookup.lookup(payload.kind, query).await.map_err(|e| {
+        ApiError::new(
+            ErrorCode::UpstreamError,
+            "The source could not be looked up",
+        )
+        .with_source(e)
+    })?;
+    if let SourceInfo::Book(books) = &source_info
+        && books.is_empty()
+    {
+        return Err(ApiError::not_found("No book matches the query"));
+    }
+
+    let source = Source {
+        created_by: user.id,
+        created_at: Utc::now(),
+        notes,
+        ..Source::new(source_info)
+    };
+    sources::insert_source(&api.pool, &source).await?;
+    Ok((StatusCode::CREATED, Json(source)))
+}
+
+/// Get a source.
+async fn get_source(
+    State(api): State<SourcesApi>,
+    id: Result<Path<Uuid>, PathRejection>,
+) -> Result<Json<Source>, ApiError> {
+    let Path(id) = id?;
+    sources::get_sources(&api.pool, &[id])
+        .await?
+        .into_iter()
+        .next()
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found("Source not found"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn add(kind: SourceKind, query: &str) -> AddSource {
+        AddSource {
+            kind,
+            query: query.to_string(),
+            notes: Some("  ".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_add_source_validation() {
+        let source = add(SourceKind::Website, " https://example.com/article ");
+        let (query, notes) = source.validate().unwrap();
+        assert_eq!(query, "https://example.com/article");
+        assert_eq!(notes, "");
+
+        let too_long = "a".repeat(MAX_QUERY_LENGTH + 1);
+        for invalid in [
+            add(SourceKind::Book, " "),
+            add(SourceKind::Book, &too_long),
+            add(SourceKind::Website, "example.com"),
+        ] {
+            let error = invalid.validate().unwrap_err();
+            assert_eq!(error.code(), ErrorCode::ValidationFailed);
+        }
+    }
+}
@@ -0,0 +1,73 @@
+//! Tests of the chat endpoint that are rejected before reaching the database.
+
+use axum::Router;
+use axum::http::{Method, StatusCode};
+use chat::ChatApi;
+use test_support::{authenticator, lazy_pool, send, token};
+use uuid::Uuid;
+
+/// Router backed by a pool that never connects, and a token for a new user.
+fn router() -> (Router, String) {
+    let authenticator = authenticator();
+    let token = token(&authenticator, Uuid::new_v4());
+    (ChatApi::new(lazy_pool()).router(authenticator), token)
+}
+
+#[tokio::test]
+async fn test_requires_authentication() {
+    let (router, _) = router();
+    let uri = format!("/{}/ws", Uuid::new_v4());
+
+    let (status, body) = send(router, Method::GET, &uri, None, None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "missing_credentials");
+}
+
+#[tokio::test]
+async fn test_rejects_malformed_requests() {
+    let (router, token) = router();
+
+    let (status, body) = send(
+        router.clone(),
+        Method::GET,
+        "/not-a-uuid/ws",
+        Some(&token),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "bad_request");
+
+    let uri = format!("/{}/ws?last_seen=yesterday", Uuid::new_v4());
+    let (status, body) = send(router, Method::GET, &uri, Some(&token), None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "bad_request");
+}
+
+#[tokio::test]
+async fn test_ending_requires_a_valid_conversation_id() {
+    let (router, token) = router();
+
+    for uri in ["/not-a-uuid/leave", "/not-a-uuid/complete"] {
+        let (status, _) = send(router.clone(), Method::POST, uri, Some(&token), None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
+
+#[tokio::test]
+async fn test_history_rejects_malformed_requests() {
+    let (router, token) = router();
+    let id = Uuid::new_v4();
+
+    for (uri, code) in [
+        ("/?cursor=yesterday".to_string(), "bad_request"),
+        ("/?limit=0".to_string(), "validation_failed"),
+        ("/not-a-uuid/messages".to_string(), "bad_request"),
+        (format!("/{id}/messages?limit=1000"), "validation_failed"),
+        (format!("/{id}/transcript?format=pdf"), "bad_request"),
+    ] {
+        let (status, body) = send(router.clone(), Method::GET, &uri, Some(&token), None).await;
+        assert_eq!(body["code"], code, "{uri}");
+        assert!(status.is_client_error(), "{uri}");
+    }
+}
@@ -0,0 +1,311 @@
+//! Tests of the chat API against a database.
+
+use std::net::SocketAddr;
+
+use auth::models::MockAuthenticator;
+use axum::http::{Method, StatusCode};
+use chat::ChatApi;
+use chrono::{Duration, Utc};
+use db::queries::{conversations, messages, sanctions};
+use serde_json::{Value, json};
+use shared::types::conversation::{Conversation, ConversationEndReason, Message};
+use shared::types::moderation::{Sanction, SanctionKind};
+use sqlx::PgPool;
+use test_support::{WsClient, authenticator, create_conversation, create_user, send, serve, token};
+use uuid::Uuid;
+
+/// A conversation between two new users, with a token for each participant.
+struct Fixture {
+    conversation: Conversation,
+    tokens: [String; 2],
+    authenticator: MockAuthenticator,
+}
+
+impl Fixture {
+    async fn new(pool: &PgPool) -> Self {
+        let (a, b) = (create_user(pool).await, create_user(pool).await);
+        let authenticator = authenticator();
+        Self {
+            conversation: create_conversation(pool, a, b).await,
+            tokens: [token(&authenticator, a), token(&authenticator, b)],
+            authenticator,
+        }
+    }
+
+    /// Serve the chat with the given configuration.
+    async fn serve(&self, api: ChatApi) -> SocketAddr {
+        serve(api.router(self.authenticator.clone())).await
+    }
+
+    /// Connect a participant to the conversation, resuming after `last_seen` if given.
+    async fn connect(
+        &self,
+        addr: SocketAddr,
+        participant: usize,
+        last_seen: Option<&Value>,
+    ) -> WsClient {
+        let mut uri = format!("/{}/ws", self.conversation.id);
+        if let Some(last_seen) = last_seen {
+            uri += &format!("?last_seen={}", last_seen.as_str().unwrap());
+        }
+        WsClient::connect(addr, &uri, &self.tokens[participant]).await
+    }
+}
+
+/// Send a message and return it once stored and echoed back.
+async fn say(client: &mut WsClient, content: &str) -> Value {
+    client
+        .send(json!({ "type": "message", "content": content }))
+        .await;
+    let message = client.recv_type("message").await;
+    assert_eq!(message["content"], content);
+    message
+}
+
+#[sqlx::test(migrator = "db::MIGRATOR")]
+async fn test_connections_resume_after_last_seen(pool: PgPool) {
+    let fixture = Fixture::new(&pool).await;
+    let addr = fixture.serve(ChatApi::new(pool)).await;
+
+    let mut first = fixture.connect(addr, 0, None).await;
+    assert_eq!(
+        first.recv().await,
+        json!({ "type": "synced", "last_message_id": null })
+    );
+    let sent = [
+        say(&mut first, "Hello").await,
+        say(&mut first, "Are you there?").await,
+        say(&mut first, "I'll wait").await,
+    ];
+    drop(first);
+
+    // Resuming replays only the messages after the last one seen.
+    let mut second = fixture.connect(addr, 1, Some(&sent[0]["id"])).await;
+    assert_eq!(second.recv().await["id"], sent[1]["id"]);
+    assert_eq!(second.recv().await["id"], sent[2]["id"]);
+    let synced = second.recv().await;
+    assert_eq!(synced["type"], "synced");
+    assert_eq!(synced["last_message_id"], sent[2]["id"]);
+
+    // Having seen everything, nothing is replayed.
+    let mut again = fixture.connect(addr, 1, Some(&sent[2]["id"])).await;
+    assert_eq!(
+        again.recv().await,
+        json!({ "type": "synced", "last_message_id": sent[2]["id"] })
+    );
+
+    // Without `last_seen`, the whole conversation is replayed.
+    let mut fresh = fixture.connect(addr, 1, None).await;
+    for message in &sent {
+        assert_eq!(fresh.recv().await["id"], message["id"]);
+    }
+    assert_eq!(fresh.recv().await["type"], "synced");
+}
+
+/// Follow `next_cursor` from the first page of `uri` to the last, returning the
+/// ids of the items listed.
+async fn page_through(api: &axum::Router, uri: &str, token: &str) -> Vec<Value> {
+    let mut ids = Vec::new();
+    let mut page_uri = uri.to_string();
+    loop {
+        let (status, page) = send(api.clone(), Method::GET, &page_uri, Some(token), None).await;
+        assert_eq!(status, StatusCode::OK, "{page}");
+        let items = page["items"].as_array().unwrap();
+        assert!(items.len() <= 2);
+        ids.extend(items.iter().map(|item| item["id"].clone()));
+        match page["next_cursor"].as_str() {
+            Some(cursor) => page_uri = format!("{uri}&cursor={cursor}"),
+            None => return ids,
+        }
+    }
+}
+
+#[sqlx::test(migrator = "db::MIGRATOR")]
+async fn test_history_pages_with_cursors(pool: PgPool) {
+    let fixture = Fixture::new(&pool).await;
+    let user = fixture.conversation.participant_a;
+    let mut conversations = vec![fixture.conversation.id];
+    for _ in 0..4 {
+        let partner = create_user(&pool).await;
+        conversations.push(create_conversation(&pool, user, partner).await.id);
+    }
+    let mut sent = Vec::new();
+    for i in 0..5 {
+        let message = Message {
+            id: Uuid::new_v4(),
+            conversation_id: fixture.conversation.id,
+            sender_id: user,
+            content: format!("Message {i}"),
+            sent_at: Utc::now() + Duration::milliseconds(i),
+            edited_at: None,
+            deleted_at: None,
+            sources: Vec::new(),
+        };
+        assert!(messages::insert_message(&pool, &message).await.unwrap());
+        sent.push(json!(message.id));
+    }
+    let api = ChatApi::new(pool).router(fixture.authenticator.clone());
+
+    // Conversations are listed newest first, each exactly once.
+    let listed = page_through(&api, "/?limit=2", &fixture.tokens[0]).await;
+    let newest_first: Vec<Value> = conversations.iter().rev().map(|id| json!(id)).collect();
+    assert_eq!(listed, newest_first);
+
+    // Messages are listed oldest first.
+    let uri = format!("/{}/messages?limit=2", fixture.conversation.id);
+    assert_eq!(page_through(&api, &uri, &fixture.tokens[1]).await, sent);
+
+    // The partner only took part in the first conversation.
+    let listed = page_through(&api, "/?limit=2", &fixture.tokens[1]).await;
+    assert_eq!(listed, vec![json!(fixture.conversation.id)]);
+}
+
+#[sqlx::test(migrator = "db::MIGRATOR")]
+async fn test_messages_are_revised_within_the_edit_window(pool: PgPool) {
+    let fixture = Fixture::new(&pool).await;
+    let user = fixture.conversation.participant_a;
+    let old = Message {
+        id: Uuid::new_v4(),
+        conversation_id: fixture.conversation.id,
+        sender_id: user,
+        content: "Sent a while ago".to_string(),
+        sent_at: Utc::now() - Duration::minutes(20),
+        edited_at: None,
+        deleted_at: None,
+        sources: Vec::new(),
+    };
+    assert!(messages::insert_message(&pool, &old).await.unwrap());
+    let addr = fixture.serve(ChatApi::new(pool.clone())).await;
+    let mut client = fixture.connect(addr, 0, Some(&json!(old.id))).await;
+    client.recv_type("synced").await;
+
+    client
+        .send(json!({ "type": "edit", "message_id": old.id, "content": "Too late" }))
+        .await;
+    let error = client.recv_type("error").await;
+    assert_eq!(error["code"], "conflict");
+    assert_eq!(
+        error["message"],
+        "Messages can only be edited or deleted within 15 minutes of being sent"
+    );
+
+    let message = say(&mut client, "Helo").await;
+    client
+        .send(json!({ "type": "edit", "message_id": message["id"], "content": "Hello" }))
+        .await;
+    let edited = client.recv_type("edited").await;
+    assert_eq!(edited["content"], "Hello");
+    assert!(edited["edited_at"].is_string());
+    let last = say(&mut client, "Bye").await;
+
+    // Edits count towards the flood limits like new messages.
+    let mut limited = None;
+    for i in 0..10 {
+        let content = format!("Hello {i}");
+        client
+            .send(json!({ "type": "edit", "message_id": message["id"], "content": content }))
+            .await;
+        let event = client.recv().await;
+        if event["type"] == "error" {
+            limited = Some(event);
+            break;
+        }
+        assert_eq!(event["content"], content);
+    }
+    assert_eq!(
+        limited.expect("edits were not limited")["code"],
+        "rate_limited"
+    );
+
+    client
+        .send(json!({ "type": "delete", "message_id": message["id"] }))
+        .await;
+    let deleted = client.recv_type("deleted").await;
+    assert_eq!(deleted["message_id"], message["id"]);
+
+    // Nothing can be revised once the conversation has ended.
+    conversations::end_conversation(
+        &pool,
+        fixture.conversation.id,
+        ConversationEndReason::Completed,
+        Utc::now(),
+    )
+    .await
+    .unwrap();
+    client
+        .send(json!({ "type": "delete", "message_id": last["id"] }))
+        .await;
+    let error = client.recv_type("error").await;
+    assert_eq!(error["message"], "The conversation has ended");
+    let last_id: Uuid = last["id"].as_str().unwrap().parse().unwrap();
+    let stored = messages::get_messages(&pool, fixture.conversation.id, &[last_id])
+        .await
+        .unwrap();
+    assert!(!stored[0].is_deleted());
+}
+
+#[sqlx::test(migrator = "db::MIGRATOR")]
+async fn test_revisions_made_while_disconnected_are_caught_up(pool: PgPool) {
+    let fixture = Fixture::new(&pool).await;
+    let sender = fixture.conversation.participant_a;
+    let seen = Message {
+        id: Uuid::new_v4(),
+        conversation_id: fixture.conversation.id,
+        sender_id: sender,
+        content: "Helo".to_string(),
+        sent_at: Utc::now() - Duration::minutes(20),
+        edited_at: None,
+        deleted_at: None,
+        sources: Vec::new(),
+    };
+    assert!(messages::insert_message(&pool, &seen).await.unwrap());
+
+    // The message was seen, then edited while the reader was disconnected, and
+    // the edit window has passed since.
+    messages::edit_message(
+        &pool,
+        fixture.conversation.id,
+        seen.id,
+        sender,
+        "Hello",
+        Utc::now() - Duration::minutes(10),
+        seen.sent_at,
+    )
+    .await
+    .unwrap()
+    .unwrap();
+
+    let addr = fixture.serve(ChatApi::new(pool)).await;
+    let mut reader = fixture.connect(addr, 1, Some(&json!(seen.id))).await;
+    let edited = reader.recv().await;
+    assert_eq!(edited["type"], "edited");
+    assert_eq!(edited["id"], json!(seen.id));
+    assert_eq!(edited["content"], "Hello");
+    assert_eq!(reader.recv().await["type"], "synced");
+}
+
+#[sqlx::test(migrator = "db::MIGRATOR")]
+async fn test_participants_are_disconnected_once_banned(pool: PgPool) {
+    let fixture = Fixture::new(&pool).await;
+    let api = ChatApi::new(pool.clone())
+        .with_sanction_check_interval(std::time::Duration::from_millis(50));
+    let addr = fixture.serve(api).await;
+    let mut client = fixture.connect(addr, 0, None).await;
+    assert_eq!(client.recv().await["type"], "synced");
+
+    let ban = Sanction {
+        id: Uuid::new_v4(),
+        user_id: fixture.conversation.participant_a,
+        kind: SanctionKind::Ban,
+        report_id: None,
+        reason: None,
+        issued_by: create_user(&pool).await,
+        issued_at: Utc::now(),
+        expires_at: None,
+    };
+    sanctions::insert_sanction(&pool, &ban).await.unwrap();
+
+    let error = client.recv().await;
+    assert_eq!(error["type"], "error");
+    assert_eq!(error["code"], "account_suspended");
+}
@@ -0,0 +1,94 @@
+//! Tests of adding sources and citing them in messages against a database.
+
+use async_trait::async_trait;
+use axum::http::{Method, StatusCode};
+use chat::{ChatApi, SourceLookup, SourcesApi};
+use serde_json::json;
+use shared::types::source::{PublicationDate, SourceInfo, SourceKind, WebsiteInfo};
+use sqlx::PgPool;
+use test_support::{WsClient, authenticator, create_conversation, create_user, send, serve, token};
+
+/// Lookup describing every URL as the same article, without leaving the test.
+struct Article;
+
+#[async_trait]
+impl SourceLookup for Article {
+    async fn lookup(&self, kind: SourceKind, query: &str) -> Result<SourceInfo, String> {
+        assert_eq!(kind, SourceKind::Website);
+        Ok(SourceInfo::Website(WebsiteInfo {
+            url: query.to_string(),
+            title: Some("Car-free city centres".to_string()),
+            authors: Some(vec!["Jane Doe".to_string()]),
+            publisher: None,
+            date: PublicationDate {
+                year: Some(2021),
+                month: None,
+                day: None,
+            },
+            description: None,
+        }))
+    }
+}
+
+#[sqlx::test(migrator = "db::MIGRATOR")]
+async fn test_added_sources_are_cited_in_messages(pool: PgPool) {
+    let (first, second) = (create_user(&pool).await, create_user(&pool).await);
+    let conversation = create_conversation(&pool, first, second).await;
+    let authenticator = authenticator();
+    let tokens = [token(&authenticator, first), token(&authenticator, second)];
+    let sources = SourcesApi::new(pool.clone())
+        .with_lookup(Article)
+        .router(authenticator.clone());
+
+    let added = json!({
+        "kind": "website",
+        "query": " https://example.com/car-free ",
+        "notes": "Covers three cities",
+    });
+    let (status, source) = send(
+        sources.clone(),
+        Method::POST,
+        "/",
+        Some(&tokens[0]),
+        Some(added),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(source["created_by"], json!(first));
+    assert_eq!(source["source_info"]["url"], "https://example.com/car-free");
+    assert_eq!(source["notes"], "Covers three cities");
+
+    let uri = format!("/{}", source["id"].as_str().unwrap());
+    let (status, found) = send(sources, Method::GET, &uri, Some(&tokens[1]), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(found["id"], source["id"]);
+    assert_eq!(found["source_info"], source["source_info"]);
+
+    // The other participant sees the summary of the cited source with the message.
+    let addr = serve(ChatApi::new(pool).router(authenticator)).await;
+    let uri = format!("/{}/ws", conversation.id);
+    let mut sender = WsClient::connect(addr, &uri, &tokens[0]).await;
+    let mut receiver = WsClient::connect(addr, &uri, &tokens[1]).await;
+    assert_eq!(sender.recv().await["type"], "synced");
+    assert_eq!(receiver.recv().await["type"], "synced");
+    sender
+        .send(json!({
+            "type": "message",
+            "content": "Several cities already did",
+            "source_ids": [source["id"]],
+        }))
+        .await;
+
+    let message = receiver.recv_type("message").await;
+    assert_eq!(
+        message["sources"],
+        json!([{
+            "id": source["id"],
+            "kind": "website",
+            "title": "Car-free city centres",
+            "authors": ["Jane Doe"],
+            "url": "https://example.com/car-free",
+            "credibility": 0.0,
+        }])
+    );
+}
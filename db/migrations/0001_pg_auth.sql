@@ -0,0 +1,26 @@
+-- Identities, one-time passwords and sessions for the self-hosted `PgAuthenticator`.
+
+CREATE TABLE IF NOT EXISTS auth_identities (
+    id          UUID PRIMARY KEY,
+    contact     TEXT NOT NULL UNIQUE,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- At most one outstanding code per contact; only a hash of the code is stored.
+CREATE TABLE IF NOT EXISTS auth_otp_codes (
+    contact     TEXT PRIMARY KEY,
+    code_hash   TEXT NOT NULL,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at  TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS auth_sessions (
+    id                UUID PRIMARY KEY,
+    user_id           UUID NOT NULL REFERENCES auth_identities (id) ON DELETE CASCADE,
+    refresh_token_id  UUID NOT NULL UNIQUE,
+    created_at        TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at        TIMESTAMPTZ NOT NULL,
+    revoked_at        TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS auth_sessions_user_id_idx ON auth_sessions (user_id);
@@ -0,0 +1,7 @@
+-- Roles granted to users beyond the default `user` role.
+
+CREATE TABLE IF NOT EXISTS user_roles (
+    user_id     UUID PRIMARY KEY,
+    role        TEXT NOT NULL CHECK (role IN ('user', 'moderator', 'admin')),
+    granted_at  TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,16 @@
+-- Rate limiting and lockout state for the OTP endpoints, shared by all gateway instances.
+
+-- Fixed-window request counters, keyed by client IP or contact.
+CREATE TABLE IF NOT EXISTS auth_rate_limits (
+    key                TEXT PRIMARY KEY,
+    window_started_at  TIMESTAMPTZ NOT NULL,
+    hits               INTEGER NOT NULL
+);
+
+-- Failed OTP verifications per contact and the resulting lockouts.
+CREATE TABLE IF NOT EXISTS auth_lockouts (
+    key           TEXT PRIMARY KEY,
+    failures      INTEGER NOT NULL DEFAULT 0,
+    lockouts      INTEGER NOT NULL DEFAULT 0,
+    locked_until  TIMESTAMPTZ
+);
@@ -0,0 +1,15 @@
+-- Public profiles of users, created on their first sign-in.
+--
+-- `id` is the user ID claimed by access tokens, issued either by Supabase or by
+-- `PgAuthenticator`, so it does not reference `auth_identities`.
+
+CREATE TABLE IF NOT EXISTS users (
+    id             UUID PRIMARY KEY,
+    email          TEXT,
+    phone          TEXT,
+    display_name   TEXT,
+    bio            TEXT,
+    created_at     TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at     TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_login_at  TIMESTAMPTZ NOT NULL DEFAULT now()
+);
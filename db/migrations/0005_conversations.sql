@@ -0,0 +1,57 @@
+-- Conversation requests, the conversations they are matched into and their messages.
+--
+-- The enum types mirror `ConversationRequestStatus` and `ConversationEndReason`
+-- in `shared::types::conversation`.
+
+DO $$ BEGIN
+    CREATE TYPE conversation_request_status AS ENUM ('pending', 'expired', 'matched');
+EXCEPTION
+    WHEN duplicate_object THEN NULL;
+END $$;
+
+DO $$ BEGIN
+    CREATE TYPE conversation_end_reason AS ENUM ('completed', 'user_left', 'user_reported', 'inactive');
+EXCEPTION
+    WHEN duplicate_object THEN NULL;
+END $$;
+
+-- A user's request to talk about a prompt; `match_id` is set once matched.
+CREATE TABLE IF NOT EXISTS conversation_requests (
+    id            UUID PRIMARY KEY,
+    user_id       UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    prompt        TEXT NOT NULL,
+    request_time  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    status        conversation_request_status NOT NULL DEFAULT 'pending',
+    match_id      UUID
+);
+
+CREATE INDEX IF NOT EXISTS conversation_requests_user_id_idx ON conversation_requests (user_id);
+CREATE INDEX IF NOT EXISTS conversation_requests_pending_idx
+    ON conversation_requests (prompt, request_time)
+    WHERE status = 'pending';
+
+-- `ended_at` and `end_reason` are set together when the conversation ends.
+CREATE TABLE IF NOT EXISTS conversations (
+    id             UUID PRIMARY KEY,
+    topic          TEXT NOT NULL,
+    created_at     TIMESTAMPTZ NOT NULL DEFAULT now(),
+    ended_at       TIMESTAMPTZ,
+    end_reason     conversation_end_reason,
+    participant_a  UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    participant_b  UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    CHECK (participant_a <> participant_b),
+    CHECK ((ended_at IS NULL) = (end_reason IS NULL))
+);
+
+CREATE INDEX IF NOT EXISTS conversations_participant_a_idx ON conversations (participant_a);
+CREATE INDEX IF NOT EXISTS conversations_participant_b_idx ON conversations (participant_b);
+
+CREATE TABLE IF NOT EXISTS messages (
+    id               UUID PRIMARY KEY,
+    conversation_id  UUID NOT NULL REFERENCES conversations (id) ON DELETE CASCADE,
+    sender_id        UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    content          TEXT NOT NULL,
+    sent_at          TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS messages_conversation_id_idx ON messages (conversation_id, sent_at, id);
@@ -0,0 +1,22 @@
+-- Websites and books cited by users, mirroring `shared::types::source::Source`.
+--
+-- `source_info` holds the `WebsiteInfo` of a website, or the list of
+-- `BookInfo` matches of a book, as JSON; `kind` tells which.
+
+DO $$ BEGIN
+    CREATE TYPE source_kind AS ENUM ('website', 'book');
+EXCEPTION
+    WHEN duplicate_object THEN NULL;
+END $$;
+
+CREATE TABLE IF NOT EXISTS sources (
+    id           UUID PRIMARY KEY,
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT now(),
+    created_by   UUID NOT NULL,
+    credibility  REAL NOT NULL DEFAULT 0,
+    kind         source_kind NOT NULL,
+    source_info  JSONB NOT NULL,
+    notes        TEXT NOT NULL DEFAULT ''
+);
+
+CREATE INDEX IF NOT EXISTS sources_created_by_idx ON sources (created_by);
@@ -0,0 +1,4 @@
+-- The conversation a request was matched into, set together with `match_id`.
+
+ALTER TABLE conversation_requests
+    ADD COLUMN IF NOT EXISTS conversation_id UUID REFERENCES conversations (id) ON DELETE SET NULL;
@@ -0,0 +1,7 @@
+-- Where users stand on the prompt of their conversation requests, as in
+-- `shared::types::stance::Stance`. `position` is a Likert scale from -2
+-- (strongly disagree) to 2 (strongly agree).
+
+ALTER TABLE conversation_requests
+    ADD COLUMN IF NOT EXISTS position SMALLINT NOT NULL DEFAULT 0 CHECK (position BETWEEN -2 AND 2),
+    ADD COLUMN IF NOT EXISTS statement TEXT;
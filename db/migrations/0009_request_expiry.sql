@@ -0,0 +1,21 @@
+-- Expiry of pending conversation requests.
+--
+-- Requests expire once pending for longer than the TTL set for their prompt in
+-- `prompt_ttls`, or the expiry job's default TTL otherwise.
+
+-- Mirrors `matchmaking::strategy::normalize_prompt`, so that trivially
+-- different spellings of a prompt share a TTL.
+CREATE OR REPLACE FUNCTION normalize_prompt(prompt TEXT) RETURNS TEXT
+    LANGUAGE SQL IMMUTABLE STRICT
+    AS $$ SELECT lower(regexp_replace(btrim(prompt), '\s+', ' ', 'g')) $$;
+
+-- `prompt` is stored normalized.
+CREATE TABLE IF NOT EXISTS prompt_ttls (
+    prompt       TEXT PRIMARY KEY,
+    ttl_seconds  BIGINT NOT NULL CHECK (ttl_seconds > 0),
+    updated_at   TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS conversation_requests_pending_time_idx
+    ON conversation_requests (request_time)
+    WHERE status = 'pending';
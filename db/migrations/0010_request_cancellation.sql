@@ -0,0 +1,3 @@
+-- Users may withdraw their pending conversation requests.
+
+ALTER TYPE conversation_request_status ADD VALUE IF NOT EXISTS 'cancelled';
@@ -0,0 +1,9 @@
+-- Read receipts: the last message each participant has read in a conversation.
+
+CREATE TABLE IF NOT EXISTS message_reads (
+    conversation_id  UUID NOT NULL REFERENCES conversations (id) ON DELETE CASCADE,
+    user_id          UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    message_id       UUID NOT NULL REFERENCES messages (id) ON DELETE CASCADE,
+    read_at          TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (conversation_id, user_id)
+);
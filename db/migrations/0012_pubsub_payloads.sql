@@ -0,0 +1,11 @@
+-- Payloads published with `db::pubsub::PgPubSub` that are too large for a
+-- notification. The notification carries the id of the row instead; rows are
+-- deleted once every listener has had time to fetch them.
+
+CREATE TABLE IF NOT EXISTS pubsub_payloads (
+    id          UUID PRIMARY KEY,
+    payload     TEXT NOT NULL,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS pubsub_payloads_created_at_idx ON pubsub_payloads (created_at);
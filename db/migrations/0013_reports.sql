@@ -0,0 +1,68 @@
+-- Reports filed by participants against each other, and sanctions moderators
+-- impose on resolving them.
+--
+-- The enum types mirror `ReportCategory`, `ReportStatus`, `ModerationAction` and
+-- `SanctionKind` in `shared::types::moderation`.
+
+DO $$ BEGIN
+    CREATE TYPE report_category AS ENUM ('harassment', 'hate_speech', 'threats', 'spam', 'other');
+EXCEPTION
+    WHEN duplicate_object THEN NULL;
+END $$;
+
+DO $$ BEGIN
+    CREATE TYPE report_status AS ENUM ('open', 'claimed', 'resolved');
+EXCEPTION
+    WHEN duplicate_object THEN NULL;
+END $$;
+
+DO $$ BEGIN
+    CREATE TYPE moderation_action AS ENUM ('dismiss', 'warn', 'suspend', 'ban');
+EXCEPTION
+    WHEN duplicate_object THEN NULL;
+END $$;
+
+DO $$ BEGIN
+    CREATE TYPE sanction_kind AS ENUM ('warning', 'suspension', 'ban');
+EXCEPTION
+    WHEN duplicate_object THEN NULL;
+END $$;
+
+-- `messages` is a snapshot of the reported messages, kept even if they are deleted.
+-- Each participant may report a conversation once.
+CREATE TABLE IF NOT EXISTS reports (
+    id                UUID PRIMARY KEY,
+    conversation_id   UUID NOT NULL REFERENCES conversations (id) ON DELETE CASCADE,
+    reporter_id       UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    reported_user_id  UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    category          report_category NOT NULL,
+    details           TEXT,
+    messages          JSONB NOT NULL DEFAULT '[]',
+    status            report_status NOT NULL DEFAULT 'open',
+    created_at        TIMESTAMPTZ NOT NULL DEFAULT now(),
+    claimed_by        UUID,
+    claimed_at        TIMESTAMPTZ,
+    resolved_at       TIMESTAMPTZ,
+    action            moderation_action,
+    resolution_note   TEXT,
+    UNIQUE (conversation_id, reporter_id),
+    CHECK ((status = 'open') = (claimed_by IS NULL)),
+    CHECK ((status = 'resolved') = (action IS NOT NULL AND resolved_at IS NOT NULL))
+);
+
+CREATE INDEX IF NOT EXISTS reports_queue_idx ON reports (created_at) WHERE status <> 'resolved';
+
+-- Sanctions are never deleted; suspensions lapse once `expires_at` has passed.
+CREATE TABLE IF NOT EXISTS user_sanctions (
+    id          UUID PRIMARY KEY,
+    user_id     UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    kind        sanction_kind NOT NULL,
+    report_id   UUID REFERENCES reports (id) ON DELETE SET NULL,
+    reason      TEXT,
+    issued_by   UUID NOT NULL,
+    issued_at   TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at  TIMESTAMPTZ,
+    CHECK (kind = 'suspension' OR expires_at IS NULL)
+);
+
+CREATE INDEX IF NOT EXISTS user_sanctions_user_id_idx ON user_sanctions (user_id);
@@ -0,0 +1,12 @@
+-- Users blocked by other users, who are never matched with them again.
+
+CREATE TABLE IF NOT EXISTS user_blocks (
+    blocker_id  UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    blocked_id  UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (blocker_id, blocked_id),
+    CHECK (blocker_id <> blocked_id)
+);
+
+CREATE INDEX IF NOT EXISTS user_blocks_blocked_id_idx ON user_blocks (blocked_id);
+CREATE INDEX IF NOT EXISTS conversations_created_at_idx ON conversations (created_at);
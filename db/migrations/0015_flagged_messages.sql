@@ -0,0 +1,11 @@
+-- Reports filed automatically when message filters flag a message.
+--
+-- These have no reporter. Each participant of a conversation has at most one
+-- such report waiting in the queue; messages flagged before a moderator claims
+-- it are added to its snapshot rather than filed as new reports.
+
+ALTER TABLE reports ALTER COLUMN reporter_id DROP NOT NULL;
+
+CREATE UNIQUE INDEX IF NOT EXISTS reports_flagged_idx
+    ON reports (conversation_id, reported_user_id)
+    WHERE reporter_id IS NULL AND status = 'open';
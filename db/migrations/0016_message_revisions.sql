@@ -0,0 +1,20 @@
+-- Message edits and deletions.
+--
+-- Senders may edit or delete their messages for a short while after sending
+-- them. Deleted messages keep their row, with `deleted_at` set and `content`
+-- emptied. The text replaced by each edit or deletion is kept in
+-- `message_revisions`, so that moderators can still read it.
+
+ALTER TABLE messages
+    ADD COLUMN IF NOT EXISTS edited_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+
+CREATE TABLE IF NOT EXISTS message_revisions (
+    id           UUID PRIMARY KEY,
+    message_id   UUID NOT NULL REFERENCES messages (id) ON DELETE CASCADE,
+    content      TEXT NOT NULL,
+    written_at   TIMESTAMPTZ NOT NULL,
+    replaced_at  TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS message_revisions_message_id_idx ON message_revisions (message_id, replaced_at);
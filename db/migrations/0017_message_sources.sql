@@ -0,0 +1,10 @@
+-- Sources cited by messages, in the order the sender cited them.
+
+CREATE TABLE IF NOT EXISTS message_sources (
+    message_id  UUID NOT NULL REFERENCES messages (id) ON DELETE CASCADE,
+    source_id   UUID NOT NULL REFERENCES sources (id) ON DELETE CASCADE,
+    position    INTEGER NOT NULL,
+    PRIMARY KEY (message_id, source_id)
+);
+
+CREATE INDEX IF NOT EXISTS message_sources_source_id_idx ON message_sources (source_id);
@@ -0,0 +1,9 @@
+-- Let the OTP rate limiter forget idle contacts and prune elapsed windows.
+
+-- When each window ends, so that elapsed windows can be deleted.
+ALTER TABLE auth_rate_limits
+    ADD COLUMN IF NOT EXISTS window_ends_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+-- When the contact last failed, from which its failures and lockouts decay.
+ALTER TABLE auth_lockouts
+    ADD COLUMN IF NOT EXISTS last_failure_at TIMESTAMPTZ NOT NULL DEFAULT now();
@@ -1,5 +1,6 @@
 //! Error types for the db crate.
 
+use shared::error::{ApiError, ErrorCode};
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, DbError>;
@@ -19,4 +20,27 @@ pub enum DbError {
 
     #[error("Configuration error: {0}")]
     Configuration(String),
+
+    #[error("Migration error: {0}")]
+    Migration(#[from] sqlx::migrate::MigrateError),
+
+    #[error("Pub/sub error: {0}")]
+    PubSub(String),
+}
+
+impl From<DbError> for ApiError {
+    /// Unexpected database errors are logged rather than returned to the client.
+    fn from(error: DbError) -> Self {
+        match error {
+            DbError::Connection(_) => {
+                ApiError::new(ErrorCode::ServiceUnavailable, "Database unavailable")
+                    .with_source(error)
+            }
+            DbError::Query(sqlx::Error::RowNotFound) => ApiError::not_found("Resource not found"),
+            DbError::Query(_)
+            | DbError::Configuration(_)
+            | DbError::Migration(_)
+            | DbError::PubSub(_) => ApiError::internal(error),
+        }
+    }
 }
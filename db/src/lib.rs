@@ -1,10 +1,19 @@
 //! Database Abstraction Layer
 pub mod error;
+pub mod pubsub;
 pub mod queries;
 
 use error::{DbError, Result};
+use sqlx::PgPool;
+use sqlx::migrate::Migrator;
 use sqlx::postgres::PgPoolOptions;
 
+/// The migrations in `db/migrations`, embedded at compile time.
+///
+/// Besides [`migrate`], tests use it to set up their databases with
+/// `#[sqlx::test(migrator = "db::MIGRATOR")]`.
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
 /// Creates a new PostgreSQL connection pool.
 ///
 /// This function reads the `DATABASE_URL` environment variable and creates
@@ -56,3 +65,29 @@ pub async fn create_pool() -> Result<sqlx::PgPool> {
         .await
         .map_err(DbError::Connection)
 }
+
+/// Applies the migrations in `db/migrations` that have not been applied yet.
+///
+/// The migrations are embedded at compile time and recorded in the
+/// `_sqlx_migrations` table, so running this on every startup is safe. The
+/// migrations themselves are idempotent, so databases set up by running the SQL
+/// files by hand can be brought under this function as well.
+///
+/// # Errors
+///
+/// Returns [`DbError::Migration`] if a migration fails, or if an applied
+/// migration has been modified since.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # async fn example() -> Result<(), db::error::DbError> {
+/// let pool = db::create_pool().await?;
+/// db::migrate(&pool).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn migrate(pool: &PgPool) -> Result<()> {
+    MIGRATOR.run(pool).await?;
+    Ok(())
+}
@@ -0,0 +1,80 @@
+//! Publish/subscribe within a single process.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use tokio::sync::broadcast;
+
+use super::{CHANNEL_CAPACITY, PubSub};
+use crate::error::Result;
+
+/// Backend delivering messages to subscribers in the same process only.
+#[derive(Clone, Default)]
+pub struct InProcessPubSub {
+    channels: Arc<Mutex<HashMap<String, broadcast::Sender<String>>>>,
+}
+
+impl InProcessPubSub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Deliver a payload to the channel's subscribers.
+    pub(super) fn deliver(&self, channel: &str, payload: String) {
+        let mut channels = self.channels.lock().unwrap();
+        if let Some(sender) = channels.get(channel) {
+            // Sending only fails once every subscriber has gone.
+            if sender.send(payload).is_err() {
+                channels.remove(channel);
+            }
+        }
+    }
+
+    pub(super) fn receiver(&self, channel: &str) -> broadcast::Receiver<String> {
+        let mut channels = self.channels.lock().unwrap();
+        channels
+            .entry(channel.to_string())
+            .or_insert_with(|| broadcast::channel(CHANNEL_CAPACITY).0)
+            .subscribe()
+    }
+}
+
+#[async_trait]
+impl PubSub for InProcessPubSub {
+    async fn publish(&self, channel: &str, payload: &str) -> Result<()> {
+        self.deliver(channel, payload.to_string());
+        Ok(())
+    }
+
+    async fn subscribe(&self, channel: &str) -> Result<broadcast::Receiver<String>> {
+        Ok(self.receiver(channel))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_payloads_reach_subscribers_of_the_channel() {
+        let pubsub = InProcessPubSub::new();
+        let mut first = pubsub.subscribe("chat").await.unwrap();
+        let mut second = pubsub.subscribe("chat").await.unwrap();
+        let mut other = pubsub.subscribe("matches").await.unwrap();
+
+        pubsub.publish("chat", "hello").await.unwrap();
+        assert_eq!(first.recv().await.unwrap(), "hello");
+        assert_eq!(second.recv().await.unwrap(), "hello");
+        assert!(other.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_channels_are_dropped_without_subscribers() {
+        let pubsub = InProcessPubSub::new();
+        drop(pubsub.subscribe("chat").await.unwrap());
+
+        pubsub.publish("chat", "hello").await.unwrap();
+        assert!(pubsub.channels.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,37 @@
+//! Publish/subscribe messaging between the parts of the application.
+//!
+//! Publishers send string payloads, typically JSON, on named channels, and every
+//! current subscriber of the channel receives them. Messages are not stored:
+//! subscribers only receive what is published while they are subscribed.
+//!
+//! Two backends implement [`PubSub`]: [`InProcessPubSub`], for a single
+//! instance and tests, and [`PgPubSub`], which relays messages through Postgres
+//! `LISTEN`/`NOTIFY` so that they reach subscribers on every instance connected
+//! to the database, without a separate broker.
+
+mod memory;
+mod postgres;
+
+pub use memory::InProcessPubSub;
+pub use postgres::PgPubSub;
+
+use async_trait::async_trait;
+use tokio::sync::broadcast;
+
+use crate::error::Result;
+
+/// Messages buffered per channel for subscribers that fall behind.
+///
+/// Subscribers falling further behind miss messages, and are told so by
+/// [`broadcast::error::RecvError::Lagged`].
+pub const CHANNEL_CAPACITY: usize = 256;
+
+/// Trait for publish/subscribe backends.
+#[async_trait]
+pub trait PubSub: Send + Sync + 'static {
+    /// Publish a payload to the current subscribers of a channel.
+    async fn publish(&self, channel: &str, payload: &str) -> Result<()>;
+
+    /// Receive the payloads published on a channel from now on.
+    async fn subscribe(&self, channel: &str) -> Result<broadcast::Receiver<String>>;
+}
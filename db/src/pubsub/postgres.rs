@@ -0,0 +1,195 @@
+//! Publish/subscribe across instances through Postgres `LISTEN`/`NOTIFY`.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use sqlx::PgPool;
+use sqlx::postgres::{PgListener, PgNotification};
+use tokio::sync::{OnceCell, broadcast, mpsc, oneshot};
+use uuid::Uuid;
+
+use super::{InProcessPubSub, PubSub};
+use crate::error::{DbError, Result};
+use crate::queries::notify;
+
+/// Largest payload sent within a notification; Postgres rejects those of 8000 bytes or more.
+const MAX_INLINE_PAYLOAD: usize = 7900;
+
+/// Prefix of notifications carrying their payload.
+const INLINE: &str = "=";
+
+/// Prefix of notifications carrying the id of a payload stored in `pubsub_payloads`.
+const STORED: &str = "@";
+
+/// Time waited before reconnecting after the listener failed.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Request for the listener to listen on a channel.
+struct Listen {
+    channel: String,
+    done: oneshot::Sender<Result<()>>,
+}
+
+/// Backend publishing messages with `NOTIFY`, delivered to the subscribers of
+/// every instance connected to the database.
+///
+/// Each `PgPubSub` holds a single listening connection, shared by all its
+/// subscribers and opened on the first subscription. If the connection is
+/// lost, it is reopened, but messages published in the meantime are missed.
+/// Payloads too large for a notification are stored in the `pubsub_payloads`
+/// table, and the notification carries their id instead.
+///
+/// Creating a `PgPubSub` must happen within a Tokio runtime.
+#[derive(Clone)]
+pub struct PgPubSub {
+    pool: PgPool,
+    local: InProcessPubSub,
+    listener: Arc<OnceCell<mpsc::UnboundedSender<Listen>>>,
+}
+
+impl PgPubSub {
+    /// Create a new PgPubSub using the provided pool.
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            local: InProcessPubSub::new(),
+            listener: Default::default(),
+        }
+    }
+
+    /// Start the listener task, returning the channel through which it is asked to listen.
+    fn start_listener(&self) -> mpsc::UnboundedSender<Listen> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(listen(self.pool.clone(), self.local.clone(), receiver));
+        sender
+    }
+}
+
+#[async_trait]
+impl PubSub for PgPubSub {
+    async fn publish(&self, channel: &str, payload: &str) -> Result<()> {
+        if payload.len() <= MAX_INLINE_PAYLOAD {
+            return notify::notify(&self.pool, channel, &format!("{INLINE}{payload}")).await;
+        }
+
+        let id = notify::store_payload(&self.pool, payload).await?;
+        notify::notify(&self.pool, channel, &format!("{STORED}{id}")).await
+    }
+
+    async fn subscribe(&self, channel: &str) -> Result<broadcast::Receiver<String>> {
+        // Subscribe locally first, so that nothing delivered once listening is missed.
+        let receiver = self.local.receiver(channel);
+
+        let listener = self
+            .listener
+            .get_or_init(|| async { self.start_listener() })
+            .await;
+        let (done, listening) = oneshot::channel();
+        let stopped = || DbError::PubSub("listener stopped".into());
+        listener
+            .send(Listen {
+                channel: channel.to_string(),
+                done,
+            })
+            .map_err(|_| stopped())?;
+        listening.await.map_err(|_| stopped())??;
+
+        Ok(receiver)
+    }
+}
+
+/// Listen for notifications, delivering them to local subscribers, until the
+/// `PgPubSub` is dropped.
+async fn listen(
+    pool: PgPool,
+    local: InProcessPubSub,
+    mut requests: mpsc::UnboundedReceiver<Listen>,
+) {
+    let mut channels = HashSet::new();
+    let mut listener: Option<PgListener> = None;
+
+    loop {
+        let Some(active) = listener.as_mut() else {
+            match connect(&pool, &channels).await {
+                Ok(connected) => listener = Some(connected),
+                Err(e) => {
+                    eprintln!("Pub/sub listener failed to connect: {e}");
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                }
+            }
+            continue;
+        };
+
+        tokio::select! {
+            request = requests.recv() => {
+                let Some(Listen { channel, done }) = request else {
+                    return;
+                };
+                let result = if channels.contains(&channel) {
+                    Ok(())
+                } else {
+                    active.listen(&channel).await.map_err(DbError::Query)
+                };
+                if result.is_ok() {
+                    channels.insert(channel);
+                }
+                let _ = done.send(result);
+            }
+            notification = active.try_recv() => match notification {
+                Ok(Some(notification)) => deliver(&pool, &local, notification).await,
+                // The connection was lost, and is reopened on the next call.
+                Ok(None) => {}
+                Err(e) => {
+                    eprintln!("Pub/sub listener failed: {e}");
+                    listener = None;
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                }
+            },
+        }
+    }
+}
+
+/// Open a listening connection on the given channels.
+async fn connect(pool: &PgPool, channels: &HashSet<String>) -> Result<PgListener> {
+    let mut listener = PgListener::connect_with(pool)
+        .await
+        .map_err(DbError::Connection)?;
+    listener
+        .listen_all(channels.iter().map(String::as_str))
+        .await
+        .map_err(DbError::Query)?;
+    Ok(listener)
+}
+
+/// Deliver a notification's payload to local subscribers, fetching it if it was stored.
+async fn deliver(pool: &PgPool, local: &InProcessPubSub, notification: PgNotification) {
+    let payload = notification.payload();
+    let payload = if let Some(inline) = payload.strip_prefix(INLINE) {
+        inline.to_string()
+    } else if let Some(id) = payload
+        .strip_prefix(STORED)
+        .and_then(|id| Uuid::parse_str(id).ok())
+    {
+        match notify::get_payload(pool, id).await {
+            Ok(Some(payload)) => payload,
+            Ok(None) => {
+                eprintln!(
+                    "Pub/sub payload {id} on {} has expired",
+                    notification.channel()
+                );
+                return;
+            }
+            Err(e) => {
+                eprintln!("Failed to fetch pub/sub payload {id}: {e}");
+                return;
+            }
+        }
+    } else {
+        // Sent with a plain NOTIFY rather than through `PgPubSub`.
+        payload.to_string()
+    };
+
+    local.deliver(notification.channel(), payload);
+}
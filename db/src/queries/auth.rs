@@ -0,0 +1,149 @@
+//! Queries backing the self-hosted authentication backend.
+//!
+//! These operate on the `auth_identities`, `auth_otp_codes` and `auth_sessions`
+//! tables. Secrets are never stored in plain text: OTP codes are stored as hashes
+//! and sessions are referenced by the IDs embedded in their signed tokens.
+
+use chrono::{DateTime, Utc};
+use sqlx::{PgExecutor, PgPool};
+use uuid::Uuid;
+
+use crate::error::{DbError, Result};
+
+/// Store the hash of a freshly generated OTP for the given contact.
+///
+/// Any previously outstanding code for the contact is replaced, so only the
+/// most recently sent code can be used to sign in.
+pub async fn upsert_otp(
+    pool: &PgPool,
+    contact: &str,
+    code_hash: &str,
+    expires_at: DateTime<Utc>,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO auth_otp_codes (contact, code_hash, expires_at)
+         VALUES ($1, $2, $3)
+         ON CONFLICT (contact)
+         DO UPDATE SET code_hash = EXCLUDED.code_hash,
+                       created_at = now(),
+                       expires_at = EXCLUDED.expires_at",
+    )
+    .bind(contact)
+    .bind(code_hash)
+    .bind(expires_at)
+    .execute(pool)
+    .await
+    .map_err(DbError::Query)?;
+
+    Ok(())
+}
+
+/// Consume the outstanding OTP for a contact if its hash matches and it has not expired.
+///
+/// Returns `true` if a matching code was found (and has now been deleted).
+pub async fn consume_otp(
+    executor: impl PgExecutor<'_>,
+    contact: &str,
+    code_hash: &str,
+) -> Result<bool> {
+    let result = sqlx::query(
+        "DELETE FROM auth_otp_codes
+         WHERE contact = $1 AND code_hash = $2 AND expires_at > now()",
+    )
+    .bind(contact)
+    .bind(code_hash)
+    .execute(executor)
+    .await
+    .map_err(DbError::Query)?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Return the identity ID for a contact, creating the identity if it does not exist.
+pub async fn upsert_identity(executor: impl PgExecutor<'_>, contact: &str) -> Result<Uuid> {
+    sqlx::query_scalar(
+        "INSERT INTO auth_identities (id, contact)
+         VALUES ($1, $2)
+         ON CONFLICT (contact) DO UPDATE SET contact = EXCLUDED.contact
+         RETURNING id",
+    )
+    .bind(Uuid::new_v4())
+    .bind(contact)
+    .fetch_one(executor)
+    .await
+    .map_err(DbError::Query)
+}
+
+/// Record a new session for a user.
+pub async fn insert_session(
+    executor: impl PgExecutor<'_>,
+    session_id: Uuid,
+    user_id: Uuid,
+    refresh_token_id: Uuid,
+    expires_at: DateTime<Utc>,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO auth_sessions (id, user_id, refresh_token_id, expires_at)
+         VALUES ($1, $2, $3, $4)",
+    )
+    .bind(session_id)
+    .bind(user_id)
+    .bind(refresh_token_id)
+    .bind(expires_at)
+    .execute(executor)
+    .await
+    .map_err(DbError::Query)?;
+
+    Ok(())
+}
+
+/// Replace the refresh token of an active session, extending its lifetime.
+///
+/// The old refresh token ID must match the one currently stored, so each refresh
+/// token can be used only once. Returns the session ID and user ID on success, or
+/// `None` if no active session holds the given refresh token.
+pub async fn rotate_refresh_token(
+    pool: &PgPool,
+    old_refresh_token_id: Uuid,
+    new_refresh_token_id: Uuid,
+    expires_at: DateTime<Utc>,
+) -> Result<Option<(Uuid, Uuid)>> {
+    sqlx::query_as(
+        "UPDATE auth_sessions
+         SET refresh_token_id = $2, expires_at = $3
+         WHERE refresh_token_id = $1 AND revoked_at IS NULL AND expires_at > now()
+         RETURNING id, user_id",
+    )
+    .bind(old_refresh_token_id)
+    .bind(new_refresh_token_id)
+    .bind(expires_at)
+    .fetch_optional(pool)
+    .await
+    .map_err(DbError::Query)
+}
+
+/// Return the user ID of a session if it is still active.
+pub async fn get_active_session_user(pool: &PgPool, session_id: Uuid) -> Result<Option<Uuid>> {
+    sqlx::query_scalar(
+        "SELECT user_id FROM auth_sessions
+         WHERE id = $1 AND revoked_at IS NULL AND expires_at > now()",
+    )
+    .bind(session_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(DbError::Query)
+}
+
+/// Revoke an active session. Returns `true` if a session was revoked.
+pub async fn revoke_session(pool: &PgPool, session_id: Uuid) -> Result<bool> {
+    let result = sqlx::query(
+        "UPDATE auth_sessions SET revoked_at = now()
+         WHERE id = $1 AND revoked_at IS NULL",
+    )
+    .bind(session_id)
+    .execute(pool)
+    .await
+    .map_err(DbError::Query)?;
+
+    Ok(result.rows_affected() > 0)
+}
@@ -0,0 +1,86 @@
+//! Queries for users blocking each other.
+//!
+//! These operate on the `user_blocks` table, and on the `conversations` table
+//! to find the users who should not be matched with each other.
+
+use chrono::{DateTime, Utc};
+use shared::types::moderation::Block;
+use sqlx::{PgExecutor, PgPool};
+use uuid::Uuid;
+
+use crate::error::{DbError, Result};
+
+/// Block a user, returning the block as stored.
+///
+/// Blocking a user again leaves the block as it is. Returns `None` if the
+/// blocked user has no profile.
+pub async fn block(pool: &PgPool, blocker_id: Uuid, blocked_id: Uuid) -> Result<Option<Block>> {
+    sqlx::query_as(
+        "WITH inserted AS (
+             INSERT INTO user_blocks (blocker_id, blocked_id)
+             SELECT $1, id FROM users WHERE id = $2
+             ON CONFLICT (blocker_id, blocked_id) DO NOTHING
+             RETURNING *
+         )
+         SELECT * FROM inserted
+         UNION ALL
+         SELECT * FROM user_blocks WHERE blocker_id = $1 AND blocked_id = $2",
+    )
+    .bind(blocker_id)
+    .bind(blocked_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(DbError::Query)
+}
+
+/// Unblock a user.
+///
+/// Returns `false` if the user was not blocked.
+pub async fn unblock(pool: &PgPool, blocker_id: Uuid, blocked_id: Uuid) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM user_blocks WHERE blocker_id = $1 AND blocked_id = $2")
+        .bind(blocker_id)
+        .bind(blocked_id)
+        .execute(pool)
+        .await
+        .map_err(DbError::Query)?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Return the users blocked by a user, most recently blocked first.
+pub async fn list_blocked(pool: &PgPool, blocker_id: Uuid) -> Result<Vec<Block>> {
+    sqlx::query_as(
+        "SELECT * FROM user_blocks
+         WHERE blocker_id = $1
+         ORDER BY created_at DESC, blocked_id",
+    )
+    .bind(blocker_id)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::Query)
+}
+
+/// Return the pairs among the given users who must not be matched with each other.
+///
+/// Users must not be matched if either blocked the other, or, with
+/// `met_since`, if they took part in a conversation together created since then.
+/// Each pair is returned once, in no particular order.
+pub async fn excluded_pairs(
+    executor: impl PgExecutor<'_>,
+    user_ids: &[Uuid],
+    met_since: Option<DateTime<Utc>>,
+) -> Result<Vec<(Uuid, Uuid)>> {
+    sqlx::query_as(
+        "SELECT blocker_id, blocked_id FROM user_blocks
+         WHERE blocker_id = ANY($1) AND blocked_id = ANY($1)
+         UNION
+         SELECT participant_a, participant_b FROM conversations
+         WHERE $2::timestamptz IS NOT NULL AND created_at >= $2
+           AND participant_a = ANY($1) AND participant_b = ANY($1)",
+    )
+    .bind(user_ids)
+    .bind(met_since)
+    .fetch_all(executor)
+    .await
+    .map_err(DbError::Query)
+}
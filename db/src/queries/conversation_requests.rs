@@ -0,0 +1,187 @@
+//! Queries for conversation requests.
+//!
+//! These operate on the `conversation_requests` table. Requests start out
+//! `pending` and are either `matched` by the matchmaking worker, `expired` by
+//! the expiry job or `cancelled` by the user.
+
+use chrono::{DateTime, Duration, Utc};
+use shared::types::conversation::{ConversationRequest, ConversationRequestStatus};
+use shared::types::moderation::SanctionKind;
+use sqlx::{PgExecutor, PgPool};
+use uuid::Uuid;
+
+use crate::error::{DbError, Result};
+
+/// Insert a new conversation request.
+pub async fn insert_request(
+    executor: impl PgExecutor<'_>,
+    request: &ConversationRequest,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO conversation_requests
+             (id, user_id, prompt, position, statement, request_time, status, match_id, conversation_id)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+    )
+    .bind(request.id)
+    .bind(request.user_id)
+    .bind(&request.prompt)
+    .bind(request.stance.position)
+    .bind(&request.stance.statement)
+    .bind(request.request_time)
+    .bind(request.status)
+    .bind(request.match_id)
+    .bind(request.conversation_id)
+    .execute(executor)
+    .await
+    .map_err(DbError::Query)?;
+
+    Ok(())
+}
+
+/// Return a conversation request by id.
+pub async fn get_request(pool: &PgPool, request_id: Uuid) -> Result<Option<ConversationRequest>> {
+    sqlx::query_as("SELECT * FROM conversation_requests WHERE id = $1")
+        .bind(request_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(DbError::Query)
+}
+
+/// Return a user's requests, newest first, optionally only those with the given status.
+pub async fn list_for_user(
+    pool: &PgPool,
+    user_id: Uuid,
+    status: Option<ConversationRequestStatus>,
+) -> Result<Vec<ConversationRequest>> {
+    sqlx::query_as(
+        "SELECT * FROM conversation_requests
+         WHERE user_id = $1 AND ($2::conversation_request_status IS NULL OR status = $2)
+         ORDER BY request_time DESC, id",
+    )
+    .bind(user_id)
+    .bind(status)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::Query)
+}
+
+/// Count the requests a user has pending.
+pub async fn count_pending(executor: impl PgExecutor<'_>, user_id: Uuid) -> Result<i64> {
+    sqlx::query_scalar(
+        "SELECT count(*) FROM conversation_requests WHERE user_id = $1 AND status = $2",
+    )
+    .bind(user_id)
+    .bind(ConversationRequestStatus::Pending)
+    .fetch_one(executor)
+    .await
+    .map_err(DbError::Query)
+}
+
+/// Cancel a user's pending request, returning it as cancelled.
+///
+/// Returns `None` if the user has no such request or it is no longer pending.
+pub async fn cancel(
+    executor: impl PgExecutor<'_>,
+    request_id: Uuid,
+    user_id: Uuid,
+) -> Result<Option<ConversationRequest>> {
+    sqlx::query_as(
+        "UPDATE conversation_requests
+         SET status = $3
+         WHERE id = $1 AND user_id = $2 AND status = $4
+         RETURNING *",
+    )
+    .bind(request_id)
+    .bind(user_id)
+    .bind(ConversationRequestStatus::Cancelled)
+    .bind(ConversationRequestStatus::Pending)
+    .fetch_optional(executor)
+    .await
+    .map_err(DbError::Query)
+}
+
+/// Lock up to `limit` pending requests, oldest first, for the rest of the transaction.
+///
+/// Requests locked by other transactions are skipped rather than waited for, so
+/// that several matchmaking workers can run at once without blocking each other.
+/// Requests of users banned, or suspended at `at`, are skipped too, so that they
+/// are not matched with partners they could never talk to.
+pub async fn lock_pending(
+    executor: impl PgExecutor<'_>,
+    limit: i64,
+    at: DateTime<Utc>,
+) -> Result<Vec<ConversationRequest>> {
+    sqlx::query_as(
+        "SELECT * FROM conversation_requests r
+         WHERE r.status = $1
+           AND NOT EXISTS (
+               SELECT 1 FROM user_sanctions s
+               WHERE s.user_id = r.user_id
+                 AND (s.kind = $4 OR (s.kind = $5 AND (s.expires_at IS NULL OR s.expires_at > $3)))
+           )
+         ORDER BY r.request_time, r.id
+         LIMIT $2
+         FOR UPDATE OF r SKIP LOCKED",
+    )
+    .bind(ConversationRequestStatus::Pending)
+    .bind(limit)
+    .bind(at)
+    .bind(SanctionKind::Ban)
+    .bind(SanctionKind::Suspension)
+    .fetch_all(executor)
+    .await
+    .map_err(DbError::Query)
+}
+
+/// Mark a pending request as matched with another request, in the given conversation.
+///
+/// Returns `false` if the request was no longer pending.
+pub async fn mark_matched(
+    executor: impl PgExecutor<'_>,
+    request_id: Uuid,
+    match_id: Uuid,
+    conversation_id: Uuid,
+) -> Result<bool> {
+    let result = sqlx::query(
+        "UPDATE conversation_requests
+         SET status = $4, match_id = $2, conversation_id = $3
+         WHERE id = $1 AND status = $5",
+    )
+    .bind(request_id)
+    .bind(match_id)
+    .bind(conversation_id)
+    .bind(ConversationRequestStatus::Matched)
+    .bind(ConversationRequestStatus::Pending)
+    .execute(executor)
+    .await
+    .map_err(DbError::Query)?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Mark pending requests older than their prompt's TTL as expired, returning them.
+///
+/// Requests on prompts without a TTL in `prompt_ttls` expire after `default_ttl`.
+/// Requests already matched or expired are left alone, so running this again
+/// has no effect until more requests become stale.
+pub async fn expire_stale(
+    executor: impl PgExecutor<'_>,
+    default_ttl: Duration,
+) -> Result<Vec<ConversationRequest>> {
+    sqlx::query_as(
+        "UPDATE conversation_requests r
+         SET status = $2
+         WHERE r.status = $3
+           AND r.request_time < now() - make_interval(secs => COALESCE(
+               (SELECT t.ttl_seconds FROM prompt_ttls t WHERE t.prompt = normalize_prompt(r.prompt)),
+               $1
+           ))
+         RETURNING r.*",
+    )
+    .bind(default_ttl.num_seconds())
+    .bind(ConversationRequestStatus::Expired)
+    .bind(ConversationRequestStatus::Pending)
+    .fetch_all(executor)
+    .await
+    .map_err(DbError::Query)
+}
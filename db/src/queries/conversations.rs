@@ -0,0 +1,135 @@
+//! Queries for conversations between matched users.
+//!
+//! These operate on the `conversations` table. Conversations are active until
+//! `ended_at` and `end_reason` are set, which happens only once.
+
+use chrono::{DateTime, Duration, Utc};
+use shared::types::conversation::{Conversation, ConversationEndReason};
+use sqlx::{FromRow, PgExecutor, PgPool};
+use uuid::Uuid;
+
+use crate::error::{DbError, Result};
+
+/// A conversation as seen by one of its participants, with their partner's display name.
+#[derive(Debug, Clone, PartialEq, FromRow)]
+pub struct ParticipantConversation {
+    #[sqlx(flatten)]
+    pub conversation: Conversation,
+    pub partner_id: Uuid,
+    pub partner_display_name: Option<String>,
+}
+
+/// Record a new conversation.
+pub async fn insert_conversation(
+    executor: impl PgExecutor<'_>,
+    conversation: &Conversation,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO conversations
+             (id, topic, created_at, ended_at, end_reason, participant_a, participant_b)
+         VALUES ($1, $2, $3, $4, $5, $6, $7)",
+    )
+    .bind(conversation.id)
+    .bind(&conversation.topic)
+    .bind(conversation.created_at)
+    .bind(conversation.ended_at)
+    .bind(conversation.end_reason)
+    .bind(conversation.participant_a)
+    .bind(conversation.participant_b)
+    .execute(executor)
+    .await
+    .map_err(DbError::Query)?;
+
+    Ok(())
+}
+
+/// Return a conversation by id.
+pub async fn get_conversation(
+    pool: &PgPool,
+    conversation_id: Uuid,
+) -> Result<Option<Conversation>> {
+    sqlx::query_as("SELECT * FROM conversations WHERE id = $1")
+        .bind(conversation_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(DbError::Query)
+}
+
+/// Return up to `limit` conversations a user took part in, newest first.
+///
+/// With `before`, only conversations created before the given `(created_at, id)`
+/// position are returned, so that the last conversation of a page can be passed
+/// to get the next page.
+pub async fn list_for_participant(
+    pool: &PgPool,
+    user_id: Uuid,
+    before: Option<(DateTime<Utc>, Uuid)>,
+    limit: i64,
+) -> Result<Vec<ParticipantConversation>> {
+    let (before_at, before_id) = before.unzip();
+    sqlx::query_as(
+        "SELECT c.*, u.id AS partner_id, u.display_name AS partner_display_name
+         FROM conversations c
+         JOIN users u
+           ON u.id = CASE WHEN c.participant_a = $1 THEN c.participant_b ELSE c.participant_a END
+         WHERE (c.participant_a = $1 OR c.participant_b = $1)
+           AND ($2::timestamptz IS NULL OR (c.created_at, c.id) < ($2, $3))
+         ORDER BY c.created_at DESC, c.id DESC
+         LIMIT $4",
+    )
+    .bind(user_id)
+    .bind(before_at)
+    .bind(before_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::Query)
+}
+
+/// End an active conversation, returning it as ended.
+///
+/// Returns `None` if the conversation does not exist or has already ended.
+pub async fn end_conversation(
+    executor: impl PgExecutor<'_>,
+    conversation_id: Uuid,
+    reason: ConversationEndReason,
+    at: DateTime<Utc>,
+) -> Result<Option<Conversation>> {
+    sqlx::query_as(
+        "UPDATE conversations
+         SET ended_at = $3, end_reason = $2
+         WHERE id = $1 AND ended_at IS NULL
+         RETURNING *",
+    )
+    .bind(conversation_id)
+    .bind(reason)
+    .bind(at)
+    .fetch_optional(executor)
+    .await
+    .map_err(DbError::Query)
+}
+
+/// End the active conversations without a message for longer than `timeout`,
+/// returning them as ended.
+///
+/// Conversations without any message count as active since they were created.
+pub async fn end_inactive(
+    executor: impl PgExecutor<'_>,
+    timeout: Duration,
+) -> Result<Vec<Conversation>> {
+    sqlx::query_as(
+        "UPDATE conversations c
+         SET ended_at = now(), end_reason = $2
+         WHERE c.ended_at IS NULL
+           AND COALESCE(
+                   (SELECT max(m.sent_at) FROM messages m WHERE m.conversation_id = c.id),
+                   c.created_at
+               ) < now() - make_interval(secs => $1)
+         RETURNING c.*",
+    )
+    .bind(timeout.num_seconds())
+    .bind(ConversationEndReason::Inactive)
+    .fetch_all(executor)
+    .await
+    .map_err(DbError::Query)
+}
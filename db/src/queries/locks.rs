@@ -0,0 +1,17 @@
+//! Postgres advisory locks, coordinating jobs run by several instances at once.
+
+use sqlx::PgExecutor;
+
+use crate::error::{DbError, Result};
+
+/// Try to take the transaction-level advisory lock with the given key.
+///
+/// Returns `false` without waiting if another transaction holds the lock. The
+/// lock is released when the transaction ends, so this must be called within one.
+pub async fn try_advisory_xact_lock(executor: impl PgExecutor<'_>, key: i64) -> Result<bool> {
+    sqlx::query_scalar("SELECT pg_try_advisory_xact_lock($1)")
+        .bind(key)
+        .fetch_one(executor)
+        .await
+        .map_err(DbError::Query)
+}
@@ -0,0 +1,355 @@
+//! Queries for messages sent in conversations, and receipts of their reading.
+//!
+//! These operate on the `messages`, `message_revisions` and `message_reads`
+//! tables. Messages are ordered by `sent_at`, with their id breaking ties.
+
+use chrono::{DateTime, Utc};
+use shared::types::conversation::{Message, MessageRevision};
+use sqlx::{FromRow, PgExecutor, PgPool};
+use uuid::Uuid;
+
+use crate::error::{DbError, Result};
+
+/// The last message a participant has read in a conversation.
+#[derive(Debug, Clone, PartialEq, Eq, FromRow)]
+pub struct ReadReceipt {
+    pub conversation_id: Uuid,
+    pub user_id: Uuid,
+    pub message_id: Uuid,
+    pub read_at: DateTime<Utc>,
+}
+
+/// Record a new message, unless its conversation has ended.
+///
+/// Returns `false` if the conversation has ended, or does not exist.
+pub async fn insert_message(executor: impl PgExecutor<'_>, message: &Message) -> Result<bool> {
+    let result = sqlx::query(
+        "INSERT INTO messages (id, conversation_id, sender_id, content, sent_at)
+         SELECT $1, $2, $3, $4, $5
+         WHERE EXISTS (
+             SELECT 1 FROM conversations WHERE id = $2 AND ended_at IS NULL FOR SHARE
+         )",
+    )
+    .bind(message.id)
+    .bind(message.conversation_id)
+    .bind(message.sender_id)
+    .bind(&message.content)
+    .bind(message.sent_at)
+    .execute(executor)
+    .await
+    .map_err(DbError::Query)?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Return the messages of a conversation sent after the given message, oldest first.
+///
+/// Returns every message of the conversation if `after` is `None` or is not a
+/// message of the conversation.
+pub async fn messages_after(
+    pool: &PgPool,
+    conversation_id: Uuid,
+    after: Option<Uuid>,
+) -> Result<Vec<Message>> {
+    sqlx::query_as(
+        "SELECT m.* FROM messages m
+         WHERE m.conversation_id = $1
+           AND NOT EXISTS (
+               SELECT 1 FROM messages seen
+               WHERE seen.id = $2 AND seen.conversation_id = $1
+                 AND (m.sent_at, m.id) <= (seen.sent_at, seen.id)
+           )
+         ORDER BY m.sent_at, m.id",
+    )
+    .bind(conversation_id)
+    .bind(after)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::Query)
+}
+
+/// Return up to `limit` messages of a conversation, oldest first.
+///
+/// With `after`, only messages sent after the given `(sent_at, id)` position
+/// are returned, so that the last message of a page can be passed to get the
+/// next page.
+pub async fn messages_page(
+    pool: &PgPool,
+    conversation_id: Uuid,
+    after: Option<(DateTime<Utc>, Uuid)>,
+    limit: i64,
+) -> Result<Vec<Message>> {
+    let (after_at, after_id) = after.unzip();
+    sqlx::query_as(
+        "SELECT * FROM messages
+         WHERE conversation_id = $1
+           AND ($2::timestamptz IS NULL OR (sent_at, id) > ($2, $3))
+         ORDER BY sent_at, id
+         LIMIT $4",
+    )
+    .bind(conversation_id)
+    .bind(after_at)
+    .bind(after_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::Query)
+}
+
+/// Record that a participant has read a conversation up to the given message.
+///
+/// Receipts never move backwards: reading a message older than the one last
+/// read leaves the receipt as it is. Returns the receipt as stored, or `None`
+/// if the message is not part of the conversation.
+pub async fn mark_read(
+    pool: &PgPool,
+    conversation_id: Uuid,
+    user_id: Uuid,
+    message_id: Uuid,
+) -> Result<Option<ReadReceipt>> {
+    sqlx::query_as(
+        "WITH read AS (
+             SELECT id, sent_at FROM messages WHERE id = $3 AND conversation_id = $1
+         ),
+         upserted AS (
+             INSERT INTO message_reads (conversation_id, user_id, message_id)
+             SELECT $1, $2, id FROM read
+             ON CONFLICT (conversation_id, user_id) DO UPDATE
+             SET message_id = EXCLUDED.message_id, read_at = now()
+             WHERE (SELECT (m.sent_at, m.id) FROM messages m WHERE m.id = message_reads.message_id)
+                 < (SELECT (sent_at, id) FROM read)
+             RETURNING *
+         )
+         SELECT * FROM upserted
+         UNION ALL
+         SELECT r.* FROM message_reads r
+         WHERE r.conversation_id = $1 AND r.user_id = $2
+           AND EXISTS (SELECT 1 FROM read)
+           AND NOT EXISTS (SELECT 1 FROM upserted)",
+    )
+    .bind(conversation_id)
+    .bind(user_id)
+    .bind(message_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(DbError::Query)
+}
+
+/// Return the read receipts of a conversation's participants.
+pub async fn read_receipts(pool: &PgPool, conversation_id: Uuid) -> Result<Vec<ReadReceipt>> {
+    sqlx::query_as("SELECT * FROM message_reads WHERE conversation_id = $1")
+        .bind(conversation_id)
+        .fetch_all(pool)
+        .await
+        .map_err(DbError::Query)
+}
+
+/// Return the given messages of a conversation, oldest first.
+///
+/// Ids of messages that are not part of the conversation are ignored.
+pub async fn get_messages(
+    pool: &PgPool,
+    conversation_id: Uuid,
+    ids: &[Uuid],
+) -> Result<Vec<Message>> {
+    sqlx::query_as(
+        "SELECT * FROM messages
+         WHERE conversation_id = $1 AND id = ANY($2)
+         ORDER BY sent_at, id",
+    )
+    .bind(conversation_id)
+    .bind(ids)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::Query)
+}
+
+/// Return the last `limit` messages of a conversation, oldest first.
+pub async fn latest_messages(
+    pool: &PgPool,
+    conversation_id: Uuid,
+    limit: i64,
+) -> Result<Vec<Message>> {
+    sqlx::query_as(
+        "SELECT * FROM (
+             SELECT * FROM messages
+             WHERE conversation_id = $1
+             ORDER BY sent_at DESC, id DESC
+             LIMIT $2
+         ) latest
+         ORDER BY sent_at, id",
+    )
+    .bind(conversation_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::Query)
+}
+
+/// Return the last `limit` texts a participant wrote in a conversation, by
+/// sending or editing messages, newest first.
+///
+/// Each is returned as the message it was written to, with the `content` it was
+/// given and, as `sent_at`, when. Text since replaced or deleted is included, so
+/// that editing and deleting messages does not make room for more.
+pub async fn latest_writes(
+    pool: &PgPool,
+    conversation_id: Uuid,
+    sender_id: Uuid,
+    limit: i64,
+) -> Result<Vec<Message>> {
+    sqlx::query_as(
+        "SELECT m.id, m.conversation_id, m.sender_id, w.content, w.written_at AS sent_at,
+                m.edited_at, m.deleted_at
+         FROM (
+             SELECT id AS message_id, content, COALESCE(edited_at, sent_at) AS written_at
+             FROM messages
+             WHERE conversation_id = $1 AND sender_id = $2 AND deleted_at IS NULL
+             UNION ALL
+             SELECT r.message_id, r.content, r.written_at
+             FROM message_revisions r
+             JOIN messages m ON m.id = r.message_id
+             WHERE m.conversation_id = $1 AND m.sender_id = $2
+         ) w
+         JOIN messages m ON m.id = w.message_id
+         ORDER BY w.written_at DESC, m.id DESC
+         LIMIT $3",
+    )
+    .bind(conversation_id)
+    .bind(sender_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::Query)
+}
+
+/// Replace the content of a message sent by `sender_id` since `sent_since`,
+/// keeping the content it replaces as a revision. Returns the edited message.
+///
+/// Returns `None` if there is no such message in the conversation, it has
+/// been deleted, or the conversation has ended.
+pub async fn edit_message(
+    executor: impl PgExecutor<'_>,
+    conversation_id: Uuid,
+    message_id: Uuid,
+    sender_id: Uuid,
+    content: &str,
+    at: DateTime<Utc>,
+    sent_since: DateTime<Utc>,
+) -> Result<Option<Message>> {
+    revise_message(
+        executor,
+        conversation_id,
+        message_id,
+        sender_id,
+        Some(content),
+        at,
+        sent_since,
+    )
+    .await
+}
+
+/// Delete a message sent by `sender_id` since `sent_since`, emptying it but
+/// keeping its content as a revision. Returns the deleted message.
+///
+/// Returns `None` if there is no such message in the conversation, it has
+/// already been deleted, or the conversation has ended.
+pub async fn delete_message(
+    executor: impl PgExecutor<'_>,
+    conversation_id: Uuid,
+    message_id: Uuid,
+    sender_id: Uuid,
+    at: DateTime<Utc>,
+    sent_since: DateTime<Utc>,
+) -> Result<Option<Message>> {
+    revise_message(
+        executor,
+        conversation_id,
+        message_id,
+        sender_id,
+        None,
+        at,
+        sent_since,
+    )
+    .await
+}
+
+/// Edit a message to the given content, or delete it if `content` is `None`.
+async fn revise_message(
+    executor: impl PgExecutor<'_>,
+    conversation_id: Uuid,
+    message_id: Uuid,
+    sender_id: Uuid,
+    content: Option<&str>,
+    at: DateTime<Utc>,
+    sent_since: DateTime<Utc>,
+) -> Result<Option<Message>> {
+    sqlx::query_as(
+        "WITH previous AS (
+             SELECT id, content, COALESCE(edited_at, sent_at) AS written_at
+             FROM messages
+             WHERE id = $2 AND conversation_id = $1 AND sender_id = $3
+               AND deleted_at IS NULL AND sent_at >= $6
+               AND EXISTS (
+                   SELECT 1 FROM conversations WHERE id = $1 AND ended_at IS NULL FOR SHARE
+               )
+             FOR UPDATE
+         ), revision AS (
+             INSERT INTO message_revisions (id, message_id, content, written_at, replaced_at)
+             SELECT $7, id, content, written_at, $5 FROM previous
+         )
+         UPDATE messages m
+         SET content = COALESCE($4::text, ''),
+             edited_at = CASE WHEN $4::text IS NULL THEN m.edited_at ELSE $5 END,
+             deleted_at = CASE WHEN $4::text IS NULL THEN $5 END
+         FROM previous
+         WHERE m.id = previous.id
+         RETURNING m.*",
+    )
+    .bind(conversation_id)
+    .bind(message_id)
+    .bind(sender_id)
+    .bind(content)
+    .bind(at)
+    .bind(sent_since)
+    .bind(Uuid::new_v4())
+    .fetch_optional(executor)
+    .await
+    .map_err(DbError::Query)
+}
+
+/// Return the messages of a conversation edited or deleted since the given
+/// message was sent, oldest first.
+///
+/// Nothing is returned if the given message is not part of the conversation.
+pub async fn revised_after(
+    pool: &PgPool,
+    conversation_id: Uuid,
+    after: Uuid,
+) -> Result<Vec<Message>> {
+    sqlx::query_as(
+        "SELECT m.* FROM messages m
+         JOIN messages seen ON seen.id = $2 AND seen.conversation_id = $1
+         WHERE m.conversation_id = $1
+           AND COALESCE(m.deleted_at, m.edited_at) > seen.sent_at
+         ORDER BY m.sent_at, m.id",
+    )
+    .bind(conversation_id)
+    .bind(after)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::Query)
+}
+
+/// Return the revisions of the given messages, oldest first.
+pub async fn revisions(pool: &PgPool, message_ids: &[Uuid]) -> Result<Vec<MessageRevision>> {
+    sqlx::query_as(
+        "SELECT * FROM message_revisions
+         WHERE message_id = ANY($1)
+         ORDER BY replaced_at, id",
+    )
+    .bind(message_ids)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::Query)
+}
@@ -8,5 +8,18 @@
 //! - Return a `Result<T, DbError>` for error handling
 //! - Use `sqlx::query_as!` for type-safe queries where possible
 
+pub mod auth;
+pub mod blocks;
+pub mod conversation_requests;
+pub mod conversations;
+pub mod locks;
+pub mod messages;
+pub mod notify;
+pub mod prompt_ttls;
+pub mod rate_limits;
+pub mod reports;
+pub mod roles;
+pub mod sanctions;
+pub mod sources;
 pub mod users;
 // pub use users::*;
@@ -0,0 +1,53 @@
+//! Postgres notifications, delivered to every connection listening on a channel.
+//!
+//! See [`PgPubSub`](crate::pubsub::PgPubSub) for publishing and subscribing
+//! through them.
+
+use sqlx::{PgExecutor, PgPool};
+use uuid::Uuid;
+
+use crate::error::{DbError, Result};
+
+/// Send a notification with the given payload on a channel.
+///
+/// When sent within a transaction, the notification is only delivered once the
+/// transaction commits, and not at all if it rolls back.
+pub async fn notify(executor: impl PgExecutor<'_>, channel: &str, payload: &str) -> Result<()> {
+    sqlx::query("SELECT pg_notify($1, $2)")
+        .bind(channel)
+        .bind(payload)
+        .execute(executor)
+        .await
+        .map_err(DbError::Query)?;
+
+    Ok(())
+}
+
+/// Store a payload too large to be sent in a notification, returning its id.
+///
+/// Payloads stored more than a minute ago are deleted at the same time.
+pub async fn store_payload(pool: &PgPool, payload: &str) -> Result<Uuid> {
+    let id = Uuid::new_v4();
+    sqlx::query(
+        "WITH expired AS (
+             DELETE FROM pubsub_payloads WHERE created_at < now() - interval '1 minute'
+         )
+         INSERT INTO pubsub_payloads (id, payload) VALUES ($1, $2)",
+    )
+    .bind(id)
+    .bind(payload)
+    .execute(pool)
+    .await
+    .map_err(DbError::Query)?;
+
+    Ok(id)
+}
+
+/// Return a stored payload, unless it has been deleted.
+pub async fn get_payload(pool: &PgPool, id: Uuid) -> Result<Option<String>> {
+    sqlx::query_scalar("SELECT payload FROM pubsub_payloads WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+        .map_err(DbError::Query)
+}
@@ -0,0 +1,67 @@
+//! Queries for the time-to-live of conversation requests on a prompt.
+//!
+//! These operate on the `prompt_ttls` table. Prompts are normalized by the
+//! `normalize_prompt` SQL function, ignoring case and whitespace differences.
+//! Prompts without a row use the expiry job's default TTL.
+
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{FromRow, PgPool};
+
+use crate::error::{DbError, Result};
+
+/// The TTL set for requests on a prompt, stored under the normalized prompt.
+#[derive(Debug, Clone, PartialEq, Eq, FromRow)]
+pub struct PromptTtl {
+    pub prompt: String,
+    pub ttl_seconds: i64,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// List the TTLs set for prompts, ordered by prompt.
+pub async fn list_prompt_ttls(pool: &PgPool) -> Result<Vec<PromptTtl>> {
+    sqlx::query_as("SELECT prompt, ttl_seconds, updated_at FROM prompt_ttls ORDER BY prompt")
+        .fetch_all(pool)
+        .await
+        .map_err(DbError::Query)
+}
+
+/// Return the TTL set for a prompt, if any.
+pub async fn get_prompt_ttl(pool: &PgPool, prompt: &str) -> Result<Option<Duration>> {
+    let seconds: Option<i64> = sqlx::query_scalar(
+        "SELECT ttl_seconds FROM prompt_ttls WHERE prompt = normalize_prompt($1)",
+    )
+    .bind(prompt)
+    .fetch_optional(pool)
+    .await
+    .map_err(DbError::Query)?;
+
+    Ok(seconds.map(Duration::seconds))
+}
+
+/// Set the TTL of requests on a prompt, replacing any TTL set before, and
+/// return it.
+pub async fn set_prompt_ttl(pool: &PgPool, prompt: &str, ttl: Duration) -> Result<PromptTtl> {
+    sqlx::query_as(
+        "INSERT INTO prompt_ttls (prompt, ttl_seconds)
+         VALUES (normalize_prompt($1), $2)
+         ON CONFLICT (prompt)
+         DO UPDATE SET ttl_seconds = EXCLUDED.ttl_seconds, updated_at = now()
+         RETURNING prompt, ttl_seconds, updated_at",
+    )
+    .bind(prompt)
+    .bind(ttl.num_seconds())
+    .fetch_one(pool)
+    .await
+    .map_err(DbError::Query)
+}
+
+/// Remove the TTL set for a prompt, returning whether one was set.
+pub async fn delete_prompt_ttl(pool: &PgPool, prompt: &str) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM prompt_ttls WHERE prompt = normalize_prompt($1)")
+        .bind(prompt)
+        .execute(pool)
+        .await
+        .map_err(DbError::Query)?;
+
+    Ok(result.rows_affected() > 0)
+}
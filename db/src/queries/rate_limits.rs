@@ -0,0 +1,132 @@
+//! Queries backing the Postgres store of the OTP rate limiter.
+//!
+//! These operate on the `auth_rate_limits` and `auth_lockouts` tables, so that
+//! limits hold across all instances of the gateway.
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::error::{DbError, Result};
+
+/// Count a hit against a key in its current fixed window, starting a new window
+/// if the previous one has elapsed.
+///
+/// Returns the number of hits in the window and when the window started.
+pub async fn hit(pool: &PgPool, key: &str, window_secs: f64) -> Result<(i32, DateTime<Utc>)> {
+    sqlx::query_as(
+        "INSERT INTO auth_rate_limits (key, window_started_at, window_ends_at, hits)
+         VALUES ($1, now(), now() + make_interval(secs => $2), 1)
+         ON CONFLICT (key) DO UPDATE SET
+             hits = CASE
+                 WHEN auth_rate_limits.window_started_at + make_interval(secs => $2) <= now() THEN 1
+                 ELSE auth_rate_limits.hits + 1
+             END,
+             window_started_at = CASE
+                 WHEN auth_rate_limits.window_started_at + make_interval(secs => $2) <= now() THEN now()
+                 ELSE auth_rate_limits.window_started_at
+             END,
+             window_ends_at = CASE
+                 WHEN auth_rate_limits.window_started_at + make_interval(secs => $2) <= now()
+                     THEN now() + make_interval(secs => $2)
+                 ELSE auth_rate_limits.window_started_at + make_interval(secs => $2)
+             END
+         RETURNING hits, window_started_at",
+    )
+    .bind(key)
+    .bind(window_secs)
+    .fetch_one(pool)
+    .await
+    .map_err(DbError::Query)
+}
+
+/// Return when the lockout of a key ends, if it is currently locked out.
+pub async fn get_locked_until(pool: &PgPool, key: &str) -> Result<Option<DateTime<Utc>>> {
+    sqlx::query_scalar(
+        "SELECT locked_until FROM auth_lockouts
+         WHERE key = $1 AND locked_until > now()",
+    )
+    .bind(key)
+    .fetch_optional(pool)
+    .await
+    .map_err(DbError::Query)
+}
+
+/// Record a failed attempt for a key.
+///
+/// The failures and lockouts of a key are forgotten first if it has neither
+/// failed nor been locked out for `decay_secs`.
+///
+/// Returns the number of failures since the last lockout and the number of
+/// lockouts so far.
+pub async fn record_failure(pool: &PgPool, key: &str, decay_secs: f64) -> Result<(i32, i32)> {
+    sqlx::query_as(
+        "INSERT INTO auth_lockouts (key, failures, last_failure_at)
+         VALUES ($1, 1, now())
+         ON CONFLICT (key) DO UPDATE SET
+             failures = CASE
+                 WHEN GREATEST(auth_lockouts.last_failure_at, auth_lockouts.locked_until)
+                     + make_interval(secs => $2) <= now() THEN 1
+                 ELSE auth_lockouts.failures + 1
+             END,
+             lockouts = CASE
+                 WHEN GREATEST(auth_lockouts.last_failure_at, auth_lockouts.locked_until)
+                     + make_interval(secs => $2) <= now() THEN 0
+                 ELSE auth_lockouts.lockouts
+             END,
+             last_failure_at = now()
+         RETURNING failures, lockouts",
+    )
+    .bind(key)
+    .bind(decay_secs)
+    .fetch_one(pool)
+    .await
+    .map_err(DbError::Query)
+}
+
+/// Lock a key out until the given time, resetting its failures and counting the lockout.
+pub async fn lock(pool: &PgPool, key: &str, until: DateTime<Utc>) -> Result<()> {
+    sqlx::query(
+        "UPDATE auth_lockouts
+         SET failures = 0, lockouts = lockouts + 1, locked_until = $2
+         WHERE key = $1",
+    )
+    .bind(key)
+    .bind(until)
+    .execute(pool)
+    .await
+    .map_err(DbError::Query)?;
+
+    Ok(())
+}
+
+/// Forget the failures and lockouts of a key.
+pub async fn clear_failures(pool: &PgPool, key: &str) -> Result<()> {
+    sqlx::query("DELETE FROM auth_lockouts WHERE key = $1")
+        .bind(key)
+        .execute(pool)
+        .await
+        .map_err(DbError::Query)?;
+
+    Ok(())
+}
+
+/// Delete elapsed windows, and the failures and lockouts of keys that have
+/// neither failed nor been locked out for `decay_secs`.
+///
+/// Returns the number of rows deleted.
+pub async fn prune(pool: &PgPool, decay_secs: f64) -> Result<u64> {
+    let windows = sqlx::query("DELETE FROM auth_rate_limits WHERE window_ends_at <= now()")
+        .execute(pool)
+        .await
+        .map_err(DbError::Query)?;
+    let lockouts = sqlx::query(
+        "DELETE FROM auth_lockouts
+         WHERE GREATEST(last_failure_at, locked_until) + make_interval(secs => $1) <= now()",
+    )
+    .bind(decay_secs)
+    .execute(pool)
+    .await
+    .map_err(DbError::Query)?;
+
+    Ok(windows.rows_affected() + lockouts.rows_affected())
+}
@@ -0,0 +1,151 @@
+//! Queries for reports filed against conversation participants.
+//!
+//! These operate on the `reports` table. Reports start out `open`, are
+//! `claimed` by a moderator and finally `resolved` by that moderator.
+//! Reports without a reporter are filed automatically on flagged messages.
+
+use chrono::{DateTime, Utc};
+use shared::types::moderation::{ModerationAction, Report, ReportStatus};
+use sqlx::types::Json;
+use sqlx::{PgExecutor, PgPool};
+use uuid::Uuid;
+
+use crate::error::{DbError, Result};
+
+/// Insert a new report.
+///
+/// Returns `false` if the reporter has already reported the conversation.
+pub async fn insert_report(executor: impl PgExecutor<'_>, report: &Report) -> Result<bool> {
+    let result = sqlx::query(
+        "INSERT INTO reports
+             (id, conversation_id, reporter_id, reported_user_id, category, details, messages, status, created_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+         ON CONFLICT (conversation_id, reporter_id) DO NOTHING",
+    )
+    .bind(report.id)
+    .bind(report.conversation_id)
+    .bind(report.reporter_id)
+    .bind(report.reported_user_id)
+    .bind(report.category)
+    .bind(&report.details)
+    .bind(Json(&report.messages))
+    .bind(report.status)
+    .bind(report.created_at)
+    .execute(executor)
+    .await
+    .map_err(DbError::Query)?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// File a report on messages flagged automatically, returning it as stored.
+///
+/// If the reported user already has a flagged report on the conversation
+/// waiting to be claimed, the messages and details are added to it instead.
+pub async fn insert_flagged(executor: impl PgExecutor<'_>, report: &Report) -> Result<Report> {
+    sqlx::query_as(
+        "INSERT INTO reports
+             (id, conversation_id, reporter_id, reported_user_id, category, details, messages, status, created_at)
+         VALUES ($1, $2, NULL, $3, $4, $5, $6, 'open', $7)
+         ON CONFLICT (conversation_id, reported_user_id) WHERE reporter_id IS NULL AND status = 'open'
+         DO UPDATE SET messages = reports.messages || EXCLUDED.messages,
+                       details = concat_ws(E'\\n', reports.details, EXCLUDED.details)
+         RETURNING *",
+    )
+    .bind(report.id)
+    .bind(report.conversation_id)
+    .bind(report.reported_user_id)
+    .bind(report.category)
+    .bind(&report.details)
+    .bind(Json(&report.messages))
+    .bind(report.created_at)
+    .fetch_one(executor)
+    .await
+    .map_err(DbError::Query)
+}
+
+/// Return the report with the given id, if any.
+pub async fn get_report(pool: &PgPool, id: Uuid) -> Result<Option<Report>> {
+    sqlx::query_as("SELECT * FROM reports WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+        .map_err(DbError::Query)
+}
+
+/// Return up to `limit` reports, oldest first.
+///
+/// Returns reports with the given status, or every unresolved report if `status` is `None`.
+pub async fn list_reports(
+    pool: &PgPool,
+    status: Option<ReportStatus>,
+    limit: i64,
+) -> Result<Vec<Report>> {
+    sqlx::query_as(
+        "SELECT * FROM reports
+         WHERE CASE WHEN $1::report_status IS NULL THEN status <> 'resolved' ELSE status = $1 END
+         ORDER BY created_at, id
+         LIMIT $2",
+    )
+    .bind(status)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::Query)
+}
+
+/// Claim a report for review by a moderator.
+///
+/// Claiming a report the moderator has already claimed leaves it as it is.
+/// Returns the claimed report, or `None` if the report does not exist, is
+/// claimed by another moderator or has been resolved.
+pub async fn claim(
+    executor: impl PgExecutor<'_>,
+    id: Uuid,
+    moderator_id: Uuid,
+    at: DateTime<Utc>,
+) -> Result<Option<Report>> {
+    sqlx::query_as(
+        "UPDATE reports
+         SET status = 'claimed',
+             claimed_by = $2,
+             claimed_at = CASE WHEN status = 'open' THEN $3 ELSE claimed_at END
+         WHERE id = $1
+           AND (status = 'open' OR (status = 'claimed' AND claimed_by = $2))
+         RETURNING *",
+    )
+    .bind(id)
+    .bind(moderator_id)
+    .bind(at)
+    .fetch_optional(executor)
+    .await
+    .map_err(DbError::Query)
+}
+
+/// Resolve a report claimed by the given moderator.
+///
+/// Returns the resolved report, or `None` if the report does not exist or is
+/// not claimed by the moderator.
+pub async fn resolve(
+    executor: impl PgExecutor<'_>,
+    id: Uuid,
+    moderator_id: Uuid,
+    action: ModerationAction,
+    note: Option<&str>,
+    at: DateTime<Utc>,
+) -> Result<Option<Report>> {
+    sqlx::query_as(
+        "UPDATE reports
+         SET status = 'resolved', action = $3, resolution_note = $4, resolved_at = $5
+         WHERE id = $1 AND status = 'claimed' AND claimed_by = $2
+         RETURNING *",
+    )
+    .bind(id)
+    .bind(moderator_id)
+    .bind(action)
+    .bind(note)
+    .bind(at)
+    .fetch_optional(executor)
+    .await
+    .map_err(DbError::Query)
+}
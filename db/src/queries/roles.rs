@@ -0,0 +1,35 @@
+//! Queries for roles granted to users.
+//!
+//! These operate on the `user_roles` table. Users without a row hold the
+//! default `user` role.
+
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::error::{DbError, Result};
+
+/// Return the name of the role granted to a user, if any.
+pub async fn get_role(pool: &PgPool, user_id: Uuid) -> Result<Option<String>> {
+    sqlx::query_scalar("SELECT role FROM user_roles WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(DbError::Query)
+}
+
+/// Grant a role to a user, replacing any role granted before.
+pub async fn set_role(pool: &PgPool, user_id: Uuid, role: &str) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO user_roles (user_id, role)
+         VALUES ($1, $2)
+         ON CONFLICT (user_id)
+         DO UPDATE SET role = EXCLUDED.role, granted_at = now()",
+    )
+    .bind(user_id)
+    .bind(role)
+    .execute(pool)
+    .await
+    .map_err(DbError::Query)?;
+
+    Ok(())
+}
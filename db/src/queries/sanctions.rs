@@ -0,0 +1,57 @@
+//! Queries for sanctions imposed on users.
+//!
+//! These operate on the `user_sanctions` table. Sanctions are never removed;
+//! suspensions lapse once they expire.
+
+use chrono::{DateTime, Utc};
+use shared::types::moderation::{Sanction, SanctionKind};
+use sqlx::{PgExecutor, PgPool};
+use uuid::Uuid;
+
+use crate::error::{DbError, Result};
+
+/// Insert a new sanction.
+pub async fn insert_sanction(executor: impl PgExecutor<'_>, sanction: &Sanction) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO user_sanctions (id, user_id, kind, report_id, reason, issued_by, issued_at, expires_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+    )
+    .bind(sanction.id)
+    .bind(sanction.user_id)
+    .bind(sanction.kind)
+    .bind(sanction.report_id)
+    .bind(&sanction.reason)
+    .bind(sanction.issued_by)
+    .bind(sanction.issued_at)
+    .bind(sanction.expires_at)
+    .execute(executor)
+    .await
+    .map_err(DbError::Query)?;
+
+    Ok(())
+}
+
+/// Return the sanction barring a user from the service at the given time, if any.
+///
+/// Bans take precedence over suspensions, and later-expiring suspensions over
+/// earlier ones; see [`Sanction::bars_access`].
+pub async fn barring_sanction(
+    pool: &PgPool,
+    user_id: Uuid,
+    at: DateTime<Utc>,
+) -> Result<Option<Sanction>> {
+    sqlx::query_as(
+        "SELECT * FROM user_sanctions
+         WHERE user_id = $1
+           AND (kind = $3 OR (kind = $4 AND (expires_at IS NULL OR expires_at > $2)))
+         ORDER BY kind = $3 DESC, expires_at DESC NULLS FIRST
+         LIMIT 1",
+    )
+    .bind(user_id)
+    .bind(at)
+    .bind(SanctionKind::Ban)
+    .bind(SanctionKind::Suspension)
+    .fetch_optional(pool)
+    .await
+    .map_err(DbError::Query)
+}
@@ -0,0 +1,106 @@
+//! Queries for sources cited by users.
+//!
+//! These operate on the `sources` table, and on the `message_sources` table
+//! recording which sources messages cite.
+
+use shared::types::conversation::Message;
+use shared::types::source::{Source, SourceSummary};
+use sqlx::{FromRow, PgExecutor, PgPool};
+use uuid::Uuid;
+
+use crate::error::{DbError, Result};
+
+/// A source cited by a message.
+#[derive(Debug, Clone, PartialEq, FromRow)]
+struct Citation {
+    message_id: Uuid,
+    #[sqlx(flatten)]
+    source: Source,
+}
+
+/// Insert a new source.
+pub async fn insert_source(executor: impl PgExecutor<'_>, source: &Source) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO sources (id, created_at, created_by, credibility, kind, source_info, notes)
+         VALUES ($1, $2, $3, $4, $5, $6, $7)",
+    )
+    .bind(source.id)
+    .bind(source.created_at)
+    .bind(source.created_by)
+    .bind(source.credibility)
+    .bind(source.source_info.kind())
+    .bind(&source.source_info)
+    .bind(&source.notes)
+    .execute(executor)
+    .await
+    .map_err(DbError::Query)?;
+
+    Ok(())
+}
+
+/// Return the sources with the given ids.
+///
+/// Ids of sources that do not exist are ignored.
+pub async fn get_sources(pool: &PgPool, ids: &[Uuid]) -> Result<Vec<Source>> {
+    sqlx::query_as("SELECT * FROM sources WHERE id = ANY($1)")
+        .bind(ids)
+        .fetch_all(pool)
+        .await
+        .map_err(DbError::Query)
+}
+
+/// Record that a message cites the given sources, in that order.
+pub async fn cite(
+    executor: impl PgExecutor<'_>,
+    message_id: Uuid,
+    source_ids: &[Uuid],
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO message_sources (message_id, source_id, position)
+         SELECT $1, cited.id, cited.position
+         FROM unnest($2::uuid[]) WITH ORDINALITY AS cited (id, position)
+         ON CONFLICT DO NOTHING",
+    )
+    .bind(message_id)
+    .bind(source_ids)
+    .execute(executor)
+    .await
+    .map_err(DbError::Query)?;
+
+    Ok(())
+}
+
+/// Fill in the summaries of the sources cited by the given messages.
+///
+/// Deleted messages are left without sources, like they are left without content.
+pub async fn load_citations(pool: &PgPool, messages: &mut [Message]) -> Result<()> {
+    let ids: Vec<Uuid> = messages
+        .iter()
+        .filter(|message| !message.is_deleted())
+        .map(|message| message.id)
+        .collect();
+    if ids.is_empty() {
+        return Ok(());
+    }
+
+    let citations: Vec<Citation> = sqlx::query_as(
+        "SELECT ms.message_id, s.*
+         FROM message_sources ms
+         JOIN sources s ON s.id = ms.source_id
+         WHERE ms.message_id = ANY($1)
+         ORDER BY ms.message_id, ms.position",
+    )
+    .bind(&ids)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::Query)?;
+
+    for message in messages.iter_mut().filter(|message| !message.is_deleted()) {
+        message.sources = citations
+            .iter()
+            .filter(|citation| citation.message_id == message.id)
+            .map(|citation| SourceSummary::from(&citation.source))
+            .collect();
+    }
+    Ok(())
+}
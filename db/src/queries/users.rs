@@ -0,0 +1,115 @@
+//! Queries for the public profiles of users.
+//!
+//! These operate on the `users` table. A profile is created the first time a
+//! user signs in, with the contact information they signed in with, and can
+//! then be filled in by the user.
+
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, PgExecutor, PgPool};
+use uuid::Uuid;
+
+use crate::error::{DbError, Result};
+
+/// The public profile of a user.
+#[derive(Debug, Clone, PartialEq, Eq, FromRow)]
+pub struct User {
+    pub id: Uuid,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub last_login_at: DateTime<Utc>,
+}
+
+/// Changes to a profile; fields left as `None` are kept as they are.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProfileUpdate {
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+}
+
+/// A user's profile after signing in, and whether it was created by this sign-in.
+#[derive(Debug, Clone, PartialEq, Eq, FromRow)]
+pub struct Login {
+    #[sqlx(flatten)]
+    pub user: User,
+    pub is_new_user: bool,
+}
+
+/// Return the profile of a user, if they have signed in before.
+pub async fn get_user(pool: &PgPool, user_id: Uuid) -> Result<Option<User>> {
+    sqlx::query_as("SELECT * FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(DbError::Query)
+}
+
+/// Record a sign-in, creating the user's profile if this is their first.
+///
+/// The contact information the user signed in with is stored on the profile,
+/// without erasing the other kind of contact. Accepts a transaction, so that the
+/// profile can be created together with the session.
+pub async fn upsert_user_on_login(
+    executor: impl PgExecutor<'_>,
+    user_id: Uuid,
+    email: Option<&str>,
+    phone: Option<&str>,
+) -> Result<Login> {
+    // `xmax` is only zero for rows inserted, rather than updated, by the statement
+    sqlx::query_as(
+        "INSERT INTO users (id, email, phone)
+         VALUES ($1, $2, $3)
+         ON CONFLICT (id)
+         DO UPDATE SET email = COALESCE(EXCLUDED.email, users.email),
+                       phone = COALESCE(EXCLUDED.phone, users.phone),
+                       last_login_at = now()
+         RETURNING *, (xmax = 0) AS is_new_user",
+    )
+    .bind(user_id)
+    .bind(email)
+    .bind(phone)
+    .fetch_one(executor)
+    .await
+    .map_err(DbError::Query)
+}
+
+/// Update the profile of a user, returning the updated profile.
+///
+/// Returns `None` if the user has no profile.
+pub async fn update_profile(
+    pool: &PgPool,
+    user_id: Uuid,
+    update: &ProfileUpdate,
+) -> Result<Option<User>> {
+    sqlx::query_as(
+        "UPDATE users
+         SET display_name = COALESCE($2, display_name),
+             bio = COALESCE($3, bio),
+             updated_at = now()
+         WHERE id = $1
+         RETURNING *",
+    )
+    .bind(user_id)
+    .bind(update.display_name.as_deref())
+    .bind(update.bio.as_deref())
+    .fetch_optional(pool)
+    .await
+    .map_err(DbError::Query)
+}
+
+/// Lock a user's profile for the rest of the transaction, returning whether it exists.
+///
+/// Serializes operations that check and change per-user state, such as how many
+/// requests a user has pending.
+pub async fn lock_user(executor: impl PgExecutor<'_>, user_id: Uuid) -> Result<bool> {
+    let locked: Option<i32> = sqlx::query_scalar("SELECT 1 FROM users WHERE id = $1 FOR UPDATE")
+        .bind(user_id)
+        .fetch_optional(executor)
+        .await
+        .map_err(DbError::Query)?;
+
+    Ok(locked.is_some())
+}
@@ -0,0 +1,381 @@
+//! HTTP endpoints for users to create, follow and cancel their conversation requests.
+
+use auth::middleware::{auth_standard, barred_error};
+use auth::models::{AuthenticatedUser, Authenticator};
+use axum::extract::rejection::{JsonRejection, PathRejection, QueryRejection};
+use axum::extract::ws::{
+    self, CloseFrame, WebSocket, WebSocketUpgrade, close_code, rejection::WebSocketUpgradeRejection,
+};
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::Response;
+use axum::routing::get;
+use axum::{Extension, Json, Router, middleware};
+use chrono::Utc;
+use db::error::DbError;
+use db::queries::{conversation_requests, sanctions, users};
+use serde::Deserialize;
+use serde_json::json;
+use shared::error::ApiError;
+use shared::types::conversation::{ConversationRequest, ConversationRequestStatus};
+use shared::types::stance::Stance;
+use sqlx::PgPool;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use uuid::Uuid;
+
+use crate::notify::{Notification, NotificationHub};
+
+/// Requests a user may have pending at once unless configured otherwise.
+const DEFAULT_MAX_PENDING: i64 = 3;
+
+/// Longest prompt accepted, in characters.
+pub(crate) const MAX_PROMPT_LENGTH: usize = 500;
+
+/// Longest stance statement accepted, in characters.
+const MAX_STATEMENT_LENGTH: usize = 1000;
+
+/// Time between checks that connected users have not been suspended or banned,
+/// unless configured otherwise.
+const DEFAULT_SANCTION_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// The conversation requests API, served by [`RequestsApi::router`].
+///
+/// # Notifications
+///
+/// Users connected to `GET /ws` are sent a [`Notification`] as soon as one of
+/// their requests is matched or expires. Notifications are not stored, so clients list
+/// their requests after connecting, and after reconnecting, to learn what
+/// happened while they were away. Connections that fall behind are closed with
+/// the `1013` (try again later) close code, so that they do the same.
+///
+/// Users suspended or banned while connected are disconnected within a minute
+/// with the `1008` (policy violation) close code, unless configured otherwise
+/// with [`RequestsApi::with_sanction_check_interval`].
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use auth::models::SbAuthenticator;
+/// use matchmaking::RequestsApi;
+///
+/// # async fn example() {
+/// let pool = db::create_pool().await.unwrap();
+/// let app = axum::Router::new().nest(
+///     "/conversation-requests",
+///     RequestsApi::new(pool).with_max_pending(5).router(SbAuthenticator::default()),
+/// );
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct RequestsApi {
+    pool: PgPool,
+    max_pending: i64,
+    notifications: NotificationHub,
+    sanction_check_interval: std::time::Duration,
+}
+
+impl RequestsApi {
+    /// Create a new RequestsApi using the provided pool, receiving notifications
+    /// published within this process only.
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            max_pending: DEFAULT_MAX_PENDING,
+            notifications: NotificationHub::default(),
+            sanction_check_interval: DEFAULT_SANCTION_CHECK_INTERVAL,
+        }
+    }
+
+    /// Receive notifications through the given hub, e.g. one backed by
+    /// [`PgPubSub`](db::pubsub::PgPubSub) to receive those published by workers
+    /// running on other instances.
+    pub fn with_notifications(mut self, notifications: NotificationHub) -> Self {
+        self.notifications = notifications;
+        self
+    }
+
+    /// Allow users to have at most the given number of requests pending at once.
+    pub fn with_max_pending(mut self, max_pending: i64) -> Self {
+        self.max_pending = max_pending;
+        self
+    }
+
+    /// Check that connected users have not been suspended or banned at the
+    /// given interval.
+    pub fn with_sanction_check_interval(mut self, interval: std::time::Duration) -> Self {
+        self.sanction_check_interval = interval;
+        self
+    }
+
+    /// Creates a router serving the API to users authenticated by `auth_standard`.
+    ///
+    /// The router includes the following endpoints:
+    ///  - `POST /` - create a pending request on a prompt, with the caller's stance
+    ///  - `GET /` - list the caller's requests, newest first, optionally filtered with `?status=`
+    ///  - `GET /{id}` - get one of the caller's requests
+    ///  - `DELETE /{id}` - cancel one of the caller's pending requests
+    ///  - `GET /ws` - receive notifications about the caller's requests over a WebSocket
+    ///
+    /// Other users' requests are reported as not found.
+    pub fn router<A: Authenticator>(self, authenticator: A) -> Router {
+        Router::new()
+            .route("/", get(list_requests).post(create_request))
+            .route("/ws", get(connect))
+            .route("/{id}", get(get_request).delete(cancel_request))
+            .route_layer(middleware::from_fn_with_state(
+                authenticator,
+                auth_standard::<A>,
+            ))
+            .with_state(self)
+    }
+}
+
+/// Request to be matched with someone to talk about a prompt.
+#[derive(Debug, Deserialize)]
+struct CreateRequest {
+    prompt: String,
+    stance: Stance,
+}
+
+impl CreateRequest {
+    /// Validate the request and build the pending conversation request it asks for.
+    fn into_request(self, user_id: Uuid) -> Result<ConversationRequest, ApiError> {
+        let prompt = self.prompt.trim();
+        if prompt.is_empty() {
+            return Err(ApiError::validation("Prompt must not be empty"));
+        }
+        if prompt.chars().count() > MAX_PROMPT_LENGTH {
+            return Err(ApiError::validation(format!(
+                "Prompt must be at most {MAX_PROMPT_LENGTH} characters"
+            )));
+        }
+
+        let statement = self
+            .stance
+            .statement
+            .as_deref()
+            .map(str::trim)
+            .filter(|statement| !statement.is_empty());
+        if statement.is_some_and(|statement| statement.chars().count() > MAX_STATEMENT_LENGTH) {
+            return Err(ApiError::validation(format!(
+                "Statement must be at most {MAX_STATEMENT_LENGTH} characters"
+            )));
+        }
+
+        Ok(ConversationRequest {
+            id: Uuid::new_v4(),
+            user_id,
+            prompt: prompt.to_string(),
+            stance: Stance {
+                position: self.stance.position,
+                statement: statement.map(str::to_string),
+            },
+            request_time: Utc::now(),
+            status: ConversationRequestStatus::Pending,
+            match_id: None,
+            conversation_id: None,
+        })
+    }
+}
+
+/// Filter for listing requests.
+#[derive(Debug, Deserialize)]
+struct ListQuery {
+    status: Option<ConversationRequestStatus>,
+}
+
+/// Create a pending request for the caller, unless they have too many pending.
+async fn create_request(
+    State(api): State<RequestsApi>,
+    Extension(user): Extension<AuthenticatedUser>,
+    payload: Result<Json<CreateRequest>, JsonRejection>,
+) -> Result<(StatusCode, Json<ConversationRequest>), ApiError> {
+    let Json(payload) = payload?;
+    let request = payload.into_request(user.id)?;
+
+    // Holding the user's row lock keeps concurrent requests from both passing the check.
+    let mut tx = api.pool.begin().await.map_err(DbError::Connection)?;
+    if !users::lock_user(&mut *tx, user.id).await? {
+        return Err(ApiError::not_found("User profile not found"));
+    }
+    let pending = conversation_requests::count_pending(&mut *tx, user.id).await?;
+    if pending >= api.max_pending {
+        return Err(ApiError::conflict(format!(
+            "At most {} requests may be pending at once",
+            api.max_pending
+        ))
+        .with_details(json!({ "max_pending": api.max_pending })));
+    }
+    conversation_requests::insert_request(&mut *tx, &request).await?;
+    tx.commit().await.map_err(DbError::Query)?;
+
+    Ok((StatusCode::CREATED, Json(request)))
+}
+
+/// List the caller's requests.
+async fn list_requests(
+    State(api): State<RequestsApi>,
+    Extension(user): Extension<AuthenticatedUser>,
+    query: Result<Query<ListQuery>, QueryRejection>,
+) -> Result<Json<Vec<ConversationRequest>>, ApiError> {
+    let Query(query) = query?;
+    let requests = conversation_requests::list_for_user(&api.pool, user.id, query.status).await?;
+    Ok(Json(requests))
+}
+
+/// Get one of the caller's requests.
+async fn get_request(
+    State(api): State<RequestsApi>,
+    Extension(user): Extension<AuthenticatedUser>,
+    id: Result<Path<Uuid>, PathRejection>,
+) -> Result<Json<ConversationRequest>, ApiError> {
+    let Path(id) = id?;
+    Ok(Json(owned_request(&api.pool, id, user.id).await?))
+}
+
+/// Cancel one of the caller's pending requests.
+async fn cancel_request(
+    State(api): State<RequestsApi>,
+    Extension(user): Extension<AuthenticatedUser>,
+    id: Result<Path<Uuid>, PathRejection>,
+) -> Result<Json<ConversationRequest>, ApiError> {
+    let Path(id) = id?;
+    if let Some(request) = conversation_requests::cancel(&api.pool, id, user.id).await? {
+        return Ok(Json(request));
+    }
+
+    let request = owned_request(&api.pool, id, user.id).await?;
+    Err(ApiError::conflict("Only pending requests can be cancelled")
+        .with_details(json!({ "status": request.status })))
+}
+
+/// Upgrade the caller's request to a WebSocket receiving their notifications.
+async fn connect(
+    State(api): State<RequestsApi>,
+    Extension(user): Extension<AuthenticatedUser>,
+    upgrade: Result<WebSocketUpgrade, WebSocketUpgradeRejection>,
+) -> Result<Response, ApiError> {
+    let upgrade = upgrade.map_err(|rejection| {
+        ApiError::bad_request(rejection.body_text()).with_status(rejection.status())
+    })?;
+
+    let notifications = api.notifications.subscribe(user.id).await?;
+    Ok(upgrade.on_upgrade(move |socket| relay(socket, api, user.id, notifications)))
+}
+
+/// Send notifications to the user until either side closes the connection, or
+/// the user is suspended or banned.
+async fn relay(
+    mut socket: WebSocket,
+    api: RequestsApi,
+    user_id: Uuid,
+    mut notifications: broadcast::Receiver<Notification>,
+) {
+    let interval = api.sanction_check_interval;
+    let mut sanction_checks =
+        tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+    loop {
+        tokio::select! {
+            _ = sanction_checks.tick() => {
+                if let Some(error) = barred(&api.pool, user_id).await {
+                    let frame = CloseFrame {
+                        code: close_code::POLICY,
+                        reason: error.message().into(),
+                    };
+                    let _ = socket.send(ws::Message::Close(Some(frame))).await;
+                    break;
+                }
+            }
+            notification = notifications.recv() => match notification {
+                Ok(notification) => {
+                    let Ok(text) = serde_json::to_string(&notification) else {
+                        continue;
+                    };
+                    if socket.send(ws::Message::Text(text.into())).await.is_err() {
+                        break;
+                    }
+                }
+                // The hub drops connections that fall behind by closing the channel.
+                Err(RecvError::Lagged(_) | RecvError::Closed) => {
+                    let frame = CloseFrame {
+                        code: close_code::AGAIN,
+                        reason: "Connection fell behind; reconnect and list your requests".into(),
+                    };
+                    let _ = socket.send(ws::Message::Close(Some(frame))).await;
+                    break;
+                }
+            },
+            frame = socket.recv() => match frame {
+                Some(Ok(ws::Message::Close(_))) | Some(Err(_)) | None => break,
+                // Clients have nothing to say on this connection.
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+/// The error disconnecting a user, if they have been suspended or banned since
+/// connecting.
+///
+/// Failed lookups keep the user connected until the next check.
+async fn barred(pool: &PgPool, user_id: Uuid) -> Option<ApiError> {
+    match sanctions::barring_sanction(pool, user_id, Utc::now()).await {
+        Ok(sanction) => sanction.as_ref().map(barred_error),
+        Err(e) => {
+            eprintln!("Failed to check sanctions of user {user_id}: {e}");
+            None
+        }
+    }
+}
+
+/// Look up a request, treating other users' requests as not found.
+async fn owned_request(
+    pool: &PgPool,
+    id: Uuid,
+    user_id: Uuid,
+) -> Result<ConversationRequest, ApiError> {
+    conversation_requests::get_request(pool, id)
+        .await?
+        .filter(|request| request.user_id == user_id)
+        .ok_or_else(|| ApiError::not_found("Conversation request not found"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::error::ErrorCode;
+    use shared::types::stance::Position;
+
+    fn create(prompt: &str, statement: Option<&str>) -> CreateRequest {
+        CreateRequest {
+            prompt: prompt.to_string(),
+            stance: Stance {
+                position: Position::Agree,
+                statement: statement.map(str::to_string),
+            },
+        }
+    }
+
+    #[test]
+    fn test_create_request_is_trimmed() {
+        let user_id = Uuid::new_v4();
+        let request = create("  Should cities ban cars? ", Some("  "))
+            .into_request(user_id)
+            .unwrap();
+
+        assert_eq!(request.user_id, user_id);
+        assert_eq!(request.prompt, "Should cities ban cars?");
+        assert_eq!(request.stance, Stance::new(Position::Agree));
+        assert_eq!(request.status, ConversationRequestStatus::Pending);
+    }
+
+    #[test]
+    fn test_create_request_validation() {
+        let too_long = "a".repeat(MAX_STATEMENT_LENGTH + 1);
+        for invalid in [create(" ", None), create("prompt", Some(&too_long))] {
+            let error = invalid.into_request(Uuid::new_v4()).unwrap_err();
+            assert_eq!(error.code(), ErrorCode::ValidationFailed);
+        }
+    }
+}
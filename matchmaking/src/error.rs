@@ -0,0 +1,14 @@
+//! Error types for the matchmaking crate.
+
+use db::error::DbError;
+use thiserror::Error;
+
+/// Errors returned while matching requests or notifying users of matches.
+#[derive(Error, Debug)]
+pub enum MatchmakingError {
+    #[error(transparent)]
+    Database(#[from] DbError),
+
+    #[error("Failed to encode notification: {0}")]
+    Encoding(#[from] serde_json::Error),
+}
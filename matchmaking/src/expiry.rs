@@ -0,0 +1,115 @@
+//! The background job expiring stale requests.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use db::error::DbError;
+use db::queries::{conversation_requests, locks};
+use shared::types::conversation::ConversationRequest;
+use sqlx::PgPool;
+use tokio::task::JoinHandle;
+
+use crate::error::MatchmakingError;
+use crate::notify::{ExpiryNotification, Notifier, PubSubNotifier};
+
+/// Time between expiry rounds unless configured otherwise.
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long requests stay pending unless configured otherwise, or set for their
+/// prompt through the [`PromptTtlsApi`](crate::PromptTtlsApi).
+const DEFAULT_TTL: chrono::Duration = chrono::Duration::hours(1);
+
+/// Background worker expiring requests left pending for longer than their TTL.
+///
+/// The TTL of requests on a prompt is read from the `prompt_ttls` table, falling
+/// back to the worker's default TTL. Each round runs in a transaction holding a
+/// Postgres advisory lock, so when several instances run the worker, only one of
+/// them expires requests at a time and the others skip the round. Expiring only
+/// touches pending requests, so running a round again is harmless. Requesters
+/// are notified once the transaction has committed; with the default
+/// [`PubSubNotifier`], connected users receive the notification through
+/// [`RequestsApi`](crate::RequestsApi)'s WebSocket.
+#[derive(Clone)]
+pub struct ExpiryWorker {
+    pool: PgPool,
+    notifier: Arc<dyn Notifier>,
+    default_ttl: chrono::Duration,
+    interval: Duration,
+}
+
+impl ExpiryWorker {
+    /// Key of the advisory lock held by the instance expiring requests.
+    pub const LOCK_KEY: i64 = 0x6d67_5f65_7870_6972;
+
+    /// Create a new ExpiryWorker publishing expired requests with [`PubSubNotifier`].
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            notifier: Arc::new(PubSubNotifier::postgres(pool.clone())),
+            pool,
+            default_ttl: DEFAULT_TTL,
+            interval: DEFAULT_INTERVAL,
+        }
+    }
+
+    /// Expire requests on prompts without a TTL of their own after the given time.
+    pub fn with_default_ttl(mut self, ttl: chrono::Duration) -> Self {
+        self.default_ttl = ttl;
+        self
+    }
+
+    /// Deliver expiry notifications using the given notifier.
+    pub fn with_notifier(mut self, notifier: impl Notifier) -> Self {
+        self.notifier = Arc::new(notifier);
+        self
+    }
+
+    /// Wait the given time between rounds.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Run a single expiry round, returning the requests expired.
+    ///
+    /// Returns no requests if another instance is running a round.
+    pub async fn run_once(&self) -> Result<Vec<ConversationRequest>, MatchmakingError> {
+        let mut tx = self.pool.begin().await.map_err(DbError::Connection)?;
+        if !locks::try_advisory_xact_lock(&mut *tx, Self::LOCK_KEY).await? {
+            return Ok(Vec::new());
+        }
+        let expired = conversation_requests::expire_stale(&mut *tx, self.default_ttl).await?;
+        tx.commit().await.map_err(DbError::Query)?;
+
+        for request in &expired {
+            let notification = ExpiryNotification {
+                user_id: request.user_id,
+                request_id: request.id,
+                prompt: request.prompt.clone(),
+            };
+            if let Err(e) = self.notifier.expired(&notification).await {
+                eprintln!(
+                    "Failed to notify user {} of expired request {}: {e}",
+                    request.user_id, request.id
+                );
+            }
+        }
+        Ok(expired)
+    }
+
+    /// Run expiry rounds forever, logging failed rounds.
+    pub async fn run(self) {
+        let mut interval = tokio::time::interval(self.interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.run_once().await {
+                eprintln!("Expiry round failed: {e}");
+            }
+        }
+    }
+
+    /// Run the worker in the background.
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(self.run())
+    }
+}
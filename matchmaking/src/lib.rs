@@ -0,0 +1,30 @@
+//! # Matchmaking Crate
+//!
+//! Pairs users who asked to talk about the same prompt, preferably ones who
+//! disagree about it. Users create `ConversationRequest`s, which stay `Pending`
+//! until the [`MatchmakingWorker`] pairs two of them according to its
+//! [`MatchingStrategy`], creates a `Conversation` for the pair and notifies both
+//! users through a [`Notifier`]. Users who blocked each other are never paired.
+//! Requests left pending for too long are expired by the [`ExpiryWorker`].
+//! Users manage their requests through the [`RequestsApi`], which also relays
+//! the notifications picked up by a [`NotificationHub`] to connected users.
+//! Admins set how long requests on a prompt stay pending through the
+//! [`PromptTtlsApi`].
+
+mod api;
+pub mod error;
+mod expiry;
+pub mod notify;
+pub mod strategy;
+mod ttls;
+mod worker;
+
+pub use api::RequestsApi;
+pub use error::MatchmakingError;
+pub use expiry::ExpiryWorker;
+pub use notify::{
+    ExpiryNotification, MatchNotification, Notification, NotificationHub, Notifier, PubSubNotifier,
+};
+pub use strategy::{ExcludedPairs, MatchingStrategy, OpposingStances, Pairing, SamePrompt};
+pub use ttls::PromptTtlsApi;
+pub use worker::{Match, MatchmakingWorker};
@@ -0,0 +1,291 @@
+//! Notifying users of what became of their requests.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use db::error::DbError;
+use db::pubsub::{InProcessPubSub, PgPubSub, PubSub};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{OnceCell, broadcast};
+use uuid::Uuid;
+
+use crate::error::MatchmakingError;
+
+/// Pub/sub channel on which [`PubSubNotifier`] publishes matches.
+pub const MATCH_CHANNEL: &str = "conversation_matched";
+
+/// Pub/sub channel on which [`PubSubNotifier`] publishes expired requests.
+pub const EXPIRY_CHANNEL: &str = "conversation_request_expired";
+
+/// Notifications buffered per user for connections that fall behind.
+const CHANNEL_CAPACITY: usize = 16;
+
+/// Tells one user that their request was matched, and where to talk.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MatchNotification {
+    pub user_id: Uuid,
+    pub request_id: Uuid,
+    pub partner_request_id: Uuid,
+    pub conversation_id: Uuid,
+    pub topic: String,
+}
+
+/// Tells a user that their request expired before it could be matched.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExpiryNotification {
+    pub user_id: Uuid,
+    pub request_id: Uuid,
+    pub prompt: String,
+}
+
+/// Trait for services delivering notifications about requests to users.
+#[async_trait]
+pub trait Notifier: Send + Sync + 'static {
+    /// Tell a user their request was matched.
+    async fn matched(&self, notification: &MatchNotification) -> Result<(), MatchmakingError>;
+
+    /// Tell a user their request expired.
+    async fn expired(&self, notification: &ExpiryNotification) -> Result<(), MatchmakingError>;
+}
+
+/// Notifier publishing notifications as JSON on the [`MATCH_CHANNEL`] and
+/// [`EXPIRY_CHANNEL`] pub/sub channels, for the [`NotificationHub`] of the
+/// instances holding the users' connections to pick up.
+///
+/// With [`PgPubSub`], the default, notifications reach every instance connected
+/// to the database.
+#[derive(Clone)]
+pub struct PubSubNotifier {
+    pubsub: Arc<dyn PubSub>,
+}
+
+impl PubSubNotifier {
+    /// Create a new PubSubNotifier publishing through the given backend.
+    pub fn new(pubsub: impl PubSub) -> Self {
+        Self::from_arc(Arc::new(pubsub))
+    }
+
+    /// Create a new PubSubNotifier publishing through a backend shared with others.
+    pub fn from_arc(pubsub: Arc<dyn PubSub>) -> Self {
+        Self { pubsub }
+    }
+
+    /// Create a new PubSubNotifier publishing through Postgres with the provided pool.
+    pub fn postgres(pool: PgPool) -> Self {
+        Self::new(PgPubSub::new(pool))
+    }
+
+    async fn publish(
+        &self,
+        channel: &str,
+        notification: &impl Serialize,
+    ) -> Result<(), MatchmakingError> {
+        let payload = serde_json::to_string(notification)?;
+        self.pubsub.publish(channel, &payload).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Notifier for PubSubNotifier {
+    async fn matched(&self, notification: &MatchNotification) -> Result<(), MatchmakingError> {
+        self.publish(MATCH_CHANNEL, notification).await
+    }
+
+    async fn expired(&self, notification: &ExpiryNotification) -> Result<(), MatchmakingError> {
+        self.publish(EXPIRY_CHANNEL, notification).await
+    }
+}
+
+/// A notification delivered to a user over the notifications WebSocket.
+///
+/// Every notification is an object whose `type` field names it, e.g.
+///
+/// ```json
+/// { "type": "matched", "request_id": "...", "conversation_id": "...", ... }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Notification {
+    /// One of the user's requests was matched.
+    Matched(MatchNotification),
+    /// One of the user's requests expired before it could be matched.
+    Expired(ExpiryNotification),
+}
+
+impl Notification {
+    /// The user the notification is for.
+    pub fn user_id(&self) -> Uuid {
+        match self {
+            Notification::Matched(notification) => notification.user_id,
+            Notification::Expired(notification) => notification.user_id,
+        }
+    }
+}
+
+/// Decodes the payloads published on a channel into notifications.
+type Decoder = fn(&str) -> serde_json::Result<Notification>;
+
+/// The channels a [`NotificationHub`] listens to, with how their payloads are decoded.
+const CHANNELS: [(&str, Decoder); 2] = [
+    (MATCH_CHANNEL, |payload| {
+        serde_json::from_str(payload).map(Notification::Matched)
+    }),
+    (EXPIRY_CHANNEL, |payload| {
+        serde_json::from_str(payload).map(Notification::Expired)
+    }),
+];
+
+type Users = Arc<Mutex<HashMap<Uuid, broadcast::Sender<Notification>>>>;
+
+/// Hands the notifications published by [`PubSubNotifier`] to the connections
+/// of the users they are for.
+///
+/// Each hub subscribes to the notification channels once, and hands
+/// notifications to the local connections of their user. Notifications are not
+/// stored: users who were not connected when one was published, or whose
+/// connection fell behind, learn what became of their requests by listing them.
+#[derive(Clone)]
+pub struct NotificationHub {
+    pubsub: Arc<dyn PubSub>,
+    users: Users,
+    dispatcher: Arc<OnceCell<()>>,
+}
+
+impl Default for NotificationHub {
+    fn default() -> Self {
+        Self::new(InProcessPubSub::new())
+    }
+}
+
+impl NotificationHub {
+    /// Create a new NotificationHub receiving notifications through the given backend.
+    pub fn new(pubsub: impl PubSub) -> Self {
+        Self::from_arc(Arc::new(pubsub))
+    }
+
+    /// Create a new NotificationHub receiving notifications through a backend shared with others.
+    pub fn from_arc(pubsub: Arc<dyn PubSub>) -> Self {
+        Self {
+            pubsub,
+            users: Default::default(),
+            dispatcher: Default::default(),
+        }
+    }
+
+    /// Receive the notifications published for a user from now on.
+    pub async fn subscribe(
+        &self,
+        user_id: Uuid,
+    ) -> Result<broadcast::Receiver<Notification>, DbError> {
+        self.dispatcher
+            .get_or_try_init(|| async {
+                let mut subscriptions = Vec::with_capacity(CHANNELS.len());
+                for (channel, decode) in CHANNELS {
+                    subscriptions.push((self.pubsub.subscribe(channel).await?, decode));
+                }
+                for (payloads, decode) in subscriptions {
+                    tokio::spawn(dispatch(payloads, decode, self.users.clone()));
+                }
+                Ok::<_, DbError>(())
+            })
+            .await?;
+
+        let mut users = self.users.lock().unwrap();
+        Ok(users
+            .entry(user_id)
+            .or_insert_with(|| broadcast::channel(CHANNEL_CAPACITY).0)
+            .subscribe())
+    }
+}
+
+/// Hand the notifications published on a channel to the local connections of their user.
+async fn dispatch(mut payloads: broadcast::Receiver<String>, decode: Decoder, users: Users) {
+    loop {
+        let payload = match payloads.recv().await {
+            Ok(payload) => payload,
+            Err(RecvError::Lagged(missed)) => {
+                // Dropping the senders closes every connection, so that users list their requests.
+                eprintln!(
+                    "Notification hub fell {missed} notifications behind; closing connections"
+                );
+                users.lock().unwrap().clear();
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+
+        let notification = match decode(&payload) {
+            Ok(notification) => notification,
+            Err(e) => {
+                eprintln!("Ignoring malformed notification: {e}");
+                continue;
+            }
+        };
+
+        let mut users = users.lock().unwrap();
+        let user_id = notification.user_id();
+        if let Some(sender) = users.get(&user_id) {
+            // Sending only fails once every connection has gone.
+            if sender.send(notification).is_err() {
+                users.remove(&user_id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matched(user_id: Uuid) -> MatchNotification {
+        MatchNotification {
+            user_id,
+            request_id: Uuid::new_v4(),
+            partner_request_id: Uuid::new_v4(),
+            conversation_id: Uuid::new_v4(),
+            topic: "Should cities ban cars?".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_notifications_reach_their_user() {
+        let pubsub = InProcessPubSub::new();
+        let notifier = PubSubNotifier::new(pubsub.clone());
+        let hub = NotificationHub::new(pubsub);
+        let (user, other) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut receiver = hub.subscribe(user).await.unwrap();
+        let mut elsewhere = hub.subscribe(other).await.unwrap();
+
+        let notification = matched(user);
+        notifier.matched(&notification).await.unwrap();
+
+        assert_eq!(
+            receiver.recv().await.unwrap(),
+            Notification::Matched(notification)
+        );
+        assert!(elsewhere.try_recv().is_err());
+
+        let notification = ExpiryNotification {
+            user_id: user,
+            request_id: Uuid::new_v4(),
+            prompt: "Should cities ban cars?".to_string(),
+        };
+        notifier.expired(&notification).await.unwrap();
+        assert_eq!(
+            receiver.recv().await.unwrap(),
+            Notification::Expired(notification)
+        );
+    }
+
+    #[test]
+    fn test_notification_is_tagged() {
+        let notification = Notification::Matched(matched(Uuid::new_v4()));
+        let json = serde_json::to_value(&notification).unwrap();
+        assert_eq!(json["type"], "matched");
+        assert_eq!(json["topic"], "Should cities ban cars?");
+    }
+}
@@ -0,0 +1,349 @@
+//! Rules deciding which pending requests are paired into conversations.
+
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Duration, Utc};
+use shared::types::conversation::ConversationRequest;
+use uuid::Uuid;
+
+/// Two pending requests to be matched into a conversation, by request id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Pairing {
+    pub first: Uuid,
+    pub second: Uuid,
+}
+
+impl Pairing {
+    pub fn new(first: Uuid, second: Uuid) -> Self {
+        Self { first, second }
+    }
+}
+
+/// Users who must not be matched with each other, in either order.
+///
+/// Users are excluded from each other if either blocked the other, or if they
+/// met too recently to be matched again.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExcludedPairs(HashSet<(Uuid, Uuid)>);
+
+impl ExcludedPairs {
+    /// Exclude two users from each other.
+    pub fn insert(&mut self, a: Uuid, b: Uuid) {
+        self.0.insert((a.min(b), a.max(b)));
+    }
+
+    /// Whether two users are excluded from each other.
+    pub fn contains(&self, a: Uuid, b: Uuid) -> bool {
+        self.0.contains(&(a.min(b), a.max(b)))
+    }
+}
+
+impl FromIterator<(Uuid, Uuid)> for ExcludedPairs {
+    fn from_iter<I: IntoIterator<Item = (Uuid, Uuid)>>(pairs: I) -> Self {
+        let mut excluded = Self::default();
+        for (a, b) in pairs {
+            excluded.insert(a, b);
+        }
+        excluded
+    }
+}
+
+/// Trait for rules pairing pending conversation requests.
+///
+/// Strategies only choose pairs; the [`MatchmakingWorker`](crate::MatchmakingWorker)
+/// creates the conversations. Pairs naming unknown requests, pairing a request
+/// twice, pairing a user with themselves or pairing excluded users are discarded
+/// by the worker.
+pub trait MatchingStrategy: Send + Sync + 'static {
+    /// Choose pairs among `pending`, which is ordered oldest first, never
+    /// pairing the users of a pair in `excluded`.
+    ///
+    /// Requests left unpaired stay pending for the next round.
+    fn pair(
+        &self,
+        pending: &[ConversationRequest],
+        excluded: &ExcludedPairs,
+        now: DateTime<Utc>,
+    ) -> Vec<Pairing>;
+}
+
+/// Pairs the oldest requests made on the same prompt, first come first served.
+///
+/// Prompts are compared ignoring case and surrounding or repeated whitespace.
+/// Each user is matched at most once per round, even if they have pending
+/// requests on several prompts. Requests are passed over for excluded users,
+/// and wait for the next request on the prompt instead.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SamePrompt;
+
+impl MatchingStrategy for SamePrompt {
+    fn pair(
+        &self,
+        pending: &[ConversationRequest],
+        excluded: &ExcludedPairs,
+        _now: DateTime<Utc>,
+    ) -> Vec<Pairing> {
+        let mut waiting: HashMap<String, Vec<&ConversationRequest>> = HashMap::new();
+        let mut matched_users = HashSet::new();
+        let mut pairings = Vec::new();
+
+        for request in pending {
+            if matched_users.contains(&request.user_id) {
+                continue;
+            }
+
+            let queue = waiting
+                .entry(normalize_prompt(&request.prompt))
+                .or_default();
+            let partner = queue.iter().position(|other| {
+                other.user_id != request.user_id
+                    && !matched_users.contains(&other.user_id)
+                    && !excluded.contains(other.user_id, request.user_id)
+            });
+
+            match partner {
+                Some(index) => {
+                    let partner = queue.remove(index);
+                    matched_users.insert(partner.user_id);
+                    matched_users.insert(request.user_id);
+                    pairings.push(Pairing::new(partner.id, request.id));
+                }
+                None => queue.push(request),
+            }
+        }
+
+        pairings
+    }
+}
+
+/// Pairs requests on the same prompt whose stances differ the most.
+///
+/// Until one of two requests has waited for `fallback_after`, they are only
+/// paired if their positions oppose each other: one agrees with the prompt and
+/// the other disagrees. After that, nearby and even identical stances are
+/// accepted too, so that nobody waits forever for an opponent.
+///
+/// Pairs whose positions are further apart are matched first; among equally
+/// distant pairs, the one holding the oldest request wins. As with
+/// [`SamePrompt`], each user is matched at most once per round, and never with
+/// excluded users.
+#[derive(Debug, Clone, Copy)]
+pub struct OpposingStances {
+    fallback_after: Duration,
+}
+
+impl OpposingStances {
+    /// Fall back to nearby stances once a request has waited for the given time.
+    pub fn new(fallback_after: Duration) -> Self {
+        Self { fallback_after }
+    }
+}
+
+impl Default for OpposingStances {
+    fn default() -> Self {
+        Self::new(Duration::minutes(5))
+    }
+}
+
+impl MatchingStrategy for OpposingStances {
+    fn pair(
+        &self,
+        pending: &[ConversationRequest],
+        excluded: &ExcludedPairs,
+        now: DateTime<Utc>,
+    ) -> Vec<Pairing> {
+        let mut by_prompt: HashMap<String, Vec<&ConversationRequest>> = HashMap::new();
+        for request in pending {
+            by_prompt
+                .entry(normalize_prompt(&request.prompt))
+                .or_default()
+                .push(request);
+        }
+
+        // Requests keep their oldest-first order within a prompt, so `older`
+        // was made no later than `newer`.
+        let mut candidates = Vec::new();
+        for requests in by_prompt.values() {
+            for (i, older) in requests.iter().enumerate() {
+                for newer in &requests[i + 1..] {
+                    let (a, b) = (older.stance.position, newer.stance.position);
+                    let waited = now - older.request_time;
+                    if older.user_id == newer.user_id
+                        || excluded.contains(older.user_id, newer.user_id)
+                        || !(a.opposes(b) || waited >= self.fallback_after)
+                    {
+                        continue;
+                    }
+                    candidates.push((*older, *newer, a.distance(b)));
+                }
+            }
+        }
+        candidates.sort_by_key(|(older, newer, distance)| {
+            (Reverse(*distance), older.request_time, newer.request_time)
+        });
+
+        let mut matched_users = HashSet::new();
+        let mut pairings = Vec::new();
+        for (older, newer, _) in candidates {
+            if matched_users.contains(&older.user_id) || matched_users.contains(&newer.user_id) {
+                continue;
+            }
+            matched_users.insert(older.user_id);
+            matched_users.insert(newer.user_id);
+            pairings.push(Pairing::new(older.id, newer.id));
+        }
+
+        pairings
+    }
+}
+
+/// Normalize a prompt so that trivially different spellings of it compare equal.
+pub fn normalize_prompt(prompt: &str) -> String {
+    prompt
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use shared::types::conversation::ConversationRequestStatus;
+    use shared::types::stance::{Position, Stance};
+
+    /// A pending request made `age_minutes` ago.
+    pub(crate) fn request(user_id: Uuid, prompt: &str, age_minutes: i64) -> ConversationRequest {
+        ConversationRequest {
+            id: Uuid::new_v4(),
+            user_id,
+            prompt: prompt.to_string(),
+            stance: Stance::default(),
+            request_time: Utc::now() - Duration::minutes(age_minutes),
+            status: ConversationRequestStatus::Pending,
+            match_id: None,
+            conversation_id: None,
+        }
+    }
+
+    /// A pending request on the prompt "prompt" taking the given position.
+    fn stance(user_id: Uuid, position: Position, age_minutes: i64) -> ConversationRequest {
+        ConversationRequest {
+            stance: Stance::new(position),
+            ..request(user_id, "prompt", age_minutes)
+        }
+    }
+
+    #[test]
+    fn test_pairs_oldest_requests_on_same_prompt() {
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let pending = vec![
+            request(a, "Should cities ban cars?", 3),
+            request(b, "Is remote work here to stay?", 2),
+            request(b, "should cities  ban cars? ", 2),
+            request(c, "Should cities ban cars?", 1),
+        ];
+
+        let pairings = SamePrompt.pair(&pending, &ExcludedPairs::default(), Utc::now());
+        assert_eq!(pairings, vec![Pairing::new(pending[0].id, pending[2].id)]);
+    }
+
+    #[test]
+    fn test_does_not_pair_user_with_themselves() {
+        let a = Uuid::new_v4();
+        let pending = vec![request(a, "prompt", 2), request(a, "prompt", 1)];
+
+        assert!(
+            SamePrompt
+                .pair(&pending, &ExcludedPairs::default(), Utc::now())
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_matches_each_user_once_per_round() {
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let pending = vec![
+            request(a, "first", 4),
+            request(a, "second", 3),
+            request(b, "first", 2),
+            request(c, "second", 1),
+        ];
+
+        let pairings = SamePrompt.pair(&pending, &ExcludedPairs::default(), Utc::now());
+        assert_eq!(pairings, vec![Pairing::new(pending[0].id, pending[2].id)]);
+    }
+
+    #[test]
+    fn test_prefers_most_opposed_stances() {
+        let (a, b, c, d) = (
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+        );
+        let pending = vec![
+            stance(a, Position::Agree, 3),
+            stance(b, Position::Disagree, 2),
+            stance(c, Position::StronglyDisagree, 1),
+            stance(d, Position::StronglyAgree, 0),
+        ];
+
+        let pairings =
+            OpposingStances::default().pair(&pending, &ExcludedPairs::default(), Utc::now());
+        assert_eq!(
+            pairings,
+            vec![
+                Pairing::new(pending[2].id, pending[3].id),
+                Pairing::new(pending[0].id, pending[1].id),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_falls_back_to_nearby_stances_after_waiting() {
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let pending = vec![
+            stance(a, Position::Agree, 10),
+            stance(b, Position::Neutral, 1),
+            stance(c, Position::StronglyAgree, 0),
+        ];
+
+        let strategy = OpposingStances::new(Duration::minutes(15));
+        assert!(
+            strategy
+                .pair(&pending, &ExcludedPairs::default(), Utc::now())
+                .is_empty()
+        );
+
+        let strategy = OpposingStances::new(Duration::minutes(5));
+        assert_eq!(
+            strategy.pair(&pending, &ExcludedPairs::default(), Utc::now()),
+            vec![Pairing::new(pending[0].id, pending[1].id)]
+        );
+    }
+
+    #[test]
+    fn test_excluded_users_are_never_paired() {
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let excluded: ExcludedPairs = [(b, a)].into_iter().collect();
+        assert!(excluded.contains(a, b));
+
+        let pending = vec![
+            request(a, "prompt", 3),
+            request(b, "prompt", 2),
+            request(c, "prompt", 1),
+        ];
+        let pairings = SamePrompt.pair(&pending, &excluded, Utc::now());
+        assert_eq!(pairings, vec![Pairing::new(pending[0].id, pending[2].id)]);
+
+        let pending = vec![
+            stance(a, Position::StronglyAgree, 3),
+            stance(b, Position::StronglyDisagree, 2),
+            stance(c, Position::Disagree, 1),
+        ];
+        let pairings = OpposingStances::default().pair(&pending, &excluded, Utc::now());
+        assert_eq!(pairings, vec![Pairing::new(pending[0].id, pending[2].id)]);
+    }
+}
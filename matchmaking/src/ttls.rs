@@ -0,0 +1,178 @@
+//! HTTP endpoints for admins to set how long requests on a prompt stay pending.
+
+use auth::authorization::{Authorization, RoleSource, authorize, require_permission};
+use auth::middleware::auth_standard;
+use auth::models::Authenticator;
+use axum::extract::rejection::{JsonRejection, QueryRejection};
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Json, Router, middleware};
+use chrono::{DateTime, Duration, Utc};
+use db::queries::prompt_ttls::{self, PromptTtl};
+use serde::{Deserialize, Serialize};
+use shared::error::ApiError;
+use shared::types::role::Permission;
+use sqlx::PgPool;
+
+use crate::api::MAX_PROMPT_LENGTH;
+
+/// Longest TTL accepted, in seconds.
+const MAX_TTL_SECONDS: i64 = 30 * 24 * 60 * 60;
+
+/// The prompt TTLs API, served by [`PromptTtlsApi::router`].
+///
+/// Requests on prompts without a TTL set here expire after the
+/// [`ExpiryWorker`](crate::ExpiryWorker)'s default TTL. Prompts are matched
+/// ignoring case and whitespace differences.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use auth::authorization::PgRoles;
+/// use auth::models::SbAuthenticator;
+/// use matchmaking::PromptTtlsApi;
+///
+/// # async fn example() {
+/// let pool = db::create_pool().await.unwrap();
+/// let app = axum::Router::new().nest(
+///     "/admin/prompt-ttls",
+///     PromptTtlsApi::new(pool.clone())
+///         .with_role_source(PgRoles::new(pool))
+///         .router(SbAuthenticator::default()),
+/// );
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct PromptTtlsApi {
+    pool: PgPool,
+    authorization: Authorization,
+}
+
+impl PromptTtlsApi {
+    /// Create a new PromptTtlsApi using the provided pool.
+    ///
+    /// Admins are recognized by the role claimed by their token unless
+    /// configured otherwise with [`PromptTtlsApi::with_role_source`].
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            authorization: require_permission(Permission::ManagePrompts),
+        }
+    }
+
+    /// Determine users' roles using the given source instead of their tokens' claims.
+    pub fn with_role_source(mut self, roles: impl RoleSource) -> Self {
+        self.authorization = self.authorization.with_role_source(roles);
+        self
+    }
+
+    /// Creates a router serving the API to users authenticated by `auth_standard`
+    /// and holding the `manage_prompts` permission.
+    ///
+    /// The router includes the following endpoints:
+    ///  - `GET /` - list the TTLs set for prompts
+    ///  - `PUT /` - set the TTL of requests on a prompt, replacing any TTL set before
+    ///  - `DELETE /?prompt=` - remove the TTL set for a prompt
+    pub fn router<A: Authenticator>(self, authenticator: A) -> Router {
+        Router::new()
+            .route("/", get(list_ttls).put(set_ttl).delete(delete_ttl))
+            .route_layer(middleware::from_fn_with_state(
+                self.authorization.clone(),
+                authorize,
+            ))
+            .route_layer(middleware::from_fn_with_state(
+                authenticator,
+                auth_standard::<A>,
+            ))
+            .with_state(self)
+    }
+}
+
+/// The TTL set for requests on a prompt, as returned by the API.
+#[derive(Debug, Serialize)]
+struct PromptTtlView {
+    prompt: String,
+    ttl_seconds: i64,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<PromptTtl> for PromptTtlView {
+    fn from(ttl: PromptTtl) -> Self {
+        Self {
+            prompt: ttl.prompt,
+            ttl_seconds: ttl.ttl_seconds,
+            updated_at: ttl.updated_at,
+        }
+    }
+}
+
+/// TTL to set for requests on a prompt.
+#[derive(Debug, Deserialize)]
+struct SetTtl {
+    prompt: String,
+    ttl_seconds: i64,
+}
+
+impl SetTtl {
+    /// Validate the TTL, returning the trimmed prompt and the TTL.
+    fn validate(&self) -> Result<(&str, Duration), ApiError> {
+        let prompt = valid_prompt(&self.prompt)?;
+        if !(1..=MAX_TTL_SECONDS).contains(&self.ttl_seconds) {
+            return Err(ApiError::validation(format!(
+                "TTL must be between 1 and {MAX_TTL_SECONDS} seconds"
+            )));
+        }
+        Ok((prompt, Duration::seconds(self.ttl_seconds)))
+    }
+}
+
+/// The prompt whose TTL to remove.
+#[derive(Debug, Deserialize)]
+struct DeleteQuery {
+    prompt: String,
+}
+
+/// List the TTLs set for prompts.
+async fn list_ttls(State(api): State<PromptTtlsApi>) -> Result<Json<Vec<PromptTtlView>>, ApiError> {
+    let ttls = prompt_ttls::list_prompt_ttls(&api.pool).await?;
+    Ok(Json(ttls.into_iter().map(PromptTtlView::from).collect()))
+}
+
+/// Set the TTL of requests on a prompt.
+async fn set_ttl(
+    State(api): State<PromptTtlsApi>,
+    payload: Result<Json<SetTtl>, JsonRejection>,
+) -> Result<Json<PromptTtlView>, ApiError> {
+    let Json(payload) = payload?;
+    let (prompt, ttl) = payload.validate()?;
+    let ttl = prompt_ttls::set_prompt_ttl(&api.pool, prompt, ttl).await?;
+    Ok(Json(ttl.into()))
+}
+
+/// Remove the TTL set for a prompt, so its requests use the default TTL again.
+async fn delete_ttl(
+    State(api): State<PromptTtlsApi>,
+    query: Result<Query<DeleteQuery>, QueryRejection>,
+) -> Result<StatusCode, ApiError> {
+    let Query(query) = query?;
+    let prompt = valid_prompt(&query.prompt)?;
+    if !prompt_ttls::delete_prompt_ttl(&api.pool, prompt).await? {
+        return Err(ApiError::not_found("No TTL is set for this prompt"));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Trim a prompt, checking it is one requests could be made on.
+fn valid_prompt(prompt: &str) -> Result<&str, ApiError> {
+    let prompt = prompt.trim();
+    if prompt.is_empty() {
+        return Err(ApiError::validation("Prompt must not be empty"));
+    }
+    if prompt.chars().count() > MAX_PROMPT_LENGTH {
+        return Err(ApiError::validation(format!(
+            "Prompt must be at most {MAX_PROMPT_LENGTH} characters"
+        )));
+    }
+    Ok(prompt)
+}
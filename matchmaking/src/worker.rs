@@ -0,0 +1,315 @@
+//! The background worker matching pending requests.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use db::error::DbError;
+use db::queries::{blocks, conversation_requests, conversations};
+use shared::types::conversation::{Conversation, ConversationRequest, ConversationRequestStatus};
+use sqlx::PgPool;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::error::MatchmakingError;
+use crate::notify::{MatchNotification, Notifier, PubSubNotifier};
+use crate::strategy::{ExcludedPairs, MatchingStrategy, OpposingStances, Pairing};
+
+/// Time between matching rounds unless configured otherwise.
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Pending requests considered per round unless configured otherwise.
+const DEFAULT_BATCH_SIZE: i64 = 500;
+
+/// Two requests matched into a new conversation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match {
+    pub conversation: Conversation,
+    pub first: ConversationRequest,
+    pub second: ConversationRequest,
+}
+
+impl Match {
+    /// The notifications telling each of the two users about the match.
+    pub fn notifications(&self) -> [MatchNotification; 2] {
+        let notify =
+            |request: &ConversationRequest, partner: &ConversationRequest| MatchNotification {
+                user_id: request.user_id,
+                request_id: request.id,
+                partner_request_id: partner.id,
+                conversation_id: self.conversation.id,
+                topic: self.conversation.topic.clone(),
+            };
+        [
+            notify(&self.first, &self.second),
+            notify(&self.second, &self.first),
+        ]
+    }
+}
+
+/// Background worker pairing pending conversation requests.
+///
+/// Each round locks a batch of pending requests with `FOR UPDATE SKIP LOCKED`,
+/// lets the [`MatchingStrategy`] pair them, and for each pair creates a
+/// [`Conversation`] and marks both requests `Matched` with each other's id, all in
+/// one transaction. The users are notified once the transaction has committed;
+/// with the default [`PubSubNotifier`], connected users receive the notification
+/// through [`RequestsApi`](crate::RequestsApi)'s WebSocket. Since locked requests are skipped, several workers can run at once.
+///
+/// Users who blocked one another, in either direction, are never matched. With
+/// [`MatchmakingWorker::with_rematch_cooldown`], neither are users who took
+/// part in a conversation together within the cooldown.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use matchmaking::{MatchmakingWorker, SamePrompt};
+/// use std::time::Duration;
+///
+/// # async fn example() {
+/// let pool = db::create_pool().await.unwrap();
+/// MatchmakingWorker::new(pool)
+///     .with_strategy(SamePrompt)
+///     .with_interval(Duration::from_secs(5))
+///     .spawn();
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct MatchmakingWorker {
+    pool: PgPool,
+    strategy: Arc<dyn MatchingStrategy>,
+    notifier: Arc<dyn Notifier>,
+    interval: Duration,
+    batch_size: i64,
+    rematch_cooldown: Option<chrono::Duration>,
+}
+
+impl MatchmakingWorker {
+    /// Create a new MatchmakingWorker pairing opposing stances on the same prompt
+    /// with [`OpposingStances`] and publishing matches with [`PubSubNotifier`].
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            notifier: Arc::new(PubSubNotifier::postgres(pool.clone())),
+            pool,
+            strategy: Arc::new(OpposingStances::default()),
+            interval: DEFAULT_INTERVAL,
+            batch_size: DEFAULT_BATCH_SIZE,
+            rematch_cooldown: None,
+        }
+    }
+
+    /// Pair requests using the given strategy.
+    pub fn with_strategy(mut self, strategy: impl MatchingStrategy) -> Self {
+        self.strategy = Arc::new(strategy);
+        self
+    }
+
+    /// Deliver match notifications using the given notifier.
+    pub fn with_notifier(mut self, notifier: impl Notifier) -> Self {
+        self.notifier = Arc::new(notifier);
+        self
+    }
+
+    /// Wait the given time between rounds.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Consider at most the given number of pending requests per round.
+    pub fn with_batch_size(mut self, batch_size: i64) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Never match users again within the given time of a conversation they had together.
+    pub fn with_rematch_cooldown(mut self, cooldown: chrono::Duration) -> Self {
+        self.rematch_cooldown = Some(cooldown);
+        self
+    }
+
+    /// Run a single matching round, returning the matches made.
+    pub async fn run_once(&self) -> Result<Vec<Match>, MatchmakingError> {
+        let mut tx = self.pool.begin().await.map_err(DbError::Connection)?;
+        let now = Utc::now();
+        let pending = conversation_requests::lock_pending(&mut *tx, self.batch_size, now).await?;
+        if pending.len() < 2 {
+            return Ok(Vec::new());
+        }
+
+        let user_ids: Vec<Uuid> = pending.iter().map(|request| request.user_id).collect();
+        let met_since = self.rematch_cooldown.map(|cooldown| now - cooldown);
+        let excluded: ExcludedPairs = blocks::excluded_pairs(&mut *tx, &user_ids, met_since)
+            .await?
+            .into_iter()
+            .collect();
+
+        let pairings = self.strategy.pair(&pending, &excluded, now);
+        let mut matches = Vec::new();
+        for (first, second) in valid_pairs(&pending, &excluded, &pairings) {
+            let conversation = Conversation {
+                id: Uuid::new_v4(),
+                topic: first.prompt.clone(),
+                created_at: Utc::now(),
+                ended_at: None,
+                end_reason: None,
+                participant_a: first.user_id,
+                participant_b: second.user_id,
+            };
+            conversations::insert_conversation(&mut *tx, &conversation).await?;
+            conversation_requests::mark_matched(&mut *tx, first.id, second.id, conversation.id)
+                .await?;
+            conversation_requests::mark_matched(&mut *tx, second.id, first.id, conversation.id)
+                .await?;
+
+            matches.push(Match {
+                first: matched(first, second, &conversation),
+                second: matched(second, first, &conversation),
+                conversation,
+            });
+        }
+        tx.commit().await.map_err(DbError::Query)?;
+
+        for notification in matches.iter().flat_map(Match::notifications) {
+            if let Err(e) = self.notifier.matched(&notification).await {
+                eprintln!(
+                    "Failed to notify user {} of match {}: {e}",
+                    notification.user_id, notification.conversation_id
+                );
+            }
+        }
+        Ok(matches)
+    }
+
+    /// Run matching rounds forever, logging failed rounds.
+    pub async fn run(self) {
+        let mut interval = tokio::time::interval(self.interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.run_once().await {
+                eprintln!("Matchmaking round failed: {e}");
+            }
+        }
+    }
+
+    /// Run the worker in the background.
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(self.run())
+    }
+}
+
+/// The request as it is once matched.
+fn matched(
+    request: &ConversationRequest,
+    partner: &ConversationRequest,
+    conversation: &Conversation,
+) -> ConversationRequest {
+    ConversationRequest {
+        status: ConversationRequestStatus::Matched,
+        match_id: Some(partner.id),
+        conversation_id: Some(conversation.id),
+        ..request.clone()
+    }
+}
+
+/// Resolve the pairings chosen by a strategy, discarding those naming unknown
+/// requests, reusing a request, pairing a user with themselves or pairing
+/// excluded users.
+fn valid_pairs<'a>(
+    pending: &'a [ConversationRequest],
+    excluded: &ExcludedPairs,
+    pairings: &[Pairing],
+) -> Vec<(&'a ConversationRequest, &'a ConversationRequest)> {
+    let by_id: HashMap<Uuid, &ConversationRequest> = pending
+        .iter()
+        .map(|request| (request.id, request))
+        .collect();
+    let mut used = HashSet::new();
+
+    pairings
+        .iter()
+        .filter_map(|pairing| {
+            let first = *by_id.get(&pairing.first)?;
+            let second = *by_id.get(&pairing.second)?;
+            if first.user_id == second.user_id
+                || excluded.contains(first.user_id, second.user_id)
+                || used.contains(&first.id)
+                || used.contains(&second.id)
+            {
+                return None;
+            }
+            used.insert(first.id);
+            used.insert(second.id);
+            Some((first, second))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategy::tests::request;
+
+    #[test]
+    fn test_invalid_pairings_are_discarded() {
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let pending = vec![
+            request(a, "p", 3),
+            request(a, "p", 2),
+            request(b, "p", 1),
+            request(c, "p", 0),
+        ];
+        let ids: Vec<Uuid> = pending.iter().map(|r| r.id).collect();
+
+        let pairs = valid_pairs(
+            &pending,
+            &ExcludedPairs::default(),
+            &[
+                Pairing::new(ids[0], ids[1]),
+                Pairing::new(ids[0], Uuid::new_v4()),
+                Pairing::new(ids[0], ids[2]),
+                Pairing::new(ids[2], ids[3]),
+                Pairing::new(ids[1], ids[3]),
+            ],
+        );
+        let pairs: Vec<(Uuid, Uuid)> = pairs.iter().map(|(x, y)| (x.id, y.id)).collect();
+        assert_eq!(pairs, vec![(ids[0], ids[2]), (ids[1], ids[3])]);
+    }
+
+    #[test]
+    fn test_excluded_pairings_are_discarded() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let pending = vec![request(a, "p", 1), request(b, "p", 0)];
+        let excluded: ExcludedPairs = [(a, b)].into_iter().collect();
+
+        let pairings = [Pairing::new(pending[1].id, pending[0].id)];
+        assert!(valid_pairs(&pending, &excluded, &pairings).is_empty());
+    }
+
+    #[test]
+    fn test_match_notifies_both_users() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let (first, second) = (request(a, "p", 1), request(b, "p", 0));
+        let conversation = Conversation {
+            id: Uuid::new_v4(),
+            topic: "p".to_string(),
+            created_at: Utc::now(),
+            ended_at: None,
+            end_reason: None,
+            participant_a: a,
+            participant_b: b,
+        };
+        let m = Match {
+            first: matched(&first, &second, &conversation),
+            second: matched(&second, &first, &conversation),
+            conversation,
+        };
+
+        let [to_a, to_b] = m.notifications();
+        assert_eq!((to_a.user_id, to_a.partner_request_id), (a, second.id));
+        assert_eq!((to_b.user_id, to_b.partner_request_id), (b, first.id));
+        assert_eq!(m.first.match_id, Some(second.id));
+    }
+}
@@ -0,0 +1,123 @@
+//! Tests of the conversation requests and prompt TTLs APIs that are rejected
+//! before reaching the database.
+
+use axum::Router;
+use axum::http::{Method, StatusCode};
+use matchmaking::{PromptTtlsApi, RequestsApi};
+use serde_json::json;
+use shared::types::role::Role;
+use test_support::{authenticator, lazy_pool, send, token};
+use uuid::Uuid;
+
+/// Router backed by a pool that never connects, and a token for a new user.
+fn router() -> (Router, String) {
+    let authenticator = authenticator();
+    let token = token(&authenticator, Uuid::new_v4());
+    (RequestsApi::new(lazy_pool()).router(authenticator), token)
+}
+
+#[tokio::test]
+async fn test_requires_authentication() {
+    let (router, _) = router();
+
+    let (status, body) = send(router, Method::GET, "/", None, None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "missing_credentials");
+}
+
+#[tokio::test]
+async fn test_create_rejects_invalid_requests() {
+    let (router, token) = router();
+
+    let missing_stance = json!({ "prompt": "Should cities ban cars?" });
+    let (status, body) = send(
+        router.clone(),
+        Method::POST,
+        "/",
+        Some(&token),
+        Some(missing_stance),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["code"], "validation_failed");
+
+    let empty_prompt = json!({ "prompt": " ", "stance": { "position": "agree" } });
+    let (status, body) = send(router, Method::POST, "/", Some(&token), Some(empty_prompt)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["message"], "Prompt must not be empty");
+}
+
+#[tokio::test]
+async fn test_rejects_malformed_ids_and_filters() {
+    let (router, token) = router();
+
+    let (status, body) = send(
+        router.clone(),
+        Method::GET,
+        "/not-a-uuid",
+        Some(&token),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "bad_request");
+
+    let (status, body) = send(router, Method::GET, "/?status=unknown", Some(&token), None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "bad_request");
+}
+
+#[tokio::test]
+async fn test_notifications_require_a_websocket() {
+    let (router, token) = router();
+
+    let (status, body) = send(router.clone(), Method::GET, "/ws", None, None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "missing_credentials");
+
+    // a plain request is rejected rather than treated as a request id
+    let (status, body) = send(router, Method::GET, "/ws", Some(&token), None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "bad_request");
+}
+
+#[tokio::test]
+async fn test_prompt_ttls_require_admins() {
+    let authenticator = authenticator();
+    let user_id = Uuid::new_v4();
+    authenticator.grant_role(user_id, Role::Moderator);
+    let token = token(&authenticator, user_id);
+    let router = PromptTtlsApi::new(lazy_pool()).router(authenticator);
+
+    let (status, body) = send(router, Method::GET, "/", Some(&token), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["details"]["required_permission"], "manage_prompts");
+}
+
+#[tokio::test]
+async fn test_prompt_ttls_are_validated() {
+    let authenticator = authenticator();
+    let user_id = Uuid::new_v4();
+    authenticator.grant_role(user_id, Role::Admin);
+    let token = token(&authenticator, user_id);
+    let router = PromptTtlsApi::new(lazy_pool()).router(authenticator);
+
+    for invalid in [
+        json!({ "prompt": " ", "ttl_seconds": 600 }),
+        json!({ "prompt": "Should cities ban cars?", "ttl_seconds": 0 }),
+    ] {
+        let (status, body) = send(
+            router.clone(),
+            Method::PUT,
+            "/",
+            Some(&token),
+            Some(invalid),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], "validation_failed");
+    }
+
+    let (status, _) = send(router, Method::DELETE, "/", Some(&token), None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,148 @@
+//! Tests of the expiry worker and the prompt TTLs it applies against a database.
+
+use axum::http::{Method, StatusCode};
+use chrono::{Duration, Utc};
+use db::pubsub::InProcessPubSub;
+use db::queries::{conversation_requests, locks, prompt_ttls};
+use matchmaking::{ExpiryWorker, PromptTtlsApi, PubSubNotifier};
+use serde_json::json;
+use shared::types::conversation::{ConversationRequest, ConversationRequestStatus};
+use shared::types::role::Role;
+use shared::types::stance::{Position, Stance};
+use sqlx::PgPool;
+use test_support::{authenticator, create_user, send, token};
+use uuid::Uuid;
+
+/// Worker expiring requests after an hour unless their prompt has a TTL,
+/// notifying expiries within the test only.
+fn worker(pool: &PgPool) -> ExpiryWorker {
+    ExpiryWorker::new(pool.clone())
+        .with_default_ttl(Duration::hours(1))
+        .with_notifier(PubSubNotifier::new(InProcessPubSub::new()))
+}
+
+/// Create a request on a prompt, pending for the given time.
+async fn request(pool: &PgPool, prompt: &str, pending_for: Duration) -> ConversationRequest {
+    let request = ConversationRequest {
+        id: Uuid::new_v4(),
+        user_id: create_user(pool).await,
+        prompt: prompt.to_string(),
+        stance: Stance::new(Position::Agree),
+        request_time: Utc::now() - pending_for,
+        status: ConversationRequestStatus::Pending,
+        match_id: None,
+        conversation_id: None,
+    };
+    conversation_requests::insert_request(pool, &request)
+        .await
+        .unwrap();
+    request
+}
+
+/// The current status of a request.
+async fn status(pool: &PgPool, request: &ConversationRequest) -> ConversationRequestStatus {
+    conversation_requests::get_request(pool, request.id)
+        .await
+        .unwrap()
+        .unwrap()
+        .status
+}
+
+#[sqlx::test(migrator = "db::MIGRATOR")]
+async fn test_requests_expire_after_their_prompts_ttl(pool: PgPool) {
+    // Set for a differently spelled prompt, the TTL applies once normalized.
+    prompt_ttls::set_prompt_ttl(&pool, "  Should cities   BAN cars? ", Duration::minutes(10))
+        .await
+        .unwrap();
+    let short_lived = request(&pool, "should cities ban cars?", Duration::minutes(20)).await;
+    let fresh = request(&pool, "Is remote work here to stay?", Duration::minutes(20)).await;
+    let stale = request(&pool, "Is remote work here to stay?", Duration::hours(2)).await;
+
+    let worker = worker(&pool);
+    let mut expired: Vec<Uuid> = worker
+        .run_once()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|request| request.id)
+        .collect();
+    expired.sort();
+    let mut expected = vec![short_lived.id, stale.id];
+    expected.sort();
+    assert_eq!(expired, expected);
+    assert_eq!(
+        status(&pool, &short_lived).await,
+        ConversationRequestStatus::Expired
+    );
+    assert_eq!(
+        status(&pool, &fresh).await,
+        ConversationRequestStatus::Pending
+    );
+    assert_eq!(
+        status(&pool, &stale).await,
+        ConversationRequestStatus::Expired
+    );
+
+    // Running the round again expires nothing more.
+    assert!(worker.run_once().await.unwrap().is_empty());
+    assert_eq!(
+        status(&pool, &fresh).await,
+        ConversationRequestStatus::Pending
+    );
+}
+
+#[sqlx::test(migrator = "db::MIGRATOR")]
+async fn test_rounds_are_skipped_while_another_instance_runs_one(pool: PgPool) {
+    let stale = request(&pool, "Should cities ban cars?", Duration::hours(2)).await;
+    let worker = worker(&pool);
+
+    let mut other = pool.begin().await.unwrap();
+    assert!(
+        locks::try_advisory_xact_lock(&mut *other, ExpiryWorker::LOCK_KEY)
+            .await
+            .unwrap()
+    );
+    assert!(worker.run_once().await.unwrap().is_empty());
+    assert_eq!(
+        status(&pool, &stale).await,
+        ConversationRequestStatus::Pending
+    );
+
+    // Once the other round is over, the request expires.
+    other.commit().await.unwrap();
+    let expired = worker.run_once().await.unwrap();
+    assert_eq!(expired.len(), 1);
+    assert_eq!(expired[0].id, stale.id);
+}
+
+#[sqlx::test(migrator = "db::MIGRATOR")]
+async fn test_admins_set_prompt_ttls(pool: PgPool) {
+    let authenticator = authenticator();
+    let admin = Uuid::new_v4();
+    authenticator.grant_role(admin, Role::Admin);
+    let token = token(&authenticator, admin);
+    let router = PromptTtlsApi::new(pool.clone()).router(authenticator);
+    let ttl = json!({ "prompt": " Should Cities ban cars? ", "ttl_seconds": 600 });
+
+    let (status, body) = send(router.clone(), Method::PUT, "/", Some(&token), Some(ttl)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["prompt"], "should cities ban cars?");
+    assert_eq!(
+        prompt_ttls::get_prompt_ttl(&pool, "should cities ban cars?")
+            .await
+            .unwrap(),
+        Some(Duration::minutes(10))
+    );
+
+    let (status, body) = send(router.clone(), Method::GET, "/", Some(&token), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert_eq!(body[0]["ttl_seconds"], 600);
+
+    let uri = "/?prompt=SHOULD%20cities%20ban%20cars%3F";
+    let (status, _) = send(router.clone(), Method::DELETE, uri, Some(&token), None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, body) = send(router, Method::DELETE, uri, Some(&token), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["code"], "not_found");
+}
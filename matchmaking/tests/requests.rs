@@ -0,0 +1,128 @@
+//! Tests of the conversation requests API against a database.
+
+use axum::Router;
+use axum::http::{Method, StatusCode};
+use chrono::Utc;
+use db::queries::sanctions;
+use matchmaking::RequestsApi;
+use serde_json::{Value, json};
+use shared::types::moderation::{Sanction, SanctionKind};
+use sqlx::PgPool;
+use test_support::{WsClient, authenticator, create_user, send, serve, token};
+use uuid::Uuid;
+
+/// Router allowing `max_pending` pending requests, and a token for a new user.
+async fn router(pool: &PgPool, max_pending: i64) -> (Router, String) {
+    let authenticator = authenticator();
+    let token = token(&authenticator, create_user(pool).await);
+    let api = RequestsApi::new(pool.clone()).with_max_pending(max_pending);
+    (api.router(authenticator), token)
+}
+
+fn request(prompt: &str) -> Value {
+    json!({ "prompt": prompt, "stance": { "position": "agree" } })
+}
+
+#[sqlx::test(migrator = "db::MIGRATOR")]
+async fn test_pending_requests_are_limited(pool: PgPool) {
+    let (router, token) = router(&pool, 2).await;
+
+    // Concurrent requests all check the limit under the user's row lock.
+    let attempts = (0..5).map(|i| {
+        let (router, token) = (router.clone(), token.clone());
+        tokio::spawn(async move {
+            let body = request(&format!("Prompt {i}"));
+            send(router, Method::POST, "/", Some(&token), Some(body)).await
+        })
+    });
+    let mut created = 0;
+    for attempt in attempts.collect::<Vec<_>>() {
+        let (status, body) = attempt.await.unwrap();
+        match status {
+            StatusCode::CREATED => created += 1,
+            StatusCode::CONFLICT => assert_eq!(body["details"]["max_pending"], 2),
+            status => panic!("unexpected status {status}: {body}"),
+        }
+    }
+    assert_eq!(created, 2);
+
+    let (_, pending) = send(
+        router.clone(),
+        Method::GET,
+        "/?status=pending",
+        Some(&token),
+        None,
+    )
+    .await;
+    assert_eq!(pending.as_array().unwrap().len(), 2);
+
+    // Cancelling a request makes room for another.
+    let uri = format!("/{}", pending[0]["id"].as_str().unwrap());
+    let (status, _) = send(router.clone(), Method::DELETE, &uri, Some(&token), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = send(
+        router,
+        Method::POST,
+        "/",
+        Some(&token),
+        Some(request("Again")),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+}
+
+#[sqlx::test(migrator = "db::MIGRATOR")]
+async fn test_only_pending_requests_can_be_cancelled(pool: PgPool) {
+    let (router, token) = router(&pool, 3).await;
+    let (_, created) = send(
+        router.clone(),
+        Method::POST,
+        "/",
+        Some(&token),
+        Some(request("Should cities ban cars?")),
+    )
+    .await;
+    let uri = format!("/{}", created["id"].as_str().unwrap());
+
+    let (status, body) = send(router.clone(), Method::DELETE, &uri, Some(&token), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["status"], "cancelled");
+
+    let (status, body) = send(router.clone(), Method::DELETE, &uri, Some(&token), None).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["details"]["status"], "cancelled");
+
+    // Other users' requests are not found, let alone cancelled.
+    let (other, other_token) = self::router(&pool, 3).await;
+    let (status, _) = send(other, Method::DELETE, &uri, Some(&other_token), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (_, body) = send(router, Method::GET, &uri, Some(&token), None).await;
+    assert_eq!(body["status"], "cancelled");
+}
+
+#[sqlx::test(migrator = "db::MIGRATOR")]
+async fn test_notifications_stop_once_banned(pool: PgPool) {
+    let authenticator = authenticator();
+    let user_id = create_user(&pool).await;
+    let api = RequestsApi::new(pool.clone())
+        .with_sanction_check_interval(std::time::Duration::from_millis(50));
+    let addr = serve(api.router(authenticator.clone())).await;
+    let mut client = WsClient::connect(addr, "/ws", &token(&authenticator, user_id)).await;
+
+    let ban = Sanction {
+        id: Uuid::new_v4(),
+        user_id,
+        kind: SanctionKind::Ban,
+        report_id: None,
+        reason: None,
+        issued_by: create_user(&pool).await,
+        issued_at: Utc::now(),
+        expires_at: None,
+    };
+    sanctions::insert_sanction(&pool, &ban).await.unwrap();
+
+    assert_eq!(
+        client.recv_close().await,
+        (1008, "Account banned".to_string())
+    );
+}
@@ -0,0 +1,119 @@
+//! Tests of the matchmaking worker against a database.
+
+use chrono::{Duration, Utc};
+use db::pubsub::InProcessPubSub;
+use db::queries::{blocks, conversation_requests, sanctions};
+use matchmaking::{MatchmakingWorker, PubSubNotifier};
+use shared::types::conversation::{ConversationRequest, ConversationRequestStatus};
+use shared::types::moderation::{Sanction, SanctionKind};
+use shared::types::stance::{Position, Stance};
+use sqlx::PgPool;
+use test_support::{create_conversation, create_user};
+use uuid::Uuid;
+
+/// Worker notifying matches within the test only.
+fn worker(pool: &PgPool) -> MatchmakingWorker {
+    MatchmakingWorker::new(pool.clone()).with_notifier(PubSubNotifier::new(InProcessPubSub::new()))
+}
+
+/// Create a pending request by a user, taking a position on a prompt.
+async fn request(
+    pool: &PgPool,
+    user_id: Uuid,
+    prompt: &str,
+    position: Position,
+) -> ConversationRequest {
+    let request = ConversationRequest {
+        id: Uuid::new_v4(),
+        user_id,
+        prompt: prompt.to_string(),
+        stance: Stance::new(position),
+        request_time: Utc::now(),
+        status: ConversationRequestStatus::Pending,
+        match_id: None,
+        conversation_id: None,
+    };
+    conversation_requests::insert_request(pool, &request)
+        .await
+        .unwrap();
+    request
+}
+
+#[sqlx::test(migrator = "db::MIGRATOR")]
+async fn test_blocks_keep_users_apart_both_ways(pool: PgPool) {
+    let worker = worker(&pool);
+
+    for (i, blocker_first) in [true, false].into_iter().enumerate() {
+        let prompt = format!("Prompt {i}");
+        let (first, second) = (create_user(&pool).await, create_user(&pool).await);
+        request(&pool, first, &prompt, Position::Agree).await;
+        request(&pool, second, &prompt, Position::Disagree).await;
+        let (blocker, blocked) = match blocker_first {
+            true => (first, second),
+            false => (second, first),
+        };
+        blocks::block(&pool, blocker, blocked).await.unwrap();
+
+        assert!(worker.run_once().await.unwrap().is_empty());
+
+        // Once unblocked, they are matched like anyone else.
+        blocks::unblock(&pool, blocker, blocked).await.unwrap();
+        let matches = worker.run_once().await.unwrap();
+        assert_eq!(matches.len(), 1);
+        assert!(matches[0].conversation.has_participant(first));
+        assert!(matches[0].conversation.has_participant(second));
+    }
+}
+
+#[sqlx::test(migrator = "db::MIGRATOR")]
+async fn test_recent_partners_are_not_matched_again(pool: PgPool) {
+    let (first, second) = (create_user(&pool).await, create_user(&pool).await);
+    create_conversation(&pool, first, second).await;
+    request(&pool, first, "Should cities ban cars?", Position::Agree).await;
+    request(&pool, second, "Should cities ban cars?", Position::Disagree).await;
+
+    let cooling_down = worker(&pool).with_rematch_cooldown(Duration::days(7));
+    assert!(cooling_down.run_once().await.unwrap().is_empty());
+
+    // Once the conversation is older than the cooldown, they may meet again.
+    sqlx::query("UPDATE conversations SET created_at = now() - interval '8 days'")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let matches = cooling_down.run_once().await.unwrap();
+    assert_eq!(matches.len(), 1);
+    assert!(matches[0].conversation.has_participant(first));
+    assert!(matches[0].conversation.has_participant(second));
+}
+
+#[sqlx::test(migrator = "db::MIGRATOR")]
+async fn test_barred_users_are_not_matched(pool: PgPool) {
+    let (barred, other) = (create_user(&pool).await, create_user(&pool).await);
+    request(&pool, barred, "Should cities ban cars?", Position::Agree).await;
+    request(&pool, other, "Should cities ban cars?", Position::Disagree).await;
+    let suspension = Sanction {
+        id: Uuid::new_v4(),
+        user_id: barred,
+        kind: SanctionKind::Suspension,
+        report_id: None,
+        reason: None,
+        issued_by: create_user(&pool).await,
+        issued_at: Utc::now(),
+        expires_at: Some(Utc::now() + Duration::days(1)),
+    };
+    sanctions::insert_sanction(&pool, &suspension)
+        .await
+        .unwrap();
+
+    let worker = worker(&pool);
+    assert!(worker.run_once().await.unwrap().is_empty());
+
+    // Once the suspension has lapsed, the pending request is matched again.
+    sqlx::query("UPDATE user_sanctions SET expires_at = now() - interval '1 minute'")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let matches = worker.run_once().await.unwrap();
+    assert_eq!(matches.len(), 1);
+    assert!(matches[0].conversation.has_participant(barred));
+}
@@ -0,0 +1,100 @@
+//! HTTP endpoints for users to block each other.
+
+use auth::middleware::auth_standard;
+use auth::models::{AuthenticatedUser, Authenticator};
+use axum::extract::rejection::PathRejection;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::routing::{get, put};
+use axum::{Extension, Json, Router, middleware};
+use db::queries::blocks;
+use shared::error::ApiError;
+use shared::types::moderation::Block;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// The block list API, served by [`BlocksApi::router`].
+///
+/// Blocked users are never matched with the user who blocked them, and the
+/// other way around.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use auth::models::SbAuthenticator;
+/// use moderation::BlocksApi;
+///
+/// # async fn example() {
+/// let pool = db::create_pool().await.unwrap();
+/// let app = axum::Router::new().nest(
+///     "/blocks",
+///     BlocksApi::new(pool).router(SbAuthenticator::default()),
+/// );
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct BlocksApi {
+    pool: PgPool,
+}
+
+impl BlocksApi {
+    /// Create a new BlocksApi using the provided pool.
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Creates a router serving the API to users authenticated by `auth_standard`.
+    ///
+    /// The router includes the following endpoints:
+    ///  - `GET /` - list the users the caller blocked, most recently blocked first
+    ///  - `PUT /{user_id}` - block a user; blocking them again leaves the block as it is
+    ///  - `DELETE /{user_id}` - unblock a user
+    pub fn router<A: Authenticator>(self, authenticator: A) -> Router {
+        Router::new()
+            .route("/", get(list_blocks))
+            .route("/{user_id}", put(block_user).delete(unblock_user))
+            .route_layer(middleware::from_fn_with_state(
+                authenticator,
+                auth_standard::<A>,
+            ))
+            .with_state(self)
+    }
+}
+
+/// List the users the caller blocked.
+async fn list_blocks(
+    State(api): State<BlocksApi>,
+    Extension(user): Extension<AuthenticatedUser>,
+) -> Result<Json<Vec<Block>>, ApiError> {
+    Ok(Json(blocks::list_blocked(&api.pool, user.id).await?))
+}
+
+/// Block a user.
+async fn block_user(
+    State(api): State<BlocksApi>,
+    Extension(user): Extension<AuthenticatedUser>,
+    blocked_id: Result<Path<Uuid>, PathRejection>,
+) -> Result<Json<Block>, ApiError> {
+    let Path(blocked_id) = blocked_id?;
+    if blocked_id == user.id {
+        return Err(ApiError::validation("You cannot block yourself"));
+    }
+
+    blocks::block(&api.pool, user.id, blocked_id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found("User not found"))
+}
+
+/// Unblock a user.
+async fn unblock_user(
+    State(api): State<BlocksApi>,
+    Extension(user): Extension<AuthenticatedUser>,
+    blocked_id: Result<Path<Uuid>, PathRejection>,
+) -> Result<StatusCode, ApiError> {
+    let Path(blocked_id) = blocked_id?;
+    if !blocks::unblock(&api.pool, user.id, blocked_id).await? {
+        return Err(ApiError::not_found("User is not blocked"));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
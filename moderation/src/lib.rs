@@ -0,0 +1,20 @@
+//! # Moderation Crate
+//!
+//! Lets participants report each other, and moderators act on their reports.
+//! Filing a report through the [`ReportsApi`] snapshots the offending messages
+//! and ends the conversation with `UserReported`. Messages flagged by the
+//! chat's message filters are reported too, without a reporter, but leave the
+//! conversation going. Reports then wait in the moderation queue, served by
+//! the [`ModerationApi`], until a moderator claims and resolves them, possibly
+//! warning, suspending or banning the reported user.
+//! Suspended and banned users are turned away by the authentication middleware.
+//! Users may also block each other through the [`BlocksApi`], so that they are
+//! never matched again.
+
+mod blocks;
+mod queue;
+mod reports;
+
+pub use blocks::BlocksApi;
+pub use queue::ModerationApi;
+pub use reports::ReportsApi;
@@ -0,0 +1,322 @@
+//! HTTP endpoints for moderators to review reports and sanction reported users.
+
+use auth::authorization::{Authorization, RoleSource, authorize, require_permission};
+use auth::middleware::auth_standard;
+use auth::models::{AuthenticatedUser, Authenticator};
+use axum::extract::rejection::{JsonRejection, PathRejection, QueryRejection};
+use axum::extract::{Path, Query, State};
+use axum::routing::{get, post};
+use axum::{Extension, Json, Router, middleware};
+use chrono::{Duration, Utc};
+use db::error::DbError;
+use db::queries::{messages, reports, sanctions};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use shared::error::ApiError;
+use shared::types::conversation::MessageRevision;
+use shared::types::moderation::{ModerationAction, Report, ReportStatus, Sanction, SanctionKind};
+use shared::types::role::{Permission, Role};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Most reports listed at once.
+const LIST_LIMIT: i64 = 100;
+
+/// Length of suspensions unless the moderator chooses otherwise, in days.
+const DEFAULT_SUSPENSION_DAYS: i64 = 7;
+
+/// Longest suspension a moderator may impose, in days; longer ones should be bans.
+const MAX_SUSPENSION_DAYS: i64 = 365;
+
+/// Longest resolution note accepted, in characters.
+const MAX_NOTE_LENGTH: usize = 2000;
+
+/// The moderation queue API, served by [`ModerationApi::router`].
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use auth::authorization::PgRoles;
+/// use auth::models::SbAuthenticator;
+/// use moderation::ModerationApi;
+///
+/// # async fn example() {
+/// let pool = db::create_pool().await.unwrap();
+/// let app = axum::Router::new().nest(
+///     "/moderation",
+///     ModerationApi::new(pool.clone())
+///         .with_role_source(PgRoles::new(pool))
+///         .router(SbAuthenticator::default()),
+/// );
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ModerationApi {
+    pool: PgPool,
+    authorization: Authorization,
+}
+
+impl ModerationApi {
+    /// Create a new ModerationApi using the provided pool.
+    ///
+    /// Moderators are recognized by the role claimed by their token unless
+    /// configured otherwise with [`ModerationApi::with_role_source`].
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            authorization: require_permission(Permission::ReviewReports),
+        }
+    }
+
+    /// Determine users' roles using the given source instead of their tokens' claims.
+    pub fn with_role_source(mut self, roles: impl RoleSource) -> Self {
+        self.authorization = self.authorization.with_role_source(roles);
+        self
+    }
+
+    /// Creates a router serving the API to users authenticated by `auth_standard`
+    /// and holding the `review_reports` permission.
+    ///
+    /// The router includes the following endpoints:
+    ///  - `GET /reports` - list reports, oldest first; unresolved ones unless filtered with `?status=`
+    ///  - `GET /reports/{id}` - get a report, with its snapshot of the reported messages
+    ///  - `GET /reports/{id}/revisions` - list the earlier text of the reported messages,
+    ///    replaced by their senders' edits and deletions
+    ///  - `POST /reports/{id}/claim` - claim an open report for review by the caller
+    ///  - `POST /reports/{id}/resolve` - resolve a report claimed by the caller with an
+    ///    action, sanctioning the reported user unless it is `dismiss`
+    ///
+    /// Warning, suspending and banning users also requires the `sanction_users` permission.
+    pub fn router<A: Authenticator>(self, authenticator: A) -> Router {
+        Router::new()
+            .route("/reports", get(list_reports))
+            .route("/reports/{id}", get(get_report))
+            .route("/reports/{id}/revisions", get(list_revisions))
+            .route("/reports/{id}/claim", post(claim_report))
+            .route("/reports/{id}/resolve", post(resolve_report))
+            .route_layer(middleware::from_fn_with_state(
+                self.authorization.clone(),
+                authorize,
+            ))
+            .route_layer(middleware::from_fn_with_state(
+                authenticator,
+                auth_standard::<A>,
+            ))
+            .with_state(self)
+    }
+}
+
+/// Filter for listing reports.
+#[derive(Debug, Deserialize)]
+struct ListQuery {
+    status: Option<ReportStatus>,
+}
+
+/// Resolution of a claimed report.
+///
+/// `suspension_days` sets the length of suspensions, and is only accepted with
+/// the `suspend` action.
+#[derive(Debug, Deserialize)]
+struct ResolveReport {
+    action: ModerationAction,
+    note: Option<String>,
+    suspension_days: Option<i64>,
+}
+
+impl ResolveReport {
+    /// Validate the resolution, returning its trimmed note and how long the
+    /// sanction it imposes lasts, if it lapses.
+    fn validate(&self) -> Result<(Option<String>, Option<Duration>), ApiError> {
+        let note = self
+            .note
+            .as_deref()
+            .map(str::trim)
+            .filter(|note| !note.is_empty());
+        if note.is_some_and(|note| note.chars().count() > MAX_NOTE_LENGTH) {
+            return Err(ApiError::validation(format!(
+                "Note must be at most {MAX_NOTE_LENGTH} characters"
+            )));
+        }
+
+        let duration = match (self.action, self.suspension_days) {
+            (ModerationAction::Suspend, days) => {
+                let days = days.unwrap_or(DEFAULT_SUSPENSION_DAYS);
+                if !(1..=MAX_SUSPENSION_DAYS).contains(&days) {
+                    return Err(ApiError::validation(format!(
+                        "Suspensions must last between 1 and {MAX_SUSPENSION_DAYS} days"
+                    )));
+                }
+                Some(Duration::days(days))
+            }
+            (_, Some(_)) => {
+                return Err(ApiError::validation(
+                    "Only suspensions have a length in days",
+                ));
+            }
+            (_, None) => None,
+        };
+
+        Ok((note.map(str::to_string), duration))
+    }
+}
+
+/// A resolved report and the sanction imposed on resolving it, if any.
+#[derive(Debug, Serialize)]
+struct Resolution {
+    report: Report,
+    sanction: Option<Sanction>,
+}
+
+/// List reports in the queue.
+async fn list_reports(
+    State(api): State<ModerationApi>,
+    query: Result<Query<ListQuery>, QueryRejection>,
+) -> Result<Json<Vec<Report>>, ApiError> {
+    let Query(query) = query?;
+    Ok(Json(
+        reports::list_reports(&api.pool, query.status, LIST_LIMIT).await?,
+    ))
+}
+
+/// Get a report.
+async fn get_report(
+    State(api): State<ModerationApi>,
+    id: Result<Path<Uuid>, PathRejection>,
+) -> Result<Json<Report>, ApiError> {
+    let Path(id) = id?;
+    Ok(Json(existing_report(&api.pool, id).await?))
+}
+
+/// List the revisions of the messages a report is about, oldest first.
+async fn list_revisions(
+    State(api): State<ModerationApi>,
+    id: Result<Path<Uuid>, PathRejection>,
+) -> Result<Json<Vec<MessageRevision>>, ApiError> {
+    let Path(id) = id?;
+    let report = existing_report(&api.pool, id).await?;
+    let message_ids: Vec<Uuid> = report.messages.iter().map(|message| message.id).collect();
+    Ok(Json(messages::revisions(&api.pool, &message_ids).await?))
+}
+
+/// Claim a report for review by the caller.
+async fn claim_report(
+    State(api): State<ModerationApi>,
+    Extension(user): Extension<AuthenticatedUser>,
+    id: Result<Path<Uuid>, PathRejection>,
+) -> Result<Json<Report>, ApiError> {
+    let Path(id) = id?;
+    if let Some(report) = reports::claim(&api.pool, id, user.id, Utc::now()).await? {
+        return Ok(Json(report));
+    }
+
+    let report = existing_report(&api.pool, id).await?;
+    Err(unavailable("Only open reports can be claimed", &report))
+}
+
+/// Resolve a report claimed by the caller, sanctioning the reported user as decided.
+async fn resolve_report(
+    State(api): State<ModerationApi>,
+    Extension(user): Extension<AuthenticatedUser>,
+    Extension(role): Extension<Role>,
+    id: Result<Path<Uuid>, PathRejection>,
+    payload: Result<Json<ResolveReport>, JsonRejection>,
+) -> Result<Json<Resolution>, ApiError> {
+    let Path(id) = id?;
+    let Json(payload) = payload?;
+    let (note, duration) = payload.validate()?;
+    let kind = payload.action.sanction();
+    if kind.is_some() && !role.has_permission(Permission::SanctionUsers) {
+        return Err(ApiError::forbidden(format!(
+            "Requires the {} permission",
+            Permission::SanctionUsers
+        )));
+    }
+
+    let now = Utc::now();
+    let mut tx = api.pool.begin().await.map_err(DbError::Connection)?;
+    let Some(report) =
+        reports::resolve(&mut *tx, id, user.id, payload.action, note.as_deref(), now).await?
+    else {
+        let report = existing_report(&api.pool, id).await?;
+        return Err(unavailable(
+            "Only reports claimed by you can be resolved",
+            &report,
+        ));
+    };
+
+    let sanction = kind.map(|kind| Sanction {
+        id: Uuid::new_v4(),
+        user_id: report.reported_user_id,
+        kind,
+        report_id: Some(report.id),
+        reason: note,
+        issued_by: user.id,
+        issued_at: now,
+        expires_at: match kind {
+            SanctionKind::Suspension => duration.map(|duration| now + duration),
+            SanctionKind::Warning | SanctionKind::Ban => None,
+        },
+    });
+    if let Some(sanction) = &sanction {
+        sanctions::insert_sanction(&mut *tx, sanction).await?;
+    }
+    tx.commit().await.map_err(DbError::Query)?;
+
+    Ok(Json(Resolution { report, sanction }))
+}
+
+/// Look up a report.
+async fn existing_report(pool: &PgPool, id: Uuid) -> Result<Report, ApiError> {
+    reports::get_report(pool, id)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Report not found"))
+}
+
+/// Conflict for a report that is no longer in the state an operation requires.
+fn unavailable(message: &str, report: &Report) -> ApiError {
+    ApiError::conflict(message).with_details(json!({
+        "status": report.status,
+        "claimed_by": report.claimed_by,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::error::ErrorCode;
+
+    fn resolve(action: ModerationAction, suspension_days: Option<i64>) -> ResolveReport {
+        ResolveReport {
+            action,
+            note: Some("  Repeated insults ".to_string()),
+            suspension_days,
+        }
+    }
+
+    #[test]
+    fn test_resolution_durations() {
+        let (note, duration) = resolve(ModerationAction::Suspend, None).validate().unwrap();
+        assert_eq!(note.as_deref(), Some("Repeated insults"));
+        assert_eq!(duration, Some(Duration::days(DEFAULT_SUSPENSION_DAYS)));
+
+        let (_, duration) = resolve(ModerationAction::Suspend, Some(30))
+            .validate()
+            .unwrap();
+        assert_eq!(duration, Some(Duration::days(30)));
+
+        let (_, duration) = resolve(ModerationAction::Ban, None).validate().unwrap();
+        assert_eq!(duration, None);
+    }
+
+    #[test]
+    fn test_resolution_validation() {
+        for invalid in [
+            resolve(ModerationAction::Suspend, Some(0)),
+            resolve(ModerationAction::Suspend, Some(MAX_SUSPENSION_DAYS + 1)),
+            resolve(ModerationAction::Warn, Some(7)),
+        ] {
+            let error = invalid.validate().unwrap_err();
+            assert_eq!(error.code(), ErrorCode::ValidationFailed);
+        }
+    }
+}
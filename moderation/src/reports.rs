@@ -0,0 +1,240 @@
+//! HTTP endpoint for participants to report each other.
+
+use std::collections::HashSet;
+
+use auth::middleware::auth_standard;
+use auth::models::{AuthenticatedUser, Authenticator};
+use axum::extract::State;
+use axum::extract::rejection::JsonRejection;
+use axum::http::StatusCode;
+use axum::routing::post;
+use axum::{Extension, Json, Router, middleware};
+use chat::ChatHub;
+use chat::lifecycle::announce_end;
+use chrono::Utc;
+use db::error::DbError;
+use db::queries::{conversations, messages, reports, sources};
+use serde::Deserialize;
+use shared::error::ApiError;
+use shared::types::conversation::ConversationEndReason;
+use shared::types::moderation::{Report, ReportCategory, ReportStatus};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Longest report details accepted, in characters.
+const MAX_DETAILS_LENGTH: usize = 2000;
+
+/// Most messages a report may point out.
+const MAX_REPORTED_MESSAGES: usize = 50;
+
+/// Messages snapshotted when a report points out none in particular.
+const SNAPSHOT_LENGTH: i64 = 50;
+
+/// The reports API, served by [`ReportsApi::router`].
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use auth::models::SbAuthenticator;
+/// use moderation::ReportsApi;
+///
+/// # async fn example() {
+/// let pool = db::create_pool().await.unwrap();
+/// let app = axum::Router::new().nest(
+///     "/reports",
+///     ReportsApi::new(pool).router(SbAuthenticator::default()),
+/// );
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ReportsApi {
+    pool: PgPool,
+    hub: ChatHub,
+}
+
+impl ReportsApi {
+    /// Create a new ReportsApi using the provided pool.
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            hub: ChatHub::default(),
+        }
+    }
+
+    /// Tell participants that reported conversations ended through the given hub,
+    /// which should be the one chat connections are served from.
+    pub fn with_hub(mut self, hub: ChatHub) -> Self {
+        self.hub = hub;
+        self
+    }
+
+    /// Creates a router serving the API to users authenticated by `auth_standard`.
+    ///
+    /// The router includes the following endpoints:
+    ///  - `POST /` - report the other participant of one of the caller's conversations,
+    ///    ending the conversation if it is still active
+    ///
+    /// Each participant may report a conversation once.
+    pub fn router<A: Authenticator>(self, authenticator: A) -> Router {
+        Router::new()
+            .route("/", post(file_report))
+            .route_layer(middleware::from_fn_with_state(
+                authenticator,
+                auth_standard::<A>,
+            ))
+            .with_state(self)
+    }
+}
+
+/// Report of the other participant of a conversation.
+///
+/// `message_ids` points out the offending messages; if empty, the latest
+/// messages of the conversation are snapshotted instead.
+#[derive(Debug, Deserialize)]
+struct FileReport {
+    conversation_id: Uuid,
+    category: ReportCategory,
+    details: Option<String>,
+    #[serde(default)]
+    message_ids: Vec<Uuid>,
+}
+
+impl FileReport {
+    /// Validate the report, returning its trimmed details and the distinct messages it points out.
+    fn validate(&self) -> Result<(Option<String>, Vec<Uuid>), ApiError> {
+        let details = self
+            .details
+            .as_deref()
+            .map(str::trim)
+            .filter(|details| !details.is_empty());
+        if details.is_some_and(|details| details.chars().count() > MAX_DETAILS_LENGTH) {
+            return Err(ApiError::validation(format!(
+                "Details must be at most {MAX_DETAILS_LENGTH} characters"
+            )));
+        }
+
+        let mut seen = HashSet::new();
+        let message_ids: Vec<Uuid> = self
+            .message_ids
+            .iter()
+            .copied()
+            .filter(|id| seen.insert(*id))
+            .collect();
+        if message_ids.len() > MAX_REPORTED_MESSAGES {
+            return Err(ApiError::validation(format!(
+                "At most {MAX_REPORTED_MESSAGES} messages may be reported"
+            )));
+        }
+
+        Ok((details.map(str::to_string), message_ids))
+    }
+}
+
+/// File a report against the other participant, ending the conversation.
+async fn file_report(
+    State(api): State<ReportsApi>,
+    Extension(user): Extension<AuthenticatedUser>,
+    payload: Result<Json<FileReport>, JsonRejection>,
+) -> Result<(StatusCode, Json<Report>), ApiError> {
+    let Json(payload) = payload?;
+    let (details, message_ids) = payload.validate()?;
+
+    let conversation = conversations::get_conversation(&api.pool, payload.conversation_id)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Conversation not found"))?;
+    let reported_user_id = conversation
+        .partner_of(user.id)
+        .ok_or_else(|| ApiError::forbidden("Only participants may report the conversation"))?;
+
+    let mut messages = if message_ids.is_empty() {
+        messages::latest_messages(&api.pool, conversation.id, SNAPSHOT_LENGTH).await?
+    } else {
+        let messages = messages::get_messages(&api.pool, conversation.id, &message_ids).await?;
+        if messages.len() != message_ids.len() {
+            return Err(ApiError::validation(
+                "Reported messages must belong to the conversation",
+            ));
+        }
+        messages
+    };
+    sources::load_citations(&api.pool, &mut messages).await?;
+
+    let now = Utc::now();
+    let report = Report {
+        id: Uuid::new_v4(),
+        conversation_id: conversation.id,
+        reporter_id: Some(user.id),
+        reported_user_id,
+        category: payload.category,
+        details,
+        messages,
+        status: ReportStatus::Open,
+        created_at: now,
+        claimed_by: None,
+        claimed_at: None,
+        resolved_at: None,
+        action: None,
+        resolution_note: None,
+    };
+
+    let mut tx = api.pool.begin().await.map_err(DbError::Connection)?;
+    if !reports::insert_report(&mut *tx, &report).await? {
+        return Err(ApiError::conflict(
+            "You have already reported this conversation",
+        ));
+    }
+    // Conversations that already ended, say because the reported user left, stay as they are.
+    let ended = conversations::end_conversation(
+        &mut *tx,
+        conversation.id,
+        ConversationEndReason::UserReported,
+        now,
+    )
+    .await?;
+    tx.commit().await.map_err(DbError::Query)?;
+
+    if let Some(ended) = ended {
+        announce_end(&api.hub, &ended, Some(user.id)).await;
+    }
+    Ok((StatusCode::CREATED, Json(report)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::error::ErrorCode;
+
+    fn report(details: Option<&str>, message_ids: Vec<Uuid>) -> FileReport {
+        FileReport {
+            conversation_id: Uuid::new_v4(),
+            category: ReportCategory::Harassment,
+            details: details.map(str::to_string),
+            message_ids,
+        }
+    }
+
+    #[test]
+    fn test_report_is_normalized() {
+        let id = Uuid::new_v4();
+        let (details, message_ids) = report(Some("  Insults  "), vec![id, id])
+            .validate()
+            .unwrap();
+        assert_eq!(details.as_deref(), Some("Insults"));
+        assert_eq!(message_ids, vec![id]);
+
+        let (details, _) = report(Some("   "), Vec::new()).validate().unwrap();
+        assert_eq!(details, None);
+    }
+
+    #[test]
+    fn test_report_validation() {
+        let too_long = "a".repeat(MAX_DETAILS_LENGTH + 1);
+        let too_many = (0..=MAX_REPORTED_MESSAGES)
+            .map(|_| Uuid::new_v4())
+            .collect();
+        for invalid in [report(Some(&too_long), Vec::new()), report(None, too_many)] {
+            let error = invalid.validate().unwrap_err();
+            assert_eq!(error.code(), ErrorCode::ValidationFailed);
+        }
+    }
+}
@@ -0,0 +1,120 @@
+//! Tests of the reports and moderation endpoints that are rejected before reaching the database.
+
+use auth::models::MockAuthenticator;
+use axum::http::{Method, StatusCode};
+use moderation::{BlocksApi, ModerationApi, ReportsApi};
+use serde_json::json;
+use shared::types::role::Role;
+use test_support::{authenticator, lazy_pool, send, token};
+use uuid::Uuid;
+
+/// Create a session for a new user, holding the given role if any.
+fn token_for(authenticator: &MockAuthenticator, role: Option<Role>) -> String {
+    let user_id = Uuid::new_v4();
+    if let Some(role) = role {
+        authenticator.grant_role(user_id, role);
+    }
+    token(authenticator, user_id)
+}
+
+#[tokio::test]
+async fn test_reports_require_authentication() {
+    let router = ReportsApi::new(lazy_pool()).router(authenticator());
+    let report = json!({ "conversation_id": Uuid::new_v4(), "category": "spam" });
+
+    let (status, body) = send(router, Method::POST, "/", None, Some(report)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "missing_credentials");
+}
+
+#[tokio::test]
+async fn test_reports_are_validated() {
+    let authenticator = authenticator();
+    let token = token_for(&authenticator, None);
+    let router = ReportsApi::new(lazy_pool()).router(authenticator);
+
+    let unknown_category = json!({ "conversation_id": Uuid::new_v4(), "category": "rudeness" });
+    let (status, _) = send(
+        router.clone(),
+        Method::POST,
+        "/",
+        Some(&token),
+        Some(unknown_category),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let too_long = json!({
+        "conversation_id": Uuid::new_v4(),
+        "category": "other",
+        "details": "a".repeat(2001),
+    });
+    let (status, body) = send(router, Method::POST, "/", Some(&token), Some(too_long)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["code"], "validation_failed");
+}
+
+#[tokio::test]
+async fn test_queue_requires_moderators() {
+    let authenticator = authenticator();
+    let token = token_for(&authenticator, None);
+    let router = ModerationApi::new(lazy_pool()).router(authenticator);
+
+    let revisions = format!("/reports/{}/revisions", Uuid::new_v4());
+    for uri in ["/reports", revisions.as_str()] {
+        let (status, body) = send(router.clone(), Method::GET, uri, Some(&token), None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["details"]["required_permission"], "review_reports");
+    }
+}
+
+#[tokio::test]
+async fn test_resolutions_are_validated() {
+    let authenticator = authenticator();
+    let token = token_for(&authenticator, Some(Role::Moderator));
+    let router = ModerationApi::new(lazy_pool()).router(authenticator);
+    let uri = format!("/reports/{}/resolve", Uuid::new_v4());
+
+    let (status, body) = send(
+        router.clone(),
+        Method::POST,
+        &uri,
+        Some(&token),
+        Some(json!({ "action": "suspend", "suspension_days": 0 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["code"], "validation_failed");
+
+    let (status, _) = send(
+        router,
+        Method::POST,
+        "/reports/not-a-uuid/resolve",
+        Some(&token),
+        Some(json!({ "action": "warn" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_users_cannot_block_themselves() {
+    let authenticator = authenticator();
+    let user_id = Uuid::new_v4();
+    let token = token(&authenticator, user_id);
+    let router = BlocksApi::new(lazy_pool()).router(authenticator);
+
+    let (status, body) = send(
+        router.clone(),
+        Method::PUT,
+        &format!("/{user_id}"),
+        Some(&token),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["code"], "validation_failed");
+
+    let (status, _) = send(router, Method::DELETE, "/not-a-uuid", Some(&token), None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,119 @@
+//! Tests of filing and resolving reports against a database.
+
+use axum::http::{Method, StatusCode};
+use chrono::Utc;
+use db::queries::{conversations, messages, sanctions};
+use moderation::{ModerationApi, ReportsApi};
+use serde_json::json;
+use shared::types::conversation::{ConversationEndReason, ConversationState, Message};
+use shared::types::moderation::SanctionKind;
+use shared::types::role::Role;
+use sqlx::PgPool;
+use test_support::{authenticator, create_conversation, create_user, send, token};
+use uuid::Uuid;
+
+#[sqlx::test(migrator = "db::MIGRATOR")]
+async fn test_reports_end_the_conversation_and_lead_to_sanctions(pool: PgPool) {
+    let (reporter, reported) = (create_user(&pool).await, create_user(&pool).await);
+    let conversation = create_conversation(&pool, reporter, reported).await;
+    let message = Message {
+        id: Uuid::new_v4(),
+        conversation_id: conversation.id,
+        sender_id: reported,
+        content: "Get lost".to_string(),
+        sent_at: Utc::now(),
+        edited_at: None,
+        deleted_at: None,
+        sources: Vec::new(),
+    };
+    assert!(messages::insert_message(&pool, &message).await.unwrap());
+
+    let authenticator = authenticator();
+    let moderator = create_user(&pool).await;
+    authenticator.grant_role(moderator, Role::Moderator);
+    let reports = ReportsApi::new(pool.clone()).router(authenticator.clone());
+    let queue = ModerationApi::new(pool.clone()).router(authenticator.clone());
+    let (reporter_token, moderator_token) = (
+        token(&authenticator, reporter),
+        token(&authenticator, moderator),
+    );
+
+    let report = json!({
+        "conversation_id": conversation.id,
+        "category": "harassment",
+        "message_ids": [message.id],
+    });
+    let (status, report) = send(
+        reports.clone(),
+        Method::POST,
+        "/",
+        Some(&reporter_token),
+        Some(report),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(report["reported_user_id"], json!(reported));
+    assert_eq!(report["messages"][0]["content"], "Get lost");
+
+    let ended = conversations::get_conversation(&pool, conversation.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(matches!(
+        ended.state(),
+        Ok(ConversationState::Ended {
+            reason: ConversationEndReason::UserReported,
+            ..
+        })
+    ));
+
+    // Participants report a conversation once.
+    let again = json!({ "conversation_id": conversation.id, "category": "spam" });
+    let (status, _) = send(
+        reports,
+        Method::POST,
+        "/",
+        Some(&reporter_token),
+        Some(again),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let id = report["id"].as_str().unwrap();
+    let (status, _) = send(
+        queue.clone(),
+        Method::POST,
+        &format!("/reports/{id}/claim"),
+        Some(&moderator_token),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, resolution) = send(
+        queue,
+        Method::POST,
+        &format!("/reports/{id}/resolve"),
+        Some(&moderator_token),
+        Some(json!({ "action": "suspend", "suspension_days": 3 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(resolution["report"]["status"], "resolved");
+
+    let sanction = sanctions::barring_sanction(&pool, reported, Utc::now())
+        .await
+        .unwrap()
+        .expect("the reported user is suspended");
+    assert_eq!(sanction.kind, SanctionKind::Suspension);
+    assert_eq!(
+        sanction.report_id.map(|id| id.to_string()).as_deref(),
+        Some(id)
+    );
+    assert_eq!(sanction.issued_by, moderator);
+    assert!(
+        sanctions::barring_sanction(&pool, reporter, Utc::now())
+            .await
+            .unwrap()
+            .is_none()
+    );
+}
@@ -0,0 +1,335 @@
+use axum::extract::rejection::{JsonRejection, PathRejection, QueryRejection};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+
+use crate::request_id;
+
+/// Stable, machine-readable error codes returned by the API
+///
+/// Clients may branch on these, so existing codes must not be renamed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The request is malformed
+    BadRequest,
+    /// The request is well-formed but its content is invalid
+    ValidationFailed,
+    /// The contact information is not valid for the chosen channel
+    InvalidContact,
+    /// The one-time password is wrong or has expired
+    InvalidOtp,
+    /// The request carries no credentials
+    MissingCredentials,
+    /// The credentials are malformed, invalid or expired
+    InvalidToken,
+    /// The session the credentials belong to has ended
+    SessionInactive,
+    /// The user may not perform the operation
+    Forbidden,
+    /// The user has been suspended or banned
+    AccountSuspended,
+    /// The requested resource does not exist
+    NotFound,
+    /// The request conflicts with the current state of the resource
+    Conflict,
+    /// The request body is too large
+    PayloadTooLarge,
+    /// Too many requests were made; see the `Retry-After` header
+    RateLimited,
+    /// The one-time password could not be delivered
+    OtpDeliveryFailed,
+    /// A service the API depends on failed
+    UpstreamError,
+    /// The API is temporarily unable to handle the request
+    ServiceUnavailable,
+    /// An unexpected error occurred
+    Internal,
+}
+
+impl ErrorCode {
+    /// The code as it appears in responses
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::BadRequest => "bad_request",
+            ErrorCode::ValidationFailed => "validation_failed",
+            ErrorCode::InvalidContact => "invalid_contact",
+            ErrorCode::InvalidOtp => "invalid_otp",
+            ErrorCode::MissingCredentials => "missing_credentials",
+            ErrorCode::InvalidToken => "invalid_token",
+            ErrorCode::SessionInactive => "session_inactive",
+            ErrorCode::Forbidden => "forbidden",
+            ErrorCode::AccountSuspended => "account_suspended",
+            ErrorCode::NotFound => "not_found",
+            ErrorCode::Conflict => "conflict",
+            ErrorCode::PayloadTooLarge => "payload_too_large",
+            ErrorCode::RateLimited => "rate_limited",
+            ErrorCode::OtpDeliveryFailed => "otp_delivery_failed",
+            ErrorCode::UpstreamError => "upstream_error",
+            ErrorCode::ServiceUnavailable => "service_unavailable",
+            ErrorCode::Internal => "internal",
+        }
+    }
+
+    /// The HTTP status errors with this code are returned with, unless overridden
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ErrorCode::BadRequest
+            | ErrorCode::InvalidContact
+            | ErrorCode::InvalidOtp
+            | ErrorCode::MissingCredentials => StatusCode::BAD_REQUEST,
+            ErrorCode::ValidationFailed => StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::InvalidToken | ErrorCode::SessionInactive => StatusCode::UNAUTHORIZED,
+            ErrorCode::Forbidden | ErrorCode::AccountSuspended => StatusCode::FORBIDDEN,
+            ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::Conflict => StatusCode::CONFLICT,
+            ErrorCode::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorCode::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::OtpDeliveryFailed | ErrorCode::UpstreamError => StatusCode::BAD_GATEWAY,
+            ErrorCode::ServiceUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+            ErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An error returned by the API
+///
+/// Responds with a JSON body of the form
+///
+/// ```json
+/// { "code": "invalid_otp", "message": "Invalid or expired OTP", "details": {}, "request_id": "..." }
+/// ```
+///
+/// where `details` is only present if set, and `request_id` only if the request
+/// went through the [`request_id`](crate::request_id::request_id) middleware.
+///
+/// Errors from other crates are converted into an `ApiError` by a `From`
+/// implementation next to the error type, so that handlers can use `?`. The
+/// message must be safe to show to clients; the underlying error can be attached
+/// with [`ApiError::with_source`] to be logged instead.
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    code: ErrorCode,
+    message: String,
+    details: Option<Value>,
+    source: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ApiErrorBody<'a> {
+    code: ErrorCode,
+    message: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<&'a Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+}
+
+impl ApiError {
+    /// Create a new error with the given code and message, using the code's status
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            status: code.status(),
+            code,
+            message: message.into(),
+            details: None,
+            source: None,
+        }
+    }
+
+    /// An error for a malformed request
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::BadRequest, message)
+    }
+
+    /// An error for a request whose content is invalid
+    pub fn validation(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::ValidationFailed, message)
+    }
+
+    /// An error for a user lacking the right to perform an operation
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Forbidden, message)
+    }
+
+    /// An error for a resource that does not exist
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::NotFound, message)
+    }
+
+    /// An error for a request conflicting with the state of a resource
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Conflict, message)
+    }
+
+    /// An unexpected error, whose cause is logged rather than returned
+    pub fn internal(source: impl fmt::Display) -> Self {
+        Self::new(ErrorCode::Internal, "Internal server error").with_source(source)
+    }
+
+    /// Respond with the given status instead of the code's default status
+    pub fn with_status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Attach a JSON object with further information for the client
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    /// Attach the underlying error, which is logged but not returned to the client
+    pub fn with_source(mut self, source: impl fmt::Display) -> Self {
+        self.source = Some(source.to_string());
+        self
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        self.code
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn details(&self) -> Option<&Value> {
+        self.details.as_ref()
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)?;
+        if let Some(source) = &self.source {
+            write!(f, " ({source})")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let request_id = request_id::current();
+
+        if self.status.is_server_error() {
+            eprintln!(
+                "[{}] {} {}",
+                request_id.as_deref().unwrap_or("-"),
+                self.status,
+                self
+            );
+        }
+
+        let body = ApiErrorBody {
+            code: self.code,
+            message: &self.message,
+            details: self.details.as_ref(),
+            request_id,
+        };
+        (self.status, Json(body)).into_response()
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        let code = match rejection {
+            JsonRejection::JsonDataError(_) => ErrorCode::ValidationFailed,
+            _ => ErrorCode::BadRequest,
+        };
+        ApiError::new(code, rejection.body_text()).with_status(rejection.status())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        ApiError::bad_request(rejection.body_text()).with_status(rejection.status())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        ApiError::bad_request(rejection.body_text()).with_status(rejection.status())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::to_bytes;
+    use serde_json::json;
+
+    async fn body(error: ApiError) -> (StatusCode, Value) {
+        let response = error.into_response();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_response_body() {
+        let error = ApiError::new(ErrorCode::InvalidOtp, "Invalid or expired OTP");
+        assert_eq!(
+            body(error).await,
+            (
+                StatusCode::BAD_REQUEST,
+                json!({ "code": "invalid_otp", "message": "Invalid or expired OTP" })
+            )
+        );
+
+        let error = ApiError::forbidden("Requires the admin role")
+            .with_details(json!({ "required_role": "admin" }));
+        assert_eq!(
+            body(error).await,
+            (
+                StatusCode::FORBIDDEN,
+                json!({
+                    "code": "forbidden",
+                    "message": "Requires the admin role",
+                    "details": { "required_role": "admin" },
+                })
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_source_is_not_returned() {
+        let error = ApiError::internal("connection refused").with_status(StatusCode::BAD_GATEWAY);
+        assert_eq!(
+            error.to_string(),
+            "internal: Internal server error (connection refused)"
+        );
+
+        let (status, body) = body(error).await;
+        assert_eq!(status, StatusCode::BAD_GATEWAY);
+        assert_eq!(body["message"], "Internal server error");
+        assert!(!body.to_string().contains("connection refused"));
+    }
+
+    #[test]
+    fn test_code_serialization_matches_as_str() {
+        for code in [
+            ErrorCode::InvalidOtp,
+            ErrorCode::RateLimited,
+            ErrorCode::Internal,
+        ] {
+            assert_eq!(json!(code), json!(code.as_str()));
+        }
+    }
+}
@@ -1,4 +1,9 @@
+pub mod error;
+pub mod request_id;
+#[cfg(feature = "sqlx")]
 pub mod state;
 pub mod types;
 
+pub use error::{ApiError, ErrorCode};
+#[cfg(feature = "sqlx")]
 pub use state::AppState;
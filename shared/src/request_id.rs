@@ -0,0 +1,87 @@
+//! Request ids, correlating error responses with server logs.
+//!
+//! The [`request_id`] middleware assigns every request an id, taken from the
+//! `X-Request-Id` header if the client or a proxy sent a usable one, and otherwise
+//! generated. The id is echoed in the response's `X-Request-Id` header, inserted into
+//! the request extensions as a [`RequestId`], and available anywhere while the
+//! request is handled through [`current`].
+
+use axum::extract::Request;
+use axum::http::HeaderValue;
+use axum::middleware::Next;
+use axum::response::Response;
+use uuid::Uuid;
+
+/// Header carrying the request id.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest request id accepted from a client.
+const MAX_LENGTH: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Id of a request, inserted into the request extensions by [`request_id`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+/// The id of the request being handled, if it went through [`request_id`].
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// Middleware assigning each request an id.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let app = Router::new()
+///     .route("/", get(handler))
+///     .layer(axum::middleware::from_fn(shared::request_id::request_id));
+/// ```
+pub async fn request_id(mut request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| is_valid(value))
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    request.extensions_mut().insert(RequestId(id.clone()));
+    let mut response = REQUEST_ID.scope(id.clone(), next.run(request)).await;
+
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+/// Whether a client supplied id is safe to log and echo back.
+fn is_valid(id: &str) -> bool {
+    !id.is_empty() && id.len() <= MAX_LENGTH && id.bytes().all(|b| b.is_ascii_graphic())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_valid() {
+        assert!(is_valid("3f1c9a52-0d6e-4b8f-a1e2-7c4d5b6a9e01"));
+        assert!(is_valid("req_123"));
+        assert!(!is_valid(""));
+        assert!(!is_valid("has space"));
+        assert!(!is_valid(&"a".repeat(MAX_LENGTH + 1)));
+    }
+
+    #[tokio::test]
+    async fn test_current_outside_scope() {
+        assert_eq!(current(), None);
+        let id = REQUEST_ID
+            .scope("abc".to_string(), async { current() })
+            .await;
+        assert_eq!(id.as_deref(), Some("abc"));
+    }
+}
@@ -1,21 +1,57 @@
 use chrono::{DateTime, Utc};
+use std::fmt;
 use uuid::Uuid;
 
+use super::source::SourceSummary;
+use super::stance::Stance;
+
+/// Lifecycle of a conversation request, stored as the `conversation_request_status` enum
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[cfg_attr(feature = "sqlx", derive(sqlx::Type))]
+#[cfg_attr(
+    feature = "sqlx",
+    sqlx(type_name = "conversation_request_status", rename_all = "snake_case")
+)]
 pub enum ConversationRequestStatus {
     Pending,
     Expired,
     Matched,
+    /// Withdrawn by the user while pending
+    Cancelled,
 }
 
+/// A user's request to be matched with someone to talk about a prompt
+///
+/// The `stance` is where the user stands on the prompt; requests are preferably
+/// matched with someone standing elsewhere.
+/// Once matched, `match_id` is the id of the request it was paired with and
+/// `conversation_id` the id of the conversation created for the pair.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
 pub struct ConversationRequest {
     pub id: Uuid,
     pub user_id: Uuid,
     pub prompt: String,
+    #[cfg_attr(feature = "sqlx", sqlx(flatten))]
+    pub stance: Stance,
     pub request_time: DateTime<Utc>,
     pub status: ConversationRequestStatus,
     pub match_id: Option<Uuid>,
+    pub conversation_id: Option<Uuid>,
 }
 
+/// Why a conversation ended, stored as the `conversation_end_reason` enum
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[cfg_attr(feature = "sqlx", derive(sqlx::Type))]
+#[cfg_attr(
+    feature = "sqlx",
+    sqlx(type_name = "conversation_end_reason", rename_all = "snake_case")
+)]
 pub enum ConversationEndReason {
     Completed,
     UserLeft,
@@ -23,20 +59,239 @@ pub enum ConversationEndReason {
     Inactive,
 }
 
+/// A conversation between two matched users
+///
+/// `ended_at` and `end_reason` are set together once it ends, as the database
+/// enforces; see [`Conversation::state`] for the lifecycle they encode.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
 pub struct Conversation {
     pub id: Uuid,
     pub topic: String,
     pub created_at: DateTime<Utc>,
-    pub ended_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
     pub end_reason: Option<ConversationEndReason>,
     pub participant_a: Uuid,
     pub participant_b: Uuid,
 }
 
+/// Where a conversation is in its lifecycle
+///
+/// Conversations start out active and end exactly once; ended conversations
+/// never become active again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "state", rename_all = "snake_case"))]
+pub enum ConversationState {
+    Active,
+    Ended {
+        reason: ConversationEndReason,
+        at: DateTime<Utc>,
+    },
+}
+
+/// Error returned for a conversation with only one of `ended_at` and `end_reason` set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InconsistentEnd {
+    pub conversation_id: Uuid,
+}
+
+impl fmt::Display for InconsistentEnd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "conversation {} has only one of ended_at and end_reason set",
+            self.conversation_id
+        )
+    }
+}
+
+impl std::error::Error for InconsistentEnd {}
+
+impl Conversation {
+    /// Where the conversation is in its lifecycle
+    ///
+    /// Fails if only one of `ended_at` and `end_reason` is set, rather than
+    /// guessing which of them is wrong.
+    pub fn state(&self) -> Result<ConversationState, InconsistentEnd> {
+        match (self.end_reason, self.ended_at) {
+            (None, None) => Ok(ConversationState::Active),
+            (Some(reason), Some(at)) => Ok(ConversationState::Ended { reason, at }),
+            _ => Err(InconsistentEnd {
+                conversation_id: self.id,
+            }),
+        }
+    }
+
+    /// Whether the conversation has ended
+    ///
+    /// Like the queries guarding messages, this only looks at `ended_at`.
+    pub fn has_ended(&self) -> bool {
+        self.ended_at.is_some()
+    }
+
+    /// Whether the user is one of the two participants
+    pub fn has_participant(&self, user_id: Uuid) -> bool {
+        self.participant_a == user_id || self.participant_b == user_id
+    }
+
+    /// The other participant, if the user is one of the two
+    pub fn partner_of(&self, user_id: Uuid) -> Option<Uuid> {
+        if self.participant_a == user_id {
+            Some(self.participant_b)
+        } else if self.participant_b == user_id {
+            Some(self.participant_a)
+        } else {
+            None
+        }
+    }
+}
+
+/// A message sent in a conversation
+///
+/// `edited_at` is set when the sender last edited the message. Once the sender
+/// deletes it, `deleted_at` is set and `content` is emptied; the text it had
+/// is kept as a [`MessageRevision`], as is the text replaced by each edit.
+/// `sources` summarizes the sources the sender cited, which are stored in the
+/// `message_sources` table rather than with the message.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
 pub struct Message {
     pub id: Uuid,
     pub conversation_id: Uuid,
     pub sender_id: Uuid,
     pub content: String,
     pub sent_at: DateTime<Utc>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub edited_at: Option<DateTime<Utc>>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub deleted_at: Option<DateTime<Utc>>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    #[cfg_attr(feature = "sqlx", sqlx(skip))]
+    pub sources: Vec<SourceSummary>,
+}
+
+impl Message {
+    /// Whether the sender has deleted the message
+    pub fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+}
+
+/// Text a message had before it was edited or deleted, kept for moderators
+///
+/// `written_at` is when the message got this text, on being sent or edited,
+/// and `replaced_at` when it lost it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct MessageRevision {
+    pub id: Uuid,
+    pub message_id: Uuid,
+    pub content: String,
+    pub written_at: DateTime<Utc>,
+    pub replaced_at: DateTime<Utc>,
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+    use crate::types::stance::Position;
+    use serde_json::json;
+
+    #[test]
+    fn test_enums_serialize_as_snake_case() {
+        assert_eq!(json!(ConversationRequestStatus::Pending), json!("pending"));
+        assert_eq!(
+            json!(ConversationEndReason::UserReported),
+            json!("user_reported")
+        );
+    }
+
+    #[test]
+    fn test_conversation_round_trip() {
+        let conversation = Conversation {
+            id: Uuid::new_v4(),
+            topic: "Should cities ban cars?".to_string(),
+            created_at: Utc::now(),
+            ended_at: Some(Utc::now()),
+            end_reason: Some(ConversationEndReason::UserLeft),
+            participant_a: Uuid::new_v4(),
+            participant_b: Uuid::new_v4(),
+        };
+        let json = serde_json::to_value(&conversation).unwrap();
+        assert_eq!(json["end_reason"], "user_left");
+        assert_eq!(
+            serde_json::from_value::<Conversation>(json).unwrap(),
+            conversation
+        );
+
+        let request = ConversationRequest {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            prompt: "Should cities ban cars?".to_string(),
+            stance: Stance::new(Position::Agree),
+            request_time: Utc::now(),
+            status: ConversationRequestStatus::Matched,
+            match_id: Some(Uuid::new_v4()),
+            conversation_id: Some(Uuid::new_v4()),
+        };
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(
+            serde_json::from_value::<ConversationRequest>(json).unwrap(),
+            request
+        );
+    }
+
+    #[test]
+    fn test_conversation_state() {
+        let mut conversation = Conversation {
+            id: Uuid::new_v4(),
+            topic: "Should cities ban cars?".to_string(),
+            created_at: Utc::now(),
+            ended_at: None,
+            end_reason: None,
+            participant_a: Uuid::new_v4(),
+            participant_b: Uuid::new_v4(),
+        };
+        assert_eq!(conversation.state(), Ok(ConversationState::Active));
+        assert!(!conversation.has_ended());
+
+        let at = Utc::now();
+        conversation.ended_at = Some(at);
+        conversation.end_reason = Some(ConversationEndReason::Completed);
+        let ended = ConversationState::Ended {
+            reason: ConversationEndReason::Completed,
+            at,
+        };
+        assert_eq!(conversation.state(), Ok(ended));
+        assert!(conversation.has_ended());
+        assert_eq!(
+            json!(ended),
+            json!({ "state": "ended", "reason": "completed", "at": at })
+        );
+
+        // A half-set pair is reported rather than taken for an active conversation.
+        conversation.end_reason = None;
+        let error = conversation.state().unwrap_err();
+        assert_eq!(error.conversation_id, conversation.id);
+        assert!(conversation.has_ended());
+    }
+
+    #[test]
+    fn test_messages_snapshotted_before_revisions_deserialize() {
+        let message: Message = serde_json::from_value(json!({
+            "id": Uuid::new_v4(),
+            "conversation_id": Uuid::new_v4(),
+            "sender_id": Uuid::new_v4(),
+            "content": "Hi",
+            "sent_at": Utc::now(),
+        }))
+        .unwrap();
+        assert_eq!(message.edited_at, None);
+        assert!(message.sources.is_empty());
+        assert!(!message.is_deleted());
+    }
 }
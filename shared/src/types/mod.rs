@@ -1,2 +1,5 @@
 pub mod conversation;
+pub mod moderation;
+pub mod role;
 pub mod source;
+pub mod stance;
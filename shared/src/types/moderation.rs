@@ -0,0 +1,204 @@
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use super::conversation::Message;
+
+/// What a report is about, stored as the `report_category` enum
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[cfg_attr(feature = "sqlx", derive(sqlx::Type))]
+#[cfg_attr(
+    feature = "sqlx",
+    sqlx(type_name = "report_category", rename_all = "snake_case")
+)]
+pub enum ReportCategory {
+    Harassment,
+    HateSpeech,
+    Threats,
+    Spam,
+    Other,
+}
+
+/// Where a report is in the moderation queue, stored as the `report_status` enum
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[cfg_attr(feature = "sqlx", derive(sqlx::Type))]
+#[cfg_attr(
+    feature = "sqlx",
+    sqlx(type_name = "report_status", rename_all = "snake_case")
+)]
+pub enum ReportStatus {
+    /// Waiting for a moderator
+    Open,
+    /// Being reviewed by the moderator who claimed it
+    Claimed,
+    /// Closed with a [`ModerationAction`]
+    Resolved,
+}
+
+/// How a moderator resolved a report, stored as the `moderation_action` enum
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[cfg_attr(feature = "sqlx", derive(sqlx::Type))]
+#[cfg_attr(
+    feature = "sqlx",
+    sqlx(type_name = "moderation_action", rename_all = "snake_case")
+)]
+pub enum ModerationAction {
+    /// The report was unfounded; the reported user is not sanctioned
+    Dismiss,
+    Warn,
+    Suspend,
+    Ban,
+}
+
+impl ModerationAction {
+    /// The sanction imposed on the reported user by this action, if any
+    pub fn sanction(&self) -> Option<SanctionKind> {
+        match self {
+            ModerationAction::Dismiss => None,
+            ModerationAction::Warn => Some(SanctionKind::Warning),
+            ModerationAction::Suspend => Some(SanctionKind::Suspension),
+            ModerationAction::Ban => Some(SanctionKind::Ban),
+        }
+    }
+}
+
+/// A participant's report of the other participant of a conversation
+///
+/// Reports without a `reporter_id` were filed automatically on messages flagged
+/// by the chat's message filters.
+/// `messages` is a snapshot of the offending messages taken when the report was
+/// filed, stored in the `messages` JSONB column. Once claimed, `claimed_by` is
+/// the moderator reviewing the report, who sets `action` on resolving it.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct Report {
+    pub id: Uuid,
+    pub conversation_id: Uuid,
+    pub reporter_id: Option<Uuid>,
+    pub reported_user_id: Uuid,
+    pub category: ReportCategory,
+    pub details: Option<String>,
+    #[cfg_attr(feature = "sqlx", sqlx(json))]
+    pub messages: Vec<Message>,
+    pub status: ReportStatus,
+    pub created_at: DateTime<Utc>,
+    pub claimed_by: Option<Uuid>,
+    pub claimed_at: Option<DateTime<Utc>>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub action: Option<ModerationAction>,
+    pub resolution_note: Option<String>,
+}
+
+/// A kind of sanction imposed on a user, stored as the `sanction_kind` enum
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[cfg_attr(feature = "sqlx", derive(sqlx::Type))]
+#[cfg_attr(
+    feature = "sqlx",
+    sqlx(type_name = "sanction_kind", rename_all = "snake_case")
+)]
+pub enum SanctionKind {
+    /// Recorded against the user without restricting them
+    Warning,
+    /// Bars the user from the service until it expires
+    Suspension,
+    /// Bars the user from the service for good
+    Ban,
+}
+
+/// A sanction imposed on a user by a moderator, usually on resolving a report
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct Sanction {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub kind: SanctionKind,
+    pub report_id: Option<Uuid>,
+    pub reason: Option<String>,
+    pub issued_by: Uuid,
+    pub issued_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl Sanction {
+    /// Whether the sanction bars the user from the service at the given time
+    ///
+    /// Bans always do, suspensions until they expire, and warnings never do.
+    pub fn bars_access(&self, at: DateTime<Utc>) -> bool {
+        match self.kind {
+            SanctionKind::Warning => false,
+            SanctionKind::Suspension => self.expires_at.is_none_or(|expires_at| expires_at > at),
+            SanctionKind::Ban => true,
+        }
+    }
+}
+
+/// A user blocked by another, who will never be matched with them again
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct Block {
+    pub blocker_id: Uuid,
+    pub blocked_id: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn sanction(kind: SanctionKind, expires_at: Option<DateTime<Utc>>) -> Sanction {
+        Sanction {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            kind,
+            report_id: None,
+            reason: None,
+            issued_by: Uuid::new_v4(),
+            issued_at: Utc::now(),
+            expires_at,
+        }
+    }
+
+    #[test]
+    fn test_sanctions_barring_access() {
+        let now = Utc::now();
+        let week = Some(now + Duration::days(7));
+
+        assert!(!sanction(SanctionKind::Warning, None).bars_access(now));
+        assert!(sanction(SanctionKind::Suspension, week).bars_access(now));
+        assert!(!sanction(SanctionKind::Suspension, week).bars_access(now + Duration::days(8)));
+        assert!(sanction(SanctionKind::Ban, None).bars_access(now + Duration::days(365)));
+    }
+
+    #[test]
+    fn test_action_sanctions() {
+        assert_eq!(ModerationAction::Dismiss.sanction(), None);
+        assert_eq!(
+            ModerationAction::Warn.sanction(),
+            Some(SanctionKind::Warning)
+        );
+        assert_eq!(
+            ModerationAction::Suspend.sanction(),
+            Some(SanctionKind::Suspension)
+        );
+        assert_eq!(ModerationAction::Ban.sanction(), Some(SanctionKind::Ban));
+    }
+
+    #[test]
+    fn test_report_serialization() {
+        let json = serde_json::to_value(ReportCategory::HateSpeech).unwrap();
+        assert_eq!(json, "hate_speech");
+        let action: ModerationAction = serde_json::from_str("\"suspend\"").unwrap();
+        assert_eq!(action, ModerationAction::Suspend);
+    }
+}
@@ -0,0 +1,147 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// The role of a user, determining what they are permitted to do
+///
+/// Roles are ordered by privilege: every moderator permission is also held by
+/// admins, and every user permission by moderators.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// A regular participant in conversations
+    #[default]
+    User,
+    /// Reviews reports and acts on users who break the rules
+    Moderator,
+    /// Manages sources, roles, prompts and everything moderators can do
+    Admin,
+}
+
+/// An action that requires more than a regular user's privileges
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    /// Review reports filed against conversations
+    ReviewReports,
+    /// Warn, suspend or ban users
+    SanctionUsers,
+    /// Change the credibility of sources
+    ManageSources,
+    /// Grant and revoke roles
+    ManageRoles,
+    /// Set how long requests on a prompt stay pending
+    ManagePrompts,
+}
+
+impl Role {
+    /// All roles, from least to most privileged
+    pub const ALL: [Role; 3] = [Role::User, Role::Moderator, Role::Admin];
+
+    /// The name of the role as used in tokens and the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::User => "user",
+            Role::Moderator => "moderator",
+            Role::Admin => "admin",
+        }
+    }
+
+    /// The least privileged role holding the given permission
+    pub fn minimum_for(permission: Permission) -> Role {
+        match permission {
+            Permission::ReviewReports | Permission::SanctionUsers => Role::Moderator,
+            Permission::ManageSources | Permission::ManageRoles | Permission::ManagePrompts => {
+                Role::Admin
+            }
+        }
+    }
+
+    /// Whether this role grants at least the privileges of `other`
+    pub fn includes(&self, other: Role) -> bool {
+        *self >= other
+    }
+
+    /// Whether this role holds the given permission
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        self.includes(Role::minimum_for(permission))
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Error returned when parsing an unknown role name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownRole(pub String);
+
+impl fmt::Display for UnknownRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown role: {}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownRole {}
+
+impl FromStr for Role {
+    type Err = UnknownRole;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Role::ALL
+            .into_iter()
+            .find(|role| role.as_str() == s)
+            .ok_or_else(|| UnknownRole(s.to_string()))
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Permission::ReviewReports => "review_reports",
+            Permission::SanctionUsers => "sanction_users",
+            Permission::ManageSources => "manage_sources",
+            Permission::ManageRoles => "manage_roles",
+            Permission::ManagePrompts => "manage_prompts",
+        };
+        f.write_str(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_role_hierarchy() {
+        assert!(Role::Admin.includes(Role::Moderator));
+        assert!(Role::Moderator.includes(Role::User));
+        assert!(Role::Moderator.includes(Role::Moderator));
+        assert!(!Role::User.includes(Role::Moderator));
+        assert!(!Role::Moderator.includes(Role::Admin));
+    }
+
+    #[test]
+    fn test_role_permissions() {
+        assert!(!Role::User.has_permission(Permission::ReviewReports));
+        assert!(Role::Moderator.has_permission(Permission::ReviewReports));
+        assert!(Role::Moderator.has_permission(Permission::SanctionUsers));
+        assert!(!Role::Moderator.has_permission(Permission::ManageSources));
+        assert!(Role::Admin.has_permission(Permission::ManageSources));
+        assert!(Role::Admin.has_permission(Permission::ManageRoles));
+        assert!(!Role::Moderator.has_permission(Permission::ManagePrompts));
+        assert!(Role::Admin.has_permission(Permission::ManagePrompts));
+    }
+
+    #[test]
+    fn test_role_names_roundtrip() {
+        for role in Role::ALL {
+            assert_eq!(role.to_string().parse::<Role>(), Ok(role));
+        }
+        assert_eq!("root".parse::<Role>(), Err(UnknownRole("root".into())));
+    }
+}
@@ -1,12 +1,14 @@
-use uuid::Uuid;
 use chrono::{DateTime, Utc};
-use serde::{Deserialize, de};
+use regex::Regex;
 use serde::de::Visitor;
+use serde::{de, Deserialize};
 use std::fmt;
-use regex::Regex;
+use uuid::Uuid;
 
 /// A website or book source created by a user
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
 pub struct Source {
     pub id: Uuid,
     pub created_at: DateTime<Utc>,
@@ -16,15 +18,50 @@ pub struct Source {
     pub notes: String,
 }
 
+/// What participants are shown of a source cited in a message
+///
+/// For books, the details are those of the first match.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SourceSummary {
+    pub id: Uuid,
+    pub kind: SourceKind,
+    pub title: Option<String>,
+    pub authors: Vec<String>,
+    pub url: Option<String>,
+    pub credibility: f32,
+}
+
 /// Details about a particular website or a list of book matches
-#[derive(Debug)]
+///
+/// Serialized without a tag, as the website's details or the list of book
+/// matches, and stored that way in the `source_info` JSONB column, with the
+/// variant recorded in the `kind` column.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(untagged))]
 pub enum SourceInfo {
     Website(WebsiteInfo),
     Book(Vec<BookInfo>),
 }
 
+/// The kind of a source, stored as the `source_kind` enum
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[cfg_attr(feature = "sqlx", derive(sqlx::Type))]
+#[cfg_attr(
+    feature = "sqlx",
+    sqlx(type_name = "source_kind", rename_all = "snake_case")
+)]
+pub enum SourceKind {
+    Website,
+    Book,
+}
+
 /// Details about a particular website
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct WebsiteInfo {
     pub url: String,
     pub title: Option<String>,
@@ -35,7 +72,8 @@ pub struct WebsiteInfo {
 }
 
 /// Details about a particular book
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct BookInfo {
     pub title: String,
     pub authors: Option<Vec<String>>,
@@ -46,7 +84,7 @@ pub struct BookInfo {
 }
 
 /// A source publication date consisting of a year, month, and day
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub struct PublicationDate {
     pub year: Option<u16>,
     pub month: Option<u8>,
@@ -60,18 +98,84 @@ impl Source {
             id: Uuid::new_v4(),
             created_at: Utc::now(),
             created_by: Uuid::nil(), // TODO: fetch user uuid
-            credibility: 0.0, // TODO: implement credibility
+            credibility: 0.0,        // TODO: implement credibility
             source_info,
             notes: String::new(),
         }
     }
 }
 
+impl From<&Source> for SourceSummary {
+    fn from(source: &Source) -> Self {
+        let (title, authors, url) = match &source.source_info {
+            SourceInfo::Website(website) => (
+                website.title.clone(),
+                website.authors.clone(),
+                Some(website.url.clone()),
+            ),
+            SourceInfo::Book(books) => match books.first() {
+                Some(book) => (Some(book.title.clone()), book.authors.clone(), None),
+                None => (None, None, None),
+            },
+        };
+        Self {
+            id: source.id,
+            kind: source.source_info.kind(),
+            title,
+            authors: authors.unwrap_or_default(),
+            url,
+            credibility: source.credibility,
+        }
+    }
+}
+
+impl SourceInfo {
+    /// The kind of source these details describe
+    pub fn kind(&self) -> SourceKind {
+        match self {
+            SourceInfo::Website(_) => SourceKind::Website,
+            SourceInfo::Book(_) => SourceKind::Book,
+        }
+    }
+}
+
+/// `SourceInfo` is stored as JSONB
+#[cfg(feature = "sqlx")]
+impl sqlx::Type<sqlx::Postgres> for SourceInfo {
+    fn type_info() -> sqlx::postgres::PgTypeInfo {
+        <sqlx::types::Json<Self> as sqlx::Type<sqlx::Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &sqlx::postgres::PgTypeInfo) -> bool {
+        <sqlx::types::Json<Self> as sqlx::Type<sqlx::Postgres>>::compatible(ty)
+    }
+}
+
+#[cfg(feature = "sqlx")]
+impl sqlx::Encode<'_, sqlx::Postgres> for SourceInfo {
+    fn encode_by_ref(
+        &self,
+        buf: &mut sqlx::postgres::PgArgumentBuffer,
+    ) -> Result<sqlx::encode::IsNull, sqlx::error::BoxDynError> {
+        sqlx::types::Json(self).encode_by_ref(buf)
+    }
+}
+
+#[cfg(feature = "sqlx")]
+impl<'r> sqlx::Decode<'r, sqlx::Postgres> for SourceInfo {
+    fn decode(value: sqlx::postgres::PgValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        let sqlx::types::Json(info) = sqlx::types::Json::<Self>::decode(value)?;
+        Ok(info)
+    }
+}
+
 impl PublicationDate {
     // Return an empty PublicationDate object
     fn nil() -> PublicationDate {
         PublicationDate {
-            year: None, month: None, day: None
+            year: None,
+            month: None,
+            day: None,
         }
     }
 
@@ -125,11 +229,32 @@ impl<'de> Deserialize<'de> for PublicationDate {
                 Ok(PublicationDate::parse_ymd_string(value))
             }
         }
-        
+
         deserializer.deserialize_string(DateVisitor)
     }
 }
 
+/// Custom Serializer for PublicationDate producing strings of the form '[yyyy][-mm][-dd]'
+#[cfg(feature = "serde")]
+impl serde::Serialize for PublicationDate {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut date = String::new();
+        if let Some(year) = self.year {
+            date.push_str(&format!("{:04}", year));
+            if let Some(month) = self.month {
+                date.push_str(&format!("-{:02}", month));
+                if let Some(day) = self.day {
+                    date.push_str(&format!("-{:02}", day));
+                }
+            }
+        }
+        serializer.serialize_str(&date)
+    }
+}
+
 // Unit tests for PublicationDate::parse_ymd_string
 #[cfg(test)]
 mod tests {
@@ -139,7 +264,9 @@ mod tests {
     fn test_parse_ymd_string_ymd() {
         let input = "2005-03-14";
         let expected = PublicationDate {
-            year: Some(2005), month: Some(3), day: Some(14)
+            year: Some(2005),
+            month: Some(3),
+            day: Some(14),
         };
         let result = PublicationDate::parse_ymd_string(input);
         assert_eq!(result, expected);
@@ -149,7 +276,9 @@ mod tests {
     fn test_parse_ymd_string_ym() {
         let input = "2005-03";
         let expected = PublicationDate {
-            year: Some(2005), month: Some(3), day: None
+            year: Some(2005),
+            month: Some(3),
+            day: None,
         };
         let result = PublicationDate::parse_ymd_string(input);
         assert_eq!(result, expected);
@@ -159,7 +288,9 @@ mod tests {
     fn test_parse_ymd_string_y() {
         let input = "2005";
         let expected = PublicationDate {
-            year: Some(2005), month: None, day: None
+            year: Some(2005),
+            month: None,
+            day: None,
         };
         let result = PublicationDate::parse_ymd_string(input);
         assert_eq!(result, expected);
@@ -172,4 +303,106 @@ mod tests {
         let result = PublicationDate::parse_ymd_string(input);
         assert_eq!(result, expected);
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_publication_date_round_trip() {
+        for input in ["2005-03-14", "2005-03", "2005", ""] {
+            let date: PublicationDate = serde_json::from_value(serde_json::json!(input)).unwrap();
+            assert_eq!(
+                serde_json::to_value(date).unwrap(),
+                serde_json::json!(input)
+            );
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_source_info_round_trip() {
+        let website = SourceInfo::Website(WebsiteInfo {
+            url: "https://example.com/article".to_string(),
+            title: Some("An article".to_string()),
+            authors: Some(vec!["Jane Doe".to_string()]),
+            publisher: None,
+            date: PublicationDate {
+                year: Some(2020),
+                month: Some(1),
+                day: None,
+            },
+            description: None,
+        });
+        let json = serde_json::to_value(&website).unwrap();
+        assert!(json.is_object());
+        assert_eq!(json["date"], "2020-01");
+        assert_eq!(serde_json::from_value::<SourceInfo>(json).unwrap(), website);
+
+        let book = SourceInfo::Book(vec![BookInfo {
+            title: "A book".to_string(),
+            authors: None,
+            publisher: Some("A publisher".to_string()),
+            date: PublicationDate {
+                year: Some(1999),
+                month: None,
+                day: None,
+            },
+            categories: Some(vec!["History".to_string()]),
+            pages: Some(320),
+        }]);
+        let json = serde_json::to_value(&book).unwrap();
+        assert!(json.is_array());
+        assert_eq!(serde_json::from_value::<SourceInfo>(json).unwrap(), book);
+        assert_eq!(book.kind(), SourceKind::Book);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_source_round_trip() {
+        let source = Source::new(SourceInfo::Book(Vec::new()));
+        let json = serde_json::to_value(&source).unwrap();
+        assert_eq!(serde_json::from_value::<Source>(json).unwrap(), source);
+    }
+
+    #[test]
+    fn test_source_summary() {
+        let mut source = Source::new(SourceInfo::Book(vec![BookInfo {
+            title: "A book".to_string(),
+            authors: Some(vec!["John Roe".to_string()]),
+            publisher: None,
+            date: PublicationDate::nil(),
+            categories: None,
+            pages: None,
+        }]));
+        source.credibility = 0.75;
+        let summary = SourceSummary::from(&source);
+        assert_eq!(summary.kind, SourceKind::Book);
+        assert_eq!(summary.title.as_deref(), Some("A book"));
+        assert_eq!(summary.authors, vec!["John Roe".to_string()]);
+        assert_eq!(summary.url, None);
+        assert_eq!(summary.credibility, 0.75);
+
+        let website = Source::new(SourceInfo::Website(WebsiteInfo {
+            url: "https://example.com".to_string(),
+            title: None,
+            authors: None,
+            publisher: None,
+            date: PublicationDate::nil(),
+            description: None,
+        }));
+        let summary = SourceSummary::from(&website);
+        assert_eq!(summary.url.as_deref(), Some("https://example.com"));
+        assert!(summary.authors.is_empty());
+    }
+
+    #[cfg(feature = "sqlx")]
+    #[test]
+    fn test_sql_types() {
+        use sqlx::postgres::PgTypeInfo;
+        use sqlx::Type;
+
+        assert_eq!(SourceInfo::type_info(), PgTypeInfo::with_name("JSONB"));
+        assert_eq!(
+            SourceKind::type_info(),
+            PgTypeInfo::with_name("source_kind")
+        );
+    }
 }
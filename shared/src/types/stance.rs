@@ -0,0 +1,103 @@
+/// A position on a five-point Likert scale, from strongly disagreeing with a
+/// prompt to strongly agreeing with it
+///
+/// Stored as a `SMALLINT` from -2 to 2, so that positions can be compared.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[cfg_attr(feature = "sqlx", derive(sqlx::Type))]
+#[repr(i16)]
+pub enum Position {
+    StronglyDisagree = -2,
+    Disagree = -1,
+    #[default]
+    Neutral = 0,
+    Agree = 1,
+    StronglyAgree = 2,
+}
+
+impl Position {
+    /// The largest possible [`Position::distance`], between the two ends of the scale
+    pub const MAX_DISTANCE: u8 = 4;
+
+    /// The position on the scale, from -2 to 2
+    pub fn value(&self) -> i16 {
+        *self as i16
+    }
+
+    /// How many steps apart two positions are on the scale
+    pub fn distance(&self, other: Position) -> u8 {
+        self.value().abs_diff(other.value()) as u8
+    }
+
+    /// Whether one of the positions agrees with the prompt and the other disagrees
+    pub fn opposes(&self, other: Position) -> bool {
+        self.value() * other.value() < 0
+    }
+}
+
+/// Where a user stands on the prompt of their conversation request
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct Stance {
+    pub position: Position,
+    /// The user's position in their own words
+    pub statement: Option<String>,
+}
+
+impl Stance {
+    pub fn new(position: Position) -> Self {
+        Self {
+            position,
+            statement: None,
+        }
+    }
+
+    pub fn with_statement(mut self, statement: impl Into<String>) -> Self {
+        self.statement = Some(statement.into());
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_position_distance() {
+        assert_eq!(
+            Position::StronglyDisagree.distance(Position::StronglyAgree),
+            Position::MAX_DISTANCE
+        );
+        assert_eq!(Position::Agree.distance(Position::Neutral), 1);
+        assert!(Position::Disagree.opposes(Position::StronglyAgree));
+        assert!(!Position::Neutral.opposes(Position::Agree));
+        assert!(!Position::Agree.opposes(Position::StronglyAgree));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_stance_serialization() {
+        let stance =
+            Stance::new(Position::StronglyAgree).with_statement("Cars make cities unlivable");
+        let json = serde_json::to_value(&stance).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "position": "strongly_agree", "statement": "Cars make cities unlivable" })
+        );
+        assert_eq!(serde_json::from_value::<Stance>(json).unwrap(), stance);
+    }
+
+    #[cfg(feature = "sqlx")]
+    #[test]
+    fn test_sql_types() {
+        use sqlx::postgres::{PgTypeInfo, Postgres};
+        use sqlx::Type;
+
+        assert_eq!(
+            <Position as Type<Postgres>>::type_info(),
+            PgTypeInfo::with_name("INT2")
+        );
+    }
+}
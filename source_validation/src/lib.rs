@@ -1,6 +1,14 @@
-use shared::types::source::{Source, SourceInfo, WebsiteInfo};
+use shared::types::source::{Source, SourceInfo, SourceKind, WebsiteInfo};
 use std::error::Error;
 
+// Extract source info from a website URL, or from a book by its name, using the Bibify API.
+pub async fn extract_source(kind: SourceKind, query: &str) -> Result<Source, Box<dyn Error>> {
+    match kind {
+        SourceKind::Website => extract_source_url(query).await,
+        SourceKind::Book => extract_source_book(query).await,
+    }
+}
+
 // Extract source info from a website URL using the Bibify API.
 async fn extract_source_url(url: &str) -> Result<Source, Box<dyn Error>> {
     let request_target = r#"https://api.bibify.org/api/website"#;
@@ -8,15 +16,13 @@ async fn extract_source_url(url: &str) -> Result<Source, Box<dyn Error>> {
 
     let client = reqwest::Client::new();
 
-    let request = client.request(reqwest::Method::GET, request_target)
+    let request = client
+        .request(reqwest::Method::GET, request_target)
         .query(&query)
         .build()
         .unwrap();
 
-    let response = client.execute(request)
-        .await?
-        .text()
-        .await?;
+    let response = client.execute(request).await?.text().await?;
 
     let website_info: WebsiteInfo = serde_json::from_str(&response)?;
     let source_info = SourceInfo::Website(website_info);
@@ -32,15 +38,13 @@ async fn extract_source_book(name: &str) -> Result<Source, Box<dyn Error>> {
 
     let client = reqwest::Client::new();
 
-    let request = client.request(reqwest::Method::GET, request_target)
+    let request = client
+        .request(reqwest::Method::GET, request_target)
         .query(&query)
         .build()
         .unwrap();
 
-    let response = client.execute(request)
-        .await?
-        .text()
-        .await?;
+    let response = client.execute(request).await?.text().await?;
 
     let book_info = serde_json::from_str(&response)?;
     let source_info = SourceInfo::Book(book_info);
@@ -0,0 +1,185 @@
+//! # Test Support Crate
+//!
+//! Fixtures shared by the integration tests of the API crates, which drive
+//! their routers with [`send`] as users holding tokens minted by a
+//! [`MockAuthenticator`].
+//!
+//! Tests of requests rejected before reaching the database use a
+//! [`lazy_pool`], which never connects. Tests that need a database are written
+//! with `#[sqlx::test(migrator = "db::MIGRATOR")]`, which creates a fresh,
+//! migrated database for each test on the server at `DATABASE_URL`, and set up
+//! the rows they need with [`create_user`] and [`create_conversation`].
+//! WebSocket endpoints are tested by [`serve`]-ing the router and connecting a
+//! [`WsClient`] to it.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use auth::models::{AuthSession, MockAuthenticator};
+use axum::Router;
+use axum::body::{Body, to_bytes};
+use axum::http::{HeaderValue, Method, Request, StatusCode, header};
+use chrono::Utc;
+use db::queries::{conversations, users};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::Value;
+use shared::types::conversation::Conversation;
+use sqlx::PgPool;
+use sqlx::postgres::PgPoolOptions;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+/// Secret signing the tokens of the [`authenticator`].
+pub const SECRET: &str = "test-secret";
+
+/// How long a [`WsClient`] waits for the next event before failing the test.
+const RECV_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A pool that never connects, for requests rejected before reaching the database.
+pub fn lazy_pool() -> PgPool {
+    PgPoolOptions::new()
+        .connect_lazy("postgres://localhost/unused")
+        .unwrap()
+}
+
+/// An authenticator signing tokens with [`SECRET`].
+pub fn authenticator() -> MockAuthenticator {
+    MockAuthenticator::new(SECRET)
+}
+
+/// Create a session for a user and return its access token.
+pub fn token(authenticator: &MockAuthenticator, user_id: Uuid) -> String {
+    authenticator
+        .create_session(user_id)
+        .access_token()
+        .to_string()
+}
+
+/// Send a request, with a bearer token and JSON body if given, and return the
+/// response status and JSON body, or `Value::Null` if it has none.
+pub async fn send(
+    router: Router,
+    method: Method,
+    uri: &str,
+    token: Option<&str>,
+    body: Option<Value>,
+) -> (StatusCode, Value) {
+    let mut request = Request::builder().method(method).uri(uri);
+    if let Some(token) = token {
+        request = request.header(header::AUTHORIZATION, format!("Bearer {token}"));
+    }
+    let body = match body {
+        Some(body) => {
+            request = request.header(header::CONTENT_TYPE, "application/json");
+            Body::from(body.to_string())
+        }
+        None => Body::empty(),
+    };
+
+    let response = router.oneshot(request.body(body).unwrap()).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+/// Create the profile of a new user, returning their id.
+pub async fn create_user(pool: &PgPool) -> Uuid {
+    let user_id = Uuid::new_v4();
+    users::upsert_user_on_login(pool, user_id, None, None)
+        .await
+        .unwrap();
+    user_id
+}
+
+/// Create an active conversation between two users.
+pub async fn create_conversation(pool: &PgPool, first: Uuid, second: Uuid) -> Conversation {
+    let conversation = Conversation {
+        id: Uuid::new_v4(),
+        topic: "Should cities ban cars?".to_string(),
+        created_at: Utc::now(),
+        ended_at: None,
+        end_reason: None,
+        participant_a: first,
+        participant_b: second,
+    };
+    conversations::insert_conversation(pool, &conversation)
+        .await
+        .unwrap();
+    conversation
+}
+
+/// Serve a router on a free local port for the rest of the test, returning its address.
+pub async fn serve(router: Router) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+    addr
+}
+
+/// A WebSocket connection to a served router, exchanging JSON events.
+pub struct WsClient(WebSocketStream<MaybeTlsStream<TcpStream>>);
+
+impl WsClient {
+    /// Connect to a WebSocket endpoint with a bearer token.
+    pub async fn connect(addr: SocketAddr, uri: &str, token: &str) -> Self {
+        let mut request = format!("ws://{addr}{uri}").into_client_request().unwrap();
+        request.headers_mut().insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+        );
+        let (stream, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+        Self(stream)
+    }
+
+    /// Send a JSON event.
+    pub async fn send(&mut self, event: Value) {
+        self.0.send(Message::text(event.to_string())).await.unwrap();
+    }
+
+    /// Receive the next JSON event, failing if none arrives in time or the
+    /// connection closes.
+    pub async fn recv(&mut self) -> Value {
+        loop {
+            let message = tokio::time::timeout(RECV_TIMEOUT, self.0.next())
+                .await
+                .expect("no event received in time")
+                .expect("connection closed")
+                .unwrap();
+            match message {
+                Message::Text(text) => return serde_json::from_str(&text).unwrap(),
+                Message::Close(frame) => panic!("connection closed: {frame:?}"),
+                _ => {}
+            }
+        }
+    }
+
+    /// Receive events until the connection is closed, returning the close
+    /// code and reason.
+    pub async fn recv_close(&mut self) -> (u16, String) {
+        loop {
+            let message = tokio::time::timeout(RECV_TIMEOUT, self.0.next())
+                .await
+                .expect("connection not closed in time")
+                .expect("connection closed without a close frame")
+                .unwrap();
+            if let Message::Close(frame) = message {
+                let frame = frame.expect("close frame without a code");
+                return (frame.code.into(), frame.reason.to_string());
+            }
+        }
+    }
+
+    /// Receive events until one of the given type, returning it.
+    pub async fn recv_type(&mut self, kind: &str) -> Value {
+        loop {
+            let event = self.recv().await;
+            if event["type"] == kind {
+                return event;
+            }
+        }
+    }
+}